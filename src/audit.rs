@@ -0,0 +1,263 @@
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use toml::Value;
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::models::AuditEntry;
+use crate::storage::Storage;
+
+/// Where config and key changes in this process come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditSource {
+    Cli,
+    Tui,
+}
+
+impl AuditSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cli => "cli",
+            Self::Tui => "tui",
+        }
+    }
+}
+
+static SOURCE: OnceLock<AuditSource> = OnceLock::new();
+
+/// Turns on the audit log for this process. Until it is set, changes are
+/// not recorded, which keeps tests away from the real database.
+pub fn set_source(source: AuditSource) {
+    let _ = SOURCE.set(source);
+}
+
+/// One change, before it is stamped with a time and source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditChange {
+    pub action: &'static str,
+    pub subject: String,
+    pub detail: String,
+}
+
+impl AuditChange {
+    fn new(action: &'static str, subject: &str, detail: String) -> Self {
+        Self {
+            action,
+            subject: subject.to_string(),
+            detail,
+        }
+    }
+}
+
+/// What saving `after` over `before` changes: providers added, removed,
+/// enabled, disabled or edited, budgets added, removed or edited, and any
+/// other top-level setting. Details name the changed fields only, so
+/// webhook URLs and other values never reach the log.
+pub fn config_changes(before: &AppConfig, after: &AppConfig) -> Vec<AuditChange> {
+    let (Ok(Value::Table(old)), Ok(Value::Table(new))) =
+        (Value::try_from(before), Value::try_from(after))
+    else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+
+    let accounts = |cfg: &AppConfig| -> BTreeSet<String> {
+        cfg.enabled_providers
+            .iter()
+            .chain(cfg.provider_settings.keys())
+            .cloned()
+            .collect()
+    };
+    let (old_accounts, new_accounts) = (accounts(before), accounts(after));
+    let enabled = |cfg: &AppConfig, name: &str| cfg.enabled_providers.iter().any(|p| p == name);
+    for name in old_accounts.difference(&new_accounts) {
+        changes.push(AuditChange::new("provider.removed", name, String::new()));
+    }
+    for name in &new_accounts {
+        if !old_accounts.contains(name) {
+            changes.push(AuditChange::new("provider.added", name, String::new()));
+            continue;
+        }
+        match (enabled(before, name), enabled(after, name)) {
+            (false, true) => {
+                changes.push(AuditChange::new("provider.enabled", name, String::new()))
+            }
+            (true, false) => {
+                changes.push(AuditChange::new("provider.disabled", name, String::new()))
+            }
+            _ => {}
+        }
+        let settings = |table: &toml::Table| {
+            table
+                .get("provider_settings")
+                .and_then(|s| s.get(name.as_str()))
+                .cloned()
+        };
+        let fields = changed_fields(settings(&old).as_ref(), settings(&new).as_ref());
+        if !fields.is_empty() {
+            changes.push(AuditChange::new(
+                "provider.changed",
+                name,
+                fields.join(", "),
+            ));
+        }
+    }
+
+    let budgets = |table: &toml::Table| -> Vec<(String, Value)> {
+        table
+            .get("budgets")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|b| Some((b.get("name")?.as_str()?.to_string(), b.clone())))
+            .collect()
+    };
+    let (old_budgets, new_budgets) = (budgets(&old), budgets(&new));
+    let find = |list: &[(String, Value)], name: &str| {
+        list.iter().find(|(n, _)| n == name).map(|(_, b)| b.clone())
+    };
+    for (name, budget) in &old_budgets {
+        match find(&new_budgets, name) {
+            None => changes.push(AuditChange::new("budget.removed", name, String::new())),
+            Some(now) => {
+                let fields = changed_fields(Some(budget), Some(&now));
+                if !fields.is_empty() {
+                    changes.push(AuditChange::new("budget.changed", name, fields.join(", ")));
+                }
+            }
+        }
+    }
+    for (name, _) in &new_budgets {
+        if find(&old_budgets, name).is_none() {
+            changes.push(AuditChange::new("budget.added", name, String::new()));
+        }
+    }
+
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        if matches!(
+            key.as_str(),
+            "enabled_providers" | "provider_settings" | "budgets"
+        ) || old.get(key) == new.get(key)
+        {
+            continue;
+        }
+        let fields = changed_fields(old.get(key), new.get(key));
+        changes.push(AuditChange::new("config.changed", key, fields.join(", ")));
+    }
+    changes
+}
+
+/// Keys that differ between two tables; empty when either is not a table.
+fn changed_fields(old: Option<&Value>, new: Option<&Value>) -> Vec<String> {
+    let empty = toml::Table::new();
+    let table = |v: Option<&Value>| match v {
+        Some(Value::Table(t)) => Some(t.clone()),
+        None => Some(empty.clone()),
+        Some(_) => None,
+    };
+    let (Some(old), Some(new)) = (table(old), table(new)) else {
+        return Vec::new();
+    };
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect()
+}
+
+/// Appends `changes` to the configured database's `audit_log`, stamped with
+/// this process's source. Does nothing before [`set_source`].
+pub fn record(changes: Vec<AuditChange>) -> Result<(), AppError> {
+    let Some(source) = SOURCE.get() else {
+        return Ok(());
+    };
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let at = Utc::now();
+    let entries: Vec<AuditEntry> = changes
+        .into_iter()
+        .map(|c| AuditEntry {
+            at,
+            source: source.as_str().to_string(),
+            action: c.action.to_string(),
+            subject: c.subject,
+            detail: c.detail,
+        })
+        .collect();
+    Storage::open_configured()?
+        .record_audit(&entries)
+        .map_err(|e| AppError::Storage(format!("audit log: {e}")))
+}
+
+/// Records a stored key being set or deleted for `provider`.
+pub fn record_key(action: &'static str, provider: &str) -> Result<(), AppError> {
+    record(vec![AuditChange::new(action, provider, String::new())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Budget, ProviderSettings, SlackConfig};
+
+    fn actions(changes: &[AuditChange]) -> Vec<(&str, &str, &str)> {
+        changes
+            .iter()
+            .map(|c| (c.action, c.subject.as_str(), c.detail.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn config_changes_name_providers_budgets_and_settings_without_values() {
+        let mut before = AppConfig {
+            enabled_providers: vec!["openai".into(), "anthropic".into()],
+            ..AppConfig::default()
+        };
+        before.budgets.push(Budget {
+            name: "monthly".into(),
+            scope: Default::default(),
+            amount_usd: 100.0,
+            period: Default::default(),
+            on_exceeded: None,
+        });
+        let mut after = before.clone();
+        after.enabled_providers = vec!["openai".into(), "gemini".into()];
+        after.provider_settings.insert(
+            "openai".into(),
+            ProviderSettings {
+                base_url: Some("https://proxy.example.com".into()),
+                ..ProviderSettings::default()
+            },
+        );
+        after.budgets[0].amount_usd = 250.0;
+        after.notifier.slack = Some(SlackConfig {
+            webhook_url: "https://hooks.slack.com/secret".into(),
+        });
+
+        assert_eq!(
+            actions(&config_changes(&before, &after)),
+            vec![
+                ("provider.removed", "anthropic", ""),
+                ("provider.added", "gemini", ""),
+                ("provider.changed", "openai", "base_url"),
+                ("budget.changed", "monthly", "amount_usd"),
+                ("config.changed", "notifier", "slack"),
+            ]
+        );
+        assert!(config_changes(&after, &after).is_empty());
+
+        after.enabled_providers.retain(|p| p != "openai");
+        after.budgets.clear();
+        assert_eq!(
+            actions(&config_changes(&before, &after))[..3],
+            [
+                ("provider.removed", "anthropic", ""),
+                ("provider.added", "gemini", ""),
+                ("provider.disabled", "openai", ""),
+            ]
+        );
+    }
+}
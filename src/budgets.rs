@@ -0,0 +1,360 @@
+use crate::config::{AppConfig, Budget, BudgetPeriod, BudgetScope};
+use crate::error::AppError;
+use crate::models::AlertRecord;
+use crate::storage::Storage;
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration as StdDuration, Instant};
+
+/// Alert kind for budget breaches; the subject is the budget name.
+pub const BUDGET_ALERT_KIND: &str = "budget";
+
+/// Spend against one budget for its current period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetStatus {
+    pub budget: Budget,
+    pub spent_usd: f64,
+    pub period_start: DateTime<Utc>,
+}
+
+impl BudgetStatus {
+    pub fn exceeded(&self) -> bool {
+        self.spent_usd > self.budget.amount_usd
+    }
+
+    pub fn message(&self) -> String {
+        format!(
+            "Budget '{}' ({}) exceeded: ${:.2} spent of ${:.2} {}",
+            self.budget.name,
+            String::from(self.budget.scope.clone()),
+            self.spent_usd,
+            self.budget.amount_usd,
+            self.budget.period.as_str()
+        )
+    }
+}
+
+/// Outcome of [`check`]: budgets that went over their limit since the last
+/// check, and the names of budgets whose open alert was resolved.
+#[derive(Debug, Default)]
+pub struct BudgetCheck {
+    pub breached: Vec<(BudgetStatus, AlertRecord)>,
+    pub recovered: Vec<String>,
+}
+
+/// Start of the calendar period containing `now`, in UTC. Weeks start on Monday.
+pub fn period_start(period: BudgetPeriod, now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.date_naive();
+    let day = match period {
+        BudgetPeriod::Daily => today,
+        BudgetPeriod::Weekly => {
+            today - Duration::days(i64::from(today.weekday().num_days_from_monday()))
+        }
+        BudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+    };
+    Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN))
+}
+
+/// Whether `model` carries `tag`: it contains one of the tag's patterns.
+pub fn model_has_tag(tags: &BTreeMap<String, Vec<String>>, tag: &str, model: &str) -> bool {
+    tags.get(tag)
+        .is_some_and(|patterns| patterns.iter().any(|p| model.contains(p.as_str())))
+}
+
+/// Spend against `budget` in the period containing `now`.
+pub fn status(
+    budget: &Budget,
+    tags: &BTreeMap<String, Vec<String>>,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<BudgetStatus, AppError> {
+    let since = period_start(budget.period, now);
+    let spent_usd = match &budget.scope {
+        BudgetScope::Global => storage.cost_since_matching(since, None, None)?,
+        BudgetScope::Provider(name) => storage.cost_since_matching(since, Some(name), None)?,
+        BudgetScope::Model(name) => storage.cost_since_matching(since, None, Some(name))?,
+        BudgetScope::Tag(tag) => storage
+            .cost_by_model_between(since, now + Duration::seconds(1))?
+            .into_iter()
+            .filter(|(model, _)| model_has_tag(tags, tag, model))
+            .map(|(_, cost)| cost)
+            .sum(),
+    };
+    Ok(BudgetStatus {
+        budget: budget.clone(),
+        spent_usd,
+        period_start: since,
+    })
+}
+
+pub fn evaluate(
+    cfg: &AppConfig,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<Vec<BudgetStatus>, AppError> {
+    cfg.budgets
+        .iter()
+        .map(|budget| status(budget, &cfg.tags, storage, now))
+        .collect()
+}
+
+/// Opens a `budget` alert for each budget over its limit and resolves the
+/// alerts of budgets back under it (typically because a new period began).
+/// A budget already alerted on is not reported again until it recovers.
+pub fn check(
+    cfg: &AppConfig,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<BudgetCheck, AppError> {
+    let mut out = BudgetCheck::default();
+    for status in evaluate(cfg, storage, now)? {
+        if status.exceeded() {
+            if let Some(alert) = storage.record_alert(
+                BUDGET_ALERT_KIND,
+                &status.budget.name,
+                &status.message(),
+                now,
+            )? {
+                out.breached.push((status, alert));
+            }
+        } else if storage.resolve_alerts(BUDGET_ALERT_KIND, &status.budget.name, now)? > 0 {
+            out.recovered.push(status.budget.name);
+        }
+    }
+    Ok(out)
+}
+
+/// Environment handed to an `on_exceeded` command.
+pub fn breach_env(status: &BudgetStatus) -> Vec<(&'static str, String)> {
+    let (provider, model, tag) = match &status.budget.scope {
+        BudgetScope::Global => (String::new(), String::new(), String::new()),
+        BudgetScope::Provider(name) => (name.clone(), String::new(), String::new()),
+        BudgetScope::Model(name) => (String::new(), name.clone(), String::new()),
+        BudgetScope::Tag(name) => (String::new(), String::new(), name.clone()),
+    };
+    vec![
+        ("LLM_METER_BUDGET", status.budget.name.clone()),
+        ("LLM_METER_BUDGET_SCOPE", status.budget.scope.clone().into()),
+        ("LLM_METER_BUDGET_PROVIDER", provider),
+        ("LLM_METER_BUDGET_MODEL", model),
+        ("LLM_METER_BUDGET_TAG", tag),
+        (
+            "LLM_METER_BUDGET_PERIOD",
+            status.budget.period.as_str().into(),
+        ),
+        (
+            "LLM_METER_BUDGET_PERIOD_START",
+            status.period_start.to_rfc3339(),
+        ),
+        (
+            "LLM_METER_BUDGET_LIMIT_USD",
+            format!("{:.2}", status.budget.amount_usd),
+        ),
+        (
+            "LLM_METER_BUDGET_SPENT_USD",
+            format!("{:.2}", status.spent_usd),
+        ),
+    ]
+}
+
+/// Runs `command` through the platform shell with the breach environment,
+/// capturing its output so it never draws over the TUI. A command still
+/// running after `timeout` is killed and reported as an error.
+pub fn run_on_exceeded(
+    command: &str,
+    status: &BudgetStatus,
+    timeout: Option<StdDuration>,
+) -> Result<Output, AppError> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let mut child = cmd
+        .envs(breach_env(status))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Drained on their own threads so a chatty command can't fill the pipe
+    // and stall before it exits.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let exit = loop {
+        if let Some(exit) = child.try_wait()? {
+            break exit;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            child.kill()?;
+            child.wait()?;
+            // The readers are left behind: a backgrounded grandchild may
+            // still hold the pipes open.
+            return Err(AppError::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "timed out after {}s (on_exceeded_timeout_secs) and was killed",
+                    timeout.unwrap_or_default().as_secs()
+                ),
+            )));
+        }
+        std::thread::sleep(StdDuration::from_millis(20));
+    };
+    Ok(Output {
+        status: exit,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostRecord, UsageSource};
+    use tempfile::TempDir;
+
+    fn cost(provider: &str, model: &str, at: DateTime<Utc>, total: f64) -> CostRecord {
+        CostRecord {
+            provider: provider.into(),
+            model: model.into(),
+            timestamp: at,
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    #[test]
+    fn periods_start_at_utc_midnight_monday_and_first_of_month() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 12, 15, 30, 0).unwrap();
+        assert_eq!(
+            period_start(BudgetPeriod::Daily, now),
+            Utc.with_ymd_and_hms(2026, 3, 12, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            period_start(BudgetPeriod::Weekly, now),
+            Utc.with_ymd_and_hms(2026, 3, 9, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            period_start(BudgetPeriod::Monthly, now),
+            Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn breaches_alert_once_per_period_and_resolve_on_recovery() {
+        let dir = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&dir.path().join("meter.db")).unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 3, 12, 15, 0, 0).unwrap();
+        storage
+            .replace_snapshot(
+                now - Duration::days(30),
+                &[],
+                &[],
+                &[
+                    cost("openai", "gpt-4o", now, 8.0),
+                    cost("anthropic", "claude", now, 5.0),
+                    cost("openai", "gpt-4o", now - Duration::days(20), 50.0),
+                ],
+            )
+            .unwrap();
+        let cfg = AppConfig {
+            budgets: vec![
+                Budget {
+                    name: "openai-cap".into(),
+                    scope: BudgetScope::Provider("openai".into()),
+                    amount_usd: 7.5,
+                    period: BudgetPeriod::Monthly,
+                    on_exceeded: Some("exit 0".into()),
+                },
+                Budget {
+                    name: "chat".into(),
+                    scope: BudgetScope::Tag("chat".into()),
+                    amount_usd: 12.0,
+                    period: BudgetPeriod::Monthly,
+                    on_exceeded: None,
+                },
+                Budget {
+                    name: "all".into(),
+                    scope: BudgetScope::Global,
+                    amount_usd: 20.0,
+                    period: BudgetPeriod::Monthly,
+                    on_exceeded: None,
+                },
+            ],
+            tags: BTreeMap::from([("chat".into(), vec!["gpt-4o".into(), "claude".into()])]),
+            ..AppConfig::default()
+        };
+
+        let first = check(&cfg, &storage, now).unwrap();
+        assert_eq!(first.breached.len(), 2);
+        assert_eq!(first.breached[1].0.spent_usd, 13.0);
+        let (status, alert) = &first.breached[0];
+        assert_eq!(status.spent_usd, 8.0);
+        assert_eq!(alert.subject, "openai-cap");
+        assert!(breach_env(status).contains(&("LLM_METER_BUDGET_PROVIDER", "openai".into())));
+
+        assert!(check(&cfg, &storage, now).unwrap().breached.is_empty());
+
+        let next_month = Utc.with_ymd_and_hms(2026, 4, 1, 0, 5, 0).unwrap();
+        let recovered = check(&cfg, &storage, next_month).unwrap();
+        assert_eq!(
+            recovered.recovered,
+            vec!["openai-cap".to_string(), "chat".to_string()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn on_exceeded_sees_the_breach_environment() {
+        let status = BudgetStatus {
+            budget: Budget {
+                name: "cap".into(),
+                scope: BudgetScope::Model("gpt-4o".into()),
+                amount_usd: 10.0,
+                period: BudgetPeriod::Daily,
+                on_exceeded: None,
+            },
+            spent_usd: 12.5,
+            period_start: Utc.with_ymd_and_hms(2026, 3, 12, 0, 0, 0).unwrap(),
+        };
+        let output = run_on_exceeded(
+            "echo \"$LLM_METER_BUDGET $LLM_METER_BUDGET_MODEL $LLM_METER_BUDGET_SPENT_USD\"",
+            &status,
+            None,
+        )
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "cap gpt-4o 12.50\n"
+        );
+
+        let started = Instant::now();
+        let err = run_on_exceeded("sleep 5", &status, Some(StdDuration::from_millis(200)))
+            .expect_err("hung command is killed");
+        assert!(err.to_string().contains("timed out"));
+        assert!(started.elapsed() < StdDuration::from_secs(2));
+    }
+}
@@ -0,0 +1,415 @@
+use crate::error::AppError;
+use crate::models::{CostRecord, Granularity, UsageSource};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Words that end the model part of a meter or SKU description, e.g. the
+/// `inp` in `gpt-4o-0513-Inp-regnl` or the `Input` in `Gemini 1.5 Flash Input Text`.
+const MODEL_STOP_WORDS: &[&str] = &[
+    "inp",
+    "input",
+    "outp",
+    "output",
+    "in",
+    "out",
+    "text",
+    "tokens",
+    "token",
+    "1k",
+    "1m",
+    "characters",
+    "images",
+    "audio",
+    "cached",
+];
+
+/// Cloud billing export an import reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BillingSource {
+    /// Azure Cost Management export (CSV), filtered to Azure OpenAI meters.
+    Azure,
+    /// GCP BigQuery billing export (CSV or newline-delimited JSON), filtered
+    /// to Vertex AI SKUs.
+    Gcp,
+}
+
+impl BillingSource {
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        match raw.to_ascii_lowercase().as_str() {
+            "azure" => Ok(Self::Azure),
+            "gcp" | "bigquery" => Ok(Self::Gcp),
+            _ => Err(AppError::Config(
+                "Unsupported billing source. Use azure or gcp.".into(),
+            )),
+        }
+    }
+
+    /// Provider name imported rows are stored under without `--provider`.
+    pub fn default_provider(self) -> &'static str {
+        match self {
+            Self::Azure => "azure-openai",
+            Self::Gcp => "vertex-ai",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Azure => "Azure OpenAI",
+            Self::Gcp => "Vertex AI",
+        }
+    }
+}
+
+/// Cost rows parsed from one export, merged per model and bucket.
+#[derive(Debug, Default)]
+pub struct BillingImport {
+    pub rows: Vec<CostRecord>,
+    /// Line items that matched the source's AI service.
+    pub line_items: usize,
+    /// Line items for other services.
+    pub skipped: usize,
+}
+
+impl BillingImport {
+    /// First bucket start and last bucket end, or `None` when nothing matched.
+    pub fn span(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = self.rows.iter().map(|r| r.timestamp).min()?;
+        let end = self
+            .rows
+            .iter()
+            .map(|r| r.bucket_end.unwrap_or(r.timestamp))
+            .max()?;
+        Some((start, end))
+    }
+}
+
+type Record = BTreeMap<String, String>;
+
+/// Parses a billing export into USD cost rows for `provider`. Amounts billed
+/// in another currency are divided by `usd_rate` (units of that currency per
+/// USD, as with `[display] usd_rate`).
+pub fn parse(
+    source: BillingSource,
+    reader: impl Read,
+    provider: &str,
+    usd_rate: Option<f64>,
+) -> Result<BillingImport, AppError> {
+    let mut merged: BTreeMap<(String, DateTime<Utc>), CostRecord> = BTreeMap::new();
+    let mut out = BillingImport::default();
+    let mut foreign_currency: Option<String> = None;
+
+    for (idx, record) in read_records(reader)?.iter().enumerate() {
+        let line = idx + 1;
+        let matches = match source {
+            BillingSource::Azure => [
+                "metersubcategory",
+                "metercategory",
+                "servicename",
+                "productname",
+                "product",
+            ]
+            .iter()
+            .filter_map(|name| record.get(*name))
+            .any(|v| v.to_ascii_lowercase().contains("openai")),
+            BillingSource::Gcp => field(record, &["service.description", "service_description"])
+                .is_some_and(|v| v.to_ascii_lowercase().contains("vertex")),
+        };
+        if !matches {
+            out.skipped += 1;
+            continue;
+        }
+
+        let bad = |what: &str| {
+            AppError::Config(format!(
+                "{} export line item {line}: missing or invalid {what}.",
+                source.label()
+            ))
+        };
+        let (description, start, end, width) = match source {
+            BillingSource::Azure => {
+                let description =
+                    field(record, &["metername", "meter"]).ok_or_else(|| bad("MeterName"))?;
+                let day = field(record, &["date", "usagedatetime"])
+                    .and_then(parse_date)
+                    .ok_or_else(|| bad("Date"))?;
+                let start = Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN));
+                (
+                    description,
+                    start,
+                    start + Duration::days(1),
+                    Granularity::Day,
+                )
+            }
+            BillingSource::Gcp => {
+                let description = field(record, &["sku.description", "sku_description"])
+                    .ok_or_else(|| bad("sku.description"))?;
+                let start = field(record, &["usage_start_time"])
+                    .and_then(parse_time)
+                    .ok_or_else(|| bad("usage_start_time"))?;
+                let end = field(record, &["usage_end_time"])
+                    .and_then(parse_time)
+                    .filter(|end| *end > start)
+                    .unwrap_or(start + Duration::hours(1));
+                let width = match end - start {
+                    d if d <= Duration::minutes(1) => Granularity::Minute,
+                    d if d <= Duration::hours(1) => Granularity::Hour,
+                    _ => Granularity::Day,
+                };
+                (description, start, end, width)
+            }
+        };
+        let amount: f64 = field(
+            record,
+            &["costinbillingcurrency", "cost", "pretaxcost", "billedcost"],
+        )
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| bad("cost"))?;
+        let currency = field(
+            record,
+            &[
+                "billingcurrencycode",
+                "billingcurrency",
+                "currency",
+                "currencycode",
+            ],
+        )
+        .unwrap_or("USD")
+        .trim()
+        .to_ascii_uppercase();
+
+        let amount_usd = if currency == "USD" {
+            amount
+        } else {
+            if foreign_currency.get_or_insert_with(|| currency.clone()) != &currency {
+                return Err(AppError::Config(format!(
+                    "{} export mixes billing currencies; import one currency at a time.",
+                    source.label()
+                )));
+            }
+            match usd_rate {
+                Some(rate) if rate.is_finite() && rate > 0.0 => amount / rate,
+                _ => {
+                    return Err(AppError::Config(format!(
+                        "Line items are billed in {currency}; pass --usd-rate ({currency} per USD) to convert them."
+                    )))
+                }
+            }
+        };
+
+        let (model, output) = model_and_direction(description);
+        let row = merged
+            .entry((model.clone(), start))
+            .or_insert_with(|| CostRecord {
+                provider: provider.to_string(),
+                model,
+                input_cost: 0.0,
+                output_cost: 0.0,
+                total_cost: 0.0,
+                currency: "USD".into(),
+                timestamp: start,
+                bucket_end: Some(end),
+                source_granularity: Some(width),
+                source: UsageSource::Import,
+            });
+        if output {
+            row.output_cost += amount_usd;
+        } else {
+            row.input_cost += amount_usd;
+        }
+        row.total_cost += amount_usd;
+        out.line_items += 1;
+    }
+
+    out.rows = merged.into_values().collect();
+    Ok(out)
+}
+
+/// CSV rows keyed by lowercased header, or JSON objects (an array or one per
+/// line, as BigQuery exports them) flattened to lowercased dotted keys.
+fn read_records(mut reader: impl Read) -> Result<Vec<Record>, AppError> {
+    let mut raw = String::new();
+    reader.read_to_string(&mut raw)?;
+    let raw = raw.trim_start_matches('\u{feff}');
+    let trimmed = raw.trim_start();
+
+    if trimmed.starts_with('[') {
+        let items: Vec<Value> = serde_json::from_str(trimmed)?;
+        return Ok(items.iter().map(flatten).collect());
+    }
+    if trimmed.starts_with('{') {
+        return trimmed
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Ok(flatten(&serde_json::from_str(l)?)))
+            .collect();
+    }
+
+    let mut csv = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(raw.as_bytes());
+    let headers: Vec<String> = csv
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .collect();
+    let mut records = Vec::new();
+    for record in csv.records() {
+        let record = record?;
+        records.push(
+            headers
+                .iter()
+                .zip(record.iter())
+                .map(|(h, v)| (h.clone(), v.to_string()))
+                .collect(),
+        );
+    }
+    Ok(records)
+}
+
+fn flatten(value: &Value) -> Record {
+    fn walk(prefix: &str, value: &Value, out: &mut Record) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let key = key.to_ascii_lowercase();
+                    let path = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    walk(&path, value, out);
+                }
+            }
+            Value::String(s) => {
+                out.insert(prefix.to_string(), s.clone());
+            }
+            Value::Number(n) => {
+                out.insert(prefix.to_string(), n.to_string());
+            }
+            _ => {}
+        }
+    }
+    let mut out = Record::new();
+    walk("", value, &mut out);
+    out
+}
+
+/// The first of `names` with a non-blank value.
+fn field<'a>(record: &'a Record, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .filter_map(|name| record.get(*name))
+        .map(|v| v.trim())
+        .find(|v| !v.is_empty())
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    let raw = raw.trim();
+    parse_time(raw)
+        .map(|t| t.date_naive())
+        .or_else(|| NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok())
+        .or_else(|| NaiveDate::parse_from_str(raw, "%m/%d/%Y").ok())
+}
+
+/// RFC 3339, or BigQuery's `2024-06-01 00:00:00 UTC`.
+fn parse_time(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(raw) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    let naive = raw.trim_end_matches(" UTC");
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(naive, fmt).ok())
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+/// Model id and whether the line is output, from a meter or SKU description:
+/// `gpt-4o-0513-Outp-glbl 1M Tokens` is `gpt-4o-0513` output, `Gemini 1.5
+/// Flash Input Text` is `gemini-1.5-flash` input. Lines that name neither
+/// direction count as input.
+fn model_and_direction(description: &str) -> (String, bool) {
+    let lower = description.to_ascii_lowercase();
+    let parts: Vec<&str> = lower
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|p| !p.is_empty())
+        .collect();
+    let output = parts
+        .iter()
+        .any(|p| matches!(*p, "output" | "outp" | "out"));
+    let model: Vec<&str> = parts
+        .iter()
+        .copied()
+        .take_while(|p| !MODEL_STOP_WORDS.contains(p))
+        .collect();
+    let model = if model.is_empty() {
+        lower.trim().to_string()
+    } else {
+        model.join("-")
+    };
+    (model, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn azure_export_keeps_openai_meters_and_converts_currency() {
+        let csv = "\u{feff}Date,MeterCategory,MeterSubCategory,MeterName,CostInBillingCurrency,BillingCurrencyCode
+06/01/2024,Cognitive Services,Azure OpenAI,gpt-4o-0513-Inp-glbl 1M Tokens,4.00,EUR
+06/01/2024,Cognitive Services,Azure OpenAI,gpt-4o-0513-Outp-glbl 1M Tokens,2.00,EUR
+06/02/2024,Cognitive Services,Azure OpenAI,gpt-4o-0513-Inp-glbl 1M Tokens,1.00,EUR
+06/01/2024,Storage,Blob,Hot LRS,9.00,EUR
+";
+        let import = parse(
+            BillingSource::Azure,
+            csv.as_bytes(),
+            "azure-openai",
+            Some(0.5),
+        )
+        .expect("parse");
+        assert_eq!((import.line_items, import.skipped), (3, 1));
+        assert_eq!(import.rows.len(), 2);
+        let first = &import.rows[0];
+        assert_eq!(first.model, "gpt-4o-0513");
+        assert_eq!(
+            (first.input_cost, first.output_cost, first.total_cost),
+            (8.0, 4.0, 12.0)
+        );
+        assert_eq!(
+            first.timestamp,
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            import.span(),
+            Some((
+                Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap()
+            ))
+        );
+
+        let err = parse(BillingSource::Azure, csv.as_bytes(), "azure-openai", None).unwrap_err();
+        assert!(err.to_string().contains("pass --usd-rate (EUR per USD)"));
+    }
+
+    #[test]
+    fn bigquery_json_lines_keep_vertex_skus_at_their_hour() {
+        let json = r#"{"service":{"description":"Vertex AI"},"sku":{"description":"Gemini 1.5 Flash Input Text"},"usage_start_time":"2024-06-01 10:00:00 UTC","usage_end_time":"2024-06-01 11:00:00 UTC","cost":0.25,"currency":"USD"}
+{"service":{"description":"Vertex AI"},"sku":{"description":"Gemini 1.5 Flash Output Text"},"usage_start_time":"2024-06-01 10:00:00 UTC","usage_end_time":"2024-06-01 11:00:00 UTC","cost":0.5,"currency":"USD"}
+{"service":{"description":"Compute Engine"},"sku":{"description":"N2 Instance Core"},"usage_start_time":"2024-06-01 10:00:00 UTC","cost":3,"currency":"USD"}
+"#;
+        let import = parse(BillingSource::Gcp, json.as_bytes(), "vertex-ai", None).expect("parse");
+        assert_eq!((import.line_items, import.skipped), (2, 1));
+        let row = &import.rows[0];
+        assert_eq!(row.model, "gemini-1.5-flash");
+        assert_eq!((row.input_cost, row.output_cost), (0.25, 0.5));
+        assert_eq!(row.source_granularity, Some(Granularity::Hour));
+        assert_eq!(
+            row.bucket_end,
+            Some(Utc.with_ymd_and_hms(2024, 6, 1, 11, 0, 0).unwrap())
+        );
+    }
+}
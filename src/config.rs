@@ -1,30 +1,125 @@
+use crate::audit;
 use crate::error::AppError;
+use crate::models::{EdgeBuckets, Granularity};
+use crate::pricing::ModelDeprecation;
+use chrono::NaiveDate;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 pub const SERVICE_NAME: &str = "llm-meter";
 
+/// Profile used when neither `--profile` nor `LLM_METER_PROFILE` names one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+static DB_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static PROFILE: OnceLock<String> = OnceLock::new();
+
 pub fn normalize_provider_name(provider: &str) -> String {
     provider.trim().to_ascii_lowercase()
 }
 
-fn app_home_dir() -> Result<PathBuf, AppError> {
+const DB_FILE_NAMES: [&str; 3] = [
+    "snapshots.sqlite",
+    "snapshots.sqlite-wal",
+    "snapshots.sqlite-shm",
+];
+
+struct AppDirs {
+    config: PathBuf,
+    data: PathBuf,
+    /// Pre-XDG home (`<data_local>/config` + `<data_local>/data`) to migrate from.
+    legacy_home: Option<PathBuf>,
+}
+
+impl AppDirs {
+    fn under_home(home: PathBuf) -> Self {
+        Self {
+            config: home.join("config"),
+            data: home.join("data"),
+            legacy_home: None,
+        }
+    }
+
+    /// A non-default profile keeps its files under `profiles/<name>` of the
+    /// default profile's directories, and has no pre-XDG layout to migrate.
+    fn for_profile(self, profile: &str) -> Self {
+        if profile == DEFAULT_PROFILE {
+            return self;
+        }
+        Self {
+            config: self.config.join("profiles").join(profile),
+            data: self.data.join("profiles").join(profile),
+            legacy_home: None,
+        }
+    }
+}
+
+fn app_dirs() -> Result<AppDirs, AppError> {
+    Ok(base_app_dirs()?.for_profile(&profile()))
+}
+
+fn base_app_dirs() -> Result<AppDirs, AppError> {
+    // Unit tests that save config or write the audit log stay out of the
+    // user's real directories.
+    if cfg!(test) {
+        return Ok(AppDirs::under_home(
+            std::env::temp_dir().join(format!("llm-meter-test-{}", std::process::id())),
+        ));
+    }
     if let Ok(custom) = std::env::var("LLM_METER_HOME") {
-        return Ok(PathBuf::from(custom));
+        return Ok(AppDirs::under_home(PathBuf::from(custom)));
     }
 
     if let Some(dirs) = ProjectDirs::from("com", "neubell", SERVICE_NAME) {
-        let candidate = dirs.data_local_dir().to_path_buf();
-        if fs::create_dir_all(&candidate).is_ok() {
-            return Ok(candidate);
+        let config = dirs.config_dir().to_path_buf();
+        let data = dirs.data_local_dir().to_path_buf();
+        if fs::create_dir_all(&config).is_ok() && fs::create_dir_all(&data).is_ok() {
+            return Ok(AppDirs {
+                config,
+                legacy_home: Some(data.clone()),
+                data,
+            });
         }
     }
 
     let cwd = std::env::current_dir()?;
-    Ok(cwd.join(".llm-meter"))
+    Ok(AppDirs::under_home(cwd.join(".llm-meter")))
+}
+
+/// Moves files from the pre-XDG layout into the split config/data dirs. Files
+/// already present at the destination win; returns whether anything moved.
+fn migrate_legacy_layout(legacy_home: &Path, config: &Path, data: &Path) -> Result<bool, AppError> {
+    let mut moves = vec![(
+        legacy_home.join("config").join("config.toml"),
+        config.join("config.toml"),
+    )];
+    for name in DB_FILE_NAMES {
+        moves.push((legacy_home.join("data").join(name), data.join(name)));
+    }
+
+    let mut moved = false;
+    for (from, to) in moves {
+        if !from.is_file() || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::rename(&from, &to).is_err() {
+            fs::copy(&from, &to)?;
+            fs::remove_file(&from)?;
+        }
+        moved = true;
+    }
+
+    // Only removes the legacy subdirectories once they are empty.
+    let _ = fs::remove_dir(legacy_home.join("config"));
+    let _ = fs::remove_dir(legacy_home.join("data"));
+    Ok(moved)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,12 +128,582 @@ pub struct AppConfig {
     pub enabled_providers: Vec<String>,
     pub provider_settings: HashMap<String, ProviderSettings>,
     pub pricing_overrides: Vec<PricingOverride>,
+    #[serde(default)]
+    pub accessible: bool,
+    #[serde(default)]
+    pub notifier: NotifierConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database_path: Option<String>,
+    /// SQLite journal for writable opens; `delete` for databases on network
+    /// mounts, where WAL's shared-memory index doesn't work.
+    #[serde(default)]
+    pub database_journal_mode: JournalMode,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    /// Consecutive failed refreshes before a provider is quarantined; 0 disables.
+    #[serde(default = "default_quarantine_after_failures")]
+    pub quarantine_after_failures: u32,
+    /// Bucket size usage is resampled to before storage (`hour` or `day`).
+    #[serde(default)]
+    pub usage_granularity: Granularity,
+    /// Fetch the 30d window at 1d-window bucket width on every refresh, so
+    /// switching windows never needs another fetch.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub refresh_all_windows: bool,
+    /// Fetch from providers as soon as the TUI starts. When off, it opens on
+    /// stored data and the first fetch waits for `r` or the first tick.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub startup_refresh: bool,
+    /// Treatment of buckets straddling the start of a window in its totals.
+    #[serde(default)]
+    pub edge_buckets: EdgeBuckets,
+    /// DogStatsD sink fed after every successful refresh.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsConfig>,
+    /// `[[exports]]` pipelines run by `exports run`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exports: Vec<ExportPipeline>,
+    /// `[[budgets]]` spend limits checked after every refresh.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub budgets: Vec<Budget>,
+    /// Seconds a budget's `on_exceeded` command may run before it is killed
+    /// and reported as failed; `0` waits indefinitely.
+    #[serde(default = "default_on_exceeded_timeout_secs")]
+    pub on_exceeded_timeout_secs: u64,
+    /// `[tags]`: tag name to model substrings, matched like `model_pattern`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, Vec<String>>,
+    /// `[model_groups]`: family name to model id globs (`*` matches any run
+    /// of characters), e.g. `"gpt-4o" = ["gpt-4o", "gpt-4o-2024-*"]`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_groups: BTreeMap<String, Vec<String>>,
+    /// Days before a provider's `key_expires_at` the dashboard starts warning.
+    #[serde(default = "default_key_expiry_warning_days")]
+    pub key_expiry_warning_days: u32,
+    /// `[[deprecations]]`: retirement dates added to, or replacing, the
+    /// built-in list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deprecations: Vec<ModelDeprecation>,
+    /// Days before a model's `deprecated_on` its spend starts being flagged.
+    #[serde(default = "default_deprecation_warning_days")]
+    pub deprecation_warning_days: u32,
+    /// Seconds the local clock may differ from a provider's `Date` header
+    /// before refreshes warn about it.
+    #[serde(default = "default_clock_skew_warning_secs")]
+    pub clock_skew_warning_secs: u64,
+    /// Seconds one refresh may spend calling providers. Accounts not done
+    /// by then keep their stored rows until the next refresh; `0` waits
+    /// indefinitely.
+    #[serde(default = "default_refresh_timeout_secs")]
+    pub refresh_timeout_secs: u64,
+    /// Open the database with the SQLCipher key kept in the keyring. Toggled
+    /// by `db encrypt` / `db decrypt`, which also convert the file.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypt_database: bool,
+    /// Secret mixed into `--redact` hashes so common model names can't be
+    /// recovered by hashing a list of them. Generated on first use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redact_salt: Option<String>,
+    /// Units of each currency per USD, e.g. `EUR = 0.92`, used to convert
+    /// costs stored in a provider's `billing_currency` when aggregating.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fx_rates: BTreeMap<String, f64>,
+    /// Limits on the provider API calls llm-meter itself sends.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_caps: Option<RequestCaps>,
+    /// Refuse outbound HTTP to anything but the configured providers' hosts,
+    /// including webhooks, notifiers and export uploads.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub local_only: bool,
+}
+
+fn default_quarantine_after_failures() -> u32 {
+    3
+}
+
+fn default_key_expiry_warning_days() -> u32 {
+    14
+}
+
+fn default_deprecation_warning_days() -> u32 {
+    90
+}
+
+fn default_clock_skew_warning_secs() -> u64 {
+    120
+}
+
+fn default_refresh_timeout_secs() -> u64 {
+    120
+}
+
+fn default_on_exceeded_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub currency: String,
+    pub locale: Option<String>,
+    /// Units of `currency` per 1 USD; required for non-USD currencies.
+    pub usd_rate: Option<f64>,
+    /// Show cost for today, 7d and 30d side by side above the panels.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub multi_window_kpis: bool,
+    /// Start with share-of-spend gauges instead of the provider/model tables.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub share_gauges: bool,
+    /// Start with `[model_groups]` families in the Top Models panel.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub group_models: bool,
+    /// Columns of the provider and model tables, after the name.
+    #[serde(skip_serializing_if = "is_default_breakdown_columns")]
+    pub breakdown_columns: Vec<BreakdownColumn>,
+    /// Providers listed first in the provider panel, in this order, whatever
+    /// their spend.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned_providers: Vec<String>,
+    /// Models (or `[model_groups]` families, when grouped) listed first in the
+    /// model panel, even outside the top 10 or without spend in the window.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned_models: Vec<String>,
+}
+
+/// A column of the provider and model breakdown tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakdownColumn {
+    Cost,
+    /// Input plus output tokens.
+    Tokens,
+    /// Input and output tokens side by side.
+    InputOutput,
+    /// Percentage of the window's spend.
+    Share,
+    /// Cost change against the previous window of the same length.
+    Delta,
+}
+
+impl BreakdownColumn {
+    pub const ALL: [BreakdownColumn; 5] = [
+        BreakdownColumn::Cost,
+        BreakdownColumn::Tokens,
+        BreakdownColumn::InputOutput,
+        BreakdownColumn::Share,
+        BreakdownColumn::Delta,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BreakdownColumn::Cost => "Cost",
+            BreakdownColumn::Tokens => "Tokens",
+            BreakdownColumn::InputOutput => "In / Out",
+            BreakdownColumn::Share => "Share",
+            BreakdownColumn::Delta => "Δ prev",
+        }
+    }
+}
+
+fn default_breakdown_columns() -> Vec<BreakdownColumn> {
+    vec![BreakdownColumn::Cost]
+}
+
+fn is_default_breakdown_columns(columns: &[BreakdownColumn]) -> bool {
+    columns == default_breakdown_columns()
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            currency: "USD".into(),
+            locale: None,
+            usd_rate: None,
+            multi_window_kpis: false,
+            share_gauges: false,
+            group_models: false,
+            breakdown_columns: default_breakdown_columns(),
+            pinned_providers: Vec::new(),
+            pinned_models: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifierConfig {
+    pub pagerduty: Option<PagerDutyConfig>,
+    pub opsgenie: Option<OpsgenieConfig>,
+    pub slack: Option<SlackConfig>,
+    /// Signed JSON summary POSTed after every successful refresh.
+    pub webhook: Option<WebhookConfig>,
+    /// InfluxDB / Telegraf write endpoint that receives the refresh's cost points.
+    pub influx: Option<InfluxConfig>,
+    pub warning_severity: String,
+    pub critical_severity: String,
+    pub warning_priority: String,
+    pub critical_priority: String,
+    /// Raise a warning when one refresh adds at least this much cost (USD).
+    pub refresh_delta_alert_usd: Option<f64>,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            pagerduty: None,
+            opsgenie: None,
+            slack: None,
+            webhook: None,
+            influx: None,
+            warning_severity: "warning".into(),
+            critical_severity: "critical".into(),
+            warning_priority: "P3".into(),
+            critical_priority: "P1".into(),
+            refresh_delta_alert_usd: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// DogStatsD agent address (`host:port`).
+    pub address: String,
+    /// Prepended to every metric name, e.g. `llm_meter.cost`.
+    pub prefix: String,
+    /// Extra `key:value` tags added to every metric.
+    pub tags: Vec<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:8125".into(),
+            prefix: "llm_meter".into(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// `[request_caps]`: how many provider API calls refreshes and backfills may
+/// send, so llm-meter does not use up the admin APIs' rate limits itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestCaps {
+    /// Most requests one refresh or backfill sends, across its accounts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_refresh: Option<u32>,
+    /// Most requests per UTC day, counted across processes in the database.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_day: Option<u32>,
+}
+
+/// A USD spend limit over a calendar period (UTC).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Budget {
+    pub name: String,
+    #[serde(default)]
+    pub scope: BudgetScope,
+    pub amount_usd: f64,
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Shell command run once when the budget is first exceeded in a period,
+    /// with `LLM_METER_BUDGET_*` variables describing the breach.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_exceeded: Option<String>,
+}
+
+/// What a budget counts: `global`, `provider:<name>`, `model:<name>` or
+/// `tag:<name>` (models listed under that name in `[tags]`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BudgetScope {
+    #[default]
+    Global,
+    Provider(String),
+    Model(String),
+    Tag(String),
+}
+
+impl TryFrom<String> for BudgetScope {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let raw = raw.trim();
+        match raw.split_once(':') {
+            None if raw.eq_ignore_ascii_case("global") => Ok(Self::Global),
+            Some(("provider", name)) if !name.trim().is_empty() => {
+                Ok(Self::Provider(normalize_provider_name(name)))
+            }
+            Some(("model", name)) if !name.trim().is_empty() => {
+                Ok(Self::Model(name.trim().to_string()))
+            }
+            Some(("tag", name)) if !name.trim().is_empty() => Ok(Self::Tag(name.trim().to_string())),
+            _ => Err(format!(
+                "invalid budget scope '{raw}'; use global, provider:<name>, model:<name> or tag:<name>"
+            )),
+        }
+    }
+}
+
+impl From<BudgetScope> for String {
+    fn from(scope: BudgetScope) -> Self {
+        match scope {
+            BudgetScope::Global => "global".into(),
+            BudgetScope::Provider(name) => format!("provider:{name}"),
+            BudgetScope::Model(name) => format!("model:{name}"),
+            BudgetScope::Tag(name) => format!("tag:{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    Daily,
+    Weekly,
+    #[default]
+    Monthly,
+}
+
+impl BudgetPeriod {
+    pub const ALL: [Self; 3] = [Self::Daily, Self::Weekly, Self::Monthly];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+}
+
+/// One `[[exports]]` entry: all cost rows rendered as `format` and handed to
+/// the named sink. Sink-specific fields are ignored by other sinks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportPipeline {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `file`, `s3`, `http`, `webhook`, `influx` or `google-sheets`.
+    pub sink: String,
+    #[serde(default = "default_export_format")]
+    pub format: String,
+    /// Path, `s3://bucket/prefix/` or URL, depending on the sink. May carry
+    /// `{date}`-style placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// `manual` (default), `hourly`, `daily`, `weekly` or a 5-field cron
+    /// expression, in UTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(default = "default_true")]
+    pub header: bool,
+    #[serde(default)]
+    pub quote_all: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sheet_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u32>,
+    /// Replace provider and model names with stable hashes.
+    #[serde(default)]
+    pub redact: bool,
+    /// Replace model ids with their `[model_groups]` family.
+    #[serde(default)]
+    pub group_models: bool,
+}
+
+fn default_export_format() -> String {
+    "json".into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl ExportPipeline {
+    pub fn new(sink: &str, format: &str) -> Self {
+        Self {
+            name: None,
+            sink: sink.into(),
+            format: format.into(),
+            to: None,
+            schedule: None,
+            delimiter: None,
+            header: true,
+            quote_all: false,
+            sheet_id: None,
+            credentials: None,
+            range: None,
+            days: None,
+            redact: false,
+            group_models: false,
+        }
+    }
+
+    /// `name`, or `sink:to` when unnamed.
+    pub fn label(&self) -> String {
+        match (&self.name, &self.to) {
+            (Some(name), _) => name.clone(),
+            (None, Some(to)) => format!("{}:{to}", self.sink),
+            (None, None) => self.sink.clone(),
+        }
+    }
+
+    pub fn schedule(&self) -> &str {
+        self.schedule.as_deref().unwrap_or("manual")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PagerDutyConfig {
+    pub routing_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackConfig {
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfluxConfig {
+    /// Full write URL, e.g. `http://localhost:8086/api/v2/write?org=acme&bucket=llm&precision=ns`.
+    pub write_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OpsgenieConfig {
+    pub api_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProviderSettings {
     pub base_url: Option<String>,
     pub organization_id: Option<String>,
+    /// Usage bucket size (`1m`, `1h`, `1d`) for providers that support it.
+    /// Defaults to `1h` up to 7-day lookbacks and `1d` beyond.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_width: Option<String>,
+    /// OpenAI project whose per-model rate limits are read on refresh, or
+    /// the Google Cloud project `gemini` reads usage from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// Azure resource ID of the Azure OpenAI resource `azure-openai` reads
+    /// usage for, `/subscriptions/<id>/resourceGroups/<group>/providers/Microsoft.CognitiveServices/accounts/<name>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    /// Azure Monitor metrics `api-version` for `azure-openai`; defaults to
+    /// `2023-10-01`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// Model behind each `azure-openai` deployment, by deployment name, for
+    /// pricing. Deployments not listed keep their own name as the model.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deployments: BTreeMap<String, String>,
+    /// Profile in `~/.aws/credentials` a `bedrock` account signs with;
+    /// without one, the `AWS_*` variables or the default profile apply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aws_profile: Option<String>,
+    /// AWS region whose `bedrock` metrics to read; defaults to the profile's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Whether `bedrock` takes costs from Cost Explorer instead of pricing
+    /// token counts. Each Cost Explorer request is billed by AWS.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cost_explorer: bool,
+    /// ISO code of the currency this account is billed in, e.g. `EUR`;
+    /// unset means USD. Needs a matching `[fx_rates]` entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing_currency: Option<String>,
+    /// Adapter (`openai`, `anthropic`, `custom-openai-compatible`, ...)
+    /// behind an account whose name is not one, e.g. after
+    /// `rename-provider openai openai-prod`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Name the dashboard and Provider Manager show instead of the account
+    /// name, e.g. `vLLM prod`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Who the key belongs to, for shared installs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Free-form notes, e.g. where the key came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Day the stored key stops working (`YYYY-MM-DD`), for rotation reminders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_expires_at: Option<NaiveDate>,
+    /// Environment variable the key is read from when the keychain has none,
+    /// instead of `<PROVIDER>_API_KEY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Most requests this account may have in flight at once; unset or 0
+    /// means no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<u32>,
+    /// Minimum gap between the starts of two requests, e.g. between pages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_request_interval_ms: Option<u64>,
+    /// JSON-lines logs or directories of them read by the local-log
+    /// adapters (`local-session`, `claude-code`, `codex-cli`); `~/` expands.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_paths: Vec<String>,
+    /// API accounts that also report this account's traffic, e.g.
+    /// `["anthropic"]` for `claude-code`; see [`OverlapPolicy`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_overlap: Option<OverlapPolicy>,
+}
+
+/// What an account does with rows an account in its `overlaps` already
+/// reports: buckets of that account's vendor ending before the API's
+/// freshness horizon, the earlier of its latest bucket end and its last
+/// successful refresh.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlapPolicy {
+    /// Drop them, so the API's numbers stand and nothing counts twice.
+    #[default]
+    PreferApi,
+    /// Store them anyway and only report the overlap.
+    KeepBoth,
+}
+
+/// Journal mode the database is switched to on every writable open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    /// Write-ahead log: readers and the writer don't block each other.
+    /// Needs every process on the same host.
+    #[default]
+    Wal,
+    /// Rollback journal: slower under concurrent use, but safe on shared
+    /// or network filesystems.
+    Delete,
+}
+
+impl JournalMode {
+    pub fn pragma(self) -> &'static str {
+        match self {
+            Self::Wal => "WAL",
+            Self::Delete => "DELETE",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,29 +721,266 @@ impl Default for AppConfig {
             enabled_providers: vec![],
             provider_settings: HashMap::new(),
             pricing_overrides: vec![],
+            accessible: false,
+            notifier: NotifierConfig::default(),
+            database_path: None,
+            database_journal_mode: JournalMode::default(),
+            display: DisplayConfig::default(),
+            quarantine_after_failures: default_quarantine_after_failures(),
+            usage_granularity: Granularity::default(),
+            refresh_all_windows: false,
+            startup_refresh: true,
+            edge_buckets: EdgeBuckets::default(),
+            metrics: None,
+            exports: Vec::new(),
+            budgets: Vec::new(),
+            on_exceeded_timeout_secs: default_on_exceeded_timeout_secs(),
+            tags: BTreeMap::new(),
+            model_groups: BTreeMap::new(),
+            key_expiry_warning_days: default_key_expiry_warning_days(),
+            deprecations: Vec::new(),
+            deprecation_warning_days: default_deprecation_warning_days(),
+            clock_skew_warning_secs: default_clock_skew_warning_secs(),
+            refresh_timeout_secs: default_refresh_timeout_secs(),
+            encrypt_database: false,
+            redact_salt: None,
+            fx_rates: BTreeMap::new(),
+            request_caps: None,
+            local_only: false,
         }
     }
 }
 
+impl AppConfig {
+    /// The `redact_salt`, generating one if unset. Returns whether the config
+    /// changed and needs saving.
+    pub fn ensure_redact_salt(&mut self) -> Result<bool, AppError> {
+        if self.redact_salt.is_some() {
+            return Ok(false);
+        }
+        self.redact_salt = Some(random_hex(16)?);
+        Ok(true)
+    }
+
+    /// Family `model` belongs to under `[model_groups]`, or `model` itself.
+    /// Groups are tried in name order; the first with a matching glob wins.
+    pub fn model_family<'a>(&'a self, model: &'a str) -> &'a str {
+        self.model_groups
+            .iter()
+            .find(|(_, globs)| globs.iter().any(|g| glob_matches(g, model)))
+            .map_or(model, |(family, _)| family.as_str())
+    }
+
+    /// Adapter name for the `provider` account: its `kind`, else its own name.
+    pub fn provider_kind(&self, provider: &str) -> String {
+        let provider = normalize_provider_name(provider);
+        self.provider_settings
+            .get(&provider)
+            .and_then(|s| s.kind.as_deref())
+            .map_or(provider.clone(), normalize_provider_name)
+    }
+
+    /// Name shown for `provider`: its `display_name`, else the account name.
+    pub fn provider_label(&self, provider: &str) -> String {
+        self.provider_settings
+            .get(&normalize_provider_name(provider))
+            .and_then(|s| s.display_name.clone())
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| provider.to_string())
+    }
+
+    /// Providers with a `key_expires_at`, with the days left on `today`
+    /// (negative once expired), soonest first.
+    pub fn key_expiries(&self, today: NaiveDate) -> Vec<(String, i64)> {
+        let mut out: Vec<(String, i64)> = self
+            .provider_settings
+            .iter()
+            .filter_map(|(name, s)| {
+                s.key_expires_at
+                    .map(|at| (name.clone(), (at - today).num_days()))
+            })
+            .collect();
+        out.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        out
+    }
+
+    /// Keys expiring within `key_expiry_warning_days` of `today`, or expired.
+    pub fn expiring_keys(&self, today: NaiveDate) -> Vec<(String, i64)> {
+        let window = i64::from(self.key_expiry_warning_days);
+        self.key_expiries(today)
+            .into_iter()
+            .filter(|(_, days)| *days <= window)
+            .collect()
+    }
+
+    /// Moves the `from` account's settings, enabled state and provider-scoped
+    /// budgets to `to`, recording the adapter as `kind` so it keeps fetching.
+    pub fn rename_provider(&mut self, from: &str, to: &str) -> Result<(), AppError> {
+        let (from, to) = (normalize_provider_name(from), normalize_provider_name(to));
+        if to.is_empty() || to.contains(char::is_whitespace) {
+            return Err(AppError::Config(format!(
+                "'{to}' is not a valid provider name."
+            )));
+        }
+        let known = |name: &str| {
+            self.provider_settings.contains_key(name)
+                || self.enabled_providers.iter().any(|p| p == name)
+        };
+        if !known(&from) {
+            return Err(AppError::Config(format!(
+                "Provider '{from}' is not configured."
+            )));
+        }
+        if from == to || known(&to) {
+            return Err(AppError::Config(format!("Provider '{to}' already exists.")));
+        }
+
+        let kind = self.provider_kind(&from);
+        let mut settings = self.provider_settings.remove(&from).unwrap_or_default();
+        settings.kind = (kind != to).then_some(kind);
+        self.provider_settings.insert(to.clone(), settings);
+        for provider in &mut self.enabled_providers {
+            if *provider == from {
+                provider.clone_from(&to);
+            }
+        }
+        for budget in &mut self.budgets {
+            if matches!(&budget.scope, BudgetScope::Provider(name) if normalize_provider_name(name) == from)
+            {
+                budget.scope = BudgetScope::Provider(to.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whole-string match where `*` stands for any run of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
 pub fn config_dir() -> Result<PathBuf, AppError> {
-    Ok(app_home_dir()?.join("config"))
+    Ok(app_dirs()?.config)
 }
 
 pub fn data_dir() -> Result<PathBuf, AppError> {
-    Ok(app_home_dir()?.join("data"))
+    Ok(app_dirs()?.data)
+}
+
+/// User override for the `notify digest` template.
+pub fn digest_template_path() -> Result<PathBuf, AppError> {
+    Ok(config_dir()?.join("templates").join("digest.txt"))
 }
 
 pub fn config_path() -> Result<PathBuf, AppError> {
     Ok(config_dir()?.join("config.toml"))
 }
 
+/// Selects the profile for this process (the `--profile` flag, else
+/// `LLM_METER_PROFILE`). Names are lower-cased and may hold letters, digits,
+/// `-` and `_`.
+pub fn set_profile(name: &str) -> Result<(), AppError> {
+    let _ = PROFILE.set(profile_name(name)?);
+    Ok(())
+}
+
+fn profile_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim().to_ascii_lowercase();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(AppError::Config(format!(
+            "Invalid profile name '{name}': use letters, digits, '-' and '_'."
+        )));
+    }
+    Ok(name)
+}
+
+/// The profile this process runs as; see [`set_profile`].
+pub fn profile() -> String {
+    PROFILE
+        .get()
+        .map_or(DEFAULT_PROFILE, String::as_str)
+        .to_string()
+}
+
+/// Sets the database path for this process (the `--db` flag). Takes
+/// precedence over `database_path` in config and `LLM_METER_DB`.
+pub fn set_db_path_override(path: PathBuf) {
+    let _ = DB_PATH_OVERRIDE.set(path);
+}
+
 pub fn db_path() -> Result<PathBuf, AppError> {
-    Ok(data_dir()?.join("snapshots.sqlite"))
+    let path = match DB_PATH_OVERRIDE.get() {
+        Some(path) => path.clone(),
+        None => resolve_db_path(
+            load_config()?.database_path.as_deref(),
+            std::env::var_os("LLM_METER_DB").map(PathBuf::from),
+            &config_dir()?,
+            &data_dir()?,
+        ),
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+/// Config first, env second, default last. A relative `database_path` is
+/// taken relative to the config directory.
+fn resolve_db_path(
+    configured: Option<&str>,
+    env: Option<PathBuf>,
+    config_dir: &Path,
+    data_dir: &Path,
+) -> PathBuf {
+    if let Some(raw) = configured.map(str::trim).filter(|s| !s.is_empty()) {
+        let path = expand_home(raw);
+        return if path.is_relative() {
+            config_dir.join(path)
+        } else {
+            path
+        };
+    }
+    if let Some(path) = env.filter(|p| !p.as_os_str().is_empty()) {
+        return path;
+    }
+    data_dir.join("snapshots.sqlite")
+}
+
+fn expand_home(raw: &str) -> PathBuf {
+    if let Some(rest) = raw.strip_prefix("~/") {
+        if let Some(base) = directories::BaseDirs::new() {
+            return base.home_dir().join(rest);
+        }
+    }
+    PathBuf::from(raw)
 }
 
 pub fn ensure_dirs() -> Result<(), AppError> {
-    fs::create_dir_all(config_dir()?)?;
-    fs::create_dir_all(data_dir()?)?;
+    let dirs = app_dirs()?;
+    fs::create_dir_all(&dirs.config)?;
+    fs::create_dir_all(&dirs.data)?;
+    if let Some(legacy_home) = &dirs.legacy_home {
+        migrate_legacy_layout(legacy_home, &dirs.config, &dirs.data)?;
+    }
     Ok(())
 }
 
@@ -149,6 +1051,40 @@ fn normalize_config(config: &mut AppConfig) -> bool {
     changed
 }
 
+/// Deserializes a parsed `config.toml`, also returning the dotted paths of
+/// keys the schema doesn't know. Invalid values are reported with their
+/// path, e.g. `budgets[0].amount_usd`.
+pub fn parse_config(raw: &toml::Value) -> Result<(AppConfig, Vec<String>), AppError> {
+    let mut unknown = Vec::new();
+    let mut record = |path: serde_ignored::Path| unknown.push(path.to_string());
+    let de = serde_ignored::Deserializer::new(raw.clone(), &mut record);
+    let parsed: AppConfig = serde_path_to_error::deserialize(de)
+        .map_err(|e| AppError::Config(format!("config.toml: {}: {}", e.path(), e.inner())))?;
+    Ok((parsed, unknown))
+}
+
+/// Keys in `config.toml` that no setting reads, typically typos or options
+/// from another version. Fails like [`load_config`] on invalid values.
+pub fn unknown_config_keys() -> Result<Vec<String>, AppError> {
+    let path = config_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+    Ok(parse_config(&raw)?.1)
+}
+
+/// Rewrites `config.toml` from the parsed settings, dropping unknown keys.
+/// The previous file is kept as `config.toml.bak`.
+pub fn reserialize_config() -> Result<PathBuf, AppError> {
+    let path = config_path()?;
+    let cfg = load_config()?;
+    let backup = path.with_extension("toml.bak");
+    fs::copy(&path, &backup)?;
+    save_config(&cfg)?;
+    Ok(backup)
+}
+
 pub fn load_config() -> Result<AppConfig, AppError> {
     let path = config_path()?;
     if !path.exists() {
@@ -159,14 +1095,14 @@ pub fn load_config() -> Result<AppConfig, AppError> {
     let mut raw_toml: toml::Value = toml::from_str(&raw_str)?;
     migrate_legacy_api_keys(&mut raw_toml)?;
 
-    let mut parsed: AppConfig = raw_toml.clone().try_into()?;
+    let (mut parsed, _) = parse_config(&raw_toml)?;
     let normalized = normalize_config(&mut parsed);
 
     // Persist migrated config if legacy fields were removed.
     let rewritten = toml::to_string_pretty(&raw_toml)?;
     if rewritten != raw_str || normalized {
         if normalized {
-            save_config(&parsed)?;
+            write_config(&parsed)?;
         } else {
             fs::write(path, rewritten)?;
         }
@@ -175,7 +1111,14 @@ pub fn load_config() -> Result<AppConfig, AppError> {
     Ok(parsed)
 }
 
+/// Writes `config.toml` and records what changed in the audit log.
 pub fn save_config(config: &AppConfig) -> Result<(), AppError> {
+    let before = saved_config();
+    write_config(config)?;
+    audit::record(audit::config_changes(&before, config))
+}
+
+fn write_config(config: &AppConfig) -> Result<(), AppError> {
     ensure_dirs()?;
     let path = config_path()?;
     let raw = toml::to_string_pretty(config)?;
@@ -183,26 +1126,141 @@ pub fn save_config(config: &AppConfig) -> Result<(), AppError> {
     Ok(())
 }
 
+/// The config as last written, without migrations; the default when the
+/// file is missing or unreadable.
+fn saved_config() -> AppConfig {
+    config_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|raw| toml::from_str::<toml::Value>(&raw).ok())
+        .and_then(|raw| parse_config(&raw).ok())
+        .map(|(cfg, _)| cfg)
+        .unwrap_or_default()
+}
+
+/// Keyring service holding this profile's secrets: `llm-meter/<profile>`.
+pub fn keyring_service() -> String {
+    format!("{SERVICE_NAME}/{}", profile())
+}
+
+/// Accounts already checked for an entry from before profiles existed.
+static MIGRATED_ACCOUNTS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The keyring entry for `account` in this profile. Entries stored under the
+/// bare [`SERVICE_NAME`] by earlier versions move into the default profile
+/// the first time they are used; an entry already in the profile wins.
+fn keyring_entry(account: &str) -> Result<keyring::Entry, AppError> {
+    #[cfg(test)]
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    let entry = keyring::Entry::new(&keyring_service(), account)?;
+    if profile() != DEFAULT_PROFILE
+        || MIGRATED_ACCOUNTS
+            .lock()
+            .is_ok_and(|done| done.contains(account))
+    {
+        return Ok(entry);
+    }
+
+    let legacy = keyring::Entry::new(SERVICE_NAME, account)?;
+    match legacy.get_password() {
+        Ok(secret) => {
+            match entry.get_password() {
+                Err(keyring::Error::NoEntry) => entry.set_password(&secret)?,
+                Ok(_) => {}
+                Err(e) => return Err(AppError::Keyring(e)),
+            }
+            match legacy.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => return Err(AppError::Keyring(e)),
+            }
+        }
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => return Err(AppError::Keyring(e)),
+    }
+    if let Ok(mut done) = MIGRATED_ACCOUNTS.lock() {
+        done.insert(account.to_string());
+    }
+    Ok(entry)
+}
+
+/// Keyring account under [`keyring_service`] holding the database key.
+pub const DATABASE_KEY_ACCOUNT: &str = "database";
+
+/// The SQLCipher key to open the database with, or `None` when
+/// `encrypt_database` is off.
+pub fn database_key() -> Result<Option<String>, AppError> {
+    if !load_config()?.encrypt_database {
+        return Ok(None);
+    }
+    stored_database_key()?.map(Some).ok_or_else(|| {
+        AppError::Config(format!(
+            "encrypt_database is set but the keyring has no '{DATABASE_KEY_ACCOUNT}' entry under '{}'; the database cannot be opened.",
+            keyring_service()
+        ))
+    })
+}
+
+pub fn stored_database_key() -> Result<Option<String>, AppError> {
+    let entry = keyring_entry(DATABASE_KEY_ACCOUNT)?;
+    match entry.get_password() {
+        Ok(v) if !v.is_empty() => Ok(Some(v)),
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Keyring(e)),
+    }
+}
+
+/// Generates a random 256-bit key, stores it in the keyring and returns it
+/// hex-encoded once it reads back.
+pub fn create_database_key() -> Result<String, AppError> {
+    let key = random_hex(32)?;
+    keyring_entry(DATABASE_KEY_ACCOUNT)?.set_password(&key)?;
+    // Losing this key loses the database, so refuse stores that don't keep it.
+    if stored_database_key()?.as_deref() != Some(key.as_str()) {
+        return Err(AppError::Config(
+            "The keyring did not keep the database key; refusing to encrypt.".into(),
+        ));
+    }
+    Ok(key)
+}
+
+/// `len` bytes from the OS random source, hex-encoded.
+fn random_hex(len: usize) -> Result<String, AppError> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| AppError::Config(format!("cannot read random bytes: {e}")))?;
+    Ok(hex::encode(bytes))
+}
+
+pub fn delete_database_key() -> Result<(), AppError> {
+    let entry = keyring_entry(DATABASE_KEY_ACCOUNT)?;
+    match entry.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(AppError::Keyring(e)),
+    }
+}
+
+/// Keyring account under [`keyring_service`] holding `provider`'s API key.
+pub fn keyring_account(provider: &str) -> String {
+    format!("provider:{}", normalize_provider_name(provider))
+}
+
 pub fn set_api_key(provider: &str, key: &str) -> Result<(), AppError> {
-    let normalized = normalize_provider_name(provider);
-    let entry = keyring::Entry::new(SERVICE_NAME, &format!("provider:{normalized}"))?;
+    let entry = keyring_entry(&keyring_account(provider))?;
     entry.set_password(key)?;
-    Ok(())
+    audit::record_key("key.set", &normalize_provider_name(provider))
 }
 
 pub fn delete_api_key(provider: &str) -> Result<(), AppError> {
-    let normalized = normalize_provider_name(provider);
-    let entry = keyring::Entry::new(SERVICE_NAME, &format!("provider:{normalized}"))?;
+    let entry = keyring_entry(&keyring_account(provider))?;
     match entry.delete_credential() {
-        Ok(()) => Ok(()),
+        Ok(()) => audit::record_key("key.deleted", &normalize_provider_name(provider)),
         Err(keyring::Error::NoEntry) => Ok(()),
         Err(e) => Err(AppError::Keyring(e)),
     }
 }
 
 pub fn has_api_key(provider: &str) -> Result<bool, AppError> {
-    let normalized = normalize_provider_name(provider);
-    let entry = keyring::Entry::new(SERVICE_NAME, &format!("provider:{normalized}"))?;
+    let entry = keyring_entry(&keyring_account(provider))?;
     match entry.get_password() {
         Ok(v) => Ok(!v.is_empty()),
         Err(keyring::Error::NoEntry) => Ok(false),
@@ -210,19 +1268,49 @@ pub fn has_api_key(provider: &str) -> Result<bool, AppError> {
     }
 }
 
+/// The key stored in the keychain for `provider`, ignoring env vars.
+pub fn stored_api_key(provider: &str) -> Result<Option<String>, AppError> {
+    let entry = keyring_entry(&keyring_account(provider))?;
+    match entry.get_password() {
+        Ok(v) if !v.is_empty() => Ok(Some(v)),
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Keyring(e)),
+    }
+}
+
 pub fn get_api_key(provider: &str) -> Result<String, AppError> {
+    resolve_api_key(provider, None)
+}
+
+/// Like [`get_api_key`], but honors the account's `api_key_env`.
+pub fn provider_api_key(cfg: &AppConfig, provider: &str) -> Result<String, AppError> {
+    let env_name = cfg
+        .provider_settings
+        .get(&normalize_provider_name(provider))
+        .and_then(|s| s.api_key_env.as_deref());
+    resolve_api_key(provider, env_name)
+}
+
+/// `<PROVIDER>_API_KEY`, uppercased with `-` turned into `_`.
+pub fn default_api_key_env(provider: &str) -> String {
+    format!(
+        "{}_API_KEY",
+        normalize_provider_name(provider)
+            .to_ascii_uppercase()
+            .replace('-', "_")
+    )
+}
+
+fn resolve_api_key(provider: &str, env_name: Option<&str>) -> Result<String, AppError> {
     let normalized = normalize_provider_name(provider);
-    let entry = keyring::Entry::new(SERVICE_NAME, &format!("provider:{normalized}"))?;
+    let entry = keyring_entry(&keyring_account(provider))?;
     if let Ok(value) = entry.get_password() {
         if !value.is_empty() {
             return Ok(value);
         }
     }
 
-    let env_name = format!(
-        "{}_API_KEY",
-        normalized.to_ascii_uppercase().replace('-', "_")
-    );
+    let env_name = env_name.map_or_else(|| default_api_key_env(provider), str::to_string);
     if let Ok(value) = std::env::var(env_name) {
         if !value.is_empty() {
             return Ok(value);
@@ -247,12 +1335,111 @@ pub fn ensure_initialized() -> Result<(), AppError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_config_lists_unknown_keys_and_locates_invalid_values() {
+        let raw: toml::Value =
+            toml::from_str(&toml::to_string_pretty(&AppConfig::default()).unwrap()).unwrap();
+        assert!(parse_config(&raw).unwrap().1.is_empty());
+
+        let raw: toml::Value = toml::from_str(
+            r#"
+            refresh_seconds = 60
+            enabled_providers = ["openai"]
+            provider_settings = { openai = { base_url = "https://x", colour = "red" } }
+            pricing_overrides = []
+            refresh_secs = 30
+
+            [[budgets]]
+            name = "cap"
+            scope = "global"
+            amount_usd = 10.0
+            period = "monthly"
+            "#,
+        )
+        .unwrap();
+        let (cfg, unknown) = parse_config(&raw).unwrap();
+        assert_eq!(cfg.budgets.len(), 1);
+        assert_eq!(
+            unknown,
+            vec!["provider_settings.openai.colour", "refresh_secs"]
+        );
+
+        let raw: toml::Value = toml::from_str(
+            r#"
+            refresh_seconds = 60
+            enabled_providers = []
+            provider_settings = {}
+            pricing_overrides = []
+
+            [[budgets]]
+            name = "cap"
+            scope = "global"
+            amount_usd = "ten"
+            period = "monthly"
+            "#,
+        )
+        .unwrap();
+        let err = parse_config(&raw).unwrap_err().to_string();
+        assert!(err.contains("budgets[0].amount_usd"), "{err}");
+    }
+
     #[test]
     fn normalize_provider_name_trims_and_lowercases() {
         assert_eq!(normalize_provider_name(" OpenAI "), "openai");
         assert_eq!(normalize_provider_name("AnThRoPiC"), "anthropic");
     }
 
+    #[test]
+    fn rename_provider_keeps_adapter_kind_and_moves_budgets() {
+        let mut cfg = AppConfig {
+            enabled_providers: vec!["openai".into()],
+            budgets: vec![Budget {
+                name: "cap".into(),
+                scope: BudgetScope::Provider("openai".into()),
+                amount_usd: 10.0,
+                period: BudgetPeriod::Monthly,
+                on_exceeded: None,
+            }],
+            ..AppConfig::default()
+        };
+        cfg.rename_provider("OpenAI", "openai-prod")
+            .expect("rename");
+        assert_eq!(cfg.enabled_providers, vec!["openai-prod".to_string()]);
+        assert_eq!(cfg.provider_kind("openai-prod"), "openai");
+        assert_eq!(
+            cfg.budgets[0].scope,
+            BudgetScope::Provider("openai-prod".into())
+        );
+
+        cfg.rename_provider("openai-prod", "openai")
+            .expect("rename back");
+        assert_eq!(cfg.provider_settings["openai"].kind, None);
+        assert!(cfg.rename_provider("missing", "x").is_err());
+        cfg.enabled_providers.push("anthropic".into());
+        assert!(cfg.rename_provider("openai", "anthropic").is_err());
+    }
+
+    #[test]
+    fn custom_accounts_resolve_their_kind_and_display_name() {
+        let cfg: AppConfig = toml::from_str(
+            r#"
+            refresh_seconds = 60
+            enabled_providers = ["litellm"]
+            pricing_overrides = []
+
+            [provider_settings.litellm]
+            base_url = "http://litellm.internal:4000"
+            organization_id = "org"
+            kind = "custom-openai-compatible"
+            display_name = "LiteLLM proxy"
+            "#,
+        )
+        .expect("parse");
+        assert_eq!(cfg.provider_kind("LiteLLM"), "custom-openai-compatible");
+        assert_eq!(cfg.provider_label("litellm"), "LiteLLM proxy");
+        assert_eq!(cfg.provider_label("openai"), "openai");
+    }
+
     #[test]
     fn normalize_config_dedupes_and_normalizes_keys() {
         let mut cfg = AppConfig {
@@ -264,6 +1451,7 @@ mod tests {
                     ProviderSettings {
                         base_url: Some("https://example.com".into()),
                         organization_id: None,
+                        ..ProviderSettings::default()
                     },
                 ),
                 (
@@ -271,6 +1459,7 @@ mod tests {
                     ProviderSettings {
                         base_url: None,
                         organization_id: Some("org_1".into()),
+                        ..ProviderSettings::default()
                     },
                 ),
             ]),
@@ -280,6 +1469,7 @@ mod tests {
                 input_per_1m: 1.0,
                 output_per_1m: 2.0,
             }],
+            ..AppConfig::default()
         };
 
         let changed = normalize_config(&mut cfg);
@@ -292,4 +1482,140 @@ mod tests {
         assert!(cfg.provider_settings.contains_key("anthropic"));
         assert_eq!(cfg.pricing_overrides[0].provider, "openai");
     }
+
+    #[test]
+    fn config_without_new_fields_still_parses() {
+        let raw = r#"
+            refresh_seconds = 30
+            enabled_providers = ["openai"]
+            pricing_overrides = []
+
+            [provider_settings]
+        "#;
+        let cfg: AppConfig = toml::from_str(raw).expect("parse legacy config");
+        assert_eq!(cfg.refresh_seconds, 30);
+        assert!(!cfg.accessible);
+    }
+
+    #[test]
+    fn budget_scopes_round_trip_through_toml() {
+        let raw = r#"
+            refresh_seconds = 60
+            enabled_providers = []
+            pricing_overrides = []
+            provider_settings = {}
+
+            [[budgets]]
+            name = "cap"
+            scope = "provider:OpenAI"
+            amount_usd = 10.0
+        "#;
+        let cfg: AppConfig = toml::from_str(raw).expect("parse budgets");
+        assert_eq!(cfg.budgets[0].scope, BudgetScope::Provider("openai".into()));
+        assert_eq!(cfg.budgets[0].period, BudgetPeriod::Monthly);
+        assert!(toml::to_string(&cfg)
+            .unwrap()
+            .contains("scope = \"provider:openai\""));
+
+        let bad = raw.replace("provider:OpenAI", "team:ml");
+        assert!(toml::from_str::<AppConfig>(&bad).is_err());
+    }
+
+    #[test]
+    fn migrate_legacy_layout_moves_files_without_overwriting() {
+        let tmp = tempfile::TempDir::new().expect("tempdir");
+        let legacy = tmp.path().join("share");
+        let config = tmp.path().join("xdg-config");
+        let data = legacy.clone();
+        fs::create_dir_all(legacy.join("config")).expect("mkdir config");
+        fs::create_dir_all(legacy.join("data")).expect("mkdir data");
+        fs::write(legacy.join("config/config.toml"), "refresh_seconds = 30\n").expect("cfg");
+        fs::write(legacy.join("data/snapshots.sqlite"), "db").expect("db");
+
+        assert!(migrate_legacy_layout(&legacy, &config, &data).expect("migrate"));
+        assert_eq!(
+            fs::read_to_string(config.join("config.toml")).expect("moved cfg"),
+            "refresh_seconds = 30\n"
+        );
+        assert_eq!(
+            fs::read_to_string(data.join("snapshots.sqlite")).expect("moved db"),
+            "db"
+        );
+        assert!(!legacy.join("config").exists());
+        assert!(!legacy.join("data").exists());
+
+        fs::create_dir_all(legacy.join("config")).expect("mkdir config");
+        fs::write(legacy.join("config/config.toml"), "stale").expect("stale cfg");
+        assert!(!migrate_legacy_layout(&legacy, &config, &data).expect("migrate again"));
+        assert_eq!(
+            fs::read_to_string(config.join("config.toml")).expect("kept cfg"),
+            "refresh_seconds = 30\n"
+        );
+    }
+
+    #[test]
+    fn profiles_get_their_own_directories_and_valid_names() {
+        let home = AppDirs::under_home(PathBuf::from("/home/x/.llm-meter"));
+        assert_eq!(
+            home.for_profile(DEFAULT_PROFILE).config,
+            Path::new("/home/x/.llm-meter/config")
+        );
+        let work = AppDirs::under_home(PathBuf::from("/home/x/.llm-meter")).for_profile("work");
+        assert_eq!(
+            work.config,
+            Path::new("/home/x/.llm-meter/config/profiles/work")
+        );
+        assert_eq!(
+            work.data,
+            Path::new("/home/x/.llm-meter/data/profiles/work")
+        );
+
+        assert_eq!(profile_name(" Work_2 ").expect("valid"), "work_2");
+        assert!(profile_name("").is_err());
+        assert!(profile_name("../other").is_err());
+        assert!(profile_name("a/b").is_err());
+    }
+
+    #[test]
+    fn model_family_takes_the_first_group_whose_glob_matches() {
+        let cfg = AppConfig {
+            model_groups: BTreeMap::from([
+                ("claude".into(), vec!["claude-*".into()]),
+                ("gpt-4o".into(), vec!["gpt-4o".into(), "gpt-4o-20*".into()]),
+                ("gpt-4o-mini".into(), vec!["gpt-4o-mini*".into()]),
+            ]),
+            ..AppConfig::default()
+        };
+        assert_eq!(cfg.model_family("gpt-4o-2024-08-06"), "gpt-4o");
+        assert_eq!(cfg.model_family("gpt-4o"), "gpt-4o");
+        assert_eq!(cfg.model_family("gpt-4o-mini-2024-07-18"), "gpt-4o-mini");
+        assert_eq!(cfg.model_family("claude-3-5-sonnet"), "claude");
+        assert_eq!(cfg.model_family("o1-preview"), "o1-preview");
+        assert!(glob_matches("*-mini*", "gpt-4o-mini"));
+        assert!(!glob_matches("gpt-*-mini", "gpt-4o-mini-2024"));
+    }
+
+    #[test]
+    fn resolve_db_path_prefers_config_then_env_then_default() {
+        let config = Path::new("/etc/llm-meter");
+        let data = Path::new("/var/lib/llm-meter");
+        let env = Some(PathBuf::from("/mnt/env.sqlite"));
+
+        assert_eq!(
+            resolve_db_path(Some("/mnt/big/meter.sqlite"), env.clone(), config, data),
+            PathBuf::from("/mnt/big/meter.sqlite")
+        );
+        assert_eq!(
+            resolve_db_path(Some("db/meter.sqlite"), env.clone(), config, data),
+            PathBuf::from("/etc/llm-meter/db/meter.sqlite")
+        );
+        assert_eq!(
+            resolve_db_path(Some("  "), env.clone(), config, data),
+            PathBuf::from("/mnt/env.sqlite")
+        );
+        assert_eq!(
+            resolve_db_path(None, None, config, data),
+            PathBuf::from("/var/lib/llm-meter/snapshots.sqlite")
+        );
+    }
 }
@@ -0,0 +1,47 @@
+use std::io::IsTerminal;
+
+use dialoguer::Input;
+
+use crate::error::AppError;
+
+/// Confirmation for a destructive command: `--yes` skips it, otherwise the
+/// user types `expected` (e.g. the provider name) at a terminal. Scripts
+/// without a terminal must pass `--yes`.
+pub fn typed(action: &str, expected: &str, yes: bool) -> Result<(), AppError> {
+    if yes {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(AppError::Config(format!(
+            "{action} needs --yes when not run from a terminal."
+        )));
+    }
+    let answer: String = Input::new()
+        .with_prompt(format!("{action}. Type '{expected}' to confirm"))
+        .allow_empty(true)
+        .interact_text()?;
+    check(&answer, expected)
+}
+
+fn check(answer: &str, expected: &str) -> Result<(), AppError> {
+    if answer.trim() == expected {
+        Ok(())
+    } else {
+        Err(AppError::Config(
+            "Confirmation did not match; nothing was changed.".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_exact_expected_text_confirms() {
+        assert!(typed("Purge 'openai'", "openai", true).is_ok());
+        assert!(check(" openai\n", "openai").is_ok());
+        assert!(check("OpenAI", "openai").is_err());
+        assert!(check("", "openai").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::models::{AlertRecord, TimeWindow};
+use crate::pricing::{deprecation_catalog, find_deprecation, ModelDeprecation};
+use crate::storage::Storage;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::HashMap;
+
+/// Alert kind for spend on deprecated models; the subject is `provider/model`.
+pub const DEPRECATION_ALERT_KIND: &str = "deprecated_model";
+
+/// A model with spend in the window that is retired, or retires within
+/// `deprecation_warning_days`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeprecatedSpend {
+    pub provider: String,
+    pub model: String,
+    pub deprecation: ModelDeprecation,
+    pub cost_usd: f64,
+    /// Days until `deprecated_on`; zero or negative once it has passed.
+    pub days_left: i64,
+}
+
+impl DeprecatedSpend {
+    pub fn subject(&self) -> String {
+        format!("{}/{}", self.provider, self.model)
+    }
+
+    /// `retired 2026-01-05` or `retires in 12d`.
+    pub fn when(&self) -> String {
+        if self.days_left <= 0 {
+            format!("retired {}", self.deprecation.deprecated_on)
+        } else {
+            format!("retires in {}d", self.days_left)
+        }
+    }
+
+    pub fn message(&self, window: TimeWindow) -> String {
+        let mut message = format!(
+            "Model '{}' ({}) {} but had ${:.2} of spend in the last {}",
+            self.model,
+            self.provider,
+            self.when(),
+            self.cost_usd,
+            window.as_label()
+        );
+        if let Some(replacement) = &self.deprecation.replacement {
+            message.push_str(&format!("; migrate to {replacement}"));
+        }
+        message
+    }
+}
+
+/// Outcome of [`check`]: alerts opened for models newly flagged, and the
+/// subjects whose alert was resolved because their spend stopped.
+#[derive(Debug, Default)]
+pub struct DeprecationCheck {
+    pub flagged: Vec<AlertRecord>,
+    pub cleared: Vec<String>,
+}
+
+/// Models in `costs` (USD by provider and model) with nonzero spend that are
+/// retired or retire within the warning period, soonest first.
+pub fn deprecated_spend(
+    cfg: &AppConfig,
+    costs: &HashMap<(String, String), f64>,
+    today: NaiveDate,
+) -> Vec<DeprecatedSpend> {
+    let catalog = deprecation_catalog(&cfg.deprecations);
+    let mut out: Vec<DeprecatedSpend> = costs
+        .iter()
+        .filter(|(_, cost)| **cost > 0.0)
+        .filter_map(|((provider, model), cost)| {
+            let deprecation = find_deprecation(&catalog, provider, model)?;
+            let days_left = (deprecation.deprecated_on - today).num_days();
+            (days_left <= i64::from(cfg.deprecation_warning_days)).then(|| DeprecatedSpend {
+                provider: provider.clone(),
+                model: model.clone(),
+                deprecation: deprecation.clone(),
+                cost_usd: *cost,
+                days_left,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        a.days_left
+            .cmp(&b.days_left)
+            .then(b.cost_usd.total_cmp(&a.cost_usd))
+    });
+    out
+}
+
+/// [`deprecated_spend`] over the enabled providers' stored costs in `window`.
+pub fn evaluate(
+    cfg: &AppConfig,
+    storage: &Storage,
+    window: TimeWindow,
+    now: DateTime<Utc>,
+) -> Result<Vec<DeprecatedSpend>, AppError> {
+    let since = now - Duration::hours(window.as_hours());
+    let costs = storage.cost_by_provider_model_since(since, &cfg.enabled_providers)?;
+    Ok(deprecated_spend(cfg, &costs, now.date_naive()))
+}
+
+/// Opens one `deprecated_model` alert per flagged model and resolves the
+/// alerts of models that no longer have spend in `window`. A model already
+/// alerted on is not reported again until its alert is resolved.
+pub fn check(
+    cfg: &AppConfig,
+    storage: &Storage,
+    window: TimeWindow,
+    now: DateTime<Utc>,
+) -> Result<DeprecationCheck, AppError> {
+    let mut out = DeprecationCheck::default();
+    let flagged = evaluate(cfg, storage, window, now)?;
+    for spend in &flagged {
+        if let Some(alert) = storage.record_alert(
+            DEPRECATION_ALERT_KIND,
+            &spend.subject(),
+            &spend.message(window),
+            now,
+        )? {
+            out.flagged.push(alert);
+        }
+    }
+    for subject in storage.open_alert_subjects(DEPRECATION_ALERT_KIND)? {
+        if !flagged.iter().any(|s| s.subject() == subject)
+            && storage.resolve_alerts(DEPRECATION_ALERT_KIND, &subject, now)? > 0
+        {
+            out.cleared.push(subject);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostRecord, UsageSource};
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn cost(provider: &str, model: &str, at: DateTime<Utc>, total: f64) -> CostRecord {
+        CostRecord {
+            provider: provider.into(),
+            model: model.into(),
+            timestamp: at,
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    #[test]
+    fn spend_on_retired_and_retiring_models_is_flagged_soonest_first() {
+        let today = NaiveDate::from_ymd_opt(2025, 10, 1).unwrap();
+        let cfg = AppConfig {
+            deprecation_warning_days: 30,
+            ..AppConfig::default()
+        };
+        let costs = HashMap::from([
+            (("openai".into(), "gpt-4o".into()), 9.0),
+            (("openai".into(), "o1-preview".into()), 2.0),
+            (("openai".into(), "o1-mini".into()), 0.0),
+            (
+                ("anthropic".into(), "claude-3-5-sonnet-20241022".into()),
+                4.0,
+            ),
+            (("anthropic".into(), "claude-3-opus-20240229".into()), 6.0),
+        ]);
+        let flagged = deprecated_spend(&cfg, &costs, today);
+        let subjects: Vec<_> = flagged.iter().map(DeprecatedSpend::subject).collect();
+        assert_eq!(
+            subjects,
+            vec!["openai/o1-preview", "anthropic/claude-3-5-sonnet-20241022"]
+        );
+        assert_eq!(flagged[0].when(), "retired 2025-07-28");
+        assert_eq!(flagged[1].when(), "retires in 21d");
+        assert_eq!(
+            flagged[1].message(TimeWindow::SevenDays),
+            "Model 'claude-3-5-sonnet-20241022' (anthropic) retires in 21d but had $4.00 of spend in the last 7d; migrate to claude-sonnet-4"
+        );
+    }
+
+    #[test]
+    fn alerts_open_once_and_resolve_when_spend_stops() {
+        let dir = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&dir.path().join("meter.db")).unwrap();
+        let now = Utc.with_ymd_and_hms(2025, 8, 1, 12, 0, 0).unwrap();
+        storage
+            .replace_snapshot(
+                now - Duration::days(30),
+                &[],
+                &[],
+                &[cost("openai", "o1-preview", now - Duration::days(2), 3.0)],
+            )
+            .unwrap();
+        let cfg = AppConfig {
+            enabled_providers: vec!["openai".into()],
+            ..AppConfig::default()
+        };
+
+        let first = check(&cfg, &storage, TimeWindow::SevenDays, now).unwrap();
+        assert_eq!(first.flagged.len(), 1);
+        assert_eq!(first.flagged[0].subject, "openai/o1-preview");
+        assert!(check(&cfg, &storage, TimeWindow::SevenDays, now)
+            .unwrap()
+            .flagged
+            .is_empty());
+
+        let later = now + Duration::days(10);
+        let cleared = check(&cfg, &storage, TimeWindow::SevenDays, later).unwrap();
+        assert_eq!(cleared.cleared, vec!["openai/o1-preview".to_string()]);
+    }
+}
@@ -5,12 +5,24 @@ pub enum AppError {
     #[error("config error: {0}")]
     Config(String),
 
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    #[error("request cap of {0} provider API calls reached")]
+    RequestCap(u32),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+
     #[error(transparent)]
     Http(#[from] reqwest::Error),
 
@@ -20,9 +32,19 @@ pub enum AppError {
     #[error(transparent)]
     Keyring(#[from] keyring::Error),
 
+    #[error(transparent)]
+    Prompt(#[from] dialoguer::Error),
+
     #[error(transparent)]
     TomlDe(#[from] toml::de::Error),
 
     #[error(transparent)]
     TomlSer(#[from] toml::ser::Error),
 }
+
+impl AppError {
+    /// True when a provider answered 401, i.e. the stored key was rejected.
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, AppError::Http(e) if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED))
+    }
+}
@@ -0,0 +1,553 @@
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::format::MoneyFormat;
+use crate::fx::FxRates;
+use crate::models::CostRecord;
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, SecondsFormat, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const COST_HEADER: [&str; 7] = [
+    "provider",
+    "model",
+    "input_cost",
+    "output_cost",
+    "total_cost",
+    "currency",
+    "timestamp",
+];
+
+/// FinOps FOCUS columns written by `--format focus`; `x_` columns are
+/// llm-meter extensions, as the specification allows.
+const FOCUS_HEADER: [&str; 21] = [
+    "BilledCost",
+    "BillingAccountId",
+    "BillingCurrency",
+    "BillingPeriodEnd",
+    "BillingPeriodStart",
+    "ChargeCategory",
+    "ChargeDescription",
+    "ChargePeriodEnd",
+    "ChargePeriodStart",
+    "ContractedCost",
+    "EffectiveCost",
+    "InvoiceIssuerName",
+    "ListCost",
+    "ProviderName",
+    "PublisherName",
+    "ResourceId",
+    "ResourceName",
+    "ServiceCategory",
+    "ServiceName",
+    "x_InputCost",
+    "x_OutputCost",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Jsonl,
+    Csv,
+    Xlsx,
+    Influx,
+    Focus,
+}
+
+impl ExportFormat {
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        match raw.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "xlsx" => Ok(Self::Xlsx),
+            "influx" => Ok(Self::Influx),
+            "focus" => Ok(Self::Focus),
+            _ => Err(AppError::Config(
+                "Unsupported export format. Use json, jsonl, csv, xlsx, influx or focus".into(),
+            )),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            Self::Csv | Self::Focus => "csv",
+            Self::Xlsx => "xlsx",
+            Self::Influx => "lp",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Jsonl => "application/x-ndjson",
+            Self::Csv | Self::Focus => "text/csv",
+            Self::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            Self::Influx => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// The full export document for `format`, as written to stdout or uploaded.
+pub fn render_cost(
+    format: ExportFormat,
+    rows: &[CostRecord],
+    csv: CsvOptions,
+    currency: &str,
+) -> Result<Vec<u8>, AppError> {
+    match format {
+        ExportFormat::Json => {
+            let mut out = serde_json::to_vec_pretty(rows)?;
+            out.push(b'\n');
+            Ok(out)
+        }
+        ExportFormat::Jsonl => {
+            let mut out = Vec::new();
+            for row in rows {
+                serde_json::to_writer(&mut out, row)?;
+                out.push(b'\n');
+            }
+            Ok(out)
+        }
+        ExportFormat::Csv => {
+            let mut out = Vec::new();
+            write_cost_csv(&mut out, rows, csv)?;
+            Ok(out)
+        }
+        ExportFormat::Xlsx => cost_xlsx(rows, currency),
+        ExportFormat::Influx => Ok(cost_line_protocol(rows).into_bytes()),
+        ExportFormat::Focus => {
+            let mut out = Vec::new();
+            write_cost_focus(&mut out, rows)?;
+            Ok(out)
+        }
+    }
+}
+
+/// FOCUS cost and usage rows, one `Usage` charge per cost row. Costs are
+/// estimated from list prices, so billed, effective, list and contracted cost
+/// are the same amount. The charge period is the row's bucket; rows without a
+/// stored `bucket_end` span one bucket of their source granularity.
+pub fn write_cost_focus<W: Write>(out: W, rows: &[CostRecord]) -> Result<(), AppError> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(FOCUS_HEADER)?;
+    let ts = |t: DateTime<Utc>| t.to_rfc3339_opts(SecondsFormat::Secs, true);
+    for r in rows {
+        let charge_end = r
+            .bucket_end
+            .unwrap_or_else(|| r.timestamp + r.source_granularity.unwrap_or_default().duration());
+        let month = r.timestamp.date_naive().with_day(1).unwrap_or_default();
+        let billing_start = Utc.from_utc_datetime(&month.and_time(NaiveTime::MIN));
+        let billing_end = billing_start + Months::new(1);
+        let cost = format!("{:.8}", r.total_cost);
+        writer.write_record([
+            cost.as_str(),
+            &r.provider,
+            &r.currency,
+            &ts(billing_end),
+            &ts(billing_start),
+            "Usage",
+            &format!("{} usage", r.model),
+            &ts(charge_end),
+            &ts(r.timestamp),
+            &cost,
+            &cost,
+            &r.provider,
+            &cost,
+            &r.provider,
+            &r.provider,
+            &r.model,
+            &r.model,
+            "AI and Machine Learning",
+            &r.provider,
+            &format!("{:.8}", r.input_cost),
+            &format!("{:.8}", r.output_cost),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// InfluxDB line protocol, one `llm_cost` point per row with nanosecond
+/// timestamps: `llm_cost,provider=openai,model=gpt-4o,currency=USD input=1,output=0.2,total=1.2 <ns>`.
+pub fn cost_line_protocol(rows: &[CostRecord]) -> String {
+    rows.iter()
+        .map(|r| {
+            format!(
+                "llm_cost,provider={},model={},currency={} input={},output={},total={} {}\n",
+                influx_tag(&r.provider),
+                influx_tag(&r.model),
+                influx_tag(&r.currency),
+                r.input_cost,
+                r.output_cost,
+                r.total_cost,
+                r.timestamp.timestamp_nanos_opt().unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+/// Escapes commas, spaces and `=` in tag values; empty values become `unknown`
+/// because line protocol rejects empty tags.
+fn influx_tag(value: &str) -> String {
+    if value.is_empty() {
+        return "unknown".into();
+    }
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, ',' | ' ' | '=' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub header: bool,
+    pub quote_all: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            header: true,
+            quote_all: false,
+        }
+    }
+}
+
+/// Stored costs are in each provider's billing currency; exports convert them
+/// through USD into the configured display currency.
+pub fn convert_cost_rows(rows: &mut [CostRecord], fx: &FxRates, money: &MoneyFormat) {
+    fx.rows_to_usd(rows);
+    if money.currency() == "USD" {
+        return;
+    }
+    for r in rows.iter_mut() {
+        r.input_cost = money.convert(r.input_cost);
+        r.output_cost = money.convert(r.output_cost);
+        r.total_cost = money.convert(r.total_cost);
+        r.currency = money.currency().to_string();
+    }
+}
+
+/// Stable stand-in for an identifier: `prefix-` plus 8 hex digits of
+/// HMAC-SHA256 keyed by `salt`, so the same name maps to the same id across
+/// exports without being reversible from a list of known names.
+pub fn redacted_id(prefix: &str, value: &str, salt: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(salt.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(value.as_bytes());
+    let digest = hex::encode(mac.finalize().into_bytes());
+    format!("{prefix}-{}", &digest[..8])
+}
+
+/// Replaces model ids with their `[model_groups]` family.
+pub fn group_cost_rows(rows: &mut [CostRecord], cfg: &AppConfig) {
+    for r in rows {
+        let family = cfg.model_family(&r.model).to_string();
+        r.model = family;
+    }
+}
+
+/// Replaces provider and model names with [`redacted_id`]s; amounts,
+/// currency and timestamps are kept, so totals still add up.
+pub fn redact_cost_rows(rows: &mut [CostRecord], salt: &str) {
+    for r in rows {
+        r.provider = redacted_id("provider", &r.provider, salt);
+        r.model = redacted_id("model", &r.model, salt);
+    }
+}
+
+/// Parses `--delimiter`: a single ASCII character, or `tab` / `\t`.
+pub fn parse_delimiter(raw: &str) -> Result<u8, AppError> {
+    match raw {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if raw.len() == 1 && raw.is_ascii() && raw != "\"" && raw != "\n" => {
+            Ok(raw.as_bytes()[0])
+        }
+        _ => Err(AppError::Config(format!(
+            "Unsupported delimiter '{raw}'. Use a single ASCII character or 'tab'."
+        ))),
+    }
+}
+
+pub fn write_cost_csv<W: Write>(
+    out: W,
+    rows: &[CostRecord],
+    options: CsvOptions,
+) -> Result<(), AppError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .quote_style(if options.quote_all {
+            csv::QuoteStyle::Always
+        } else {
+            csv::QuoteStyle::Necessary
+        })
+        .from_writer(out);
+
+    if options.header {
+        writer.write_record(COST_HEADER)?;
+    }
+    for r in rows {
+        writer.write_record([
+            r.provider.as_str(),
+            r.model.as_str(),
+            &format!("{:.8}", r.input_cost),
+            &format!("{:.8}", r.output_cost),
+            &format!("{:.8}", r.total_cost),
+            r.currency.as_str(),
+            &r.timestamp.to_rfc3339(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Cost rows rolled up for the workbook sheets. Maps are keyed for stable,
+/// sorted output.
+#[derive(Debug, Default)]
+pub struct CostRollup {
+    pub total: f64,
+    pub rows: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+    pub by_provider: BTreeMap<String, f64>,
+    pub by_model: BTreeMap<(String, String), f64>,
+    pub daily: BTreeMap<(NaiveDate, String), f64>,
+}
+
+impl CostRollup {
+    pub fn from_rows(rows: &[CostRecord]) -> Self {
+        let mut rollup = Self {
+            rows: rows.len(),
+            ..Self::default()
+        };
+        for r in rows {
+            rollup.total += r.total_cost;
+            rollup.first = Some(rollup.first.map_or(r.timestamp, |t| t.min(r.timestamp)));
+            rollup.last = Some(rollup.last.map_or(r.timestamp, |t| t.max(r.timestamp)));
+            *rollup.by_provider.entry(r.provider.clone()).or_default() += r.total_cost;
+            *rollup
+                .by_model
+                .entry((r.provider.clone(), r.model.clone()))
+                .or_default() += r.total_cost;
+            *rollup
+                .daily
+                .entry((r.timestamp.date_naive(), r.provider.clone()))
+                .or_default() += r.total_cost;
+        }
+        rollup
+    }
+}
+
+/// Workbook with Summary, By Provider, By Model and Daily sheets.
+pub fn cost_xlsx(rows: &[CostRecord], currency: &str) -> Result<Vec<u8>, AppError> {
+    let rollup = CostRollup::from_rows(rows);
+    let bold = Format::new().set_bold();
+    let money = Format::new().set_num_format("#,##0.0000");
+    let mut workbook = Workbook::new();
+
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    let date_or_blank = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+    let facts: [(&str, String); 5] = [
+        ("Generated at", Utc::now().to_rfc3339()),
+        ("Currency", currency.to_string()),
+        ("Cost rows", rollup.rows.to_string()),
+        ("First row", date_or_blank(rollup.first)),
+        ("Last row", date_or_blank(rollup.last)),
+    ];
+    for (row, (label, value)) in facts.iter().enumerate() {
+        summary.write_string_with_format(row as u32, 0, *label, &bold)?;
+        summary.write_string(row as u32, 1, value)?;
+    }
+    summary.write_string_with_format(facts.len() as u32, 0, "Total cost", &bold)?;
+    summary.write_number_with_format(facts.len() as u32, 1, rollup.total, &money)?;
+    summary.set_column_width(0, 14)?;
+    summary.set_column_width(1, 28)?;
+
+    let sheet = workbook.add_worksheet().set_name("By Provider")?;
+    write_header(sheet, &["Provider", "Cost"], &bold)?;
+    for (row, (provider, cost)) in rollup.by_provider.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, provider)?;
+        sheet.write_number_with_format(row, 1, *cost, &money)?;
+    }
+
+    let sheet = workbook.add_worksheet().set_name("By Model")?;
+    write_header(sheet, &["Provider", "Model", "Cost"], &bold)?;
+    for (row, ((provider, model), cost)) in rollup.by_model.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, provider)?;
+        sheet.write_string(row, 1, model)?;
+        sheet.write_number_with_format(row, 2, *cost, &money)?;
+    }
+
+    let sheet = workbook.add_worksheet().set_name("Daily")?;
+    write_header(sheet, &["Date", "Provider", "Cost"], &bold)?;
+    for (row, ((date, provider), cost)) in rollup.daily.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, date.format("%Y-%m-%d").to_string())?;
+        sheet.write_string(row, 1, provider)?;
+        sheet.write_number_with_format(row, 2, *cost, &money)?;
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+fn write_header(sheet: &mut Worksheet, titles: &[&str], bold: &Format) -> Result<(), AppError> {
+    for (col, title) in titles.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, bold)?;
+        sheet.set_column_width(col as u16, 20)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UsageSource;
+    use chrono::TimeZone;
+
+    fn row(provider: &str, model: &str) -> CostRecord {
+        CostRecord {
+            provider: provider.into(),
+            model: model.into(),
+            input_cost: 1.0,
+            output_cost: 0.25,
+            total_cost: 1.25,
+            currency: "USD".into(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    fn render(rows: &[CostRecord], options: CsvOptions) -> String {
+        let mut buf = Vec::new();
+        write_cost_csv(&mut buf, rows, options).expect("write csv");
+        String::from_utf8(buf).expect("utf8")
+    }
+
+    #[test]
+    fn redaction_is_stable_per_salt_and_keeps_amounts() {
+        let mut rows = vec![row("openai", "gpt-4o"), row("openai", "gpt-4o-mini")];
+        redact_cost_rows(&mut rows, "salt");
+        assert_eq!(rows[0].provider, rows[1].provider);
+        assert_ne!(rows[0].model, rows[1].model);
+        assert_eq!(rows[0].model, redacted_id("model", "gpt-4o", "salt"));
+        assert!(rows[0].model.starts_with("model-") && rows[0].model.len() == 14);
+        assert_ne!(rows[0].model, redacted_id("model", "gpt-4o", "other"));
+        assert_eq!(rows[0].total_cost, 1.25);
+    }
+
+    #[test]
+    fn default_options_quote_only_when_needed() {
+        let out = render(&[row("open,ai", "gpt\"4o")], CsvOptions::default());
+        let mut lines = out.lines();
+        assert_eq!(
+            lines.next(),
+            Some("provider,model,input_cost,output_cost,total_cost,currency,timestamp")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("\"open,ai\",\"gpt\"\"4o\",1.00000000,0.25000000,1.25000000,USD,2024-01-01T00:00:00+00:00")
+        );
+    }
+
+    #[test]
+    fn semicolon_quote_all_without_header() {
+        let options = CsvOptions {
+            delimiter: parse_delimiter(";").expect("delimiter"),
+            header: false,
+            quote_all: true,
+        };
+        let out = render(&[row("openai", "gpt-4o")], options);
+        assert_eq!(
+            out,
+            "\"openai\";\"gpt-4o\";\"1.00000000\";\"0.25000000\";\"1.25000000\";\"USD\";\"2024-01-01T00:00:00+00:00\"\n"
+        );
+    }
+
+    #[test]
+    fn focus_rows_span_the_bucket_and_the_billing_month() {
+        let mut hourly = row("openai", "gpt-4o");
+        hourly.timestamp = Utc.with_ymd_and_hms(2024, 1, 31, 23, 0, 0).unwrap();
+        hourly.bucket_end = Some(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap());
+        let mut daily = row("anthropic", "claude");
+        daily.source_granularity = Some(crate::models::Granularity::Day);
+
+        let body = render_cost(
+            ExportFormat::Focus,
+            &[hourly, daily],
+            CsvOptions::default(),
+            "USD",
+        )
+        .expect("focus");
+        let out = String::from_utf8(body).expect("utf8");
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some(FOCUS_HEADER.join(",").as_str()));
+        assert_eq!(
+            lines.next(),
+            Some(
+                "1.25000000,openai,USD,2024-02-01T00:00:00Z,2024-01-01T00:00:00Z,Usage,gpt-4o usage,\
+                 2024-02-01T00:00:00Z,2024-01-31T23:00:00Z,1.25000000,1.25000000,openai,1.25000000,\
+                 openai,openai,gpt-4o,gpt-4o,AI and Machine Learning,openai,1.00000000,0.25000000"
+            )
+        );
+        let daily = lines.next().expect("second row");
+        assert!(daily.contains(",2024-01-02T00:00:00Z,2024-01-01T00:00:00Z,"));
+    }
+
+    #[test]
+    fn parse_delimiter_accepts_tab_and_rejects_multi_char() {
+        assert_eq!(parse_delimiter("tab").expect("tab"), b'\t');
+        assert!(parse_delimiter(";;").is_err());
+        assert!(parse_delimiter("\"").is_err());
+    }
+
+    #[test]
+    fn rollup_groups_by_provider_model_and_day() {
+        let mut late = row("openai", "gpt-4o");
+        late.timestamp = Utc.with_ymd_and_hms(2024, 1, 2, 23, 0, 0).unwrap();
+        let rows = vec![row("openai", "gpt-4o"), late, row("anthropic", "claude")];
+
+        let rollup = CostRollup::from_rows(&rows);
+        assert!((rollup.total - 3.75).abs() < 1e-9);
+        assert!((rollup.by_provider["openai"] - 2.5).abs() < 1e-9);
+        assert_eq!(rollup.by_model.len(), 2);
+        assert_eq!(rollup.daily.len(), 3);
+        assert_eq!(
+            rollup.last,
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 23, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn line_protocol_escapes_tags_and_uses_nanoseconds() {
+        let out = cost_line_protocol(&[row("openai", "gpt 4o,mini=x")]);
+        assert_eq!(
+            out,
+            "llm_cost,provider=openai,model=gpt\\ 4o\\,mini\\=x,currency=USD \
+             input=1,output=0.25,total=1.25 1704067200000000000\n"
+        );
+    }
+
+    #[test]
+    fn xlsx_workbook_has_four_sheets() {
+        let bytes = cost_xlsx(&[row("openai", "gpt-4o")], "USD").expect("xlsx");
+        assert!(bytes.starts_with(b"PK"));
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"xl/worksheets/sheet4.xml"));
+        assert!(!contains(b"xl/worksheets/sheet5.xml"));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::config::DisplayConfig;
+
+/// How money is shown to the user: currency, symbol placement, separators and
+/// the USD conversion rate. All stored costs are USD; conversion happens only
+/// at display/export time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoneyFormat {
+    currency: String,
+    symbol: String,
+    symbol_after: bool,
+    decimal_sep: char,
+    group_sep: Option<char>,
+    usd_rate: f64,
+}
+
+impl Default for MoneyFormat {
+    fn default() -> Self {
+        Self {
+            currency: "USD".into(),
+            symbol: "$".into(),
+            symbol_after: false,
+            decimal_sep: '.',
+            group_sep: None,
+            usd_rate: 1.0,
+        }
+    }
+}
+
+impl MoneyFormat {
+    /// A non-USD currency without a `usd_rate` falls back to USD, so amounts
+    /// are never shown under the wrong currency.
+    pub fn from_config(display: &DisplayConfig) -> Self {
+        let currency = display.currency.trim().to_ascii_uppercase();
+        let (currency, usd_rate) = match display.usd_rate {
+            _ if currency.is_empty() || currency == "USD" => ("USD".to_string(), 1.0),
+            Some(rate) if rate.is_finite() && rate > 0.0 => (currency, rate),
+            _ => ("USD".to_string(), 1.0),
+        };
+
+        let (symbol_after, decimal_sep, group_sep) = match display.locale.as_deref() {
+            None => (false, '.', None),
+            Some(locale) => locale_rules(locale),
+        };
+
+        Self {
+            symbol: currency_symbol(&currency),
+            currency,
+            symbol_after,
+            decimal_sep,
+            group_sep,
+            usd_rate,
+        }
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn convert(&self, usd: f64) -> f64 {
+        usd * self.usd_rate
+    }
+
+    /// Converts `usd` and formats it, e.g. `$1234.50` or `1.234,50 €`.
+    pub fn money(&self, usd: f64, decimals: usize) -> String {
+        let value = self.convert(usd);
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{sign}{}", self.unsigned(value.abs(), decimals))
+    }
+
+    /// Like [`MoneyFormat::money`] but always carries a sign.
+    pub fn signed(&self, usd: f64, decimals: usize) -> String {
+        let value = self.convert(usd);
+        let sign = if value < 0.0 { "-" } else { "+" };
+        format!("{sign}{}", self.unsigned(value.abs(), decimals))
+    }
+
+    /// Change from `previous` to `current`, with a percentage when the
+    /// previous value is non-zero: `+$1.00 (+50.0%)`.
+    pub fn delta(&self, current: f64, previous: f64) -> String {
+        let delta = current - previous;
+        let amount = self.signed(delta, 2);
+        if previous.abs() < f64::EPSILON {
+            amount
+        } else {
+            let sign = if delta < 0.0 { "-" } else { "+" };
+            let pct = self.number((delta / previous * 100.0).abs(), 1);
+            format!("{amount} ({sign}{pct}%)")
+        }
+    }
+
+    fn unsigned(&self, value: f64, decimals: usize) -> String {
+        let number = self.number(value, decimals);
+        if self.symbol_after {
+            format!("{number} {}", self.symbol)
+        } else if self.symbol.chars().count() > 1 {
+            format!("{} {number}", self.symbol)
+        } else {
+            format!("{}{number}", self.symbol)
+        }
+    }
+
+    fn number(&self, value: f64, decimals: usize) -> String {
+        let raw = format!("{value:.decimals$}");
+        let (int_part, frac_part) = match raw.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (raw.as_str(), None),
+        };
+
+        let mut out = String::new();
+        let digits = int_part.len();
+        for (idx, ch) in int_part.chars().enumerate() {
+            if idx > 0 && (digits - idx) % 3 == 0 {
+                if let Some(sep) = self.group_sep {
+                    out.push(sep);
+                }
+            }
+            out.push(ch);
+        }
+        if let Some(frac) = frac_part {
+            out.push(self.decimal_sep);
+            out.push_str(frac);
+        }
+        out
+    }
+}
+
+/// (symbol after amount, decimal separator, group separator) by language.
+fn locale_rules(locale: &str) -> (bool, char, Option<char>) {
+    let lang = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match lang.as_str() {
+        "de" | "es" | "it" | "pt" | "da" | "nl" => (lang != "nl", ',', Some('.')),
+        "fr" | "pl" | "cs" | "sv" | "fi" | "nb" => (true, ',', Some('\u{202f}')),
+        _ => (false, '.', Some(',')),
+    }
+}
+
+fn currency_symbol(currency: &str) -> String {
+    match currency {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        other => other,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(currency: &str, locale: Option<&str>, usd_rate: Option<f64>) -> DisplayConfig {
+        DisplayConfig {
+            currency: currency.into(),
+            locale: locale.map(str::to_string),
+            usd_rate,
+            ..DisplayConfig::default()
+        }
+    }
+
+    #[test]
+    fn default_format_matches_plain_usd() {
+        let fmt = MoneyFormat::default();
+        assert_eq!(fmt.money(1234.5, 2), "$1234.50");
+        assert_eq!(fmt.signed(-0.42, 4), "-$0.4200");
+        assert_eq!(fmt.delta(3.0, 2.0), "+$1.00 (+50.0%)");
+        assert_eq!(fmt.delta(1.0, 2.0), "-$1.00 (-50.0%)");
+        assert_eq!(fmt.delta(1.5, 0.0), "+$1.50");
+    }
+
+    #[test]
+    fn german_euro_converts_and_places_symbol_after() {
+        let fmt = MoneyFormat::from_config(&display("eur", Some("de-DE"), Some(0.5)));
+        assert_eq!(fmt.currency(), "EUR");
+        assert_eq!(fmt.money(2469.0, 2), "1.234,50 €");
+        assert_eq!(fmt.delta(3.0, 2.0), "+0,50 € (+50,0%)");
+    }
+
+    #[test]
+    fn non_usd_without_rate_falls_back_to_usd() {
+        let fmt = MoneyFormat::from_config(&display("EUR", Some("en-US"), None));
+        assert_eq!(fmt.currency(), "USD");
+        assert_eq!(fmt.money(1234567.0, 0), "$1,234,567");
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::models::CostRecord;
+
+/// Exchange rates against USD, from `[fx_rates]` plus the display currency's
+/// `usd_rate`. Costs are stored in the currency each provider bills in and
+/// converted to USD through these rates wherever they are summed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FxRates {
+    /// Units of the currency per USD, keyed by upper-case ISO code.
+    per_usd: BTreeMap<String, f64>,
+}
+
+impl FxRates {
+    /// `[fx_rates]` wins over the display rate for the same currency. Entries
+    /// that are not three-letter codes or positive, finite rates are ignored,
+    /// as is a rate for USD itself.
+    pub fn from_config(cfg: &AppConfig) -> Self {
+        let display = cfg
+            .display
+            .usd_rate
+            .map(|rate| (cfg.display.currency.clone(), rate));
+        let per_usd = display
+            .into_iter()
+            .chain(
+                cfg.fx_rates
+                    .iter()
+                    .map(|(code, rate)| (code.clone(), *rate)),
+            )
+            .filter_map(|(code, rate)| {
+                let code = normalize_currency(&code)?;
+                (code != "USD" && rate.is_finite() && rate > 0.0).then_some((code, rate))
+            })
+            .collect();
+        Self { per_usd }
+    }
+
+    /// Units of `currency` per USD; `None` for a currency without a rate.
+    pub fn usd_rate(&self, currency: &str) -> Option<f64> {
+        let code = normalize_currency(currency)?;
+        if code == "USD" {
+            return Some(1.0);
+        }
+        self.per_usd.get(&code).copied()
+    }
+
+    /// `amount` in `currency` as USD. Amounts in a currency without a rate
+    /// are taken as USD, matching the aggregation queries.
+    pub fn to_usd(&self, amount: f64, currency: &str) -> f64 {
+        amount / self.usd_rate(currency).unwrap_or(1.0)
+    }
+
+    /// Rewrites `rows` in USD, e.g. before summing them in Rust.
+    pub fn rows_to_usd(&self, rows: &mut [CostRecord]) {
+        for r in rows.iter_mut().filter(|r| r.currency != "USD") {
+            r.input_cost = self.to_usd(r.input_cost, &r.currency);
+            r.output_cost = self.to_usd(r.output_cost, &r.currency);
+            r.total_cost = self.to_usd(r.total_cost, &r.currency);
+            r.currency = "USD".into();
+        }
+    }
+
+    /// Rewrites USD `rows` in `currency`, failing when it has no rate.
+    pub fn rows_from_usd(&self, rows: &mut [CostRecord], currency: &str) -> Result<(), AppError> {
+        let code = normalize_currency(currency)
+            .ok_or_else(|| AppError::Config(format!("'{currency}' is not a currency code")))?;
+        let rate = self.usd_rate(&code).ok_or_else(|| {
+            AppError::Config(format!(
+                "billing currency {code} has no rate; add it under [fx_rates]"
+            ))
+        })?;
+        for r in rows.iter_mut().filter(|r| r.currency == "USD") {
+            r.input_cost *= rate;
+            r.output_cost *= rate;
+            r.total_cost *= rate;
+            r.currency = code.clone();
+        }
+        Ok(())
+    }
+
+    /// SQL expression for `column` in USD, branching on the row's `currency`
+    /// column. Codes and rates are validated, so inlining them is safe.
+    pub fn usd_sql(&self, column: &str) -> String {
+        if self.per_usd.is_empty() {
+            return column.to_string();
+        }
+        let arms: String = self
+            .per_usd
+            .iter()
+            .map(|(code, rate)| format!(" WHEN '{code}' THEN {column} / {rate:?}"))
+            .collect();
+        format!("(CASE upper(currency){arms} ELSE {column} END)")
+    }
+}
+
+/// Upper-case three-letter code, or `None` when `raw` is not one.
+pub fn normalize_currency(raw: &str) -> Option<String> {
+    let code = raw.trim().to_ascii_uppercase();
+    (code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic())).then_some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn rates(entries: &[(&str, f64)]) -> FxRates {
+        let cfg = AppConfig {
+            fx_rates: entries.iter().map(|(c, r)| (c.to_string(), *r)).collect(),
+            ..AppConfig::default()
+        };
+        FxRates::from_config(&cfg)
+    }
+
+    #[test]
+    fn rates_skip_invalid_entries_and_include_the_display_rate() {
+        let mut cfg = AppConfig {
+            fx_rates: [("eur", 0.5), ("GBP", -1.0), ("X'; --", 2.0), ("USD", 3.0)]
+                .into_iter()
+                .map(|(c, r)| (c.to_string(), r))
+                .collect(),
+            ..AppConfig::default()
+        };
+        cfg.display.currency = "JPY".into();
+        cfg.display.usd_rate = Some(150.0);
+        let fx = FxRates::from_config(&cfg);
+
+        assert_eq!(fx.usd_rate("EUR"), Some(0.5));
+        assert_eq!(fx.usd_rate("jpy"), Some(150.0));
+        assert_eq!(fx.usd_rate("usd"), Some(1.0));
+        assert_eq!(fx.usd_rate("GBP"), None);
+        assert!((fx.to_usd(10.0, "EUR") - 20.0).abs() < 1e-9);
+        assert_eq!(
+            fx.usd_sql("total_cost"),
+            "(CASE upper(currency) WHEN 'EUR' THEN total_cost / 0.5 WHEN 'JPY' THEN total_cost / 150.0 ELSE total_cost END)"
+        );
+        assert_eq!(FxRates::default().usd_sql("total_cost"), "total_cost");
+    }
+
+    #[test]
+    fn rows_round_trip_through_a_billing_currency() {
+        let fx = rates(&[("EUR", 0.8)]);
+        let mut rows = vec![CostRecord {
+            provider: "azure-openai".into(),
+            model: "gpt-4o".into(),
+            input_cost: 1.0,
+            output_cost: 4.0,
+            total_cost: 5.0,
+            currency: "USD".into(),
+            timestamp: Utc::now(),
+            bucket_end: None,
+            source_granularity: None,
+            source: Default::default(),
+        }];
+
+        fx.rows_from_usd(&mut rows, "eur").unwrap();
+        assert_eq!(rows[0].currency, "EUR");
+        assert!((rows[0].total_cost - 4.0).abs() < 1e-9);
+
+        fx.rows_to_usd(&mut rows);
+        assert_eq!(rows[0].currency, "USD");
+        assert!((rows[0].total_cost - 5.0).abs() < 1e-9);
+
+        let err = fx.rows_from_usd(&mut rows, "CHF").unwrap_err();
+        assert!(err.to_string().contains("[fx_rates]"));
+    }
+}
@@ -1,19 +1,43 @@
+mod audit;
+mod aws;
+mod budgets;
+mod cloud_billing;
 mod config;
+mod confirm;
+mod deprecations;
 mod error;
+mod export;
+mod format;
+mod fx;
+mod metrics;
 mod models;
+mod net;
+mod notifier;
 mod pricing;
 mod providers;
+mod reconcile;
+mod report;
 mod service;
+mod sheets;
+mod sinks;
+mod statements;
 mod storage;
+mod template;
 mod ui;
+mod upload;
+mod wizard;
 
+use audit::AuditSource;
 use clap::{Parser, Subcommand};
 use config::{
     db_path, ensure_initialized, load_config, normalize_provider_name, save_config, set_api_key,
+    ExportPipeline, JournalMode,
 };
 use error::AppError;
+use format::MoneyFormat;
 use models::TimeWindow;
 use service::MeterService;
+use std::path::{Path, PathBuf};
 use storage::Storage;
 use ui::run::run_tui;
 
@@ -21,13 +45,43 @@ use ui::run::run_tui;
 #[command(name = "llm-meter")]
 #[command(about = "Online LLM token and cost monitor")]
 struct Cli {
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+    /// Profile whose config, database and keyring entries to use; defaults
+    /// to `LLM_METER_PROFILE`, else `default`.
+    #[arg(long, global = true)]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    Init,
+    /// Create the config and data directories; flags fill in the config so
+    /// one command provisions a working install.
+    Init {
+        /// Seconds between dashboard refreshes.
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        refresh_seconds: Option<u64>,
+        /// Display and export currency (ISO code); non-USD needs `--usd-rate`.
+        #[arg(long)]
+        currency: Option<String>,
+        /// Units of `--currency` per 1 USD.
+        #[arg(long)]
+        usd_rate: Option<f64>,
+        /// Locale for separators and symbol placement, e.g. `de-DE`.
+        #[arg(long)]
+        locale: Option<String>,
+        /// Enable a provider; repeat for several.
+        #[arg(long = "enable", value_name = "PROVIDER")]
+        enable: Vec<String>,
+        /// Env var holding the key of the `--enable` at the same position.
+        #[arg(long = "api-key-env", value_name = "VAR")]
+        api_key_env: Vec<String>,
+        /// Prompt for providers, keys, connection tests and a budget.
+        #[arg(long)]
+        interactive: bool,
+    },
     AddProvider {
         provider: String,
         #[arg(long)]
@@ -36,15 +90,233 @@ enum Commands {
         base_url: Option<String>,
         #[arg(long)]
         organization_id: Option<String>,
+        /// Day the key expires (`YYYY-MM-DD`), for rotation reminders.
+        #[arg(long)]
+        key_expires_at: Option<chrono::NaiveDate>,
+        /// Adapter for an account named otherwise, e.g.
+        /// `custom-openai-compatible` for a LiteLLM or vLLM server.
+        #[arg(long)]
+        kind: Option<String>,
+        /// Name shown in the TUI instead of the account name, e.g. "vLLM prod".
+        #[arg(long)]
+        display_name: Option<String>,
+        /// Fetch this much history right away, e.g. `90d` (capped per provider).
+        #[arg(long)]
+        backfill: Option<String>,
+    },
+    /// Rename a provider account (e.g. `openai` to `openai-prod`), moving its
+    /// settings, stored key and history.
+    RenameProvider { from: String, to: String },
+    Tui {
+        #[arg(long)]
+        no_color: bool,
+        /// Open on stored data; the first fetch waits for `r` or the first tick.
+        #[arg(long)]
+        no_refresh: bool,
     },
-    Tui,
     Refresh {
         #[arg(long, default_value = "7d")]
         window: String,
+        #[arg(long)]
+        json: bool,
     },
     Export {
+        /// json, jsonl, csv, xlsx, influx (line protocol) or focus (FinOps FOCUS CSV).
         #[arg(long, default_value = "json")]
         format: String,
+        /// CSV field delimiter: one ASCII character, or `tab`.
+        #[arg(long, default_value = ",")]
+        delimiter: String,
+        /// Omit the CSV header row.
+        #[arg(long)]
+        no_header: bool,
+        /// Quote every CSV field, not only those that need it.
+        #[arg(long)]
+        quote_all: bool,
+        /// Send to a destination instead of printing: `google-sheets`,
+        /// `s3://bucket/prefix/` or an `https://` URL (PUT).
+        #[arg(long)]
+        to: Option<String>,
+        /// Spreadsheet id for `--to google-sheets`.
+        #[arg(long)]
+        sheet_id: Option<String>,
+        /// Service-account JSON key; defaults to $GOOGLE_APPLICATION_CREDENTIALS.
+        #[arg(long)]
+        credentials: Option<PathBuf>,
+        /// Sheet (or A1 range) the daily rollup is appended to.
+        #[arg(long, default_value = "Sheet1")]
+        range: String,
+        /// Number of complete days, ending yesterday, to append.
+        #[arg(long, default_value_t = 1)]
+        days: u32,
+        /// Replace provider and model names with stable salted hashes.
+        #[arg(long)]
+        redact: bool,
+        /// Report models under their `[model_groups]` family names.
+        #[arg(long)]
+        group_models: bool,
+    },
+    Render {
+        #[arg(long, default_value = "7d")]
+        window: String,
+        #[arg(long, default_value_t = 120)]
+        width: u16,
+        #[arg(long, default_value_t = 30)]
+        height: u16,
+        #[arg(long)]
+        ansi: bool,
+    },
+    Notify {
+        #[command(subcommand)]
+        target: NotifyTarget,
+    },
+    /// Per-provider totals for a calendar month (`YYYY-MM`, UTC), frozen
+    /// once the month has closed.
+    Statement {
+        month: String,
+        /// Recompute a frozen statement from the current snapshot.
+        #[arg(long)]
+        force: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a read-only SQL query against the database, e.g.
+    /// `SELECT model, SUM(total_cost) FROM cost_records GROUP BY model`.
+    /// Statements other than SELECT are refused.
+    Query {
+        sql: String,
+        /// Print CSV instead of an aligned table.
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Diff a provider invoice CSV against the frozen monthly statement.
+    Reconcile {
+        /// Invoice CSV export with line-item and amount columns.
+        #[arg(long)]
+        invoice: PathBuf,
+        #[arg(long)]
+        provider: String,
+        /// Statement month, `YYYY-MM`.
+        #[arg(long)]
+        month: String,
+        /// Differences up to this many currency units are not flagged.
+        #[arg(long, default_value_t = 0.01)]
+        tolerance: f64,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Import LLM line items from a cloud billing export: Azure Cost
+    /// Management (Azure OpenAI) or GCP BigQuery billing (Vertex AI).
+    ImportBilling {
+        /// azure or gcp.
+        #[arg(long)]
+        source: String,
+        /// Export file: CSV, or JSON lines for BigQuery.
+        #[arg(long)]
+        file: PathBuf,
+        /// Provider name rows are stored under; azure-openai or vertex-ai by default.
+        #[arg(long)]
+        provider: Option<String>,
+        /// Units of the billing currency per USD, for non-USD exports.
+        #[arg(long)]
+        usd_rate: Option<f64>,
+    },
+    /// Configured `[[exports]]` pipelines.
+    Exports {
+        #[command(subcommand)]
+        action: ExportsAction,
+    },
+    /// Validate config.toml and the database; `--repair` drops unknown config
+    /// keys and rebuilds the database indices.
+    Check {
+        #[arg(long)]
+        repair: bool,
+        /// Skip typing `repair` to confirm rewriting config.toml, e.g. in scripts.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Changes to the config and stored keys, newest first.
+    Audit {
+        /// Most entries to show.
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Maintenance of the local snapshot database.
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DbAction {
+    /// Delete every stored usage and cost row for a provider, e.g. one
+    /// removed from config. Frozen statements are kept.
+    PurgeProvider {
+        name: String,
+        /// Only print how many rows would be deleted.
+        #[arg(long)]
+        dry_run: bool,
+        /// Skip typing the provider name to confirm, e.g. in scripts.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Encrypt the database with a new key kept in the OS keyring. Needs a
+    /// build with `--features encryption`.
+    Encrypt,
+    /// Convert an encrypted database back to plain SQLite and delete its key.
+    Decrypt {
+        /// Skip typing `decrypt` to confirm, e.g. in scripts.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportsAction {
+    /// List pipelines with their sink, format, destination and schedule.
+    List,
+    /// Run pipelines now; all of them unless filtered.
+    Run {
+        /// Only the pipeline with this name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Only pipelines with this schedule, e.g. `daily` from a daily cron job.
+        #[arg(long)]
+        schedule: Option<String>,
+        /// Only pipelines whose schedule has fired since their last run.
+        #[arg(long)]
+        due: bool,
+    },
+    /// Last run, last success and next due time of each pipeline.
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+enum NotifyTarget {
+    Slack {
+        #[arg(long, default_value = "1d")]
+        window: String,
+        #[arg(long)]
+        webhook_url: Option<String>,
+    },
+    /// Render the spend digest template to stdout, or post it to Slack.
+    Digest {
+        #[arg(long, default_value = "7d")]
+        window: String,
+        /// Template file; defaults to `templates/digest.txt` in the config dir.
+        #[arg(long)]
+        template: Option<PathBuf>,
+        /// Post to the configured Slack webhook instead of printing.
+        #[arg(long)]
+        slack: bool,
+        #[arg(long)]
+        webhook_url: Option<String>,
+        /// Print the built-in template, as a starting point for your own.
+        #[arg(long)]
+        default_template: bool,
     },
 }
 
@@ -66,32 +338,188 @@ fn validate_window(input: &str) -> Result<TimeWindow, AppError> {
     }
 }
 
-fn csv_field(raw: &str) -> String {
-    if raw.contains([',', '"', '\n', '\r']) {
-        format!("\"{}\"", raw.replace('"', "\"\""))
-    } else {
-        raw.to_string()
+fn parse_backfill_days(input: &str) -> Result<u32, AppError> {
+    input
+        .strip_suffix('d')
+        .and_then(|days| days.parse::<u32>().ok())
+        .filter(|days| *days > 0)
+        .ok_or_else(|| {
+            AppError::Config("Unsupported backfill. Use a number of days, e.g. 90d.".into())
+        })
+}
+
+/// The config's `redact_salt`, saving a freshly generated one.
+fn redact_salt(cfg: &mut config::AppConfig) -> Result<String, AppError> {
+    if cfg.ensure_redact_salt()? {
+        save_config(cfg)?;
+    }
+    Ok(cfg.redact_salt.clone().unwrap_or_default())
+}
+
+/// All stored cost rows in the display currency.
+fn load_export_rows(money: &MoneyFormat) -> Result<Vec<models::CostRecord>, AppError> {
+    let storage = Storage::open_configured()?;
+    let mut rows: Vec<models::CostRecord> = serde_json::from_str(&storage.export_cost_json()?)?;
+    export::convert_cost_rows(&mut rows, &storage.fx_rates(), money);
+    Ok(rows)
+}
+
+/// Rewrites the database under a new key via a sibling file, so a failed
+/// conversion leaves the original untouched. The old handle is dropped first,
+/// which checkpoints the WAL into the file being replaced.
+fn rekey_database(
+    db: &Path,
+    from: Option<&str>,
+    to: Option<&str>,
+    journal: JournalMode,
+) -> Result<(), AppError> {
+    let tmp = db.with_extension("rekey");
+    if tmp.exists() {
+        std::fs::remove_file(&tmp)?;
     }
+    Storage::open_with(db, from, journal)?.export_rekeyed(&tmp, to)?;
+    std::fs::rename(&tmp, db)?;
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
     let cli = Cli::parse();
+    if let Some(profile) = cli
+        .profile
+        .or_else(|| std::env::var("LLM_METER_PROFILE").ok())
+    {
+        config::set_profile(&profile)?;
+    }
+    if let Some(db) = cli.db {
+        config::set_db_path_override(db);
+    }
+    audit::set_source(if matches!(cli.command, Commands::Tui { .. }) {
+        AuditSource::Tui
+    } else {
+        AuditSource::Cli
+    });
+
+    if !matches!(cli.command, Commands::Init { .. } | Commands::Check { .. }) {
+        for key in config::unknown_config_keys()? {
+            eprintln!(
+                "warning: config.toml: unknown key `{key}` (`llm-meter check --repair` drops it)"
+            );
+        }
+    }
 
     match cli.command {
-        Commands::Init => {
+        Commands::Init {
+            refresh_seconds,
+            currency,
+            usd_rate,
+            locale,
+            enable,
+            api_key_env,
+            interactive,
+        } => {
+            if api_key_env.len() > enable.len() {
+                return Err(AppError::Config(
+                    "Each --api-key-env needs a matching --enable before it.".into(),
+                ));
+            }
             ensure_initialized()?;
+            let mut cfg = load_config()?;
+            if let Some(seconds) = refresh_seconds {
+                cfg.refresh_seconds = seconds;
+            }
+            let currency_given = currency.is_some();
+            if let Some(currency) = currency {
+                let currency = currency.trim().to_ascii_uppercase();
+                if currency != cfg.display.currency {
+                    // The old rate was for the old currency.
+                    cfg.display.usd_rate = None;
+                }
+                cfg.display.currency = currency;
+            }
+            if usd_rate.is_some() {
+                cfg.display.usd_rate = usd_rate;
+            }
+            if locale.is_some() {
+                cfg.display.locale = locale;
+            }
+            if currency_given
+                && MoneyFormat::from_config(&cfg.display).currency() != cfg.display.currency
+            {
+                return Err(AppError::Config(format!(
+                    "--currency {} needs a positive --usd-rate.",
+                    cfg.display.currency
+                )));
+            }
+
+            let mut enabled = Vec::new();
+            for (idx, provider) in enable.iter().enumerate() {
+                let provider = normalize_provider_name(provider);
+                if !cfg.enabled_providers.contains(&provider) {
+                    cfg.enabled_providers.push(provider.clone());
+                }
+                let settings = cfg.provider_settings.entry(provider.clone()).or_default();
+                if let Some(var) = api_key_env.get(idx) {
+                    settings.api_key_env = Some(var.clone());
+                }
+                let var = settings
+                    .api_key_env
+                    .clone()
+                    .unwrap_or_else(|| config::default_api_key_env(&provider));
+                let var =
+                    service::adapter_needs_api_key(&cfg.provider_kind(&provider)).then_some(var);
+                enabled.push((provider, var));
+            }
+            if interactive {
+                use std::io::IsTerminal;
+                if !std::io::stdin().is_terminal() {
+                    return Err(AppError::Config(
+                        "init --interactive needs a terminal; use the init flags in scripts."
+                            .into(),
+                    ));
+                }
+                let service = MeterService::new(&cfg)?;
+                wizard::run(&mut cfg, &service).await?;
+            }
+            save_config(&cfg)?;
             println!("Initialized llm-meter config and data directories.");
+            for (provider, var) in enabled {
+                let Some(var) = var else {
+                    println!(
+                        "Provider '{provider}' enabled; reads local usage logs, no key needed."
+                    );
+                    continue;
+                };
+                println!("Provider '{provider}' enabled; key from ${var} unless one is stored.");
+                if !std::env::var(&var).is_ok_and(|v| !v.is_empty()) {
+                    eprintln!("warning: ${var} is not set; refreshes of '{provider}' will fail until it is or a key is stored with `add-provider`.");
+                }
+            }
         }
         Commands::AddProvider {
             provider,
             api_key,
             base_url,
             organization_id,
+            key_expires_at,
+            kind,
+            display_name,
+            backfill,
         } => {
             ensure_initialized()?;
+            let backfill_days = backfill.as_deref().map(parse_backfill_days).transpose()?;
             let mut cfg = load_config()?;
             let provider = normalize_provider_name(&provider);
+            let kind = kind.as_deref().map(normalize_provider_name);
+            if let Some(kind) = &kind {
+                let known = service::adapter_names();
+                if !known.contains(&kind.as_str()) {
+                    return Err(AppError::Config(format!(
+                        "Unknown provider kind '{kind}'. Use one of: {}.",
+                        known.join(", ")
+                    )));
+                }
+            }
 
             if !cfg
                 .enabled_providers
@@ -101,65 +529,754 @@ async fn main() -> Result<(), AppError> {
                 cfg.enabled_providers.push(provider.clone());
             }
 
+            let existing = cfg.provider_settings.remove(&provider).unwrap_or_default();
             cfg.provider_settings.insert(
                 provider.clone(),
                 config::ProviderSettings {
                     base_url,
                     organization_id,
+                    key_expires_at: key_expires_at.or(existing.key_expires_at),
+                    kind: kind.or(existing.kind),
+                    display_name: display_name.or(existing.display_name),
+                    ..existing
                 },
             );
+            let kind = cfg.provider_kind(&provider);
+            if service::adapter_requires_base_url(&kind)
+                && cfg.provider_settings[&provider].base_url.is_none()
+            {
+                return Err(AppError::Config(format!(
+                    "'{provider}' is a {kind} account; pass --base-url with the server's address."
+                )));
+            }
 
             set_api_key(&provider, &api_key)?;
             save_config(&cfg)?;
+            let storage = Storage::open_configured()?;
+            storage.clear_needs_reauth(&provider)?;
             println!("Provider '{}' configured.", provider);
+
+            if let Some(days) = backfill_days {
+                let snap = MeterService::new(&cfg)?
+                    .backfill(&cfg, &provider, Some(days), &storage)
+                    .await?;
+                if !snap.capped.is_empty() {
+                    return Err(AppError::Config(format!(
+                        "Backfill for '{provider}' stopped at [request_caps]; nothing was stored. Retry once the cap allows more requests."
+                    )));
+                }
+                println!(
+                    "Backfilled {} usage records and {} cost rows for '{}'.",
+                    snap.usage.len(),
+                    snap.cost.len(),
+                    provider
+                );
+            }
         }
-        Commands::Tui => {
+        Commands::Tui {
+            no_color,
+            no_refresh,
+        } => {
             ensure_initialized()?;
-            run_tui().await?;
+            run_tui(no_color, no_refresh).await?;
         }
-        Commands::Refresh { window } => {
+        Commands::Refresh { window, json } => {
             ensure_initialized()?;
             let cfg = load_config()?;
-            let db = db_path()?;
-            let mut storage = Storage::open(&db)?;
-            let svc = MeterService::new()?;
-            let snap = svc
-                .refresh(&cfg, validate_window(&window)?, &mut storage)
-                .await?;
+            let storage = Storage::open_configured()?;
+            let svc = MeterService::new(&cfg)?;
+            let window = validate_window(&window)?;
+            let refreshed = svc.refresh(&cfg, window, &storage).await;
+            for provider in storage.providers_needing_reauth()? {
+                eprintln!(
+                    "warning: '{provider}' rejected its API key (401); skipping it until the key is updated with `add-provider {provider} --api-key ...`."
+                );
+            }
+            for provider in service::quarantined_providers(&cfg, &storage, chrono::Utc::now())? {
+                let (failures, last) = storage.consecutive_failures(&provider)?;
+                let next_probe = last.map(|at| at + service::quarantine_backoff(&cfg, failures));
+                eprintln!(
+                    "warning: '{provider}' is quarantined after {failures} failed refreshes; skipping it until {}.",
+                    next_probe.map_or_else(|| "the next refresh".into(), |at| at.to_rfc3339())
+                );
+            }
+            let snap = refreshed?;
+            if let Some(skew) = &snap.clock_skew {
+                eprintln!(
+                    "warning: {}; query windows are shifted by as much, so recent usage may look missing. Sync the system clock.",
+                    skew.describe()
+                );
+            }
+            if !snap.capped.is_empty() {
+                eprintln!(
+                    "warning: [request_caps] reached; not refreshed: {} (stored data kept).",
+                    snap.capped.join(", ")
+                );
+            }
+            if !snap.timed_out.is_empty() {
+                eprintln!(
+                    "warning: refresh_timeout_secs ({}s) reached; not refreshed: {} (stored data kept).",
+                    cfg.refresh_timeout_secs,
+                    snap.timed_out.join(", ")
+                );
+            }
+            for (provider, err) in &snap.failed {
+                eprintln!("warning: '{provider}' failed to refresh (stored data kept): {err}");
+            }
+            if let Err(e) = svc.send_refresh_webhook(&cfg, &snap).await {
+                eprintln!("warning: refresh webhook failed: {e}");
+            }
+            if let Err(e) = svc.send_influx_points(&cfg, &snap).await {
+                eprintln!("warning: InfluxDB write failed: {e}");
+            }
+            if let Some(sink) = &cfg.metrics {
+                if let Err(e) = metrics::emit(sink, &snap, cfg.local_only) {
+                    eprintln!("warning: metrics emission failed: {e}");
+                }
+            }
+            let check = budgets::check(&cfg, &storage, chrono::Utc::now())?;
+            for line in svc.enforce_budgets(&cfg, check).await {
+                eprintln!("warning: {line}");
+            }
+            let check = service::check_key_expiry(&cfg, &storage, chrono::Utc::now())?;
+            for line in svc.notify_key_expiry(&cfg, check).await {
+                eprintln!("warning: {line}");
+            }
+            let check = deprecations::check(&cfg, &storage, window, chrono::Utc::now())?;
+            for line in svc.notify_deprecations(&cfg, check).await {
+                eprintln!("warning: {line}");
+            }
+            for (provider, days) in cfg.expiring_keys(chrono::Utc::now().date_naive()) {
+                if days > 0 {
+                    eprintln!("warning: API key for '{provider}' expires in {days} day(s).");
+                }
+            }
+            if json {
+                let out = serde_json::json!({
+                    "fetched_at": snap.fetched_at,
+                    "usage_records": snap.usage.len(),
+                    "cost_rows": snap.cost.len(),
+                    "providers": snap.stats,
+                });
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                println!(
+                    "Fetched {} usage records and {} cost rows at {}",
+                    snap.usage.len(),
+                    snap.cost.len(),
+                    snap.fetched_at
+                );
+                if !snap.stats.is_empty() {
+                    print!("{}", report::refresh_stats_table(&snap.stats));
+                }
+            }
+        }
+        Commands::Statement { month, force, json } => {
+            ensure_initialized()?;
+            let cfg = load_config()?;
+            let storage = Storage::open_configured()?;
+            let rows = statements::statement(&cfg, &storage, &month, force, chrono::Utc::now())?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                match rows.first().and_then(|r| r.frozen_at) {
+                    Some(at) => println!("Statement {month} (frozen {})", at.to_rfc3339()),
+                    None => println!("Statement {month} (open month, live totals)"),
+                }
+                print!("{}", report::statement_table(&rows));
+            }
+        }
+        Commands::Query { sql, csv } => {
+            ensure_initialized()?;
+            let storage = Storage::open_configured()?;
+            let result = storage.run_query(&sql, None)?;
+            if csv {
+                print!("{}", report::query_csv(&result)?);
+            } else {
+                print!("{}", report::query_table(&result));
+            }
+        }
+        Commands::Reconcile {
+            invoice,
+            provider,
+            month,
+            tolerance,
+            json,
+        } => {
+            ensure_initialized()?;
+            let storage = Storage::open_configured()?;
+            let provider = normalize_provider_name(&provider);
+            let first = statements::parse_month(&month)?;
+            let month = first.format("%Y-%m").to_string();
+            let frozen = storage.statement(&month)?;
+            if frozen.is_empty() {
+                return Err(AppError::Config(format!(
+                    "No frozen statement for {month}. Run `statement {month}` after the month closes."
+                )));
+            }
+            let statement_total: f64 = frozen
+                .iter()
+                .filter(|row| row.provider == provider)
+                .map(|row| row.total_cost)
+                .sum();
+            let file = std::fs::File::open(&invoice).map_err(|e| {
+                AppError::Config(format!("cannot read invoice {}: {e}", invoice.display()))
+            })?;
+            let invoice_lines = reconcile::parse_invoice(file)?;
+            let invoice_total: f64 = invoice_lines.iter().map(|l| l.amount).sum();
+            let (start, end) = statements::month_bounds(first);
+            let tracked = storage.provider_cost_by_model_between(&provider, start, end)?;
+            let lines = reconcile::reconcile(&invoice_lines, &tracked);
+            let flagged = lines.iter().filter(|l| l.diff.abs() > tolerance).count();
+
+            if json {
+                let out = serde_json::json!({
+                    "provider": provider,
+                    "month": month,
+                    "invoice_total": invoice_total,
+                    "statement_total": statement_total,
+                    "diff": invoice_total - statement_total,
+                    "discrepancies": flagged,
+                    "lines": lines,
+                });
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                println!(
+                    "{:<40} {:>12} {:>12} {:>12}",
+                    "LINE", "INVOICE", "TRACKED", "DIFF"
+                );
+                for line in &lines {
+                    let mark = if line.diff.abs() > tolerance {
+                        " !"
+                    } else {
+                        ""
+                    };
+                    let note = if line.matched { "" } else { " (not tracked)" };
+                    println!(
+                        "{:<40} {:>12.2} {:>12.2} {:>+12.2}{mark}{note}",
+                        line.line, line.invoice, line.tracked, line.diff
+                    );
+                }
+                println!(
+                    "Invoice total {invoice_total:.2}, frozen {month} statement {statement_total:.2}, difference {:+.2}.",
+                    invoice_total - statement_total
+                );
+                let tracked_total: f64 = tracked.iter().map(|(_, c)| c).sum();
+                if (tracked_total - statement_total).abs() > tolerance {
+                    println!(
+                        "note: stored {provider} rows now total {tracked_total:.2}; the data changed after the statement was frozen."
+                    );
+                }
+                println!("{flagged} line(s) differ by more than {tolerance}.");
+            }
+        }
+        Commands::ImportBilling {
+            source,
+            file,
+            provider,
+            usd_rate,
+        } => {
+            ensure_initialized()?;
+            let cfg = load_config()?;
+            let source = cloud_billing::BillingSource::parse(&source)?;
+            let provider =
+                normalize_provider_name(provider.as_deref().unwrap_or(source.default_provider()));
+            if cfg.enabled_providers.contains(&provider) {
+                return Err(AppError::Config(format!(
+                    "'{provider}' is refreshed from its usage API, which would overwrite imported rows. Pass another --provider."
+                )));
+            }
+            let reader = std::fs::File::open(&file).map_err(|e| {
+                AppError::Config(format!(
+                    "cannot read billing export {}: {e}",
+                    file.display()
+                ))
+            })?;
+            let import = cloud_billing::parse(source, reader, &provider, usd_rate)?;
+            let Some((start, end)) = import.span() else {
+                println!(
+                    "No matching line items in {}; {} other rows skipped.",
+                    file.display(),
+                    import.skipped
+                );
+                return Ok(());
+            };
+            let storage = Storage::open_configured()?;
+            let replaced = storage.replace_costs_between(&provider, start, end, &import.rows)?;
+            let total: f64 = import.rows.iter().map(|r| r.total_cost).sum();
             println!(
-                "Fetched {} usage records and {} cost rows at {}",
-                snap.usage.len(),
-                snap.cost.len(),
-                snap.fetched_at
+                "Imported {} line items as {} cost rows for '{provider}' ({} to {}, ${total:.2}); replaced {replaced} earlier rows, skipped {} other rows.",
+                import.line_items,
+                import.rows.len(),
+                start.format("%Y-%m-%d"),
+                (end - chrono::Duration::seconds(1)).format("%Y-%m-%d"),
+                import.skipped
             );
         }
-        Commands::Export { format } => {
+        Commands::Render {
+            window,
+            width,
+            height,
+            ansi,
+        } => {
             ensure_initialized()?;
-            let db = db_path()?;
-            let storage = Storage::open(&db)?;
-            if format.eq_ignore_ascii_case("json") {
-                println!("{}", storage.export_cost_json()?);
-            } else if format.eq_ignore_ascii_case("csv") {
-                let json = storage.export_cost_json()?;
-                let rows: Vec<models::CostRecord> = serde_json::from_str(&json)?;
-                println!("provider,model,input_cost,output_cost,total_cost,currency,timestamp");
-                for r in rows {
+            let cfg = load_config()?;
+            let storage = Storage::open_configured()?;
+            let buffer = ui::headless::render_dashboard(
+                &cfg,
+                &storage,
+                validate_window(&window)?,
+                width.max(20),
+                height.max(10),
+            )?;
+            if ansi {
+                print!("{}", ui::headless::buffer_to_ansi(&buffer));
+            } else {
+                print!("{}", ui::headless::buffer_to_text(&buffer));
+            }
+        }
+        Commands::Export {
+            format,
+            delimiter,
+            no_header,
+            quote_all,
+            to,
+            sheet_id,
+            credentials,
+            range,
+            days,
+            redact,
+            group_models,
+        } => {
+            ensure_initialized()?;
+            let mut cfg = load_config()?;
+            let money = MoneyFormat::from_config(&cfg.display);
+            let mut rows = load_export_rows(&money)?;
+            if group_models {
+                export::group_cost_rows(&mut rows, &cfg);
+            }
+            if redact {
+                export::redact_cost_rows(&mut rows, &redact_salt(&mut cfg)?);
+            }
+            match to {
+                Some(to) => {
+                    let sink = sinks::sink_for_destination(&to)?;
+                    let pipeline = ExportPipeline {
+                        to: (sink != "google-sheets").then_some(to),
+                        delimiter: Some(delimiter),
+                        header: !no_header,
+                        quote_all,
+                        sheet_id,
+                        credentials,
+                        range: Some(range),
+                        days: Some(days),
+                        ..ExportPipeline::new(sink, &format)
+                    };
+                    let done = MeterService::new(&cfg)?
+                        .run_export(&pipeline, &rows, money.currency(), chrono::Utc::now())
+                        .await?;
+                    println!("Export {}: {done}.", pipeline.sink);
+                }
+                None => {
+                    let format = export::ExportFormat::parse(&format)?;
+                    let csv = if format == export::ExportFormat::Csv {
+                        export::CsvOptions {
+                            delimiter: export::parse_delimiter(&delimiter)?,
+                            header: !no_header,
+                            quote_all,
+                        }
+                    } else {
+                        export::CsvOptions::default()
+                    };
+                    let body = export::render_cost(format, &rows, csv, money.currency())?;
+                    std::io::Write::write_all(&mut std::io::stdout().lock(), &body)?;
+                }
+            }
+        }
+        Commands::Exports {
+            action: ExportsAction::List,
+        } => {
+            ensure_initialized()?;
+            let cfg = load_config()?;
+            if cfg.exports.is_empty() {
+                println!("No export pipelines configured. Add [[exports]] entries to config.toml.");
+            }
+            for pipeline in &cfg.exports {
+                let problem = sinks::check_pipeline(pipeline).err();
+                println!(
+                    "{:<24} {:<14} {:<6} {:<8} {}{}",
+                    pipeline.label(),
+                    pipeline.sink,
+                    pipeline.format,
+                    pipeline.schedule(),
+                    pipeline.to.as_deref().unwrap_or("-"),
+                    problem.map_or_else(String::new, |e| format!("  (invalid: {e})"))
+                );
+            }
+        }
+        Commands::Exports {
+            action:
+                ExportsAction::Run {
+                    name,
+                    schedule,
+                    due,
+                },
+        } => {
+            ensure_initialized()?;
+            let mut cfg = load_config()?;
+            let salt = if cfg.exports.iter().any(|p| p.redact) {
+                redact_salt(&mut cfg)?
+            } else {
+                String::new()
+            };
+            let storage = Storage::open_configured()?;
+            let now = chrono::Utc::now();
+            let mut selected: Vec<&ExportPipeline> = cfg
+                .exports
+                .iter()
+                .filter(|p| name.as_deref().is_none_or(|n| p.label() == n))
+                .filter(|p| {
+                    schedule
+                        .as_deref()
+                        .is_none_or(|s| p.schedule().eq_ignore_ascii_case(s))
+                })
+                .collect();
+            if selected.is_empty() {
+                return Err(AppError::Config(
+                    "No export pipelines match. See `exports list`.".into(),
+                ));
+            }
+            for pipeline in &selected {
+                sinks::check_pipeline(pipeline)?;
+            }
+            if due {
+                let mut due_now = Vec::new();
+                for pipeline in selected {
+                    let last_run = storage
+                        .last_export_run(&pipeline.label())?
+                        .map(|run| run.ran_at);
+                    if sinks::next_run(pipeline.schedule(), last_run, now)?
+                        .is_some_and(|at| at <= now)
+                    {
+                        due_now.push(pipeline);
+                    }
+                }
+                if due_now.is_empty() {
+                    println!("No export pipelines due.");
+                    return Ok(());
+                }
+                selected = due_now;
+            }
+            let money = MoneyFormat::from_config(&cfg.display);
+            let rows = load_export_rows(&money)?;
+            let svc = MeterService::new(&cfg)?;
+            let mut failed = 0;
+            for pipeline in selected {
+                let label = pipeline.label();
+                let mut prepared = std::borrow::Cow::Borrowed(&rows);
+                if pipeline.group_models {
+                    export::group_cost_rows(prepared.to_mut(), &cfg);
+                }
+                if pipeline.redact {
+                    export::redact_cost_rows(prepared.to_mut(), &salt);
+                }
+                match svc
+                    .run_export(pipeline, &prepared, money.currency(), now)
+                    .await
+                {
+                    Ok(done) => {
+                        storage.record_export_run(&label, now, Ok(&done))?;
+                        println!("{label}: {done}");
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        storage.record_export_run(&label, now, Err(&e.to_string()))?;
+                        eprintln!("{label}: failed: {e}");
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(AppError::Config(format!(
+                    "{failed} export pipeline(s) failed."
+                )));
+            }
+        }
+        Commands::Exports {
+            action: ExportsAction::Status,
+        } => {
+            ensure_initialized()?;
+            let cfg = load_config()?;
+            let storage = Storage::open_configured()?;
+            let now = chrono::Utc::now();
+            let fmt_ts = |at: Option<chrono::DateTime<chrono::Utc>>| {
+                at.map_or_else(|| "-".into(), |at| at.format("%Y-%m-%d %H:%M").to_string())
+            };
+            println!(
+                "{:<24} {:<14} {:<17} {:<7} {:<17} {:<17}",
+                "PIPELINE", "SCHEDULE", "LAST RUN", "RESULT", "LAST SUCCESS", "NEXT DUE"
+            );
+            for pipeline in &cfg.exports {
+                let label = pipeline.label();
+                let last = storage.last_export_run(&label)?;
+                let next =
+                    sinks::next_run(pipeline.schedule(), last.as_ref().map(|r| r.ran_at), now)
+                        .map_or_else(
+                            |e| format!("invalid: {e}"),
+                            |next| match next {
+                                None => "manual".into(),
+                                Some(at) if at <= now => "now".into(),
+                                Some(at) => fmt_ts(Some(at)),
+                            },
+                        );
+                println!(
+                    "{:<24} {:<14} {:<17} {:<7} {:<17} {}",
+                    label,
+                    pipeline.schedule(),
+                    fmt_ts(last.as_ref().map(|r| r.ran_at)),
+                    last.as_ref()
+                        .map_or("-", |r| if r.ok { "ok" } else { "error" }),
+                    fmt_ts(storage.last_successful_export(&label)?),
+                    next
+                );
+                if let Some(run) = last.filter(|r| !r.ok) {
+                    println!("    {}", run.message);
+                }
+            }
+        }
+        Commands::Audit { limit, json } => {
+            ensure_initialized()?;
+            let entries = Storage::open_configured()?.audit_log(limit)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+                return Ok(());
+            }
+            if entries.is_empty() {
+                println!("No config or key changes recorded.");
+                return Ok(());
+            }
+            println!(
+                "{:<17} {:<6} {:<18} {:<24} DETAIL",
+                "AT", "SOURCE", "ACTION", "SUBJECT"
+            );
+            for entry in &entries {
+                println!(
+                    "{:<17} {:<6} {:<18} {:<24} {}",
+                    entry.at.format("%Y-%m-%d %H:%M"),
+                    entry.source,
+                    entry.action,
+                    entry.subject,
+                    entry.detail
+                );
+            }
+        }
+        Commands::RenameProvider { from, to } => {
+            ensure_initialized()?;
+            let mut cfg = load_config()?;
+            let storage = Storage::open_configured()?;
+            let moved = service::rename_provider(&mut cfg, &storage, &from, &to, save_config)?;
+            println!(
+                "Renamed '{}' to '{}'; {moved} stored rows moved.",
+                normalize_provider_name(&from),
+                normalize_provider_name(&to)
+            );
+        }
+        Commands::Db {
+            action: DbAction::PurgeProvider { name, dry_run, yes },
+        } => {
+            ensure_initialized()?;
+            let cfg = load_config()?;
+            let storage = Storage::open_configured()?;
+            let provider = normalize_provider_name(&name);
+            let (usage, cost) = storage.provider_row_counts(&provider)?;
+            if dry_run {
+                println!("Would purge {usage} usage and {cost} cost rows for '{provider}'.");
+                return Ok(());
+            }
+            confirm::typed(
+                &format!("Purging deletes {usage} usage and {cost} cost rows for '{provider}'"),
+                &provider,
+                yes,
+            )?;
+            let (usage, cost) = storage.purge_provider(&provider)?;
+            println!("Purged {usage} usage and {cost} cost rows for '{provider}'.");
+            if cfg
+                .enabled_providers
+                .iter()
+                .any(|p| p.eq_ignore_ascii_case(&provider))
+            {
+                eprintln!(
+                    "note: '{provider}' is still enabled; the next refresh fetches its recent history again."
+                );
+            }
+        }
+        Commands::Check { repair, yes } => {
+            ensure_initialized()?;
+            let unknown = config::unknown_config_keys()?;
+            for key in &unknown {
+                println!("config.toml: unknown key `{key}`");
+            }
+            let storage = Storage::open_configured()?;
+            let mut problems = storage.quick_check()?;
+            for problem in &problems {
+                println!("database: {problem}");
+            }
+            if repair {
+                if !unknown.is_empty() {
+                    confirm::typed(
+                        &format!(
+                            "Repair rewrites config.toml without {} unknown key(s)",
+                            unknown.len()
+                        ),
+                        "repair",
+                        yes,
+                    )?;
+                    let backup = config::reserialize_config()?;
                     println!(
-                        "{},{},{:.8},{:.8},{:.8},{},{}",
-                        csv_field(&r.provider),
-                        csv_field(&r.model),
-                        r.input_cost,
-                        r.output_cost,
-                        r.total_cost,
-                        csv_field(&r.currency),
-                        csv_field(&r.timestamp.to_rfc3339()),
+                        "Rewrote config.toml without unknown keys (previous file: {}).",
+                        backup.display()
                     );
                 }
-            } else {
+                storage.reindex()?;
+                problems = storage.quick_check()?;
+                println!("Rebuilt database indices.");
+            }
+            if !problems.is_empty() {
+                return Err(AppError::Storage(if repair {
+                    "the database is still damaged after rebuilding indices; restore it from a backup".into()
+                } else {
+                    "integrity check failed. Run `llm-meter check --repair`.".into()
+                }));
+            }
+            if unknown.is_empty() || repair {
+                println!("Config and database OK.");
+            }
+        }
+        Commands::Db {
+            action: DbAction::Encrypt,
+        } => {
+            ensure_initialized()?;
+            let mut cfg = load_config()?;
+            if cfg.encrypt_database {
                 return Err(AppError::Config(
-                    "Unsupported export format. Use json or csv".into(),
+                    "The database is already encrypted.".into(),
                 ));
             }
+            if !storage::ENCRYPTION_SUPPORTED {
+                return Err(AppError::Config(
+                    "This build has no database encryption; rebuild with `--features encryption`."
+                        .into(),
+                ));
+            }
+            let db = db_path()?;
+            let key = config::create_database_key()?;
+            rekey_database(&db, None, Some(&key), cfg.database_journal_mode)?;
+            cfg.encrypt_database = true;
+            save_config(&cfg)?;
+            println!(
+                "Encrypted {}; the key is in the keyring under '{}' / '{}'.",
+                db.display(),
+                config::keyring_service(),
+                config::DATABASE_KEY_ACCOUNT
+            );
+        }
+        Commands::Db {
+            action: DbAction::Decrypt { yes },
+        } => {
+            ensure_initialized()?;
+            let mut cfg = load_config()?;
+            if !cfg.encrypt_database {
+                return Err(AppError::Config("The database is not encrypted.".into()));
+            }
+            confirm::typed(
+                "Decrypting stores the database as plain SQLite and deletes its key from the keyring",
+                "decrypt",
+                yes,
+            )?;
+            let Some(key) = config::database_key()? else {
+                return Err(AppError::Config("The database is not encrypted.".into()));
+            };
+            let db = db_path()?;
+            rekey_database(&db, Some(&key), None, cfg.database_journal_mode)?;
+            cfg.encrypt_database = false;
+            save_config(&cfg)?;
+            config::delete_database_key()?;
+            println!("Decrypted {}.", db.display());
+        }
+        Commands::Notify {
+            target:
+                NotifyTarget::Slack {
+                    window,
+                    webhook_url,
+                },
+        } => {
+            ensure_initialized()?;
+            let window = validate_window(&window)?;
+            let cfg = load_config()?;
+            let webhook_url = webhook_url
+                .or_else(|| cfg.notifier.slack.as_ref().map(|s| s.webhook_url.clone()))
+                .ok_or_else(|| {
+                    AppError::Config(
+                        "No Slack webhook configured. Pass --webhook-url or set [notifier.slack] webhook_url.".into(),
+                    )
+                })?;
+            let storage = Storage::open_configured()?;
+            let summary =
+                report::build_summary(&storage, window, cfg.edge_buckets, chrono::Utc::now())?;
+            let money = MoneyFormat::from_config(&cfg.display);
+            MeterService::new(&cfg)?
+                .send_slack_summary(&webhook_url, &summary, &money)
+                .await?;
+            println!(
+                "Posted {} summary to Slack ({}).",
+                summary.window,
+                money.money(summary.cost, 2)
+            );
+        }
+        Commands::Notify {
+            target:
+                NotifyTarget::Digest {
+                    window,
+                    template,
+                    slack,
+                    webhook_url,
+                    default_template,
+                },
+        } => {
+            if default_template {
+                print!("{}", report::DEFAULT_DIGEST_TEMPLATE);
+                return Ok(());
+            }
+            ensure_initialized()?;
+            let window = validate_window(&window)?;
+            let cfg = load_config()?;
+            let template = report::load_digest_template(template.as_deref())?;
+            let webhook_url = if slack || webhook_url.is_some() {
+                Some(
+                    webhook_url
+                        .or_else(|| cfg.notifier.slack.as_ref().map(|s| s.webhook_url.clone()))
+                        .ok_or_else(|| {
+                            AppError::Config(
+                                "No Slack webhook configured. Pass --webhook-url or set [notifier.slack] webhook_url.".into(),
+                            )
+                        })?,
+                )
+            } else {
+                None
+            };
+            let storage = Storage::open_configured()?;
+            let summary =
+                report::build_summary(&storage, window, cfg.edge_buckets, chrono::Utc::now())?;
+            let money = MoneyFormat::from_config(&cfg.display);
+            let digest = template.render(&report::digest_context(&summary, &money))?;
+            match webhook_url {
+                Some(url) => {
+                    MeterService::new(&cfg)?
+                        .send_slack_text(&url, &digest)
+                        .await?;
+                    println!("Posted {} digest to Slack.", summary.window);
+                }
+                None => print!("{digest}"),
+            }
         }
     }
 
@@ -189,10 +1306,10 @@ mod tests {
     }
 
     #[test]
-    fn csv_field_escapes_special_characters() {
-        assert_eq!(csv_field("plain"), "plain");
-        assert_eq!(csv_field("a,b"), "\"a,b\"");
-        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
-        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    fn parse_backfill_days_accepts_day_counts_only() {
+        assert_eq!(parse_backfill_days("90d").expect("90d"), 90);
+        assert!(parse_backfill_days("0d").is_err());
+        assert!(parse_backfill_days("90").is_err());
+        assert!(parse_backfill_days("3w").is_err());
     }
 }
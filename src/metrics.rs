@@ -0,0 +1,197 @@
+use crate::config::MetricsConfig;
+use crate::error::AppError;
+use crate::models::Snapshot;
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Keeps datagrams under a typical 1500-byte MTU; the DogStatsD agent splits
+/// multi-metric packets on newlines.
+const MAX_DATAGRAM_BYTES: usize = 1432;
+
+/// DogStatsD lines for one refresh. Window totals are gauges (they describe
+/// state), per-refresh increases are counts so monitors can sum them.
+pub fn statsd_lines(cfg: &MetricsConfig, snapshot: &Snapshot) -> Vec<String> {
+    let prefix = cfg.prefix.trim_end_matches('.');
+    let line = |name: &str, value: String, kind: &str, tags: &[(&str, &str)]| {
+        let tags: Vec<String> = tags
+            .iter()
+            .map(|(k, v)| format!("{k}:{}", tag_value(v)))
+            .chain(cfg.tags.iter().cloned())
+            .collect();
+        let mut out = format!("{prefix}.{name}:{value}|{kind}");
+        if !tags.is_empty() {
+            out.push_str("|#");
+            out.push_str(&tags.join(","));
+        }
+        out
+    };
+
+    let mut by_model: BTreeMap<(&str, &str), (f64, u64, u64)> = BTreeMap::new();
+    for row in &snapshot.cost {
+        by_model.entry((&row.provider, &row.model)).or_default().0 += row.total_cost;
+    }
+    for row in &snapshot.usage {
+        let entry = by_model.entry((&row.provider, &row.model)).or_default();
+        entry.1 += row.input_tokens;
+        entry.2 += row.output_tokens;
+    }
+
+    let mut lines = Vec::new();
+    for ((provider, model), (cost, input, output)) in by_model {
+        let tags = [("provider", provider), ("model", model)];
+        lines.push(line("cost", cost.to_string(), "g", &tags));
+        lines.push(line("tokens.input", input.to_string(), "g", &tags));
+        lines.push(line("tokens.output", output.to_string(), "g", &tags));
+    }
+    for delta in &snapshot.deltas {
+        let tags = [
+            ("provider", delta.provider.as_str()),
+            ("model", delta.model.as_str()),
+        ];
+        lines.push(line("cost_added", delta.cost_delta.to_string(), "c", &tags));
+    }
+    for stats in &snapshot.stats {
+        let tags = [("provider", stats.provider.as_str())];
+        lines.push(line(
+            "refresh.rows",
+            stats.rows_fetched.to_string(),
+            "c",
+            &tags,
+        ));
+        lines.push(line(
+            "refresh.http_ms",
+            stats.http_ms.to_string(),
+            "g",
+            &tags,
+        ));
+    }
+    lines
+}
+
+/// `,`, `|` and `#` delimit the DogStatsD wire format, so they cannot appear
+/// in tag values.
+fn tag_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if matches!(c, ',' | '|' | '#') { '_' } else { c })
+        .collect()
+}
+
+/// Newline-joined batches of `lines`, each at most [`MAX_DATAGRAM_BYTES`]
+/// unless a single line is longer.
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for line in lines {
+        match out.last_mut() {
+            Some(last) if last.len() + 1 + line.len() <= MAX_DATAGRAM_BYTES => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ => out.push(line.clone()),
+        }
+    }
+    out
+}
+
+/// Fire-and-forget UDP send; only local socket errors are reported. Under
+/// `local_only` the agent must resolve to a loopback address.
+pub fn emit(cfg: &MetricsConfig, snapshot: &Snapshot, local_only: bool) -> Result<(), AppError> {
+    let target = agent_address(&cfg.address, local_only)?;
+    let bind = if target.is_ipv6() {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(target)?;
+    for datagram in datagrams(&statsd_lines(cfg, snapshot)) {
+        socket.send(datagram.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// First address `address` resolves to. With `local_only`, every resolved
+/// address must be loopback, so a name cannot point metrics off the machine.
+fn agent_address(address: &str, local_only: bool) -> Result<SocketAddr, AppError> {
+    let addrs: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    if local_only && addrs.iter().any(|a| !a.ip().is_loopback()) {
+        return Err(AppError::Config(format!(
+            "local_only refused metrics to {address}, which is not a loopback address"
+        )));
+    }
+    addrs
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::Config(format!("metrics address {address} did not resolve")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostDelta, CostRecord, ProviderRefreshStats, UsageSource};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn lines_tag_provider_and_model_and_split_into_datagrams() {
+        let ts = Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts");
+        let snapshot = Snapshot {
+            usage: Vec::new(),
+            cost: vec![CostRecord {
+                provider: "openai".into(),
+                model: "gpt-4o,mini".into(),
+                input_cost: 1.0,
+                output_cost: 0.5,
+                total_cost: 1.5,
+                currency: "USD".into(),
+                timestamp: ts,
+                bucket_end: None,
+                source_granularity: None,
+                source: UsageSource::Api,
+            }],
+            fetched_at: ts,
+            deltas: vec![CostDelta {
+                provider: "openai".into(),
+                model: "gpt-4o,mini".into(),
+                cost_delta: 0.25,
+            }],
+            stats: vec![ProviderRefreshStats {
+                provider: "openai".into(),
+                rows_fetched: 3,
+                ..ProviderRefreshStats::default()
+            }],
+            capped: Vec::new(),
+            timed_out: Vec::new(),
+            failed: Vec::new(),
+            clock_skew: None,
+        };
+        let cfg = MetricsConfig {
+            tags: vec!["env:prod".into()],
+            ..MetricsConfig::default()
+        };
+        let lines = statsd_lines(&cfg, &snapshot);
+        assert_eq!(
+            lines[0],
+            "llm_meter.cost:1.5|g|#provider:openai,model:gpt-4o_mini,env:prod"
+        );
+        assert!(lines.contains(
+            &"llm_meter.cost_added:0.25|c|#provider:openai,model:gpt-4o_mini,env:prod".into()
+        ));
+        assert!(lines.contains(&"llm_meter.refresh.rows:3|c|#provider:openai,env:prod".into()));
+
+        let many = vec!["x".repeat(600); 5];
+        let packets = datagrams(&many);
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|p| p.len() <= MAX_DATAGRAM_BYTES));
+    }
+
+    #[test]
+    fn local_only_accepts_only_loopback_agents() {
+        assert!(agent_address("127.0.0.1:8125", true).is_ok());
+        assert!(agent_address("[::1]:8125", true).is_ok());
+        let err = agent_address("10.1.2.3:8125", true).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("local_only refused metrics to 10.1.2.3:8125"));
+        assert!(agent_address("10.1.2.3:8125", false).is_ok());
+    }
+}
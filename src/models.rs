@@ -8,7 +8,150 @@ pub struct UsageRecord {
     pub input_tokens: u64,
     pub output_tokens: u64,
     pub cached_tokens: u64,
+    /// Start of the usage bucket the row covers.
     pub timestamp: DateTime<Utc>,
+    /// End of the bucket (exclusive). Rows are normalized onto whole buckets,
+    /// so this is `timestamp` plus the stored width. `None` for rows stored
+    /// before bucket ends were tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_end: Option<DateTime<Utc>>,
+    /// Bucket size the provider reported this row at, before normalization.
+    /// `None` for rows stored before granularity was tracked.
+    #[serde(default)]
+    pub source_granularity: Option<Granularity>,
+    /// Mean request latency in the bucket, where the provider reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_latency_ms: Option<f64>,
+    /// Ingestion path the row came through. Rows stored before sources were
+    /// tracked all came from provider APIs.
+    #[serde(default)]
+    pub source: UsageSource,
+}
+
+/// Credit left on a prepaid provider account, in the provider's currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountBalance {
+    pub provider: String,
+    pub amount: f64,
+    pub currency: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Per-minute limits a provider reports for one model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub provider: String,
+    pub model: String,
+    pub tokens_per_minute: u64,
+    pub requests_per_minute: Option<u64>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Highest observed token rate for a model. `width` is the bucket it was
+/// measured over; buckets coarser than a minute average out bursts, so the
+/// true per-minute peak can be higher.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeakRate {
+    pub tokens_per_minute: f64,
+    pub at: DateTime<Utc>,
+    pub width: Granularity,
+}
+
+impl PeakRate {
+    /// Peak as a fraction of `limit`'s tokens per minute.
+    pub fn utilization(&self, limit: &RateLimit) -> Option<f64> {
+        (limit.tokens_per_minute > 0)
+            .then(|| self.tokens_per_minute / limit.tokens_per_minute as f64)
+    }
+}
+
+/// Usage bucket size. Ordered from finest to coarsest.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum Granularity {
+    #[serde(rename = "1m", alias = "minute")]
+    Minute,
+    #[default]
+    #[serde(rename = "1h", alias = "hour")]
+    Hour,
+    #[serde(rename = "1d", alias = "day")]
+    Day,
+}
+
+impl Granularity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Minute => "1m",
+            Granularity::Hour => "1h",
+            Granularity::Day => "1d",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "1m" | "minute" => Some(Granularity::Minute),
+            "1h" | "hour" => Some(Granularity::Hour),
+            "1d" | "day" => Some(Granularity::Day),
+            _ => None,
+        }
+    }
+
+    pub fn duration(self) -> chrono::TimeDelta {
+        match self {
+            Granularity::Minute => chrono::TimeDelta::minutes(1),
+            Granularity::Hour => chrono::TimeDelta::hours(1),
+            Granularity::Day => chrono::TimeDelta::days(1),
+        }
+    }
+}
+
+/// How a row reached the database, so spend reported by a vendor's usage API
+/// can be told apart from self-reported or imported figures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageSource {
+    /// A provider's organization usage API.
+    #[default]
+    Api,
+    /// Logs written on this machine (`local-session`, coding agents, Ollama).
+    Local,
+    /// A billing export loaded with `import-billing`.
+    Import,
+}
+
+impl UsageSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageSource::Api => "api",
+            UsageSource::Local => "local",
+            UsageSource::Import => "import",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "api" => Some(UsageSource::Api),
+            "local" => Some(UsageSource::Local),
+            "import" => Some(UsageSource::Import),
+            _ => None,
+        }
+    }
+}
+
+/// How window totals treat a bucket that starts before the window and ends
+/// inside it, such as yesterday's daily bucket in a rolling 1d window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeBuckets {
+    /// Leave it out: only buckets starting inside the window count.
+    #[default]
+    Exclude,
+    /// Count all of it.
+    Include,
+    /// Count the share of it that falls inside the window.
+    #[serde(alias = "pro-rate")]
+    Prorate,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,7 +162,17 @@ pub struct CostRecord {
     pub output_cost: f64,
     pub total_cost: f64,
     pub currency: String,
+    /// Start of the usage bucket the cost was derived from.
     pub timestamp: DateTime<Utc>,
+    /// End of that bucket (exclusive), as on [`UsageRecord::bucket_end`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_end: Option<DateTime<Utc>>,
+    /// Provider bucket size of the usage row the cost was derived from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_granularity: Option<Granularity>,
+    /// Ingestion path of the usage row the cost was derived from.
+    #[serde(default)]
+    pub source: UsageSource,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +180,186 @@ pub struct Snapshot {
     pub usage: Vec<UsageRecord>,
     pub cost: Vec<CostRecord>,
     pub fetched_at: DateTime<Utc>,
+    #[serde(default)]
+    pub deltas: Vec<CostDelta>,
+    #[serde(default)]
+    pub stats: Vec<ProviderRefreshStats>,
+    /// Accounts not refreshed because `[request_caps]` was reached; their
+    /// stored rows are unchanged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capped: Vec<String>,
+    /// Accounts not refreshed because `refresh_timeout_secs` ran out; their
+    /// stored rows are unchanged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timed_out: Vec<String>,
+    /// Accounts whose fetch failed, with the error; their stored rows are
+    /// unchanged and the failure is in `refresh_log`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<(String, String)>,
+    /// Largest clock skew against a provider's `Date` header, when it
+    /// reached `clock_skew_warning_secs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew: Option<ClockSkew>,
+}
+
+/// How far the local clock is ahead of `provider`'s (negative when behind).
+/// Query windows are computed from the local clock, so a skewed clock asks
+/// for the wrong hours.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClockSkew {
+    pub provider: String,
+    pub seconds: i64,
+}
+
+impl ClockSkew {
+    /// E.g. `local clock is 5m 12s ahead of openai`.
+    pub fn describe(&self) -> String {
+        let abs = self.seconds.unsigned_abs();
+        let amount = match abs {
+            0..60 => format!("{abs}s"),
+            60..3600 => format!("{}m {}s", abs / 60, abs % 60),
+            _ => format!("{}h {}m", abs / 3600, abs % 3600 / 60),
+        };
+        let direction = if self.seconds > 0 {
+            "ahead of"
+        } else {
+            "behind"
+        };
+        format!("local clock is {amount} {direction} {}", self.provider)
+    }
+}
+
+/// Tokens and previous-window cost of one provider or model breakdown row,
+/// for the optional table columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BreakdownDetail {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub previous_cost: f64,
+}
+
+/// Per-provider request metrics for one refresh. `rows_dropped` counts usage
+/// rows that had no price and so produced no cost row.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProviderRefreshStats {
+    pub provider: String,
+    pub rows_fetched: usize,
+    pub pages: u32,
+    pub http_ms: u128,
+    pub parse_ms: u128,
+    pub rows_stored: usize,
+    pub rows_dropped: usize,
+    /// Usage rows left out because an overlapped API account reports them,
+    /// and the cost they carried.
+    #[serde(default)]
+    pub rows_suppressed: usize,
+    #[serde(default)]
+    pub cost_suppressed: f64,
+    /// Overlapping usage rows stored anyway under `on_overlap = "keep-both"`.
+    #[serde(default)]
+    pub rows_overlapping: usize,
+    #[serde(default)]
+    pub cost_overlapping: f64,
+    /// Usage schema that parsed the fetch, e.g. `openai/buckets-v2`, and
+    /// whether it was only reached through the fallback chain.
+    #[serde(default)]
+    pub parser_version: Option<String>,
+    #[serde(default)]
+    pub parser_fallback: bool,
+}
+
+/// Operational summary of one provider for the Provider Manager: its last
+/// successful refresh, usage rows stored in the current window, and the
+/// error of its latest refresh if that one failed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderHealth {
+    pub last_success: Option<DateTime<Utc>>,
+    pub rows_in_window: usize,
+    pub last_error: Option<String>,
+}
+
+/// Cost added (or removed, for provider corrections) for one provider/model by
+/// a single refresh, relative to what was stored before it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CostDelta {
+    pub provider: String,
+    pub model: String,
+    pub cost_delta: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    New,
+    Acked,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertState::New => "new",
+            AlertState::Acked => "acked",
+            AlertState::Resolved => "resolved",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "new" => Some(AlertState::New),
+            "acked" => Some(AlertState::Acked),
+            "resolved" => Some(AlertState::Resolved),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of one `[[exports]]` pipeline run.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportRun {
+    pub pipeline: String,
+    pub ran_at: DateTime<Utc>,
+    pub ok: bool,
+    pub message: String,
+}
+
+/// One `audit_log` row: a change to the config or a stored key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    /// `cli` or `tui`.
+    pub source: String,
+    /// e.g. `provider.added`, `key.set`, `budget.changed`.
+    pub action: String,
+    /// Account, budget or config key the change applies to.
+    pub subject: String,
+    /// Names of the changed fields, never their values.
+    pub detail: String,
+}
+
+/// One provider's totals for a calendar month (UTC). `frozen_at` is `None`
+/// for live totals of a month that has not closed yet.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Statement {
+    pub month: String,
+    pub provider: String,
+    pub account: Option<String>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_cost: f64,
+    pub currency: String,
+    pub frozen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRecord {
+    pub id: i64,
+    pub kind: String,
+    pub subject: String,
+    pub message: String,
+    pub state: AlertState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
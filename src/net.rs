@@ -0,0 +1,237 @@
+use crate::config::{normalize_provider_name, AppConfig, ProviderSettings};
+use crate::error::AppError;
+use crate::providers::ProviderAdapter;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client, Proxy, Url};
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Most redirects a request follows, as with reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// Proxy that requests to IP addresses outside the allowlist are sent to.
+/// It never resolves, so those connections fail before leaving the machine.
+const REFUSED_PROXY_HOST: &str = "refused.local-only.invalid";
+
+/// Hosts a `local_only` client may connect to: the default API hosts of the
+/// configured providers' adapters and the hosts of their `base_url`s. A
+/// pattern's `*` label matches any one label, e.g. the region in
+/// `monitoring.*.amazonaws.com`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostAllowlist {
+    patterns: Vec<String>,
+}
+
+impl HostAllowlist {
+    /// Hosts of every enabled provider and every account with settings.
+    pub fn from_config(
+        cfg: &AppConfig,
+        adapter_for: impl Fn(&str) -> Option<Box<dyn ProviderAdapter + Send + Sync>>,
+    ) -> Self {
+        let mut accounts: Vec<String> = cfg
+            .enabled_providers
+            .iter()
+            .chain(cfg.provider_settings.keys())
+            .map(|name| normalize_provider_name(name))
+            .collect();
+        accounts.sort();
+        accounts.dedup();
+
+        let mut allowlist = Self::default();
+        for account in accounts {
+            let settings = cfg
+                .provider_settings
+                .get(&account)
+                .cloned()
+                .unwrap_or_default();
+            if let Some(adapter) = adapter_for(&cfg.provider_kind(&account)) {
+                allowlist.add_provider(adapter.as_ref(), &settings);
+            }
+        }
+        allowlist
+    }
+
+    /// Adds `adapter`'s default hosts and the host of `settings.base_url`.
+    pub fn add_provider(&mut self, adapter: &dyn ProviderAdapter, settings: &ProviderSettings) {
+        for host in adapter.default_hosts() {
+            self.add(host);
+        }
+        if let Some(host) = settings
+            .base_url
+            .as_deref()
+            .and_then(|raw| Url::parse(raw).ok())
+            .and_then(|url| url.host_str().map(str::to_string))
+        {
+            self.add(&host);
+        }
+    }
+
+    fn add(&mut self, pattern: &str) {
+        let pattern = host_key(pattern);
+        if !self.patterns.contains(&pattern) {
+            self.patterns.push(pattern);
+        }
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host_key(host);
+        self.patterns.iter().any(|pattern| {
+            let (pattern, host): (Vec<&str>, Vec<&str>) =
+                (pattern.split('.').collect(), host.split('.').collect());
+            pattern.len() == host.len()
+                && pattern.iter().zip(&host).all(|(p, h)| *p == "*" || p == h)
+        })
+    }
+}
+
+/// Lower-case host without IPv6 brackets or a trailing dot.
+fn host_key(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// The HTTP client for provider, notifier and export requests. With
+/// `local_only`, it connects only to hosts `allowlist` allows: other names
+/// fail to resolve, other IP addresses go to a proxy that cannot resolve,
+/// and redirects elsewhere are refused. System proxy settings are ignored
+/// then, since the proxy would be another host.
+pub fn client(local_only: bool, allowlist: Arc<RwLock<HostAllowlist>>) -> Result<Client, AppError> {
+    let builder = Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30));
+    if !local_only {
+        return Ok(builder.build()?);
+    }
+
+    let allows = {
+        let allowlist = allowlist.clone();
+        move |host: &str| {
+            allowlist
+                .read()
+                .map(|list| list.allows(host))
+                .unwrap_or(false)
+        }
+    };
+    let proxy_allows = allows.clone();
+    let redirect_allows = allows.clone();
+    Ok(builder
+        .dns_resolver(Arc::new(LocalOnlyResolver {
+            allows: Box::new(allows),
+        }))
+        .proxy(Proxy::custom(move |url| {
+            let host = url.host_str()?;
+            let literal = host_key(host).parse::<std::net::IpAddr>().is_ok();
+            (literal && !proxy_allows(host)).then(|| format!("http://{REFUSED_PROXY_HOST}"))
+        }))
+        .redirect(redirect::Policy::custom(move |attempt| {
+            let host = attempt.url().host_str().unwrap_or_default().to_string();
+            if !redirect_allows(&host) {
+                attempt.error(LocalOnlyRefused(host))
+            } else if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()?)
+}
+
+/// Resolves allowed names through the system resolver and refuses the rest.
+struct LocalOnlyResolver {
+    allows: Box<dyn Fn(&str) -> bool + Send + Sync>,
+}
+
+impl Resolve for LocalOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let allowed = host != REFUSED_PROXY_HOST && (self.allows)(&host);
+        Box::pin(async move {
+            if !allowed {
+                let refused = if host == REFUSED_PROXY_HOST {
+                    "an IP address".to_string()
+                } else {
+                    host
+                };
+                return Err(LocalOnlyRefused(refused).into());
+            }
+            let addrs =
+                tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs()).await??;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct LocalOnlyRefused(String);
+
+impl std::fmt::Display for LocalOnlyRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "local_only refused a connection to {}, which is not a configured provider host",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for LocalOnlyRefused {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::bedrock::BedrockAdapter;
+    use crate::providers::openai::OpenAiAdapter;
+
+    fn adapter_for(kind: &str) -> Option<Box<dyn ProviderAdapter + Send + Sync>> {
+        match kind {
+            "openai" => Some(Box::new(OpenAiAdapter)),
+            "bedrock" => Some(Box::new(BedrockAdapter)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn allowlist_covers_configured_providers_only() {
+        let mut cfg = AppConfig {
+            enabled_providers: vec!["openai".into()],
+            ..AppConfig::default()
+        };
+        cfg.provider_settings.insert(
+            "openai-proxy".into(),
+            ProviderSettings {
+                kind: Some("openai".into()),
+                base_url: Some("http://10.0.0.5:8080/v1".into()),
+                ..ProviderSettings::default()
+            },
+        );
+        cfg.provider_settings
+            .insert("bedrock".into(), ProviderSettings::default());
+        let allowlist = HostAllowlist::from_config(&cfg, adapter_for);
+
+        assert!(allowlist.allows("api.openai.com"));
+        assert!(allowlist.allows("API.OpenAI.com."));
+        assert!(allowlist.allows("10.0.0.5"));
+        assert!(allowlist.allows("monitoring.eu-west-1.amazonaws.com"));
+        assert!(!allowlist.allows("monitoring.amazonaws.com"));
+        assert!(!allowlist.allows("hooks.slack.com"));
+        assert!(!allowlist.allows("api.anthropic.com"));
+    }
+
+    #[tokio::test]
+    async fn local_only_client_refuses_other_hosts_before_connecting() {
+        let allowlist = Arc::new(RwLock::new(HostAllowlist::default()));
+        let client = client(true, allowlist).expect("client");
+
+        for url in ["https://hooks.slack.com/services/x", "http://192.0.2.1:9/"] {
+            let err = client.get(url).send().await.expect_err("refused");
+            let chain = std::iter::successors(Some(&err as &dyn std::error::Error), |e| e.source())
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(": ");
+            assert!(chain.contains("local_only refused"), "{chain}");
+        }
+    }
+}
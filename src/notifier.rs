@@ -0,0 +1,455 @@
+use crate::config::{get_api_key, InfluxConfig, NotifierConfig, WebhookConfig};
+use crate::error::AppError;
+use crate::format::MoneyFormat;
+use crate::models::{AlertRecord, Snapshot};
+use crate::report::SpendSummary;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const OPSGENIE_DEFAULT_URL: &str = "https://api.opsgenie.com";
+const OPSGENIE_MESSAGE_LIMIT: usize = 130;
+const WEBHOOK_SECRET_NAME: &str = "webhook";
+const INFLUX_TOKEN_NAME: &str = "influx";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    Warning,
+    Critical,
+}
+
+fn dedup_key(alert: &AlertRecord) -> String {
+    format!("llm-meter:{}:{}", alert.kind, alert.subject)
+}
+
+fn pagerduty_severity(cfg: &NotifierConfig, level: AlertLevel) -> String {
+    match level {
+        AlertLevel::Warning => cfg.warning_severity.clone(),
+        AlertLevel::Critical => cfg.critical_severity.clone(),
+    }
+}
+
+fn opsgenie_priority(cfg: &NotifierConfig, level: AlertLevel) -> String {
+    match level {
+        AlertLevel::Warning => cfg.warning_priority.clone(),
+        AlertLevel::Critical => cfg.critical_priority.clone(),
+    }
+}
+
+fn pagerduty_trigger_body(
+    routing_key: &str,
+    cfg: &NotifierConfig,
+    alert: &AlertRecord,
+    level: AlertLevel,
+) -> Value {
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": dedup_key(alert),
+        "payload": {
+            "summary": alert.message,
+            "source": "llm-meter",
+            "severity": pagerduty_severity(cfg, level),
+            "timestamp": alert.created_at.to_rfc3339(),
+            "component": alert.subject,
+            "class": alert.kind,
+        }
+    })
+}
+
+fn opsgenie_create_body(cfg: &NotifierConfig, alert: &AlertRecord, level: AlertLevel) -> Value {
+    let message: String = alert.message.chars().take(OPSGENIE_MESSAGE_LIMIT).collect();
+    json!({
+        "message": message,
+        "alias": dedup_key(alert),
+        "description": alert.message,
+        "priority": opsgenie_priority(cfg, level),
+        "source": "llm-meter",
+        "tags": ["llm-meter", alert.kind],
+    })
+}
+
+pub async fn notify_triggered(
+    client: &Client,
+    cfg: &NotifierConfig,
+    alert: &AlertRecord,
+    level: AlertLevel,
+) -> Result<(), AppError> {
+    if let Some(pd) = &cfg.pagerduty {
+        client
+            .post(PAGERDUTY_EVENTS_URL)
+            .json(&pagerduty_trigger_body(&pd.routing_key, cfg, alert, level))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    if let Some(og) = &cfg.opsgenie {
+        let api_key = get_api_key("opsgenie")?;
+        let base = og.api_url.as_deref().unwrap_or(OPSGENIE_DEFAULT_URL);
+        client
+            .post(format!("{}/v2/alerts", base.trim_end_matches('/')))
+            .header("Authorization", format!("GenieKey {api_key}"))
+            .json(&opsgenie_create_body(cfg, alert, level))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    Ok(())
+}
+
+pub async fn notify_resolved(
+    client: &Client,
+    cfg: &NotifierConfig,
+    kind: &str,
+    subject: &str,
+) -> Result<(), AppError> {
+    let key = format!("llm-meter:{kind}:{subject}");
+
+    if let Some(pd) = &cfg.pagerduty {
+        client
+            .post(PAGERDUTY_EVENTS_URL)
+            .json(&json!({
+                "routing_key": pd.routing_key,
+                "event_action": "resolve",
+                "dedup_key": key,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    if let Some(og) = &cfg.opsgenie {
+        let api_key = get_api_key("opsgenie")?;
+        let base = og.api_url.as_deref().unwrap_or(OPSGENIE_DEFAULT_URL);
+        let mut url = url::Url::parse(&format!("{}/v2/alerts/", base.trim_end_matches('/')))
+            .map_err(|e| AppError::Config(format!("Invalid Opsgenie api_url: {e}")))?;
+        url.path_segments_mut()
+            .map_err(|_| AppError::Config("Invalid Opsgenie api_url".into()))?
+            .pop_if_empty()
+            .push(&key)
+            .push("close");
+        url.query_pairs_mut().append_pair("identifierType", "alias");
+        client
+            .post(url)
+            .header("Authorization", format!("GenieKey {api_key}"))
+            .json(&json!({ "source": "llm-meter" }))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    Ok(())
+}
+
+pub fn slack_summary_blocks(summary: &SpendSummary, money: &MoneyFormat) -> Value {
+    let mut model_lines = summary
+        .top_models
+        .iter()
+        .enumerate()
+        .map(|(idx, m)| {
+            format!(
+                "{}. `{}` {} ({})",
+                idx + 1,
+                m.model,
+                money.money(m.cost, 2),
+                money.delta(m.cost, m.previous_cost)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if model_lines.is_empty() {
+        model_lines = "_No spend recorded in this window._".into();
+    }
+
+    json!({
+        "text": format!(
+            "llm-meter {} spend: {} ({})",
+            summary.window,
+            money.money(summary.cost, 2),
+            money.delta(summary.cost, summary.previous_cost)
+        ),
+        "blocks": [
+            {
+                "type": "header",
+                "text": { "type": "plain_text", "text": format!("LLM spend · last {}", summary.window) }
+            },
+            {
+                "type": "section",
+                "fields": [
+                    { "type": "mrkdwn", "text": format!("*Cost*\n{}", money.money(summary.cost, 2)) },
+                    { "type": "mrkdwn", "text": format!("*Tokens*\n{}", summary.tokens) },
+                    { "type": "mrkdwn", "text": format!(
+                        "*vs previous {}*\n{}",
+                        summary.window,
+                        money.delta(summary.cost, summary.previous_cost)
+                    ) }
+                ]
+            },
+            {
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("*Top models*\n{model_lines}") }
+            },
+            {
+                "type": "context",
+                "elements": [
+                    { "type": "mrkdwn", "text": format!("Generated {}", summary.generated_at.to_rfc3339()) }
+                ]
+            }
+        ]
+    })
+}
+
+pub async fn post_slack(client: &Client, webhook_url: &str, body: &Value) -> Result<(), AppError> {
+    client
+        .post(webhook_url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Refresh summary sent to `[notifier.webhook]`. Costs are USD.
+pub fn refresh_webhook_body(snapshot: &Snapshot) -> Value {
+    let mut providers: BTreeMap<&str, (f64, u64, u64)> = BTreeMap::new();
+    for row in &snapshot.cost {
+        providers.entry(&row.provider).or_default().0 += row.total_cost;
+    }
+    for row in &snapshot.usage {
+        let entry = providers.entry(&row.provider).or_default();
+        entry.1 += row.input_tokens;
+        entry.2 += row.output_tokens;
+    }
+    json!({
+        "event": "refresh",
+        "fetched_at": snapshot.fetched_at.to_rfc3339(),
+        "total_cost_usd": snapshot.cost.iter().map(|r| r.total_cost).sum::<f64>(),
+        "usage_records": snapshot.usage.len(),
+        "cost_rows": snapshot.cost.len(),
+        "providers": providers
+            .into_iter()
+            .map(|(provider, (cost, input, output))| json!({
+                "provider": provider,
+                "cost_usd": cost,
+                "input_tokens": input,
+                "output_tokens": output,
+            }))
+            .collect::<Vec<_>>(),
+        "deltas": snapshot.deltas,
+        "stats": snapshot.stats,
+    })
+}
+
+/// `sha256=<hex>` HMAC of `"{timestamp}.{body}"`, so receivers can reject
+/// replayed deliveries by checking the timestamp header.
+pub fn webhook_signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub async fn post_refresh_webhook(
+    client: &Client,
+    cfg: &WebhookConfig,
+    snapshot: &Snapshot,
+    now: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let body = serde_json::to_vec(&refresh_webhook_body(snapshot))?;
+    post_signed(client, &cfg.url, "application/json", body, now).await
+}
+
+/// POSTs `body` with the `X-LLM-Meter-Timestamp` / `X-LLM-Meter-Signature`
+/// headers. The signing secret is resolved like a provider key: keychain
+/// account `provider:webhook`, then `WEBHOOK_API_KEY`.
+pub async fn post_signed(
+    client: &Client,
+    url: &str,
+    content_type: &str,
+    body: Vec<u8>,
+    now: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let secret = get_api_key(WEBHOOK_SECRET_NAME)?;
+    let timestamp = now.timestamp();
+    client
+        .post(url)
+        .header("Content-Type", content_type)
+        .header("X-LLM-Meter-Timestamp", timestamp.to_string())
+        .header(
+            "X-LLM-Meter-Signature",
+            webhook_signature(&secret, timestamp, &body),
+        )
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Writes the refresh's cost rows (USD) as line protocol.
+pub async fn post_influx(
+    client: &Client,
+    cfg: &InfluxConfig,
+    snapshot: &Snapshot,
+) -> Result<(), AppError> {
+    if snapshot.cost.is_empty() {
+        return Ok(());
+    }
+    post_line_protocol(
+        client,
+        &cfg.write_url,
+        crate::export::cost_line_protocol(&snapshot.cost),
+    )
+    .await
+}
+
+/// An API token, if any, is resolved like a provider key (`provider:influx`,
+/// then `INFLUX_API_KEY`); Telegraf listeners usually need none.
+pub async fn post_line_protocol(
+    client: &Client,
+    write_url: &str,
+    body: String,
+) -> Result<(), AppError> {
+    let mut request = client
+        .post(write_url)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(body);
+    if let Ok(token) = get_api_key(INFLUX_TOKEN_NAME) {
+        request = request.header("Authorization", format!("Token {token}"));
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PagerDutyConfig;
+    use crate::models::{AlertState, UsageSource};
+    use chrono::{TimeZone, Utc};
+
+    fn sample_alert() -> AlertRecord {
+        let ts = Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts");
+        AlertRecord {
+            id: 7,
+            kind: "budget".into(),
+            subject: "monthly".into(),
+            message: "x".repeat(200),
+            state: AlertState::New,
+            created_at: ts,
+            updated_at: ts,
+        }
+    }
+
+    #[test]
+    fn pagerduty_body_maps_level_to_configured_severity() {
+        let cfg = NotifierConfig {
+            pagerduty: Some(PagerDutyConfig {
+                routing_key: "rk".into(),
+            }),
+            critical_severity: "error".into(),
+            ..NotifierConfig::default()
+        };
+        let body = pagerduty_trigger_body("rk", &cfg, &sample_alert(), AlertLevel::Critical);
+        assert_eq!(body["routing_key"], "rk");
+        assert_eq!(body["dedup_key"], "llm-meter:budget:monthly");
+        assert_eq!(body["payload"]["severity"], "error");
+
+        let body = pagerduty_trigger_body("rk", &cfg, &sample_alert(), AlertLevel::Warning);
+        assert_eq!(body["payload"]["severity"], "warning");
+    }
+
+    #[test]
+    fn slack_summary_blocks_lists_top_models_with_deltas() {
+        use crate::report::ModelSpend;
+        let summary = SpendSummary {
+            window: "1d".into(),
+            generated_at: Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts"),
+            tokens: 1200,
+            cost: 3.0,
+            previous_cost: 2.0,
+            top_models: vec![ModelSpend {
+                model: "gpt-4o".into(),
+                cost: 3.0,
+                previous_cost: 2.0,
+            }],
+        };
+        let body = slack_summary_blocks(&summary, &MoneyFormat::default());
+        assert_eq!(body["blocks"][0]["type"], "header");
+        let models = body["blocks"][2]["text"]["text"]
+            .as_str()
+            .expect("models text");
+        assert!(models.contains("1. `gpt-4o` $3.00 (+$1.00 (+50.0%))"));
+        assert!(body["text"].as_str().expect("fallback").contains("$3.00"));
+    }
+
+    #[test]
+    fn opsgenie_body_truncates_message_and_maps_priority() {
+        let cfg = NotifierConfig::default();
+        let body = opsgenie_create_body(&cfg, &sample_alert(), AlertLevel::Critical);
+        assert_eq!(
+            body["message"].as_str().map(|m| m.chars().count()),
+            Some(OPSGENIE_MESSAGE_LIMIT)
+        );
+        assert_eq!(body["priority"], "P1");
+        assert_eq!(body["alias"], "llm-meter:budget:monthly");
+    }
+
+    #[test]
+    fn refresh_webhook_body_totals_per_provider_and_signs_deterministically() {
+        use crate::models::{CostRecord, UsageRecord};
+        let ts = Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts");
+        let snapshot = Snapshot {
+            usage: vec![UsageRecord {
+                provider: "openai".into(),
+                model: "gpt-4o".into(),
+                input_tokens: 100,
+                output_tokens: 20,
+                cached_tokens: 0,
+                timestamp: ts,
+                bucket_end: None,
+                source_granularity: None,
+                source: UsageSource::Api,
+                avg_latency_ms: None,
+            }],
+            cost: vec![CostRecord {
+                provider: "openai".into(),
+                model: "gpt-4o".into(),
+                input_cost: 1.0,
+                output_cost: 0.5,
+                total_cost: 1.5,
+                currency: "USD".into(),
+                timestamp: ts,
+                bucket_end: None,
+                source_granularity: None,
+                source: UsageSource::Api,
+            }],
+            fetched_at: ts,
+            deltas: Vec::new(),
+            stats: Vec::new(),
+            capped: Vec::new(),
+            timed_out: Vec::new(),
+            failed: Vec::new(),
+            clock_skew: None,
+        };
+        let body = refresh_webhook_body(&snapshot);
+        assert_eq!(body["total_cost_usd"], 1.5);
+        assert_eq!(body["providers"][0]["provider"], "openai");
+        assert_eq!(body["providers"][0]["input_tokens"], 100);
+
+        // HMAC-SHA256("secret", "1700000000.{}") checked with Python's hmac module.
+        assert_eq!(
+            webhook_signature("secret", 1_700_000_000, b"{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+}
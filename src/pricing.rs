@@ -1,4 +1,5 @@
 use crate::config::PricingOverride;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,9 +36,163 @@ pub fn built_in_pricing() -> Vec<ModelPricing> {
             input_per_1m: 0.80,
             output_per_1m: 4.0,
         },
+        ModelPricing {
+            provider: "deepseek".into(),
+            model_pattern: "deepseek-chat".into(),
+            input_per_1m: 0.28,
+            output_per_1m: 0.42,
+        },
+        ModelPricing {
+            provider: "deepseek".into(),
+            model_pattern: "deepseek-reasoner".into(),
+            input_per_1m: 0.28,
+            output_per_1m: 0.42,
+        },
+        // Longer variants first: patterns match by substring.
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-2.5-pro".into(),
+            input_per_1m: 1.25,
+            output_per_1m: 10.0,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-2.5-flash-lite".into(),
+            input_per_1m: 0.10,
+            output_per_1m: 0.40,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-2.5-flash".into(),
+            input_per_1m: 0.30,
+            output_per_1m: 2.50,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-2.0-flash-lite".into(),
+            input_per_1m: 0.075,
+            output_per_1m: 0.30,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-2.0-flash".into(),
+            input_per_1m: 0.10,
+            output_per_1m: 0.40,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-1.5-pro".into(),
+            input_per_1m: 1.25,
+            output_per_1m: 5.0,
+        },
+        ModelPricing {
+            provider: "gemini".into(),
+            model_pattern: "gemini-1.5-flash".into(),
+            input_per_1m: 0.075,
+            output_per_1m: 0.30,
+        },
+    ]
+}
+
+/// Date a provider stops serving the models matching `model_pattern`
+/// (matched like pricing patterns), with the suggested successor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelDeprecation {
+    pub provider: String,
+    pub model_pattern: String,
+    pub deprecated_on: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+/// Retirement dates published by the providers. Add or correct entries with
+/// `[[deprecations]]` in the config.
+pub fn built_in_deprecations() -> Vec<ModelDeprecation> {
+    let entry = |provider: &str, pattern: &str, (y, m, d), replacement: &str| ModelDeprecation {
+        provider: provider.into(),
+        model_pattern: pattern.into(),
+        deprecated_on: NaiveDate::from_ymd_opt(y, m, d).expect("valid built-in date"),
+        replacement: Some(replacement.into()),
+    };
+    vec![
+        entry("openai", "gpt-4.5-preview", (2025, 7, 14), "gpt-4.1"),
+        entry("openai", "o1-preview", (2025, 7, 28), "o3"),
+        entry("openai", "o1-mini", (2025, 10, 27), "o4-mini"),
+        entry(
+            "anthropic",
+            "claude-3-sonnet",
+            (2025, 7, 21),
+            "claude-sonnet-4",
+        ),
+        entry(
+            "anthropic",
+            "claude-3-5-sonnet",
+            (2025, 10, 22),
+            "claude-sonnet-4",
+        ),
+        entry(
+            "anthropic",
+            "claude-3-opus",
+            (2026, 1, 5),
+            "claude-opus-4-1",
+        ),
     ]
 }
 
+/// `[[deprecations]]` first, then built-ins they don't replace.
+pub fn deprecation_catalog(overrides: &[ModelDeprecation]) -> Vec<ModelDeprecation> {
+    let mut catalog = overrides.to_vec();
+    for d in built_in_deprecations() {
+        if !catalog.iter().any(|c| {
+            c.provider.eq_ignore_ascii_case(&d.provider) && c.model_pattern == d.model_pattern
+        }) {
+            catalog.push(d);
+        }
+    }
+    catalog
+}
+
+/// First catalog entry covering `model` from `provider`.
+pub fn find_deprecation<'a>(
+    catalog: &'a [ModelDeprecation],
+    provider: &str,
+    model: &str,
+) -> Option<&'a ModelDeprecation> {
+    catalog
+        .iter()
+        .find(|d| d.provider.eq_ignore_ascii_case(provider) && model.contains(&d.model_pattern))
+}
+
+pub fn pricing_catalog(overrides: &[PricingOverride]) -> Vec<ModelPricing> {
+    let mut catalog: Vec<ModelPricing> = overrides
+        .iter()
+        .map(|ov| ModelPricing {
+            provider: ov.provider.clone(),
+            model_pattern: ov.model_pattern.clone(),
+            input_per_1m: ov.input_per_1m,
+            output_per_1m: ov.output_per_1m,
+        })
+        .collect();
+    for p in built_in_pricing() {
+        if !catalog.iter().any(|c| {
+            c.provider.eq_ignore_ascii_case(&p.provider) && c.model_pattern == p.model_pattern
+        }) {
+            catalog.push(p);
+        }
+    }
+    catalog
+}
+
+pub fn cost_for_tokens(
+    input_tokens: u64,
+    output_tokens: u64,
+    input_per_1m: f64,
+    output_per_1m: f64,
+) -> f64 {
+    (input_tokens as f64 / 1_000_000.0) * input_per_1m
+        + (output_tokens as f64 / 1_000_000.0) * output_per_1m
+}
+
 pub fn resolve_pricing(
     provider: &str,
     model: &str,
@@ -59,3 +214,61 @@ pub fn resolve_pricing(
         .into_iter()
         .find(|p| p.provider.eq_ignore_ascii_case(provider) && model.contains(&p.model_pattern))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cost_for_tokens_uses_per_million_rates() {
+        let cost = cost_for_tokens(2_000_000, 500_000, 3.0, 15.0);
+        assert!((cost - 13.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn resolve_pricing_prefers_overrides() {
+        let overrides = vec![PricingOverride {
+            provider: "openai".into(),
+            model_pattern: "gpt-4o".into(),
+            input_per_1m: 1.0,
+            output_per_1m: 2.0,
+        }];
+        let pricing = resolve_pricing("openai", "gpt-4o-2024-08-06", &overrides).expect("pricing");
+        assert_eq!(pricing.input_per_1m, 1.0);
+        assert!(resolve_pricing("openai", "unknown-model", &[]).is_none());
+    }
+
+    #[test]
+    fn pricing_catalog_lets_overrides_replace_built_ins() {
+        let overrides = vec![PricingOverride {
+            provider: "openai".into(),
+            model_pattern: "gpt-4o".into(),
+            input_per_1m: 1.0,
+            output_per_1m: 2.0,
+        }];
+        let catalog = pricing_catalog(&overrides);
+        let gpt4o: Vec<_> = catalog
+            .iter()
+            .filter(|p| p.provider == "openai" && p.model_pattern == "gpt-4o")
+            .collect();
+        assert_eq!(gpt4o.len(), 1);
+        assert_eq!(gpt4o[0].input_per_1m, 1.0);
+        assert_eq!(catalog.len(), built_in_pricing().len());
+    }
+
+    #[test]
+    fn deprecation_overrides_come_before_built_ins() {
+        let overrides = vec![ModelDeprecation {
+            provider: "anthropic".into(),
+            model_pattern: "claude-3-opus".into(),
+            deprecated_on: NaiveDate::from_ymd_opt(2027, 1, 1).unwrap(),
+            replacement: None,
+        }];
+        let catalog = deprecation_catalog(&overrides);
+        assert_eq!(catalog.len(), built_in_deprecations().len());
+        let opus = find_deprecation(&catalog, "Anthropic", "claude-3-opus-20240229").unwrap();
+        assert_eq!(opus.deprecated_on.to_string(), "2027-01-01");
+        assert!(find_deprecation(&catalog, "openai", "gpt-4o").is_none());
+        assert!(find_deprecation(&catalog, "openai", "claude-3-opus").is_none());
+    }
+}
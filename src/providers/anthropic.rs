@@ -1,24 +1,192 @@
 use crate::error::AppError;
-use crate::models::UsageRecord;
-use crate::providers::{ProviderAdapter, ProviderContext};
+use crate::models::{Granularity, UsageRecord, UsageSource};
+use crate::providers::{
+    data_items, item_avg_latency_ms, token_field, ParseContext, ProviderAdapter, ProviderContext,
+    UsageFetch, UsageSchema,
+};
 use async_trait::async_trait;
 use chrono::{Duration, TimeZone, Utc};
 use reqwest::Client;
 use serde_json::Value;
+use std::time::Instant;
+
+/// Safety stop for `has_more` pagination.
+const MAX_PAGES: u32 = 50;
 
 pub struct AnthropicAdapter;
 
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[
+    UsageSchema {
+        version: "anthropic/buckets-v2",
+        detect: AnthropicAdapter::has_result_buckets,
+        parse: AnthropicAdapter::parse_result_buckets,
+    },
+    UsageSchema {
+        version: "anthropic/flat-v1",
+        detect: AnthropicAdapter::has_flat_items,
+        parse: AnthropicAdapter::parse_flat_items,
+    },
+];
+
 impl AnthropicAdapter {
-    fn usage_endpoint(hours: i64) -> String {
+    fn usage_endpoint(hours: i64, bucket_width: &str) -> String {
         let end = Utc::now();
         let start = end - Duration::hours(hours);
         format!(
-            "https://api.anthropic.com/v1/organizations/usage_report/messages?starting_at={}&ending_at={}",
+            "https://api.anthropic.com/v1/organizations/usage_report/messages?starting_at={}&ending_at={}&bucket_width={}&limit={}",
             start.to_rfc3339(),
-            end.to_rfc3339()
+            end.to_rfc3339(),
+            bucket_width,
+            Self::bucket_limit(hours, bucket_width)
         )
     }
 
+    /// Configured `bucket_width`, or `1h` for lookbacks up to 7 days and `1d`
+    /// beyond, which keeps each window to a single request.
+    fn bucket_width(configured: Option<&str>, hours: i64) -> Result<&'static str, AppError> {
+        match configured {
+            None if hours <= 24 * 7 => Ok("1h"),
+            None => Ok("1d"),
+            Some("1m") => Ok("1m"),
+            Some("1h") => Ok("1h"),
+            Some("1d") => Ok("1d"),
+            Some(other) => Err(AppError::Config(format!(
+                "Unsupported anthropic bucket_width '{other}'. Use 1m, 1h, or 1d."
+            ))),
+        }
+    }
+
+    /// Buckets needed to cover `hours`, capped at the API's per-request maximum
+    /// for that width; anything beyond arrives on later pages.
+    fn bucket_limit(hours: i64, bucket_width: &str) -> i64 {
+        let (minutes_per_bucket, max) = match bucket_width {
+            "1m" => (1, 1440),
+            "1h" => (60, 168),
+            _ => (1440, 31),
+        };
+        let needed = (hours * 60 + minutes_per_bucket - 1) / minutes_per_bucket;
+        needed.clamp(1, max)
+    }
+
+    fn next_page(body: &Value) -> Option<String> {
+        if !body
+            .get("has_more")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        body.get("next_page")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    }
+
+    fn with_page(url: &str, page: &str) -> Result<String, AppError> {
+        let mut parsed = url::Url::parse(url)
+            .map_err(|e| AppError::Config(format!("Invalid anthropic usage URL: {e}")))?;
+        let kept: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(k, _)| k != "page")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .append_pair("page", page);
+        Ok(parsed.to_string())
+    }
+
+    /// Time buckets, each with a `results` array of per-model usage, as the
+    /// usage report API returns today.
+    fn has_result_buckets(body: &Value) -> bool {
+        data_items(body)
+            .first()
+            .is_some_and(|bucket| bucket.get("results").is_some())
+    }
+
+    fn parse_result_buckets(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        data_items(body)
+            .iter()
+            .flat_map(|bucket| {
+                bucket
+                    .get("results")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |result| Self::usage_row(result, bucket, ctx))
+            })
+            .collect()
+    }
+
+    /// One usage item per model and bucket directly under `data`.
+    fn has_flat_items(body: &Value) -> bool {
+        data_items(body).first().is_some_and(|item| {
+            item.get("input_tokens").is_some() || item.get("output_tokens").is_some()
+        })
+    }
+
+    fn parse_flat_items(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        data_items(body)
+            .iter()
+            .filter_map(|item| Self::usage_row(item, item, ctx))
+            .collect()
+    }
+
+    /// Input, output and cached tokens of `item`; `None` when it carries
+    /// neither input nor output. Cache writes bill at least the input price,
+    /// so they count as input; cache reads are the cached tokens.
+    fn tokens(item: &Value) -> Option<(u64, u64, u64)> {
+        let input = token_field(
+            item,
+            &["uncached_input_tokens", "input_tokens", "tokens_in"],
+        );
+        let output = token_field(item, &["output_tokens", "tokens_out"]);
+        if input.is_none() && output.is_none() {
+            return None;
+        }
+        let cache_writes =
+            token_field(item, &["cache_creation_input_tokens"]).unwrap_or_else(|| {
+                // Split by cache lifetime in bucketed reports.
+                item.get("cache_creation")
+                    .and_then(Value::as_object)
+                    .map_or(0, |split| split.values().filter_map(Value::as_u64).sum())
+            });
+        let cache_reads = token_field(item, &["cache_read_input_tokens"]).unwrap_or(0);
+        Some((
+            input.unwrap_or(0) + cache_writes,
+            output.unwrap_or(0),
+            cache_reads,
+        ))
+    }
+
+    /// Row for the tokens of `item`, timed by `bucket`, which is `item` itself
+    /// in flat payloads.
+    fn usage_row(item: &Value, bucket: &Value, ctx: &ParseContext) -> Option<UsageRecord> {
+        let (input_tokens, output_tokens, cached_tokens) = Self::tokens(item)?;
+        Some(UsageRecord {
+            provider: ctx.provider.to_string(),
+            model: item
+                .get("model")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            input_tokens,
+            output_tokens,
+            cached_tokens,
+            timestamp: Self::parse_item_timestamp(bucket).unwrap_or(ctx.fallback),
+            bucket_end: bucket
+                .get("ending_at")
+                .and_then(Value::as_str)
+                .and_then(|raw| chrono::DateTime::parse_from_rfc3339(raw).ok())
+                .map(|parsed| parsed.with_timezone(&Utc)),
+            source_granularity: ctx.granularity,
+            source: UsageSource::Api,
+            avg_latency_ms: item_avg_latency_ms(item),
+        })
+    }
+
     fn parse_item_timestamp(item: &Value) -> Option<chrono::DateTime<Utc>> {
         if let Some(raw) = item.get("starting_at").and_then(Value::as_str) {
             if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(raw) {
@@ -65,62 +233,62 @@ impl ProviderAdapter for AnthropicAdapter {
         "anthropic"
     }
 
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["api.anthropic.com"]
+    }
+
     async fn fetch_usage(
         &self,
         client: &Client,
         ctx: &ProviderContext,
-    ) -> Result<Vec<UsageRecord>, AppError> {
-        let url = ctx
+    ) -> Result<UsageFetch, AppError> {
+        let bucket_width =
+            Self::bucket_width(ctx.settings.bucket_width.as_deref(), ctx.resolution_hours)?;
+        let first_url = ctx
             .settings
             .base_url
             .clone()
-            .unwrap_or_else(|| Self::usage_endpoint(ctx.window.as_hours()));
+            .unwrap_or_else(|| Self::usage_endpoint(ctx.lookback_hours, bucket_width));
 
-        let body: Value = client
-            .get(url)
-            .header("x-api-key", &ctx.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+        let mut fetch = UsageFetch::default();
+        let mut url = first_url.clone();
+        loop {
+            let permit = ctx.acquire().await?;
+            let started = Instant::now();
+            let response = client
+                .get(&url)
+                .header("x-api-key", ctx.api_key())
+                .header("anthropic-version", "2023-06-01")
+                .send()
+                .await?;
+            fetch.note_server_date(&response);
+            let bytes = response.error_for_status()?.bytes().await?;
+            fetch.http_ms += started.elapsed().as_millis();
+            fetch.pages += 1;
+            drop(permit);
 
-        let mut out = Vec::new();
-        let items = body
-            .get("data")
-            .and_then(Value::as_array)
-            .cloned()
-            .unwrap_or_default();
+            let started = Instant::now();
+            let body: Value = serde_json::from_slice(&bytes)?;
+            fetch.parse_page(
+                USAGE_SCHEMAS,
+                &body,
+                &ParseContext {
+                    provider: self.name(),
+                    fallback: ctx.refresh_end,
+                    granularity: Granularity::parse(bucket_width),
+                },
+            );
+            fetch.parse_ms += started.elapsed().as_millis();
 
-        for item in items {
-            let model = item
-                .get("model")
-                .and_then(Value::as_str)
-                .unwrap_or("unknown")
-                .to_string();
-            let input_tokens = item
-                .get("input_tokens")
-                .and_then(Value::as_u64)
-                .or_else(|| item.get("tokens_in").and_then(Value::as_u64))
-                .unwrap_or(0);
-            let output_tokens = item
-                .get("output_tokens")
-                .and_then(Value::as_u64)
-                .or_else(|| item.get("tokens_out").and_then(Value::as_u64))
-                .unwrap_or(0);
-
-            out.push(UsageRecord {
-                provider: self.name().to_string(),
-                model,
-                input_tokens,
-                output_tokens,
-                cached_tokens: 0,
-                timestamp: Self::parse_item_timestamp(&item).unwrap_or(ctx.refresh_end),
-            });
+            match Self::next_page(&body) {
+                Some(page) if fetch.pages < MAX_PAGES => {
+                    url = Self::with_page(&first_url, &page)?;
+                }
+                _ => break,
+            }
         }
 
-        Ok(out)
+        Ok(fetch)
     }
 
     async fn test_connection(
@@ -130,9 +298,10 @@ impl ProviderAdapter for AnthropicAdapter {
     ) -> Result<Option<u16>, AppError> {
         let url = Self::resolve_test_url(ctx.settings.base_url.clone());
 
+        let _permit = ctx.acquire().await?;
         let response = client
             .get(url)
-            .header("x-api-key", &ctx.api_key)
+            .header("x-api-key", ctx.api_key())
             .header("anthropic-version", "2023-06-01")
             .send()
             .await?;
@@ -175,6 +344,91 @@ mod tests {
         assert_eq!(ts.timestamp(), 1_700_000_000);
     }
 
+    #[test]
+    fn result_buckets_count_cache_writes_as_input_and_reads_as_cached() {
+        let body = json!({ "data": [
+            { "starting_at": "2025-05-01T10:00:00Z", "ending_at": "2025-05-01T11:00:00Z",
+              "results": [
+                  { "model": "claude-sonnet-4-20250514", "uncached_input_tokens": 1000,
+                    "cache_creation": { "ephemeral_5m_input_tokens": 200,
+                                        "ephemeral_1h_input_tokens": 100 },
+                    "cache_read_input_tokens": 5000, "output_tokens": 400 },
+              ] },
+        ], "has_more": false });
+        let ctx = ParseContext {
+            provider: "anthropic",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Hour),
+        };
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("anthropic/buckets-v2"));
+        let row = &fetch.rows[0];
+        assert_eq!(
+            (row.input_tokens, row.output_tokens, row.cached_tokens),
+            (1300, 400, 5000)
+        );
+        assert_eq!(row.timestamp.to_rfc3339(), "2025-05-01T10:00:00+00:00");
+        assert!(row.bucket_end.is_some());
+
+        // Flat items with the older field names fall back to the v1 parser.
+        let legacy = json!({ "data": [
+            { "model": "claude-3-5-haiku", "starting_at": "2025-05-01T10:00:00Z",
+              "tokens_in": 30, "tokens_out": 3 },
+        ] });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &legacy, &ctx);
+        assert_eq!(fetch.parser_version, Some("anthropic/flat-v1"));
+        assert!(fetch.parser_fallback);
+        assert_eq!(
+            (fetch.rows[0].input_tokens, fetch.rows[0].output_tokens),
+            (30, 3)
+        );
+    }
+
+    #[test]
+    fn bucket_width_defaults_by_lookback_and_rejects_unknown_values() {
+        assert_eq!(AnthropicAdapter::bucket_width(None, 24).expect("1d"), "1h");
+        assert_eq!(
+            AnthropicAdapter::bucket_width(None, 24 * 7).expect("7d"),
+            "1h"
+        );
+        assert_eq!(
+            AnthropicAdapter::bucket_width(None, 24 * 30).expect("30d"),
+            "1d"
+        );
+        assert_eq!(
+            AnthropicAdapter::bucket_width(Some("1m"), 24 * 30).expect("configured"),
+            "1m"
+        );
+        assert!(AnthropicAdapter::bucket_width(Some("2h"), 24).is_err());
+    }
+
+    #[test]
+    fn bucket_limit_is_capped_per_width() {
+        assert_eq!(AnthropicAdapter::bucket_limit(24, "1h"), 24);
+        assert_eq!(AnthropicAdapter::bucket_limit(24 * 30, "1h"), 168);
+        assert_eq!(AnthropicAdapter::bucket_limit(24 * 30, "1d"), 30);
+        assert_eq!(AnthropicAdapter::bucket_limit(24 * 90, "1d"), 31);
+        assert_eq!(AnthropicAdapter::bucket_limit(24, "1m"), 1440);
+    }
+
+    #[test]
+    fn next_page_follows_has_more_and_replaces_page_param() {
+        assert_eq!(
+            AnthropicAdapter::next_page(&json!({ "has_more": true, "next_page": "p2" })),
+            Some("p2".to_string())
+        );
+        assert_eq!(
+            AnthropicAdapter::next_page(&json!({ "has_more": false, "next_page": "p2" })),
+            None
+        );
+
+        let url = AnthropicAdapter::with_page("https://example.com/usage?limit=31&page=p1", "p2")
+            .expect("url");
+        assert_eq!(url, "https://example.com/usage?limit=31&page=p2");
+    }
+
     #[test]
     fn parse_item_timestamp_returns_none_for_invalid_payload() {
         assert!(AnthropicAdapter::parse_item_timestamp(&json!({ "starting_at": "bad" })).is_none());
@@ -0,0 +1,395 @@
+use crate::error::AppError;
+use crate::models::{Granularity, UsageRecord, UsageSource};
+use crate::providers::{
+    bucket_width, ParseContext, ProviderAdapter, ProviderContext, UsageFetch, UsageSchema,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+const MANAGEMENT_ROOT: &str = "https://management.azure.com";
+
+/// Azure Monitor metrics API version used when `api_version` is unset.
+const DEFAULT_API_VERSION: &str = "2023-10-01";
+
+/// Token metrics Azure OpenAI publishes per deployment.
+const PROMPT_METRIC: &str = "ProcessedPromptTokens";
+const COMPLETION_METRIC: &str = "GeneratedTokens";
+
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[UsageSchema {
+    version: "azure-openai/metrics-v1",
+    detect: AzureOpenAiAdapter::has_metrics,
+    parse: AzureOpenAiAdapter::parse_metrics,
+}];
+
+/// Azure OpenAI usage, read from the token metrics the resource publishes to
+/// Azure Monitor. The key is an Azure Resource Manager access token with
+/// read access to the resource; usage rows are per deployment, renamed to
+/// their model through `deployments` so they price as OpenAI models.
+pub struct AzureOpenAiAdapter;
+
+impl AzureOpenAiAdapter {
+    fn resource(ctx: &ProviderContext) -> Result<&str, AppError> {
+        let resource = ctx
+            .settings
+            .resource
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .ok_or_else(|| {
+                AppError::Config(
+                    "azure-openai needs resource, the Azure resource ID of the Azure OpenAI resource."
+                        .into(),
+                )
+            })?;
+        let lower = resource.to_ascii_lowercase();
+        if !lower.starts_with("/subscriptions/")
+            || !lower.contains("/providers/microsoft.cognitiveservices/accounts/")
+        {
+            return Err(AppError::Config(format!(
+                "azure-openai resource '{resource}' is not a resource ID. Copy it from the resource's \
+                 JSON view: /subscriptions/<id>/resourceGroups/<group>/providers/Microsoft.CognitiveServices/accounts/<name>."
+            )));
+        }
+        Ok(resource.trim_end_matches('/'))
+    }
+
+    /// ISO 8601 duration Azure Monitor takes as a metrics `interval`.
+    fn interval(width: Granularity) -> &'static str {
+        match width {
+            Granularity::Minute => "PT1M",
+            Granularity::Hour => "PT1H",
+            Granularity::Day => "P1D",
+        }
+    }
+
+    /// Prompt and completion token totals per deployment over `width`
+    /// buckets from `start` to `end`.
+    fn usage_endpoint(
+        root: &str,
+        resource: &str,
+        api_version: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        width: Granularity,
+    ) -> Result<String, AppError> {
+        let mut url = url::Url::parse(&format!(
+            "{}{resource}/providers/Microsoft.Insights/metrics",
+            root.trim_end_matches('/')
+        ))
+        .map_err(|e| AppError::Config(format!("Invalid azure-openai base_url: {e}")))?;
+        url.query_pairs_mut()
+            .append_pair("api-version", api_version)
+            .append_pair(
+                "metricnames",
+                &format!("{PROMPT_METRIC},{COMPLETION_METRIC}"),
+            )
+            .append_pair(
+                "timespan",
+                &format!(
+                    "{}/{}",
+                    start.format("%Y-%m-%dT%H:%M:%SZ"),
+                    end.format("%Y-%m-%dT%H:%M:%SZ")
+                ),
+            )
+            .append_pair("interval", Self::interval(width))
+            .append_pair("aggregation", "Total")
+            .append_pair("$filter", "ModelDeploymentName eq '*'");
+        Ok(url.to_string())
+    }
+
+    fn endpoint_for(
+        ctx: &ProviderContext,
+        start: DateTime<Utc>,
+        width: Granularity,
+    ) -> Result<String, AppError> {
+        Self::usage_endpoint(
+            ctx.settings.base_url.as_deref().unwrap_or(MANAGEMENT_ROOT),
+            Self::resource(ctx)?,
+            ctx.settings
+                .api_version
+                .as_deref()
+                .unwrap_or(DEFAULT_API_VERSION),
+            start,
+            ctx.refresh_end,
+            width,
+        )
+    }
+
+    fn has_metrics(body: &Value) -> bool {
+        body.get("value")
+            .and_then(Value::as_array)
+            .and_then(|metrics| metrics.first())
+            .is_some_and(|metric| metric.get("timeseries").is_some())
+    }
+
+    /// One row per deployment and bucket, merging the prompt and completion
+    /// metrics. The model is the deployment name until `deployments` maps it.
+    fn parse_metrics(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        let width = ctx.granularity.unwrap_or(Granularity::Hour);
+        let mut rows: BTreeMap<(String, DateTime<Utc>), UsageRecord> = BTreeMap::new();
+        let metrics = body
+            .get("value")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for metric in metrics {
+            let prompt = match metric.pointer("/name/value").and_then(Value::as_str) {
+                Some(PROMPT_METRIC) => true,
+                Some(COMPLETION_METRIC) => false,
+                _ => continue,
+            };
+            let series = metric
+                .get("timeseries")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for series in series {
+                let Some(deployment) = series
+                    .get("metadatavalues")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .find(|dim| {
+                        dim.pointer("/name/value")
+                            .and_then(Value::as_str)
+                            .is_some_and(|name| name.eq_ignore_ascii_case("ModelDeploymentName"))
+                    })
+                    .and_then(|dim| dim.get("value"))
+                    .and_then(Value::as_str)
+                else {
+                    continue;
+                };
+                let points = series
+                    .get("data")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten();
+                for point in points {
+                    let Some(start) = point
+                        .get("timeStamp")
+                        .and_then(Value::as_str)
+                        .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+                        .map(|parsed| parsed.with_timezone(&Utc))
+                    else {
+                        continue;
+                    };
+                    // Totals arrive as floats, and are absent for empty buckets.
+                    let tokens = point
+                        .get("total")
+                        .and_then(Value::as_f64)
+                        .filter(|total| total.is_finite() && *total > 0.0)
+                        .map_or(0, |total| total.round() as u64);
+                    if tokens == 0 {
+                        continue;
+                    }
+                    let row = rows
+                        .entry((deployment.to_string(), start))
+                        .or_insert_with(|| UsageRecord {
+                            provider: ctx.provider.to_string(),
+                            model: deployment.to_string(),
+                            input_tokens: 0,
+                            output_tokens: 0,
+                            cached_tokens: 0,
+                            timestamp: start,
+                            bucket_end: Some(start + width.duration()),
+                            source_granularity: Some(width),
+                            avg_latency_ms: None,
+                            source: UsageSource::Api,
+                        });
+                    if prompt {
+                        row.input_tokens += tokens;
+                    } else {
+                        row.output_tokens += tokens;
+                    }
+                }
+            }
+        }
+        rows.into_values().collect()
+    }
+
+    /// Renames deployment rows to the model `deployments` maps them to.
+    fn map_deployments(rows: &mut [UsageRecord], deployments: &BTreeMap<String, String>) {
+        for row in rows {
+            if let Some(model) = deployments.get(&row.model) {
+                row.model.clone_from(model);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for AzureOpenAiAdapter {
+    fn name(&self) -> &'static str {
+        "azure-openai"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["management.azure.com"]
+    }
+
+    /// Azure OpenAI serves OpenAI's models; Azure's global prices track them.
+    fn pricing_provider<'a>(&'a self, _model: &str) -> &'a str {
+        "openai"
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let width = bucket_width(
+            "azure-openai",
+            ctx.settings.bucket_width.as_deref(),
+            ctx.resolution_hours,
+        )?;
+        let url = Self::endpoint_for(
+            ctx,
+            ctx.refresh_end - Duration::hours(ctx.lookback_hours),
+            width,
+        )?;
+
+        let mut fetch = UsageFetch {
+            pages: 1,
+            ..UsageFetch::default()
+        };
+        let permit = ctx.acquire().await?;
+        let started = Instant::now();
+        let response = client.get(url).bearer_auth(ctx.api_key()).send().await?;
+        fetch.note_server_date(&response);
+        let bytes = response.error_for_status()?.bytes().await?;
+        fetch.http_ms = started.elapsed().as_millis();
+        drop(permit);
+
+        let started = Instant::now();
+        let body: Value = serde_json::from_slice(&bytes)?;
+        fetch.parse_page(
+            USAGE_SCHEMAS,
+            &body,
+            &ParseContext {
+                provider: self.name(),
+                fallback: ctx.refresh_end,
+                granularity: Some(width),
+            },
+        );
+        Self::map_deployments(&mut fetch.rows, &ctx.settings.deployments);
+        fetch.parse_ms = started.elapsed().as_millis();
+        Ok(fetch)
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let url = Self::endpoint_for(ctx, ctx.refresh_end - Duration::hours(1), Granularity::Hour)?;
+
+        let _permit = ctx.acquire().await?;
+        let response = client.get(url).bearer_auth(ctx.api_key()).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(AppError::Config(
+                "Azure rejected the access token (unauthorized).".into(),
+            ));
+        }
+        if status.as_u16() == 404 {
+            return Err(AppError::Config(
+                "Azure found no resource with that ID; check resource.".into(),
+            ));
+        }
+
+        Err(AppError::Config(format!(
+            "Azure Monitor connection failed with HTTP status {}.",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn deployment_metrics_merge_per_bucket_and_map_to_models() {
+        let series = |deployment: &str, points: Value| {
+            json!({
+                "metadatavalues": [
+                    { "name": { "value": "modeldeploymentname" }, "value": deployment }
+                ],
+                "data": points
+            })
+        };
+        let body = json!({
+            "value": [
+                { "name": { "value": PROMPT_METRIC }, "timeseries": [
+                    series("prod-chat", json!([
+                        { "timeStamp": "2025-05-01T10:00:00Z", "total": 1200.0 },
+                        { "timeStamp": "2025-05-01T11:00:00Z" },
+                    ])),
+                    series("embed", json!([
+                        { "timeStamp": "2025-05-01T10:00:00Z", "total": 80.0 },
+                    ])),
+                ] },
+                { "name": { "value": COMPLETION_METRIC }, "timeseries": [
+                    series("prod-chat", json!([
+                        { "timeStamp": "2025-05-01T10:00:00Z", "total": 300.0 },
+                    ])),
+                ] },
+            ]
+        });
+        let ctx = ParseContext {
+            provider: "azure-openai",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Hour),
+        };
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("azure-openai/metrics-v1"));
+
+        let deployments = BTreeMap::from([("prod-chat".to_string(), "gpt-4o".to_string())]);
+        AzureOpenAiAdapter::map_deployments(&mut fetch.rows, &deployments);
+        let rows: Vec<_> = fetch
+            .rows
+            .iter()
+            .map(|r| (r.model.as_str(), r.input_tokens, r.output_tokens))
+            .collect();
+        assert_eq!(rows, vec![("embed", 80, 0), ("gpt-4o", 1200, 300)]);
+        assert_eq!(
+            fetch.rows[1].bucket_end.map(|end| end.to_rfc3339()),
+            Some("2025-05-01T11:00:00+00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn usage_endpoint_splits_token_metrics_by_deployment() {
+        let end = DateTime::parse_from_rfc3339("2025-05-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let resource = "/subscriptions/sub-1/resourceGroups/ai/providers/Microsoft.CognitiveServices/accounts/contoso";
+        let url = AzureOpenAiAdapter::usage_endpoint(
+            MANAGEMENT_ROOT,
+            resource,
+            DEFAULT_API_VERSION,
+            end - Duration::days(1),
+            end,
+            Granularity::Day,
+        )
+        .expect("url");
+        assert!(url.starts_with(&format!(
+            "https://management.azure.com{resource}/providers/Microsoft.Insights/metrics?api-version=2023-10-01"
+        )));
+        assert!(url.contains("metricnames=ProcessedPromptTokens%2CGeneratedTokens"));
+        assert!(url.contains("interval=P1D"));
+        assert!(url.contains("timespan=2025-05-01T00%3A00%3A00Z%2F2025-05-02T00%3A00%3A00Z"));
+        assert!(url.contains("%24filter=ModelDeploymentName+eq+%27*%27"));
+    }
+}
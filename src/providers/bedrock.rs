@@ -0,0 +1,612 @@
+use crate::aws::{self, AwsCredentials};
+use crate::error::AppError;
+use crate::models::{CostRecord, Granularity, UsageRecord, UsageSource};
+use crate::providers::{
+    bucket_width, CredentialKind, Credentials, ParseContext, ProviderAdapter, ProviderContext,
+    UsageFetch, UsageSchema,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+const MAX_PAGES: u32 = 50;
+
+/// CloudWatch namespace and token metrics Bedrock publishes per `ModelId`.
+const NAMESPACE: &str = "AWS/Bedrock";
+const INPUT_METRIC: &str = "InputTokenCount";
+const OUTPUT_METRIC: &str = "OutputTokenCount";
+
+/// CloudWatch's JSON protocol, and its cap on queries per `GetMetricData`.
+const CLOUDWATCH_TARGET: &str = "GraniteServiceVersion20100801";
+const CLOUDWATCH_CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+const MAX_QUERIES: usize = 500;
+
+/// Cost Explorer has a single endpoint, signed for `us-east-1`.
+const COST_EXPLORER_URL: &str = "https://ce.us-east-1.amazonaws.com/";
+const COST_EXPLORER_REGION: &str = "us-east-1";
+const COST_EXPLORER_TARGET: &str = "AWSInsightsIndexService.GetCostAndUsage";
+const COST_EXPLORER_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// Geography prefixes of cross-region inference profile IDs, e.g.
+/// `us.anthropic.claude-3-5-haiku-20241022-v1:0`.
+const INFERENCE_PROFILE_PREFIXES: &[&str] = &["us", "eu", "apac", "us-gov", "global"];
+
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[UsageSchema {
+    version: "bedrock/cloudwatch-v1",
+    detect: BedrockAdapter::has_metric_data,
+    parse: BedrockAdapter::parse_metric_data,
+}];
+
+/// Amazon Bedrock usage, read from the token metrics Bedrock publishes to
+/// CloudWatch, signed with AWS credentials rather than an API key. Costs are
+/// priced from the tokens unless `cost_explorer` takes them from Cost
+/// Explorer as billed.
+pub struct BedrockAdapter;
+
+impl BedrockAdapter {
+    fn credentials(ctx: &ProviderContext) -> Result<&AwsCredentials, AppError> {
+        match &ctx.credentials {
+            Credentials::Aws(creds) => Ok(creds),
+            _ => Err(AppError::Config("bedrock needs AWS credentials.".into())),
+        }
+    }
+
+    /// CloudWatch endpoint of the account's region, or `base_url`.
+    fn monitoring_endpoint(ctx: &ProviderContext, region: &str) -> String {
+        ctx.settings
+            .base_url
+            .clone()
+            .unwrap_or_else(|| format!("https://monitoring.{region}.amazonaws.com/"))
+    }
+
+    async fn cloudwatch(
+        client: &Client,
+        ctx: &ProviderContext,
+        creds: &AwsCredentials,
+        operation: &str,
+        body: &Value,
+    ) -> Result<Value, AppError> {
+        aws::json_request(
+            client,
+            creds,
+            &creds.region,
+            "monitoring",
+            &Self::monitoring_endpoint(ctx, &creds.region),
+            CLOUDWATCH_CONTENT_TYPE,
+            &format!("{CLOUDWATCH_TARGET}.{operation}"),
+            body,
+        )
+        .await
+    }
+
+    /// `ModelId`s with token metrics in the region. CloudWatch only lists
+    /// metrics that received data in the past two weeks.
+    async fn list_models(
+        client: &Client,
+        ctx: &ProviderContext,
+        creds: &AwsCredentials,
+        fetch: &mut UsageFetch,
+    ) -> Result<Vec<String>, AppError> {
+        let mut models = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let mut request = json!({
+                "Namespace": NAMESPACE,
+                "MetricName": INPUT_METRIC,
+                "Dimensions": [{ "Name": "ModelId" }],
+            });
+            if let Some(token) = &next_token {
+                request["NextToken"] = json!(token);
+            }
+            let permit = ctx.acquire().await?;
+            let started = Instant::now();
+            let body = Self::cloudwatch(client, ctx, creds, "ListMetrics", &request).await?;
+            fetch.http_ms += started.elapsed().as_millis();
+            drop(permit);
+            fetch.pages += 1;
+
+            models.extend(Self::model_ids(&body));
+            next_token = body
+                .get("NextToken")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if next_token.is_none() || fetch.pages >= MAX_PAGES {
+                break;
+            }
+        }
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+
+    fn model_ids(body: &Value) -> Vec<String> {
+        body.get("Metrics")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|metric| {
+                metric
+                    .get("Dimensions")
+                    .and_then(Value::as_array)?
+                    .iter()
+                    .find(|dim| dim.get("Name").and_then(Value::as_str) == Some("ModelId"))?
+                    .get("Value")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .collect()
+    }
+
+    /// `Sum` queries for both token metrics of each model, labelled with the
+    /// model so results can be told apart; ids are `in<n>` and `out<n>`.
+    fn metric_queries(models: &[String], period: i64) -> Vec<Value> {
+        let query = |id: String, metric: &str, model: &str| {
+            json!({
+                "Id": id,
+                "Label": model,
+                "MetricStat": {
+                    "Metric": {
+                        "Namespace": NAMESPACE,
+                        "MetricName": metric,
+                        "Dimensions": [{ "Name": "ModelId", "Value": model }],
+                    },
+                    "Period": period,
+                    "Stat": "Sum",
+                },
+                "ReturnData": true,
+            })
+        };
+        models
+            .iter()
+            .enumerate()
+            .flat_map(|(i, model)| {
+                [
+                    query(format!("in{i}"), INPUT_METRIC, model),
+                    query(format!("out{i}"), OUTPUT_METRIC, model),
+                ]
+            })
+            .collect()
+    }
+
+    fn has_metric_data(body: &Value) -> bool {
+        body.get("MetricDataResults").is_some()
+    }
+
+    /// One row per model and bucket, merging the input and output metrics.
+    /// CloudWatch's JSON protocol sends timestamps as epoch seconds.
+    fn parse_metric_data(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        let width = ctx.granularity.unwrap_or(Granularity::Hour);
+        let mut rows: BTreeMap<(String, DateTime<Utc>), UsageRecord> = BTreeMap::new();
+        let results = body
+            .get("MetricDataResults")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for result in results {
+            let id = result.get("Id").and_then(Value::as_str).unwrap_or_default();
+            let input = if id.starts_with("in") {
+                true
+            } else if id.starts_with("out") {
+                false
+            } else {
+                continue;
+            };
+            let Some(model) = result.get("Label").and_then(Value::as_str) else {
+                continue;
+            };
+            let timestamps = result
+                .get("Timestamps")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            let values = result
+                .get("Values")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for (at, value) in timestamps.zip(values) {
+                let Some(start) = at
+                    .as_f64()
+                    .and_then(|secs| DateTime::from_timestamp(secs as i64, 0))
+                else {
+                    continue;
+                };
+                let tokens = value
+                    .as_f64()
+                    .filter(|sum| sum.is_finite() && *sum > 0.0)
+                    .map_or(0, |sum| sum.round() as u64);
+                if tokens == 0 {
+                    continue;
+                }
+                let row = rows
+                    .entry((model.to_string(), start))
+                    .or_insert_with(|| UsageRecord {
+                        provider: ctx.provider.to_string(),
+                        model: model.to_string(),
+                        input_tokens: 0,
+                        output_tokens: 0,
+                        cached_tokens: 0,
+                        timestamp: start,
+                        bucket_end: Some(start + width.duration()),
+                        source_granularity: Some(width),
+                        avg_latency_ms: None,
+                        source: UsageSource::Api,
+                    });
+                if input {
+                    row.input_tokens += tokens;
+                } else {
+                    row.output_tokens += tokens;
+                }
+            }
+        }
+        rows.into_values().collect()
+    }
+
+    /// Cost Explorer days to request: from the first whole day after `since`,
+    /// so no day is stored that the refresh window only partly covers,
+    /// through the day of `end` (exclusive end date).
+    fn cost_days(since: DateTime<Utc>, end: DateTime<Utc>) -> Option<(NaiveDate, NaiveDate)> {
+        let first = if since.time() == NaiveTime::MIN {
+            since.date_naive()
+        } else {
+            since.date_naive().succ_opt()?
+        };
+        let last = end.date_naive().succ_opt()?;
+        (first < last).then_some((first, last))
+    }
+
+    /// Model a Cost Explorer group bills for. Marketplace models are their own
+    /// service, e.g. `Claude 3.5 Sonnet (Amazon Bedrock Edition)`; first-party
+    /// ones are named in the usage type, e.g. `USE1-TitanEmbeddingsG1-Text-input-tokens`.
+    fn cost_model(service: &str, usage_type: &str) -> String {
+        if let Some(model) = service.strip_suffix(" (Amazon Bedrock Edition)") {
+            return model.trim().to_string();
+        }
+        let name = match usage_type.split_once('-') {
+            Some((region, rest))
+                if region
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) =>
+            {
+                rest
+            }
+            _ => usage_type,
+        };
+        let lower = name.to_ascii_lowercase();
+        let end = ["-input", "-output"]
+            .iter()
+            .filter_map(|suffix| lower.rfind(suffix))
+            .min()
+            .unwrap_or(name.len());
+        name[..end].to_string()
+    }
+
+    /// Daily Bedrock costs per model, split into input and output by usage
+    /// type. Groups of other services are skipped.
+    fn parse_costs(body: &Value, provider: &str) -> Vec<CostRecord> {
+        let mut costs: BTreeMap<(String, DateTime<Utc>), CostRecord> = BTreeMap::new();
+        let days = body
+            .get("ResultsByTime")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for day in days {
+            let Some(start) = day
+                .pointer("/TimePeriod/Start")
+                .and_then(Value::as_str)
+                .and_then(|raw| NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok())
+                .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+            else {
+                continue;
+            };
+            let groups = day
+                .get("Groups")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for group in groups {
+                let keys: Vec<&str> = group
+                    .get("Keys")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                let [service, usage_type] = keys[..] else {
+                    continue;
+                };
+                if !service.contains("Bedrock") {
+                    continue;
+                }
+                let Some(amount) = group
+                    .pointer("/Metrics/UnblendedCost/Amount")
+                    .and_then(Value::as_str)
+                    .and_then(|raw| raw.parse::<f64>().ok())
+                    .filter(|amount| amount.is_finite() && *amount != 0.0)
+                else {
+                    continue;
+                };
+                let currency = group
+                    .pointer("/Metrics/UnblendedCost/Unit")
+                    .and_then(Value::as_str)
+                    .unwrap_or("USD");
+                let model = Self::cost_model(service, usage_type);
+                let cost = costs
+                    .entry((model.clone(), start))
+                    .or_insert_with(|| CostRecord {
+                        provider: provider.to_string(),
+                        model,
+                        input_cost: 0.0,
+                        output_cost: 0.0,
+                        total_cost: 0.0,
+                        currency: currency.to_string(),
+                        timestamp: start,
+                        bucket_end: Some(start + Duration::days(1)),
+                        source_granularity: Some(Granularity::Day),
+                        source: UsageSource::Api,
+                    });
+                if usage_type.to_ascii_lowercase().contains("output") {
+                    cost.output_cost += amount;
+                } else {
+                    cost.input_cost += amount;
+                }
+                cost.total_cost += amount;
+            }
+        }
+        costs.into_values().collect()
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for BedrockAdapter {
+    fn name(&self) -> &'static str {
+        "bedrock"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["monitoring.*.amazonaws.com", "ce.us-east-1.amazonaws.com"]
+    }
+
+    fn credential_kind(&self) -> CredentialKind {
+        CredentialKind::Aws
+    }
+
+    /// Bedrock model IDs start with their vendor, after the geography of a
+    /// cross-region inference profile; Anthropic and OpenAI models price as
+    /// theirs, the rest through `bedrock` pricing overrides.
+    fn pricing_provider<'a>(&'a self, model: &str) -> &'a str {
+        let model = match model.split_once('.') {
+            Some((geo, rest)) if INFERENCE_PROFILE_PREFIXES.contains(&geo) => rest,
+            _ => model,
+        };
+        match model.split_once('.') {
+            Some(("anthropic", _)) => "anthropic",
+            Some(("openai", _)) => "openai",
+            _ => self.name(),
+        }
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let creds = Self::credentials(ctx)?;
+        let width = bucket_width(
+            "bedrock",
+            ctx.settings.bucket_width.as_deref(),
+            ctx.resolution_hours,
+        )?;
+        let start = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let parse_ctx = ParseContext {
+            provider: self.name(),
+            fallback: ctx.refresh_end,
+            granularity: Some(width),
+        };
+
+        let mut fetch = UsageFetch::default();
+        let models = Self::list_models(client, ctx, creds, &mut fetch).await?;
+        for chunk in models.chunks(MAX_QUERIES / 2) {
+            let mut next_token: Option<String> = None;
+            loop {
+                let mut request = json!({
+                    "MetricDataQueries": Self::metric_queries(chunk, width.duration().num_seconds()),
+                    "StartTime": start.timestamp(),
+                    "EndTime": ctx.refresh_end.timestamp(),
+                    "ScanBy": "TimestampAscending",
+                });
+                if let Some(token) = &next_token {
+                    request["NextToken"] = json!(token);
+                }
+                let permit = ctx.acquire().await?;
+                let started = Instant::now();
+                let body = Self::cloudwatch(client, ctx, creds, "GetMetricData", &request).await?;
+                fetch.http_ms += started.elapsed().as_millis();
+                drop(permit);
+                fetch.pages += 1;
+
+                let started = Instant::now();
+                fetch.parse_page(USAGE_SCHEMAS, &body, &parse_ctx);
+                fetch.parse_ms += started.elapsed().as_millis();
+
+                next_token = body
+                    .get("NextToken")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                if next_token.is_none() || fetch.pages >= MAX_PAGES {
+                    break;
+                }
+            }
+        }
+        Ok(fetch)
+    }
+
+    async fn fetch_costs(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<Vec<CostRecord>>, AppError> {
+        if !ctx.settings.cost_explorer {
+            return Ok(None);
+        }
+        let creds = Self::credentials(ctx)?;
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let Some((first, last)) = Self::cost_days(since, ctx.refresh_end) else {
+            return Ok(Some(Vec::new()));
+        };
+
+        let mut costs = Vec::new();
+        let mut next_token: Option<String> = None;
+        for _ in 0..MAX_PAGES {
+            let mut request = json!({
+                "TimePeriod": {
+                    "Start": first.format("%Y-%m-%d").to_string(),
+                    "End": last.format("%Y-%m-%d").to_string(),
+                },
+                "Granularity": "DAILY",
+                "Metrics": ["UnblendedCost"],
+                "GroupBy": [
+                    { "Type": "DIMENSION", "Key": "SERVICE" },
+                    { "Type": "DIMENSION", "Key": "USAGE_TYPE" },
+                ],
+            });
+            if let Some(token) = &next_token {
+                request["NextPageToken"] = json!(token);
+            }
+            let _permit = ctx.acquire().await?;
+            let body = aws::json_request(
+                client,
+                creds,
+                COST_EXPLORER_REGION,
+                "ce",
+                COST_EXPLORER_URL,
+                COST_EXPLORER_CONTENT_TYPE,
+                COST_EXPLORER_TARGET,
+                &request,
+            )
+            .await?;
+            costs.extend(Self::parse_costs(&body, self.name()));
+            next_token = body
+                .get("NextPageToken")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok(Some(costs))
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let creds = Self::credentials(ctx)?;
+        let _permit = ctx.acquire().await?;
+        let request = json!({ "Namespace": NAMESPACE, "MetricName": INPUT_METRIC });
+        Self::cloudwatch(client, ctx, creds, "ListMetrics", &request).await?;
+        Ok(Some(200))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metric_results_merge_per_model_and_bucket() {
+        let models = vec!["anthropic.claude-3-5-haiku-20241022-v1:0".to_string()];
+        let queries = BedrockAdapter::metric_queries(&models, 3600);
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[1]["Id"], "out0");
+        assert_eq!(
+            queries[1]["MetricStat"]["Metric"]["MetricName"],
+            OUTPUT_METRIC
+        );
+
+        let model = models[0].as_str();
+        let body = json!({
+            "MetricDataResults": [
+                { "Id": "in0", "Label": model, "StatusCode": "Complete",
+                  "Timestamps": [1746093600.0, 1746097200.0], "Values": [1200.0, 0.0] },
+                { "Id": "out0", "Label": model, "StatusCode": "Complete",
+                  "Timestamps": [1746093600.0], "Values": [300.0] },
+            ]
+        });
+        let ctx = ParseContext {
+            provider: "bedrock",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Hour),
+        };
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("bedrock/cloudwatch-v1"));
+        assert_eq!(fetch.rows.len(), 1);
+        let row = &fetch.rows[0];
+        assert_eq!((row.input_tokens, row.output_tokens), (1200, 300));
+        assert_eq!(row.timestamp.to_rfc3339(), "2025-05-01T10:00:00+00:00");
+
+        assert_eq!(BedrockAdapter.pricing_provider(model), "anthropic");
+        assert_eq!(
+            BedrockAdapter.pricing_provider("us.anthropic.claude-sonnet-4-20250514-v1:0"),
+            "anthropic"
+        );
+        assert_eq!(
+            BedrockAdapter.pricing_provider("amazon.nova-pro-v1:0"),
+            "bedrock"
+        );
+    }
+
+    #[test]
+    fn cost_explorer_groups_split_by_model_and_direction() {
+        let body = json!({
+            "ResultsByTime": [{
+                "TimePeriod": { "Start": "2025-05-01", "End": "2025-05-02" },
+                "Groups": [
+                    { "Keys": ["Claude 3.5 Sonnet (Amazon Bedrock Edition)", "USE1-MP:USE1_InputTokenCount-Units"],
+                      "Metrics": { "UnblendedCost": { "Amount": "1.5", "Unit": "USD" } } },
+                    { "Keys": ["Claude 3.5 Sonnet (Amazon Bedrock Edition)", "USE1-MP:USE1_OutputTokenCount-Units"],
+                      "Metrics": { "UnblendedCost": { "Amount": "4.5", "Unit": "USD" } } },
+                    { "Keys": ["Amazon Bedrock", "USE1-TitanEmbeddingsG1-Text-input-tokens"],
+                      "Metrics": { "UnblendedCost": { "Amount": "0.02", "Unit": "USD" } } },
+                    { "Keys": ["Amazon Simple Storage Service", "USE1-TimedStorage-ByteHrs"],
+                      "Metrics": { "UnblendedCost": { "Amount": "9", "Unit": "USD" } } },
+                ]
+            }]
+        });
+        let costs = BedrockAdapter::parse_costs(&body, "bedrock");
+        let rows: Vec<_> = costs
+            .iter()
+            .map(|c| (c.model.as_str(), c.input_cost, c.output_cost))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("Claude 3.5 Sonnet", 1.5, 4.5),
+                ("TitanEmbeddingsG1-Text", 0.02, 0.0),
+            ]
+        );
+        assert_eq!(costs[0].source_granularity, Some(Granularity::Day));
+
+        let at = |raw: &str| {
+            DateTime::parse_from_rfc3339(raw)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let days =
+            BedrockAdapter::cost_days(at("2025-05-01T10:00:00Z"), at("2025-05-03T08:00:00Z"));
+        assert_eq!(
+            days.map(|(a, b)| (a.to_string(), b.to_string())),
+            Some(("2025-05-02".into(), "2025-05-04".into()))
+        );
+        assert!(
+            BedrockAdapter::cost_days(at("2025-05-03T01:00:00Z"), at("2025-05-03T08:00:00Z"))
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::AppError;
+use crate::models::{Granularity, UsageRecord, UsageSource};
+use crate::providers::local::{
+    expand_home, log_roots, parse_time, read_json_lines, recent_jsonl_files, vendor_for_model,
+};
+use crate::providers::{CredentialKind, ProviderAdapter, ProviderContext, UsageFetch};
+use async_trait::async_trait;
+use chrono::Duration;
+use reqwest::Client;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Usage of the Codex CLI, read from the `rollout-*.jsonl` session logs it
+/// writes under `$CODEX_HOME/sessions` (default `~/.codex/sessions`).
+pub struct CodexCliAdapter;
+
+impl CodexCliAdapter {
+    fn default_root() -> PathBuf {
+        std::env::var("CODEX_HOME")
+            .ok()
+            .filter(|home| !home.is_empty())
+            .map_or_else(|| expand_home("~/.codex"), PathBuf::from)
+            .join("sessions")
+    }
+
+    /// Usage rows from one session log. `token_count` events carry the
+    /// tokens of the last turn; the model comes from the latest
+    /// `turn_context` (or `session_meta`) line before them, so events ahead
+    /// of any model are skipped.
+    fn parse_session(&self, lines: &[Value]) -> Vec<UsageRecord> {
+        let mut model: Option<String> = None;
+        let mut rows = Vec::new();
+        for line in lines {
+            let payload = line.get("payload").unwrap_or(&Value::Null);
+            match line.get("type").and_then(Value::as_str) {
+                Some("turn_context" | "session_meta") => {
+                    if let Some(m) = payload.get("model").and_then(Value::as_str) {
+                        model = Some(m.to_string());
+                    }
+                }
+                Some("event_msg")
+                    if payload.get("type").and_then(Value::as_str) == Some("token_count") =>
+                {
+                    let (Some(model), Some(usage), Some(timestamp)) = (
+                        model.as_ref(),
+                        payload.pointer("/info/last_token_usage"),
+                        line.get("timestamp").and_then(parse_time),
+                    ) else {
+                        continue;
+                    };
+                    let tokens = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+                    // OpenAI semantics: input includes cached tokens and
+                    // output includes reasoning tokens.
+                    let (input_tokens, output_tokens) =
+                        (tokens("input_tokens"), tokens("output_tokens"));
+                    if input_tokens + output_tokens == 0 {
+                        continue;
+                    }
+                    rows.push(UsageRecord {
+                        provider: self.name().to_string(),
+                        model: model.clone(),
+                        input_tokens,
+                        output_tokens,
+                        cached_tokens: tokens("cached_input_tokens"),
+                        timestamp,
+                        bucket_end: None,
+                        source_granularity: Some(Granularity::Minute),
+                        source: UsageSource::Local,
+                        avg_latency_ms: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+        rows
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for CodexCliAdapter {
+    fn name(&self) -> &'static str {
+        "codex-cli"
+    }
+
+    fn credential_kind(&self) -> CredentialKind {
+        CredentialKind::None
+    }
+
+    fn pricing_provider<'a>(&'a self, model: &str) -> &'a str {
+        vendor_for_model(model).unwrap_or(self.name())
+    }
+
+    async fn fetch_usage(
+        &self,
+        _client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let started = Instant::now();
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let mut roots = log_roots(&ctx.settings.log_paths, &[]);
+        if roots.is_empty() {
+            roots.push(Self::default_root());
+        }
+        let mut rows = Vec::new();
+        for path in recent_jsonl_files(&roots, since) {
+            rows.extend(
+                self.parse_session(&read_json_lines(&path)?)
+                    .into_iter()
+                    .filter(|row| row.timestamp >= since && row.timestamp <= ctx.refresh_end),
+            );
+        }
+        Ok(UsageFetch {
+            rows,
+            pages: 0,
+            http_ms: 0,
+            parse_ms: started.elapsed().as_millis(),
+            ..UsageFetch::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn token_count_events_take_the_model_of_the_latest_turn_context() {
+        let adapter = CodexCliAdapter;
+        let token_count = |at: &str, input: u64, cached: u64, output: u64| {
+            json!({
+                "timestamp": at,
+                "type": "event_msg",
+                "payload": { "type": "token_count", "info": { "last_token_usage": {
+                    "input_tokens": input, "cached_input_tokens": cached,
+                    "output_tokens": output, "reasoning_output_tokens": 10
+                } } }
+            })
+        };
+        let lines = vec![
+            token_count("2025-05-01T10:00:00Z", 50, 0, 5),
+            json!({ "timestamp": "2025-05-01T10:00:01Z", "type": "turn_context",
+                    "payload": { "model": "gpt-5-codex", "cwd": "/tmp" } }),
+            token_count("2025-05-01T10:00:30Z", 1200, 1000, 80),
+            json!({ "timestamp": "2025-05-01T10:01:00Z", "type": "event_msg",
+                    "payload": { "type": "token_count", "info": null } }),
+            json!({ "timestamp": "2025-05-01T10:02:00Z", "type": "turn_context",
+                    "payload": { "model": "o4-mini" } }),
+            token_count("2025-05-01T10:02:30Z", 300, 0, 40),
+        ];
+        let rows = adapter.parse_session(&lines);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].model, "gpt-5-codex");
+        assert_eq!(
+            (
+                rows[0].input_tokens,
+                rows[0].cached_tokens,
+                rows[0].output_tokens
+            ),
+            (1200, 1000, 80)
+        );
+        assert_eq!(rows[1].model, "o4-mini");
+        assert_eq!(adapter.pricing_provider(&rows[1].model), "openai");
+    }
+}
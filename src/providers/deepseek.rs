@@ -0,0 +1,317 @@
+use crate::error::AppError;
+use crate::models::{AccountBalance, Granularity, UsageRecord, UsageSource};
+use crate::providers::{ParseContext, ProviderAdapter, ProviderContext, UsageFetch, UsageSchema};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Instant;
+
+const API_ROOT: &str = "https://api.deepseek.com";
+const PLATFORM_ROOT: &str = "https://platform.deepseek.com";
+
+/// DeepSeek usage from the platform's monthly usage endpoint, which reports
+/// tokens per model and UTC day, and the remaining credit from the public
+/// `/user/balance` API. Costs are priced from the token counts.
+pub struct DeepSeekAdapter;
+
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[UsageSchema {
+    version: "deepseek/usage-amount-v0",
+    detect: DeepSeekAdapter::has_days,
+    parse: DeepSeekAdapter::parse_days,
+}];
+
+impl DeepSeekAdapter {
+    fn usage_endpoint(root: &str, year: i32, month: u32) -> String {
+        format!(
+            "{}/api/v0/usage/amount?month={month}&year={year}",
+            root.trim_end_matches('/')
+        )
+    }
+
+    fn balance_endpoint(root: &str) -> String {
+        format!("{}/user/balance", root.trim_end_matches('/'))
+    }
+
+    /// `(year, month)` of every calendar month overlapping `[since, end]`.
+    fn months(since: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(i32, u32)> {
+        let mut months = Vec::new();
+        let (mut year, mut month) = (since.year(), since.month());
+        while (year, month) <= (end.year(), end.month()) {
+            months.push((year, month));
+            (year, month) = if month == 12 {
+                (year + 1, 1)
+            } else {
+                (year, month + 1)
+            };
+        }
+        months
+    }
+
+    fn days(body: &Value) -> &[Value] {
+        body.pointer("/data/biz_data/days")
+            .and_then(Value::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn has_days(body: &Value) -> bool {
+        body.pointer("/data/biz_data/days")
+            .is_some_and(Value::is_array)
+    }
+
+    /// Amounts come as decimal strings, or as numbers from some proxies.
+    fn amount(value: &Value) -> Option<f64> {
+        value
+            .as_f64()
+            .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+    }
+
+    /// One row per model and day. Cache hits and misses are both input;
+    /// the hits are also counted as cached.
+    fn parse_days(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        let mut rows = Vec::new();
+        for day in Self::days(body) {
+            let Some(start) = day
+                .get("date")
+                .and_then(Value::as_str)
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|start| start.and_utc())
+            else {
+                continue;
+            };
+            for item in day
+                .get("data")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let Some(model) = item.get("model").and_then(Value::as_str) else {
+                    continue;
+                };
+                let (mut hit, mut miss, mut output) = (0, 0, 0);
+                for usage in item
+                    .get("usage")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    let tokens = usage.get("amount").and_then(Self::amount).unwrap_or(0.0) as u64;
+                    match usage.get("type").and_then(Value::as_str) {
+                        Some("PROMPT_CACHE_HIT_TOKEN") => hit += tokens,
+                        Some("PROMPT_CACHE_MISS_TOKEN") => miss += tokens,
+                        Some("RESPONSE_TOKEN") => output += tokens,
+                        _ => {}
+                    }
+                }
+                if hit + miss + output == 0 {
+                    continue;
+                }
+                rows.push(UsageRecord {
+                    provider: ctx.provider.to_string(),
+                    model: model.to_string(),
+                    input_tokens: hit + miss,
+                    output_tokens: output,
+                    cached_tokens: hit,
+                    timestamp: start,
+                    bucket_end: Some(start + Duration::days(1)),
+                    source_granularity: Some(Granularity::Day),
+                    avg_latency_ms: None,
+                    source: UsageSource::Api,
+                });
+            }
+        }
+        rows
+    }
+
+    /// The first balance the account reports; accounts hold one currency.
+    fn parse_balance(body: &Value, provider: &str) -> Option<AccountBalance> {
+        let info = body.get("balance_infos")?.as_array()?.first()?;
+        Some(AccountBalance {
+            provider: provider.to_string(),
+            amount: info.get("total_balance").and_then(Self::amount)?,
+            currency: info.get("currency")?.as_str()?.to_ascii_uppercase(),
+            fetched_at: Utc::now(),
+        })
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for DeepSeekAdapter {
+    fn name(&self) -> &'static str {
+        "deepseek"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["api.deepseek.com", "platform.deepseek.com"]
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let root = ctx.settings.base_url.as_deref().unwrap_or(PLATFORM_ROOT);
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let parse_ctx = ParseContext {
+            provider: self.name(),
+            fallback: ctx.refresh_end,
+            granularity: Some(Granularity::Day),
+        };
+
+        let mut fetch = UsageFetch::default();
+        for (year, month) in Self::months(since, ctx.refresh_end) {
+            let permit = ctx.acquire().await?;
+            let started = Instant::now();
+            let response = client
+                .get(Self::usage_endpoint(root, year, month))
+                .bearer_auth(ctx.api_key())
+                .send()
+                .await?;
+            fetch.note_server_date(&response);
+            let bytes = response.error_for_status()?.bytes().await?;
+            fetch.http_ms += started.elapsed().as_millis();
+            fetch.pages += 1;
+            drop(permit);
+
+            let started = Instant::now();
+            let body: Value = serde_json::from_slice(&bytes)?;
+            fetch.parse_page(USAGE_SCHEMAS, &body, &parse_ctx);
+            fetch.parse_ms += started.elapsed().as_millis();
+        }
+        // Months are fetched whole, so days ending before the window are dropped.
+        fetch
+            .rows
+            .retain(|row| row.bucket_end.is_some_and(|end| end > since));
+        Ok(fetch)
+    }
+
+    async fn fetch_balance(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<AccountBalance>, AppError> {
+        let url = Self::balance_endpoint(ctx.settings.base_url.as_deref().unwrap_or(API_ROOT));
+        let _permit = ctx.acquire().await?;
+        let body: Value = client
+            .get(url)
+            .bearer_auth(ctx.api_key())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(Self::parse_balance(&body, self.name()))
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let url = Self::balance_endpoint(ctx.settings.base_url.as_deref().unwrap_or(API_ROOT));
+
+        let _permit = ctx.acquire().await?;
+        let response = client.get(url).bearer_auth(ctx.api_key()).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(AppError::Config(
+                "DeepSeek rejected the API key (unauthorized).".into(),
+            ));
+        }
+
+        Err(AppError::Config(format!(
+            "DeepSeek connection failed with HTTP status {}.",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn usage_days_and_balance_parse_from_string_amounts() {
+        let usage = |kind: &str, amount: &str| json!({ "type": kind, "amount": amount });
+        let body = json!({
+            "code": 0,
+            "data": { "biz_code": 0, "biz_data": { "days": [
+                { "date": "2025-08-24", "data": [
+                    { "model": "deepseek-chat", "usage": [
+                        usage("REQUEST", "12"),
+                        usage("PROMPT_CACHE_HIT_TOKEN", "4000"),
+                        usage("PROMPT_CACHE_MISS_TOKEN", "1000"),
+                        usage("RESPONSE_TOKEN", "700"),
+                    ]},
+                    { "model": "deepseek-reasoner", "usage": [usage("REQUEST", "0")] },
+                ]},
+                { "date": "2025-08-25", "data": [
+                    { "model": "deepseek-reasoner", "usage": [
+                        { "type": "RESPONSE_TOKEN", "amount": 50 },
+                    ]},
+                ]},
+            ]}}
+        });
+
+        let mut fetch = UsageFetch::default();
+        let ctx = ParseContext {
+            provider: "deepseek",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Day),
+        };
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("deepseek/usage-amount-v0"));
+        let rows: Vec<(&str, u64, u64, u64)> = fetch
+            .rows
+            .iter()
+            .map(|r| {
+                (
+                    r.model.as_str(),
+                    r.input_tokens,
+                    r.cached_tokens,
+                    r.output_tokens,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("deepseek-chat", 5000, 4000, 700),
+                ("deepseek-reasoner", 0, 0, 50),
+            ]
+        );
+        assert_eq!(
+            fetch.rows[1].bucket_end.map(|end| end.to_rfc3339()),
+            Some("2025-08-26T00:00:00+00:00".to_string())
+        );
+
+        let balance = DeepSeekAdapter::parse_balance(
+            &json!({
+                "is_available": true,
+                "balance_infos": [{
+                    "currency": "CNY",
+                    "total_balance": "110.00",
+                    "granted_balance": "10.00",
+                    "topped_up_balance": "100.00"
+                }]
+            }),
+            "deepseek",
+        )
+        .expect("balance");
+        assert_eq!((balance.amount, balance.currency.as_str()), (110.0, "CNY"));
+
+        let since = "2025-11-20T00:00:00Z".parse().unwrap();
+        let end = "2026-02-03T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            DeepSeekAdapter::months(since, end),
+            vec![(2025, 11), (2025, 12), (2026, 1), (2026, 2)]
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use crate::error::AppError;
+use crate::models::{Granularity, UsageRecord, UsageSource};
+use crate::providers::{
+    bucket_width, ParseContext, ProviderAdapter, ProviderContext, UsageFetch, UsageSchema,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Safety stop for `nextPageToken` pagination.
+const MAX_PAGES: u32 = 50;
+
+const MONITORING_ROOT: &str = "https://monitoring.googleapis.com";
+
+/// Cloud Monitoring metric Vertex AI publishes per model and token type.
+const TOKEN_METRIC: &str = "aiplatform.googleapis.com/publisher/online_serving/token_count";
+
+/// Gemini usage on Vertex AI, read from the token counts Vertex AI publishes
+/// to Cloud Monitoring for the account's Google Cloud `project_id`. The key
+/// is an OAuth access token with the `monitoring.read` scope.
+pub struct GeminiAdapter;
+
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[UsageSchema {
+    version: "gemini/timeseries-v3",
+    detect: GeminiAdapter::has_series,
+    parse: GeminiAdapter::parse_series,
+}];
+
+impl GeminiAdapter {
+    fn project(ctx: &ProviderContext) -> Result<&str, AppError> {
+        ctx.settings
+            .project_id
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .ok_or_else(|| {
+                AppError::Config(
+                    "gemini needs project_id, the Google Cloud project to read usage from.".into(),
+                )
+            })
+    }
+
+    /// Token counts summed per model and token type over `width` buckets
+    /// from `start` to `end`.
+    fn usage_endpoint(
+        root: &str,
+        project: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        width: Granularity,
+    ) -> Result<String, AppError> {
+        let mut url = url::Url::parse(&format!(
+            "{}/v3/projects/{project}/timeSeries",
+            root.trim_end_matches('/')
+        ))
+        .map_err(|e| AppError::Config(format!("Invalid gemini base_url: {e}")))?;
+        url.query_pairs_mut()
+            .append_pair("filter", &format!("metric.type = \"{TOKEN_METRIC}\""))
+            .append_pair("interval.startTime", &start.to_rfc3339())
+            .append_pair("interval.endTime", &end.to_rfc3339())
+            .append_pair(
+                "aggregation.alignmentPeriod",
+                &format!("{}s", width.duration().num_seconds()),
+            )
+            .append_pair("aggregation.perSeriesAligner", "ALIGN_SUM")
+            .append_pair("aggregation.crossSeriesReducer", "REDUCE_SUM")
+            .append_pair("aggregation.groupByFields", "resource.label.model_user_id")
+            .append_pair("aggregation.groupByFields", "metric.label.type");
+        Ok(url.to_string())
+    }
+
+    fn next_page(body: &Value) -> Option<String> {
+        body.get("nextPageToken")
+            .and_then(Value::as_str)
+            .filter(|token| !token.is_empty())
+            .map(str::to_string)
+    }
+
+    fn has_series(body: &Value) -> bool {
+        body.get("timeSeries").is_some()
+    }
+
+    /// One row per model and bucket, merging the `input` and `output` series.
+    /// Points of other token types are skipped.
+    fn parse_series(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        let width = ctx.granularity.unwrap_or(Granularity::Hour);
+        let mut rows: BTreeMap<(String, DateTime<Utc>), UsageRecord> = BTreeMap::new();
+        let series = body
+            .get("timeSeries")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for series in series {
+            let Some(model) = series
+                .pointer("/resource/labels/model_user_id")
+                .and_then(Value::as_str)
+            else {
+                continue;
+            };
+            let kind = series
+                .pointer("/metric/labels/type")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if !matches!(kind, "input" | "output") {
+                continue;
+            }
+            let points = series
+                .get("points")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for point in points {
+                let Some(end) = point
+                    .pointer("/interval/endTime")
+                    .and_then(Value::as_str)
+                    .and_then(parse_rfc3339)
+                else {
+                    continue;
+                };
+                let start = point
+                    .pointer("/interval/startTime")
+                    .and_then(Value::as_str)
+                    .and_then(parse_rfc3339)
+                    .filter(|start| *start < end)
+                    .unwrap_or(end - width.duration());
+                // int64 values arrive as JSON strings.
+                let tokens = point
+                    .pointer("/value/int64Value")
+                    .and_then(|v| v.as_str().and_then(|s| s.parse().ok()).or(v.as_u64()))
+                    .unwrap_or(0);
+                let row = rows
+                    .entry((model.to_string(), start))
+                    .or_insert_with(|| UsageRecord {
+                        provider: ctx.provider.to_string(),
+                        model: model.to_string(),
+                        input_tokens: 0,
+                        output_tokens: 0,
+                        cached_tokens: 0,
+                        timestamp: start,
+                        bucket_end: Some(end),
+                        source_granularity: Some(width),
+                        avg_latency_ms: None,
+                        source: UsageSource::Api,
+                    });
+                if kind == "input" {
+                    row.input_tokens += tokens;
+                } else {
+                    row.output_tokens += tokens;
+                }
+            }
+        }
+        rows.into_values()
+            .filter(|row| row.input_tokens + row.output_tokens > 0)
+            .collect()
+    }
+
+    fn with_page(url: &str, page: &str) -> Result<String, AppError> {
+        let mut parsed = url::Url::parse(url)
+            .map_err(|e| AppError::Config(format!("Invalid gemini usage URL: {e}")))?;
+        let kept: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(k, _)| k != "pageToken")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .append_pair("pageToken", page);
+        Ok(parsed.to_string())
+    }
+}
+
+fn parse_rfc3339(raw: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(raw)
+        .ok()
+        .map(|parsed| parsed.with_timezone(&Utc))
+}
+
+#[async_trait]
+impl ProviderAdapter for GeminiAdapter {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["monitoring.googleapis.com"]
+    }
+
+    /// Cloud Monitoring keeps these metrics for six weeks.
+    fn max_history_days(&self) -> u32 {
+        42
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let width = bucket_width(
+            "gemini",
+            ctx.settings.bucket_width.as_deref(),
+            ctx.resolution_hours,
+        )?;
+        let first_url = Self::usage_endpoint(
+            ctx.settings.base_url.as_deref().unwrap_or(MONITORING_ROOT),
+            Self::project(ctx)?,
+            ctx.refresh_end - Duration::hours(ctx.lookback_hours),
+            ctx.refresh_end,
+            width,
+        )?;
+
+        let mut fetch = UsageFetch::default();
+        let mut url = first_url.clone();
+        loop {
+            let permit = ctx.acquire().await?;
+            let started = Instant::now();
+            let response = client.get(&url).bearer_auth(ctx.api_key()).send().await?;
+            fetch.note_server_date(&response);
+            let bytes = response.error_for_status()?.bytes().await?;
+            fetch.http_ms += started.elapsed().as_millis();
+            fetch.pages += 1;
+            drop(permit);
+
+            let started = Instant::now();
+            let body: Value = serde_json::from_slice(&bytes)?;
+            fetch.parse_page(
+                USAGE_SCHEMAS,
+                &body,
+                &ParseContext {
+                    provider: self.name(),
+                    fallback: ctx.refresh_end,
+                    granularity: Some(width),
+                },
+            );
+            fetch.parse_ms += started.elapsed().as_millis();
+
+            match Self::next_page(&body) {
+                Some(page) if fetch.pages < MAX_PAGES => {
+                    url = Self::with_page(&first_url, &page)?;
+                }
+                _ => break,
+            }
+        }
+        Ok(fetch)
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let url = Self::usage_endpoint(
+            ctx.settings.base_url.as_deref().unwrap_or(MONITORING_ROOT),
+            Self::project(ctx)?,
+            ctx.refresh_end - Duration::hours(1),
+            ctx.refresh_end,
+            Granularity::Hour,
+        )?;
+
+        let _permit = ctx.acquire().await?;
+        let response = client.get(url).bearer_auth(ctx.api_key()).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(AppError::Config(
+                "Google Cloud rejected the access token (unauthorized).".into(),
+            ));
+        }
+
+        Err(AppError::Config(format!(
+            "Cloud Monitoring connection failed with HTTP status {}.",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn input_and_output_series_merge_into_one_row_per_bucket() {
+        let series = |kind: &str, points: Value| {
+            json!({
+                "metric": { "type": TOKEN_METRIC, "labels": { "type": kind } },
+                "resource": { "type": "aiplatform.googleapis.com/PublisherModel",
+                              "labels": { "model_user_id": "gemini-2.0-flash-001" } },
+                "points": points
+            })
+        };
+        let point = |start: &str, end: &str, tokens: &str| {
+            json!({ "interval": { "startTime": start, "endTime": end },
+                    "value": { "int64Value": tokens } })
+        };
+        let body = json!({
+            "timeSeries": [
+                series("input", json!([
+                    point("2025-05-01T11:00:00Z", "2025-05-01T12:00:00Z", "1500"),
+                    point("2025-05-01T10:00:00Z", "2025-05-01T11:00:00Z", "900"),
+                ])),
+                series("output", json!([
+                    point("2025-05-01T11:00:00Z", "2025-05-01T12:00:00Z", "300"),
+                ])),
+                series("cached", json!([
+                    point("2025-05-01T11:00:00Z", "2025-05-01T12:00:00Z", "50"),
+                ])),
+            ],
+            "nextPageToken": ""
+        });
+
+        let mut fetch = UsageFetch::default();
+        let ctx = ParseContext {
+            provider: "gemini",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Hour),
+        };
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("gemini/timeseries-v3"));
+        let rows = fetch.rows;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].timestamp.to_rfc3339(), "2025-05-01T10:00:00+00:00");
+        assert_eq!((rows[0].input_tokens, rows[0].output_tokens), (900, 0));
+        assert_eq!((rows[1].input_tokens, rows[1].output_tokens), (1500, 300));
+        assert_eq!(
+            rows[1].bucket_end.map(|end| end.to_rfc3339()),
+            Some("2025-05-01T12:00:00+00:00".to_string())
+        );
+        assert_eq!(GeminiAdapter::next_page(&body), None);
+    }
+
+    #[test]
+    fn usage_endpoint_groups_by_model_and_token_type() {
+        let end = DateTime::parse_from_rfc3339("2025-05-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let url = GeminiAdapter::usage_endpoint(
+            MONITORING_ROOT,
+            "my-project",
+            end - Duration::days(1),
+            end,
+            Granularity::Hour,
+        )
+        .expect("url");
+        assert!(url.starts_with(
+            "https://monitoring.googleapis.com/v3/projects/my-project/timeSeries?filter="
+        ));
+        assert!(url.contains("aggregation.alignmentPeriod=3600s"));
+        assert!(url.contains("aggregation.groupByFields=resource.label.model_user_id"));
+        assert!(url.contains("aggregation.groupByFields=metric.label.type"));
+
+        let paged = GeminiAdapter::with_page(&url, "p2").expect("page");
+        assert!(paged.ends_with("&pageToken=p2"));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::AppError;
+use chrono::{DateTime, TimeZone, Utc};
+use directories::BaseDirs;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Vendor whose prices apply to `model`, for adapters that read usage of
+/// several vendors from local logs.
+pub fn vendor_for_model(model: &str) -> Option<&'static str> {
+    let model = model.to_ascii_lowercase();
+    if model.starts_with("claude") {
+        Some("anthropic")
+    } else if [
+        "gpt",
+        "chatgpt",
+        "o1",
+        "o3",
+        "o4",
+        "text-embedding",
+        "codex",
+    ]
+    .iter()
+    .any(|prefix| model.starts_with(prefix))
+    {
+        Some("openai")
+    } else {
+        None
+    }
+}
+
+/// `path` with a leading `~/` replaced by the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Expanded `log_paths` from an account's settings, or `defaults` when none
+/// are configured.
+pub fn log_roots(log_paths: &[String], defaults: &[&str]) -> Vec<PathBuf> {
+    if log_paths.is_empty() {
+        defaults.iter().map(|p| expand_home(p)).collect()
+    } else {
+        log_paths.iter().map(|p| expand_home(p)).collect()
+    }
+}
+
+/// Modification time of `path`, or `fallback` when it cannot be read.
+pub fn modified_or(path: &Path, fallback: DateTime<Utc>) -> DateTime<Utc> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_or(fallback, DateTime::<Utc>::from)
+}
+
+/// `.jsonl` files under `roots` (searched recursively; a root may also be a
+/// file) modified at or after `since`, so refreshes only reread logs that
+/// can hold new lines. Missing roots are skipped.
+pub fn recent_jsonl_files(roots: &[PathBuf], since: DateTime<Utc>) -> Vec<PathBuf> {
+    fn walk(path: &Path, since: DateTime<Utc>, out: &mut Vec<PathBuf>) {
+        let Ok(meta) = fs::metadata(path) else {
+            return;
+        };
+        if meta.is_dir() {
+            let Ok(entries) = fs::read_dir(path) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                walk(&entry.path(), since, out);
+            }
+        } else if path.extension().is_some_and(|e| e == "jsonl")
+            && meta
+                .modified()
+                .is_ok_and(|m| DateTime::<Utc>::from(m) >= since)
+        {
+            out.push(path.to_path_buf());
+        }
+    }
+    let mut out = Vec::new();
+    for root in roots {
+        walk(root, since, &mut out);
+    }
+    out.sort();
+    out
+}
+
+/// Parsed JSON lines of `path`; lines that are not JSON (partial writes,
+/// banners) are skipped.
+pub fn read_json_lines(path: &Path) -> Result<Vec<Value>, AppError> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// RFC 3339 strings or epoch seconds (milliseconds when too large for seconds).
+pub fn parse_time(value: &Value) -> Option<DateTime<Utc>> {
+    if let Some(raw) = value.as_str() {
+        return DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .map(|t| t.with_timezone(&Utc));
+    }
+    let secs = value.as_f64()?;
+    if secs > 1e11 {
+        Utc.timestamp_millis_opt(secs as i64).single()
+    } else {
+        Utc.timestamp_opt(secs as i64, 0).single()
+    }
+}
+
+/// The first of `paths` (each a list of object keys) present in `value`.
+pub fn first_at<'a>(value: &'a Value, paths: &[&[&str]]) -> Option<&'a Value> {
+    paths.iter().find_map(|path| {
+        path.iter()
+            .try_fold(value, |v, key| v.get(key))
+            .filter(|v| !v.is_null())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn vendors_times_and_nested_lookups() {
+        assert_eq!(vendor_for_model("claude-sonnet-4-5"), Some("anthropic"));
+        assert_eq!(vendor_for_model("gpt-4o-mini"), Some("openai"));
+        assert_eq!(vendor_for_model("o3-mini"), Some("openai"));
+        assert_eq!(vendor_for_model("llama3"), None);
+
+        let at = Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap();
+        assert_eq!(parse_time(&json!("2024-06-01T10:00:00Z")), Some(at));
+        assert_eq!(parse_time(&json!(1_717_236_000)), Some(at));
+        assert_eq!(parse_time(&json!(1_717_236_000_000_i64)), Some(at));
+
+        let value = json!({ "response": { "usage": { "input_tokens": 3 } } });
+        assert_eq!(
+            first_at(&value, &[&["usage"], &["response", "usage"]])
+                .and_then(|u| u.get("input_tokens")),
+            Some(&json!(3))
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::config::data_dir;
+use crate::error::AppError;
+use crate::models::{Granularity, UsageRecord, UsageSource};
+use crate::providers::local::{
+    expand_home, first_at, modified_or, parse_time, read_json_lines, recent_jsonl_files,
+    vendor_for_model,
+};
+use crate::providers::{CredentialKind, ProviderAdapter, ProviderContext, UsageFetch};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Usage of ad-hoc scripts on this machine, read from JSON-lines logs rather
+/// than an organization's usage API.
+pub struct LocalSessionAdapter;
+
+impl LocalSessionAdapter {
+    /// `log_paths` from the account's settings, plus the `sessions` directory
+    /// in the data directory, where scripts can append their responses.
+    fn roots(log_paths: &[String]) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = log_paths.iter().map(|p| expand_home(p)).collect();
+        if let Ok(dir) = data_dir() {
+            roots.push(dir.join("sessions"));
+        }
+        roots
+    }
+
+    /// One usage row from a logged response: an OpenAI or Anthropic SDK
+    /// response object (optionally under `response`), or a LangChain
+    /// `on_llm_end` payload with `llm_output.token_usage`. Lines without a
+    /// model or tokens yield nothing.
+    fn parse_line(&self, line: &Value, fallback: DateTime<Utc>) -> Option<UsageRecord> {
+        let model = first_at(
+            line,
+            &[
+                &["model"],
+                &["response", "model"],
+                &["llm_output", "model_name"],
+                &["response", "llm_output", "model_name"],
+            ],
+        )?
+        .as_str()?
+        .to_string();
+        let usage = first_at(
+            line,
+            &[
+                &["usage"],
+                &["response", "usage"],
+                &["llm_output", "token_usage"],
+                &["response", "llm_output", "token_usage"],
+            ],
+        )?;
+        let tokens = |paths: &[&[&str]]| first_at(usage, paths).and_then(Value::as_u64);
+        let input_tokens = tokens(&[&["input_tokens"], &["prompt_tokens"]]).unwrap_or(0);
+        let output_tokens = tokens(&[&["output_tokens"], &["completion_tokens"]]).unwrap_or(0);
+        let cached_tokens = tokens(&[
+            &["cache_read_input_tokens"],
+            &["prompt_tokens_details", "cached_tokens"],
+            &["input_tokens_details", "cached_tokens"],
+        ])
+        .unwrap_or(0);
+        if input_tokens + output_tokens == 0 {
+            return None;
+        }
+        let timestamp = first_at(
+            line,
+            &[
+                &["timestamp"],
+                &["created_at"],
+                &["created"],
+                &["response", "created"],
+            ],
+        )
+        .and_then(parse_time)
+        .unwrap_or(fallback);
+        Some(UsageRecord {
+            provider: self.name().to_string(),
+            model,
+            input_tokens,
+            output_tokens,
+            cached_tokens,
+            timestamp,
+            bucket_end: None,
+            source_granularity: Some(Granularity::Minute),
+            source: UsageSource::Local,
+            avg_latency_ms: None,
+        })
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for LocalSessionAdapter {
+    fn name(&self) -> &'static str {
+        "local-session"
+    }
+
+    fn credential_kind(&self) -> CredentialKind {
+        CredentialKind::None
+    }
+
+    fn pricing_provider<'a>(&'a self, model: &str) -> &'a str {
+        vendor_for_model(model).unwrap_or(self.name())
+    }
+
+    async fn fetch_usage(
+        &self,
+        _client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let started = Instant::now();
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let mut rows = Vec::new();
+        for path in recent_jsonl_files(&Self::roots(&ctx.settings.log_paths), since) {
+            // Lines without a time of their own fall back to the file's.
+            let modified = modified_or(&path, ctx.refresh_end);
+            rows.extend(
+                read_json_lines(&path)?
+                    .iter()
+                    .filter_map(|line| self.parse_line(line, modified))
+                    .filter(|row| row.timestamp >= since && row.timestamp <= ctx.refresh_end),
+            );
+        }
+        Ok(UsageFetch {
+            rows,
+            pages: 0,
+            http_ms: 0,
+            parse_ms: started.elapsed().as_millis(),
+            ..UsageFetch::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    #[test]
+    fn parses_sdk_responses_and_langchain_callbacks() {
+        let adapter = LocalSessionAdapter;
+        let fallback = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+
+        let openai = json!({
+            "model": "gpt-4o-mini",
+            "created": 1_717_236_000,
+            "usage": { "prompt_tokens": 120, "completion_tokens": 30,
+                       "prompt_tokens_details": { "cached_tokens": 100 } }
+        });
+        let row = adapter.parse_line(&openai, fallback).expect("openai row");
+        assert_eq!(
+            (row.input_tokens, row.output_tokens, row.cached_tokens),
+            (120, 30, 100)
+        );
+        assert_eq!(
+            row.timestamp,
+            Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap()
+        );
+        assert_eq!(adapter.pricing_provider(&row.model), "openai");
+
+        let anthropic = json!({ "response": {
+            "model": "claude-3-5-haiku-latest",
+            "usage": { "input_tokens": 10, "output_tokens": 5, "cache_read_input_tokens": 7 }
+        } });
+        let row = adapter
+            .parse_line(&anthropic, fallback)
+            .expect("anthropic row");
+        assert_eq!((row.cached_tokens, row.timestamp), (7, fallback));
+        assert_eq!(adapter.pricing_provider(&row.model), "anthropic");
+
+        let langchain = json!({
+            "timestamp": "2024-06-01T11:00:00Z",
+            "llm_output": { "model_name": "gpt-4o",
+                            "token_usage": { "prompt_tokens": 8, "completion_tokens": 2 } }
+        });
+        let row = adapter
+            .parse_line(&langchain, fallback)
+            .expect("langchain row");
+        assert_eq!((row.model.as_str(), row.input_tokens), ("gpt-4o", 8));
+
+        assert!(adapter
+            .parse_line(&json!({ "model": "gpt-4o", "usage": {} }), fallback)
+            .is_none());
+    }
+}
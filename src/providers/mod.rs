@@ -1,31 +1,293 @@
+use crate::aws::AwsCredentials;
 use crate::config::ProviderSettings;
 use crate::error::AppError;
-use crate::models::{CostRecord, TimeWindow, UsageRecord};
+use crate::models::{AccountBalance, CostRecord, Granularity, RateLimit, UsageRecord};
 use crate::pricing::resolve_pricing;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use reqwest::Client;
+use serde_json::Value;
+use std::sync::Arc;
 
 pub mod anthropic;
+pub mod azure_openai;
+pub mod bedrock;
+pub mod claude_code;
+pub mod codex_cli;
+pub mod deepseek;
+pub mod gemini;
+pub mod local;
+pub mod local_session;
+pub mod ollama;
 pub mod openai;
+pub mod openai_compatible;
+pub mod openrouter;
+pub mod throttle;
+
+pub use throttle::{RequestBudget, Throttle, ThrottlePermit};
+
+/// Rows parsed from a provider's usage endpoint, plus how long it took.
+#[derive(Debug, Clone, Default)]
+pub struct UsageFetch {
+    pub rows: Vec<UsageRecord>,
+    pub pages: u32,
+    pub http_ms: u128,
+    pub parse_ms: u128,
+    /// [`UsageSchema::version`] that parsed the first page, if the adapter
+    /// parses through [`UsageFetch::parse_page`].
+    pub parser_version: Option<&'static str>,
+    /// Whether a page only parsed with a schema its detection did not pick.
+    pub parser_fallback: bool,
+    /// Costs the usage payload itself reports, e.g. what OpenRouter charged.
+    /// When set, they are stored as they are instead of pricing the rows.
+    pub costs: Option<Vec<CostRecord>>,
+    /// How far the local clock was ahead of the provider's (negative when
+    /// behind), from the `Date` header of the last usage response.
+    pub clock_skew: Option<Duration>,
+}
+
+impl UsageFetch {
+    /// Notes the clock skew against the server that sent `response`.
+    pub fn note_server_date(&mut self, response: &reqwest::Response) {
+        if let Some(skew) = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|date| clock_skew(date, Utc::now()))
+        {
+            self.clock_skew = Some(skew);
+        }
+    }
+
+    /// Adds the rows of one page, parsed by the first of `schemas` that yields
+    /// any. Schemas whose `detect` accepts the page are tried first, in order,
+    /// then the rest, so a payload whose shape changed still parses when an
+    /// older or newer parser can read it. A page no schema gets rows from adds
+    /// nothing and is credited to the first detected schema.
+    pub fn parse_page(&mut self, schemas: &[UsageSchema], body: &Value, ctx: &ParseContext) {
+        let (detected, rest): (Vec<&UsageSchema>, Vec<&UsageSchema>) =
+            schemas.iter().partition(|schema| (schema.detect)(body));
+        for (fallback, schema) in detected
+            .iter()
+            .map(|schema| (false, *schema))
+            .chain(rest.iter().map(|schema| (true, *schema)))
+        {
+            let rows = (schema.parse)(body, ctx);
+            if !rows.is_empty() {
+                self.rows.extend(rows);
+                self.parser_version.get_or_insert(schema.version);
+                self.parser_fallback |= fallback;
+                return;
+            }
+        }
+        if let Some(schema) = detected.first() {
+            self.parser_version.get_or_insert(schema.version);
+        }
+    }
+}
+
+/// What a [`UsageSchema`] parser fills in where a payload leaves it out.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseContext {
+    pub provider: &'static str,
+    /// Timestamp for items that carry none.
+    pub fallback: DateTime<Utc>,
+    pub granularity: Option<Granularity>,
+}
+
+/// Configured `bucket_width`, or `1h` for lookbacks up to 7 days and `1d`
+/// beyond, for the metrics APIs that take any of the three widths.
+pub fn bucket_width(
+    provider: &str,
+    configured: Option<&str>,
+    hours: i64,
+) -> Result<Granularity, AppError> {
+    match configured {
+        None if hours <= 24 * 7 => Ok(Granularity::Hour),
+        None => Ok(Granularity::Day),
+        Some(raw) => Granularity::parse(raw).ok_or_else(|| {
+            AppError::Config(format!(
+                "Unsupported {provider} bucket_width '{raw}'. Use 1m, 1h, or 1d."
+            ))
+        }),
+    }
+}
+
+/// One known shape of a provider's usage payload. Adapters list theirs
+/// newest first and parse pages with [`UsageFetch::parse_page`].
+pub struct UsageSchema {
+    /// Recorded in `refresh_log` for each refresh, as `<provider>/<shape>-v<n>`.
+    pub version: &'static str,
+    /// Whether a page carries this shape's markers. Parsing decides whether
+    /// it really is, so detection can stay a cheap look at the first item.
+    pub detect: fn(&Value) -> bool,
+    /// Rows of the page; items missing the token fields this shape reads
+    /// are skipped rather than stored as zeros.
+    pub parse: fn(&Value, &ParseContext) -> Vec<UsageRecord>,
+}
+
+/// `now` minus the time in an HTTP `Date` header, e.g.
+/// `Tue, 15 Oct 2024 08:12:31 GMT`. The header has whole seconds, so skews
+/// under a second or two are noise.
+pub fn clock_skew(http_date: &str, now: DateTime<Utc>) -> Option<Duration> {
+    DateTime::parse_from_rfc2822(http_date.trim())
+        .ok()
+        .map(|server| now - server.with_timezone(&Utc))
+}
+
+/// The first of `names` present on `item` as a token count, for fields
+/// providers have renamed between payload versions.
+pub fn token_field(item: &Value, names: &[&str]) -> Option<u64> {
+    names
+        .iter()
+        .find_map(|name| item.get(*name).and_then(Value::as_u64))
+}
+
+/// The items of a page's `data` array.
+pub fn data_items(body: &Value) -> &[Value] {
+    body.get("data")
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Mean request latency of a usage item, in milliseconds. Neither vendor's
+/// usage API reports it today; compatible endpoints behind `base_url` may.
+pub fn item_avg_latency_ms(item: &Value) -> Option<f64> {
+    item.get("avg_latency_ms")
+        .and_then(Value::as_f64)
+        .filter(|ms| ms.is_finite() && *ms >= 0.0)
+}
+
+/// How an adapter authenticates its requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialKind {
+    /// A key from the keyring or the account's `api_key_env`.
+    ApiKey,
+    /// AWS access keys from `aws_profile` or the environment, for SigV4.
+    Aws,
+    /// Local files need no credentials.
+    None,
+}
+
+/// Credentials resolved for one account, matching its adapter's
+/// [`CredentialKind`].
+#[derive(Debug, Clone, Default)]
+pub enum Credentials {
+    #[default]
+    None,
+    ApiKey(String),
+    Aws(AwsCredentials),
+}
 
 #[derive(Debug, Clone)]
 pub struct ProviderContext {
-    pub api_key: String,
+    pub credentials: Credentials,
     pub settings: ProviderSettings,
-    pub window: TimeWindow,
+    /// How far back from `refresh_end` to request usage.
+    pub lookback_hours: i64,
+    /// Shortest window the rows must resolve, which picks the default bucket
+    /// width; equals `lookback_hours` except for all-window refreshes.
+    pub resolution_hours: i64,
     pub refresh_end: DateTime<Utc>,
+    /// Shared limits for this account's requests; see [`throttle::for_account`].
+    pub throttle: Arc<Throttle>,
+    /// Requests left to the refresh or backfill this fetch is part of.
+    pub requests: Arc<RequestBudget>,
+}
+
+impl ProviderContext {
+    /// The account's API key; empty for adapters that use none.
+    pub fn api_key(&self) -> &str {
+        match &self.credentials {
+            Credentials::ApiKey(key) => key,
+            _ => "",
+        }
+    }
+
+    /// Counts a request against [`ProviderContext::requests`], then waits
+    /// for the account's throttle. Fails with [`AppError::RequestCap`] once
+    /// the budget is spent, so the operation stops instead of sending more.
+    pub async fn acquire(&self) -> Result<ThrottlePermit<'_>, AppError> {
+        if !self.requests.take() {
+            return Err(AppError::RequestCap(self.requests.limit().unwrap_or(0)));
+        }
+        Ok(self.throttle.acquire().await)
+    }
 }
 
 #[async_trait]
 pub trait ProviderAdapter {
     fn name(&self) -> &'static str;
 
+    /// Hosts the adapter calls when no `base_url` is set, allowed in
+    /// `local_only` mode. A `*` label matches any one label.
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Oldest usage, in days, a backfill asks this provider for.
+    fn max_history_days(&self) -> u32 {
+        90
+    }
+
+    /// What fetching authenticates with.
+    fn credential_kind(&self) -> CredentialKind {
+        CredentialKind::ApiKey
+    }
+
+    /// Whether fetching needs an API key; adapters reading local files or
+    /// signing with AWS credentials do not.
+    fn needs_api_key(&self) -> bool {
+        self.credential_kind() == CredentialKind::ApiKey
+    }
+
+    /// Whether accounts of this adapter only work with a `base_url`, having
+    /// no host of their own.
+    fn requires_base_url(&self) -> bool {
+        false
+    }
+
+    /// Provider whose prices apply to `model`.
+    fn pricing_provider<'a>(&'a self, _model: &str) -> &'a str {
+        self.name()
+    }
+
     async fn fetch_usage(
         &self,
         client: &Client,
         ctx: &ProviderContext,
-    ) -> Result<Vec<UsageRecord>, AppError>;
+    ) -> Result<UsageFetch, AppError>;
+
+    /// Per-model rate limits, where the provider exposes them. `None` when
+    /// this provider, or this account's settings, cannot report limits.
+    async fn fetch_rate_limits(
+        &self,
+        _client: &Client,
+        _ctx: &ProviderContext,
+    ) -> Result<Option<Vec<RateLimit>>, AppError> {
+        Ok(None)
+    }
+
+    /// Remaining prepaid credit, where the provider reports it. `None` when
+    /// this provider has no balance to read.
+    async fn fetch_balance(
+        &self,
+        _client: &Client,
+        _ctx: &ProviderContext,
+    ) -> Result<Option<AccountBalance>, AppError> {
+        Ok(None)
+    }
+
+    /// Costs as the provider bills them, where a separate endpoint reports
+    /// them; not called when [`UsageFetch::costs`] is set. `None` prices the
+    /// fetched usage with [`ProviderAdapter::derive_costs`].
+    async fn fetch_costs(
+        &self,
+        _client: &Client,
+        _ctx: &ProviderContext,
+    ) -> Result<Option<Vec<CostRecord>>, AppError> {
+        Ok(None)
+    }
 
     async fn test_connection(
         &self,
@@ -43,7 +305,8 @@ pub trait ProviderAdapter {
         usage
             .iter()
             .filter_map(|u| {
-                let pricing = resolve_pricing(self.name(), &u.model, overrides)?;
+                let pricing =
+                    resolve_pricing(self.pricing_provider(&u.model), &u.model, overrides)?;
                 let input_cost = (u.input_tokens as f64 / 1_000_000.0) * pricing.input_per_1m;
                 let output_cost = (u.output_tokens as f64 / 1_000_000.0) * pricing.output_per_1m;
                 Some(CostRecord {
@@ -54,8 +317,60 @@ pub trait ProviderAdapter {
                     total_cost: input_cost + output_cost,
                     currency: "USD".into(),
                     timestamp: u.timestamp,
+                    bucket_end: u.bucket_end,
+                    source_granularity: u.source_granularity,
+                    source: u.source,
                 })
             })
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ClockSkew;
+
+    #[test]
+    fn bucket_width_defaults_by_lookback_and_names_the_provider() {
+        assert_eq!(
+            bucket_width("gemini", None, 24 * 7).unwrap(),
+            Granularity::Hour
+        );
+        assert_eq!(
+            bucket_width("gemini", None, 24 * 30).unwrap(),
+            Granularity::Day
+        );
+        assert_eq!(
+            bucket_width("gemini", Some("1m"), 24 * 30).unwrap(),
+            Granularity::Minute
+        );
+        let err = bucket_width("azure-openai", Some("2h"), 24).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unsupported azure-openai bucket_width '2h'"));
+    }
+
+    #[test]
+    fn clock_skew_compares_the_date_header_with_the_local_clock() {
+        let now: DateTime<Utc> = "2024-10-15T08:17:43Z".parse().unwrap();
+        let skew = clock_skew("Tue, 15 Oct 2024 08:12:31 GMT", now).expect("http date");
+        assert_eq!(skew.num_seconds(), 312);
+        assert_eq!(
+            clock_skew("Tue, 15 Oct 2024 09:00:00 GMT", now).map(|s| s.num_seconds()),
+            Some(-2537)
+        );
+        assert!(clock_skew("yesterday", now).is_none());
+
+        let describe = |seconds| {
+            ClockSkew {
+                provider: "openai".into(),
+                seconds,
+            }
+            .describe()
+        };
+        assert_eq!(describe(312), "local clock is 5m 12s ahead of openai");
+        assert_eq!(describe(-2537), "local clock is 42m 17s behind openai");
+        assert_eq!(describe(7300), "local clock is 2h 1m ahead of openai");
+    }
+}
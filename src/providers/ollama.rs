@@ -0,0 +1,214 @@
+use crate::config::{data_dir, PricingOverride};
+use crate::error::AppError;
+use crate::models::{CostRecord, Granularity, UsageRecord, UsageSource};
+use crate::pricing::resolve_pricing;
+use crate::providers::local::{
+    expand_home, first_at, modified_or, parse_time, read_json_lines, recent_jsonl_files,
+};
+use crate::providers::{CredentialKind, ProviderAdapter, ProviderContext, UsageFetch};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const API_ROOT: &str = "http://localhost:11434";
+
+/// Models run on a local Ollama server. Ollama keeps no usage history, so
+/// rows come from JSON-lines logs of its final `/api/generate` and
+/// `/api/chat` responses, which carry the token counts. The server itself
+/// is only asked which models are loaded (`/api/ps`), to test the
+/// connection. Local models cost nothing unless a pricing override says so.
+pub struct OllamaAdapter;
+
+impl OllamaAdapter {
+    /// `log_paths` from the account's settings, plus the `ollama` directory
+    /// in the data directory, where a proxy or client can append responses.
+    fn roots(log_paths: &[String]) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = log_paths.iter().map(|p| expand_home(p)).collect();
+        if let Ok(dir) = data_dir() {
+            roots.push(dir.join("ollama"));
+        }
+        roots
+    }
+
+    /// One usage row from a logged final response. Streamed chunks
+    /// (`"done": false`) and lines without counts yield nothing.
+    fn parse_line(&self, line: &Value, fallback: DateTime<Utc>) -> Option<UsageRecord> {
+        let line = first_at(line, &[&["response"]])
+            .filter(|inner| inner.is_object())
+            .unwrap_or(line);
+        if line.get("done").and_then(Value::as_bool) == Some(false) {
+            return None;
+        }
+        let model = line.get("model")?.as_str()?.to_string();
+        let input_tokens = line
+            .get("prompt_eval_count")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let output_tokens = line.get("eval_count").and_then(Value::as_u64).unwrap_or(0);
+        if input_tokens + output_tokens == 0 {
+            return None;
+        }
+        let timestamp = first_at(line, &[&["created_at"], &["timestamp"]])
+            .and_then(parse_time)
+            .unwrap_or(fallback);
+        Some(UsageRecord {
+            provider: self.name().to_string(),
+            model,
+            input_tokens,
+            output_tokens,
+            cached_tokens: 0,
+            timestamp,
+            bucket_end: None,
+            source_granularity: Some(Granularity::Minute),
+            source: UsageSource::Local,
+            // Durations are reported in nanoseconds.
+            avg_latency_ms: line
+                .get("total_duration")
+                .and_then(Value::as_f64)
+                .map(|ns| ns / 1_000_000.0),
+        })
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for OllamaAdapter {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["localhost", "127.0.0.1"]
+    }
+
+    fn credential_kind(&self) -> CredentialKind {
+        CredentialKind::None
+    }
+
+    async fn fetch_usage(
+        &self,
+        _client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let started = Instant::now();
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let mut rows = Vec::new();
+        for path in recent_jsonl_files(&Self::roots(&ctx.settings.log_paths), since) {
+            let modified = modified_or(&path, ctx.refresh_end);
+            rows.extend(
+                read_json_lines(&path)?
+                    .iter()
+                    .filter_map(|line| self.parse_line(line, modified))
+                    .filter(|row| row.timestamp >= since && row.timestamp <= ctx.refresh_end),
+            );
+        }
+        Ok(UsageFetch {
+            rows,
+            parse_ms: started.elapsed().as_millis(),
+            ..UsageFetch::default()
+        })
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let root = ctx.settings.base_url.as_deref().unwrap_or(API_ROOT);
+        let url = format!("{}/api/ps", root.trim_end_matches('/'));
+
+        let _permit = ctx.acquire().await?;
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| AppError::Config(format!("Ollama is not reachable at {root}: {e}")))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        Err(AppError::Config(format!(
+            "Ollama connection failed with HTTP status {}.",
+            status
+        )))
+    }
+
+    /// Every row gets a cost row, so local tokens show up next to cloud
+    /// spend: `$0` unless a `provider = "ollama"` override prices the model.
+    fn derive_costs(
+        &self,
+        usage: &[UsageRecord],
+        overrides: &[PricingOverride],
+    ) -> Vec<CostRecord> {
+        usage
+            .iter()
+            .map(|u| {
+                let (input_cost, output_cost) = resolve_pricing(self.name(), &u.model, overrides)
+                    .map_or((0.0, 0.0), |pricing| {
+                        (
+                            (u.input_tokens as f64 / 1_000_000.0) * pricing.input_per_1m,
+                            (u.output_tokens as f64 / 1_000_000.0) * pricing.output_per_1m,
+                        )
+                    });
+                CostRecord {
+                    provider: u.provider.clone(),
+                    model: u.model.clone(),
+                    input_cost,
+                    output_cost,
+                    total_cost: input_cost + output_cost,
+                    currency: "USD".into(),
+                    timestamp: u.timestamp,
+                    bucket_end: u.bucket_end,
+                    source_granularity: u.source_granularity,
+                    source: u.source,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    #[test]
+    fn final_responses_become_zero_cost_rows() {
+        let adapter = OllamaAdapter;
+        let fallback = Utc.with_ymd_and_hms(2024, 10, 15, 9, 0, 0).unwrap();
+
+        let generate = json!({
+            "model": "llama3.2",
+            "created_at": "2024-10-15T08:12:31.123456789Z",
+            "response": "",
+            "done": true,
+            "total_duration": 5_043_500_667u64,
+            "prompt_eval_count": 26,
+            "eval_count": 290
+        });
+        let row = adapter
+            .parse_line(&generate, fallback)
+            .expect("generate row");
+        assert_eq!((row.input_tokens, row.output_tokens), (26, 290));
+        assert_eq!(
+            row.timestamp.to_rfc3339(),
+            "2024-10-15T08:12:31.123456789+00:00"
+        );
+        assert_eq!(row.avg_latency_ms.map(|ms| ms.round()), Some(5044.0));
+
+        let proxied = json!({ "response": { "model": "qwen2.5", "eval_count": 4 } });
+        let row = adapter.parse_line(&proxied, fallback).expect("proxied row");
+        assert_eq!((row.model.as_str(), row.timestamp), ("qwen2.5", fallback));
+
+        let chunk = json!({ "model": "llama3.2", "done": false, "eval_count": 1 });
+        assert!(adapter.parse_line(&chunk, fallback).is_none());
+
+        let costs = adapter.derive_costs(&[row], &[]);
+        assert_eq!(costs.len(), 1);
+        assert_eq!(costs[0].total_cost, 0.0);
+    }
+}
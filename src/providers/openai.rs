@@ -1,24 +1,207 @@
 use crate::error::AppError;
-use crate::models::{TimeWindow, UsageRecord};
-use crate::providers::{ProviderAdapter, ProviderContext};
+use crate::models::{Granularity, RateLimit, UsageRecord, UsageSource};
+use crate::providers::{
+    data_items, item_avg_latency_ms, token_field, ParseContext, ProviderAdapter, ProviderContext,
+    UsageFetch, UsageSchema,
+};
 use async_trait::async_trait;
-use chrono::{Duration, TimeZone, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use reqwest::Client;
 use serde_json::Value;
+use std::time::Instant;
+
+/// Safety stop for `has_more` pagination.
+const MAX_PAGES: u32 = 50;
+
+const API_ROOT: &str = "https://api.openai.com";
 
 pub struct OpenAiAdapter;
 
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[
+    UsageSchema {
+        version: "openai/buckets-v2",
+        detect: OpenAiAdapter::has_result_buckets,
+        parse: OpenAiAdapter::parse_result_buckets,
+    },
+    UsageSchema {
+        version: "openai/flat-v1",
+        detect: OpenAiAdapter::has_flat_items,
+        parse: OpenAiAdapter::parse_flat_items,
+    },
+];
+
 impl OpenAiAdapter {
-    fn usage_endpoint(window: TimeWindow) -> String {
+    /// Completions usage for the last `hours` from the server at `root`.
+    pub(crate) fn usage_endpoint(root: &str, hours: i64) -> String {
         let end = Utc::now();
-        let start = end - Duration::hours(window.as_hours());
+        let start = end - Duration::hours(hours);
         format!(
-            "https://api.openai.com/v1/organization/usage/completions?start_time={}&end_time={}",
+            "{}/v1/organization/usage/completions?start_time={}&end_time={}&bucket_width=1d&group_by=model&limit={}",
+            root.trim_end_matches('/'),
             start.timestamp(),
-            end.timestamp()
+            end.timestamp(),
+            Self::bucket_limit(hours)
         )
     }
 
+    /// Daily buckets needed to cover `hours`, plus the partial day the window
+    /// starts in, capped at the API's per-request maximum; anything beyond
+    /// arrives on later pages. Without `limit` the API returns 7 buckets.
+    fn bucket_limit(hours: i64) -> i64 {
+        ((hours + 23) / 24 + 1).clamp(1, 31)
+    }
+
+    fn next_page(body: &Value) -> Option<String> {
+        if !body
+            .get("has_more")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        body.get("next_page")
+            .and_then(Value::as_str)
+            .filter(|page| !page.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Follows `has_more` pages from `first_url` and parses them as
+    /// `provider`'s usage; shared with OpenAI-compatible servers.
+    pub(crate) async fn fetch_usage_pages(
+        client: &Client,
+        ctx: &ProviderContext,
+        provider: &'static str,
+        first_url: String,
+    ) -> Result<UsageFetch, AppError> {
+        let parse_ctx = ParseContext {
+            provider,
+            fallback: ctx.refresh_end,
+            // The completions usage endpoint buckets daily by default.
+            granularity: Some(Granularity::Day),
+        };
+
+        let mut fetch = UsageFetch::default();
+        let mut url = first_url.clone();
+        loop {
+            let mut req = client.get(&url).bearer_auth(ctx.api_key());
+            if let Some(org) = &ctx.settings.organization_id {
+                req = req.header("OpenAI-Organization", org);
+            }
+
+            let permit = ctx.acquire().await?;
+            let started = Instant::now();
+            let response = req.send().await?;
+            fetch.note_server_date(&response);
+            let bytes = response.error_for_status()?.bytes().await?;
+            fetch.http_ms += started.elapsed().as_millis();
+            fetch.pages += 1;
+            drop(permit);
+
+            let started = Instant::now();
+            let body: Value = serde_json::from_slice(&bytes)?;
+            fetch.parse_page(USAGE_SCHEMAS, &body, &parse_ctx);
+            fetch.parse_ms += started.elapsed().as_millis();
+
+            match Self::next_page(&body) {
+                Some(page) if fetch.pages < MAX_PAGES => {
+                    url = Self::with_page(&first_url, &page)?;
+                }
+                _ => break,
+            }
+        }
+        Ok(fetch)
+    }
+
+    fn with_page(url: &str, page: &str) -> Result<String, AppError> {
+        let mut parsed = url::Url::parse(url)
+            .map_err(|e| AppError::Config(format!("Invalid openai usage URL: {e}")))?;
+        let kept: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(k, _)| k != "page")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(kept)
+            .append_pair("page", page);
+        Ok(parsed.to_string())
+    }
+
+    /// Time buckets, each with a `results` array of per-model usage, as the
+    /// organization usage API returns today.
+    fn has_result_buckets(body: &Value) -> bool {
+        data_items(body)
+            .first()
+            .is_some_and(|bucket| bucket.get("results").is_some())
+    }
+
+    fn parse_result_buckets(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        data_items(body)
+            .iter()
+            .flat_map(|bucket| {
+                bucket
+                    .get("results")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(move |result| Self::usage_row(result, bucket, ctx))
+            })
+            .collect()
+    }
+
+    /// One usage item per model and bucket directly under `data`.
+    fn has_flat_items(body: &Value) -> bool {
+        data_items(body).first().is_some_and(|item| {
+            item.get("input_tokens").is_some() || item.get("output_tokens").is_some()
+        })
+    }
+
+    fn parse_flat_items(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        data_items(body)
+            .iter()
+            .filter_map(|item| Self::usage_row(item, item, ctx))
+            .collect()
+    }
+
+    /// Input, output and cached tokens of `item`, under their current or
+    /// chat-completions names; `None` when it carries neither input nor output.
+    fn tokens(item: &Value) -> Option<(u64, u64, u64)> {
+        let input = token_field(item, &["input_tokens", "prompt_tokens"]);
+        let output = token_field(item, &["output_tokens", "completion_tokens"]);
+        if input.is_none() && output.is_none() {
+            return None;
+        }
+        let cached = token_field(item, &["input_cached_tokens", "cached_tokens"]);
+        Some((input.unwrap_or(0), output.unwrap_or(0), cached.unwrap_or(0)))
+    }
+
+    /// Row for the tokens of `item`, timed by `bucket`, which is `item` itself
+    /// in flat payloads.
+    fn usage_row(item: &Value, bucket: &Value, ctx: &ParseContext) -> Option<UsageRecord> {
+        let (input_tokens, output_tokens, cached_tokens) = Self::tokens(item)?;
+        Some(UsageRecord {
+            provider: ctx.provider.to_string(),
+            model: item
+                .get("model")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+            input_tokens,
+            output_tokens,
+            cached_tokens,
+            timestamp: Self::parse_item_timestamp(bucket).unwrap_or(ctx.fallback),
+            bucket_end: bucket
+                .get("end_time")
+                .and_then(Value::as_i64)
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
+            source_granularity: ctx.granularity,
+            source: UsageSource::Api,
+            avg_latency_ms: item_avg_latency_ms(item),
+        })
+    }
+
     fn parse_item_timestamp(item: &Value) -> Option<chrono::DateTime<Utc>> {
         if let Some(secs) = item.get("start_time").and_then(Value::as_i64) {
             return Utc.timestamp_opt(secs, 0).single();
@@ -39,11 +222,38 @@ impl OpenAiAdapter {
         None
     }
 
+    fn rate_limits_endpoint(project_id: &str) -> String {
+        format!(
+            "https://api.openai.com/v1/organization/projects/{project_id}/rate_limits?limit=100"
+        )
+    }
+
+    /// Rows of a project's rate limits listing; models without a token limit
+    /// are skipped.
+    fn parse_rate_limits(body: &Value, fetched_at: DateTime<Utc>) -> Vec<RateLimit> {
+        body.get("data")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                Some(RateLimit {
+                    provider: "openai".into(),
+                    model: item.get("model")?.as_str()?.to_string(),
+                    tokens_per_minute: item.get("max_tokens_per_1_minute")?.as_u64()?,
+                    requests_per_minute: item
+                        .get("max_requests_per_1_minute")
+                        .and_then(Value::as_u64),
+                    fetched_at,
+                })
+            })
+            .collect()
+    }
+
     fn test_endpoint() -> &'static str {
         "https://api.openai.com/v1/models"
     }
 
-    fn resolve_test_url(base_url: Option<String>) -> String {
+    pub(crate) fn resolve_test_url(base_url: Option<String>) -> String {
         let Some(base) = base_url else {
             return Self::test_endpoint().to_string();
         };
@@ -68,59 +278,40 @@ impl ProviderAdapter for OpenAiAdapter {
         "openai"
     }
 
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["api.openai.com"]
+    }
+
     async fn fetch_usage(
         &self,
         client: &Client,
         ctx: &ProviderContext,
-    ) -> Result<Vec<UsageRecord>, AppError> {
-        let url = ctx
+    ) -> Result<UsageFetch, AppError> {
+        let first_url = ctx
             .settings
             .base_url
             .clone()
-            .unwrap_or_else(|| Self::usage_endpoint(ctx.window));
+            .unwrap_or_else(|| Self::usage_endpoint(API_ROOT, ctx.lookback_hours));
+        Self::fetch_usage_pages(client, ctx, self.name(), first_url).await
+    }
 
-        let mut req = client.get(url).bearer_auth(&ctx.api_key);
+    async fn fetch_rate_limits(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<Vec<RateLimit>>, AppError> {
+        let Some(project_id) = &ctx.settings.project_id else {
+            return Ok(None);
+        };
+        let mut req = client
+            .get(Self::rate_limits_endpoint(project_id))
+            .bearer_auth(ctx.api_key());
         if let Some(org) = &ctx.settings.organization_id {
             req = req.header("OpenAI-Organization", org);
         }
-
+        let _permit = ctx.acquire().await?;
         let body: Value = req.send().await?.error_for_status()?.json().await?;
-        let items = body
-            .get("data")
-            .and_then(Value::as_array)
-            .cloned()
-            .unwrap_or_default();
-
-        let mut out = Vec::with_capacity(items.len());
-        for item in items {
-            let model = item
-                .get("model")
-                .and_then(Value::as_str)
-                .unwrap_or("unknown")
-                .to_string();
-            let input_tokens = item
-                .get("input_tokens")
-                .and_then(Value::as_u64)
-                .unwrap_or(0);
-            let output_tokens = item
-                .get("output_tokens")
-                .and_then(Value::as_u64)
-                .unwrap_or(0);
-            let cached_tokens = item
-                .get("input_cached_tokens")
-                .and_then(Value::as_u64)
-                .unwrap_or(0);
-            out.push(UsageRecord {
-                provider: self.name().to_string(),
-                model,
-                input_tokens,
-                output_tokens,
-                cached_tokens,
-                timestamp: Self::parse_item_timestamp(&item).unwrap_or(ctx.refresh_end),
-            });
-        }
-
-        Ok(out)
+        Ok(Some(Self::parse_rate_limits(&body, ctx.refresh_end)))
     }
 
     async fn test_connection(
@@ -130,11 +321,12 @@ impl ProviderAdapter for OpenAiAdapter {
     ) -> Result<Option<u16>, AppError> {
         let url = Self::resolve_test_url(ctx.settings.base_url.clone());
 
-        let mut req = client.get(url).bearer_auth(&ctx.api_key);
+        let mut req = client.get(url).bearer_auth(ctx.api_key());
         if let Some(org) = &ctx.settings.organization_id {
             req = req.header("OpenAI-Organization", org);
         }
 
+        let _permit = ctx.acquire().await?;
         let response = req.send().await?;
         let status = response.status();
         if status.is_success() {
@@ -173,6 +365,142 @@ mod tests {
         assert_eq!(ts.timestamp(), 1_704_067_200);
     }
 
+    fn parse_ctx() -> ParseContext {
+        ParseContext {
+            provider: "openai",
+            fallback: Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts"),
+            granularity: Some(Granularity::Day),
+        }
+    }
+
+    #[test]
+    fn result_buckets_and_flat_items_both_parse_with_their_version() {
+        let nested = json!({ "object": "page", "data": [
+            { "object": "bucket", "start_time": 1_730_419_200, "end_time": 1_730_505_600,
+              "results": [
+                  { "model": "gpt-4o", "input_tokens": 1000, "output_tokens": 200,
+                    "input_cached_tokens": 300, "num_model_requests": 4 },
+                  { "model": null, "input_tokens": 50, "output_tokens": 5 },
+              ] },
+            { "object": "bucket", "start_time": 1_730_505_600, "end_time": 1_730_592_000,
+              "results": [] },
+        ] });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &nested, &parse_ctx());
+        assert_eq!(fetch.parser_version, Some("openai/buckets-v2"));
+        assert!(!fetch.parser_fallback);
+        assert_eq!(fetch.rows.len(), 2);
+        assert_eq!(fetch.rows[0].timestamp.timestamp(), 1_730_419_200);
+        assert_eq!(
+            fetch.rows[0].bucket_end.map(|end| end.timestamp()),
+            Some(1_730_505_600)
+        );
+        assert_eq!(
+            (fetch.rows[0].input_tokens, fetch.rows[0].cached_tokens),
+            (1000, 300)
+        );
+        assert_eq!(fetch.rows[1].model, "unknown");
+
+        let flat = json!({ "data": [
+            { "model": "gpt-4o-mini", "start_time": 1_730_419_200,
+              "input_tokens": 10, "output_tokens": 2 },
+        ] });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &flat, &parse_ctx());
+        assert_eq!(fetch.parser_version, Some("openai/flat-v1"));
+        assert_eq!(fetch.rows.len(), 1);
+        assert_eq!(fetch.rows[0].model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn usage_page_from_the_organization_api_yields_per_model_rows() {
+        // Shape of a `group_by=model` page, trimmed to the fields read.
+        let page = json!({
+            "object": "page",
+            "data": [
+                { "object": "bucket", "start_time": 1_736_553_600, "end_time": 1_736_640_000,
+                  "results": [
+                      { "object": "organization.usage.completions.result",
+                        "input_tokens": 16_000, "output_tokens": 2_100,
+                        "input_cached_tokens": 4_000, "input_audio_tokens": 0,
+                        "output_audio_tokens": 0, "num_model_requests": 12,
+                        "project_id": null, "user_id": null, "api_key_id": null,
+                        "model": "gpt-4o-mini-2024-07-18", "batch": null },
+                      { "object": "organization.usage.completions.result",
+                        "input_tokens": 900, "output_tokens": 150,
+                        "input_cached_tokens": 0, "num_model_requests": 3,
+                        "model": "o3-mini-2025-01-31" },
+                  ] },
+            ],
+            "has_more": true,
+            "next_page": "page_AAAAAGdGxdEiJdKOAAAAAGcqsYA="
+        });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &page, &parse_ctx());
+        let rows: Vec<_> = fetch
+            .rows
+            .iter()
+            .map(|r| (r.model.as_str(), r.input_tokens, r.output_tokens))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("gpt-4o-mini-2024-07-18", 16_000, 2_100),
+                ("o3-mini-2025-01-31", 900, 150),
+            ]
+        );
+
+        let next = OpenAiAdapter::next_page(&page).expect("has more");
+        let url =
+            OpenAiAdapter::with_page(&OpenAiAdapter::usage_endpoint(API_ROOT, 24 * 30), &next)
+                .expect("url");
+        assert!(url.contains("group_by=model&limit=31&page=page_AAAAAGdGxdEiJdKOAAAAAGcqsYA%3D"));
+        assert_eq!(OpenAiAdapter::bucket_limit(24), 2);
+        assert_eq!(
+            OpenAiAdapter::next_page(&json!({ "has_more": false, "next_page": "p2" })),
+            None
+        );
+    }
+
+    #[test]
+    fn renamed_token_fields_parse_through_the_fallback_chain() {
+        // Flat items with chat-completions names match neither schema's
+        // markers, so the chain tries each and the flat parser reads them.
+        let renamed = json!({ "data": [
+            { "model": "gpt-4o", "start_time": 1_730_419_200,
+              "prompt_tokens": 70, "completion_tokens": 7 },
+        ] });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &renamed, &parse_ctx());
+        assert_eq!(fetch.parser_version, Some("openai/flat-v1"));
+        assert!(fetch.parser_fallback);
+        assert_eq!(
+            (fetch.rows[0].input_tokens, fetch.rows[0].output_tokens),
+            (70, 7)
+        );
+
+        // Items without any known token field yield no rows rather than zeros.
+        let unknown = json!({ "data": [{ "model": "gpt-4o", "tokens": 9 }] });
+        let mut fetch = UsageFetch::default();
+        fetch.parse_page(USAGE_SCHEMAS, &unknown, &parse_ctx());
+        assert!(fetch.rows.is_empty());
+        assert_eq!(fetch.parser_version, None);
+    }
+
+    #[test]
+    fn parse_rate_limits_reads_token_and_request_limits() {
+        let fetched_at = Utc.timestamp_opt(1_700_000_000, 0).single().expect("ts");
+        let body = json!({ "data": [
+            { "model": "gpt-4o", "max_tokens_per_1_minute": 30000, "max_requests_per_1_minute": 500 },
+            { "model": "dall-e-3", "max_images_per_1_minute": 5 },
+        ] });
+        let limits = OpenAiAdapter::parse_rate_limits(&body, fetched_at);
+        assert_eq!(limits.len(), 1);
+        assert_eq!(limits[0].model, "gpt-4o");
+        assert_eq!(limits[0].tokens_per_minute, 30_000);
+        assert_eq!(limits[0].requests_per_minute, Some(500));
+    }
+
     #[test]
     fn parse_item_timestamp_returns_none_for_invalid_payload() {
         assert!(OpenAiAdapter::parse_item_timestamp(&json!({ "start_time": "nope" })).is_none());
@@ -0,0 +1,183 @@
+use crate::config::PricingOverride;
+use crate::error::AppError;
+use crate::models::{CostRecord, UsageRecord};
+use crate::pricing::{resolve_pricing, ModelPricing};
+use crate::providers::local::vendor_for_model;
+use crate::providers::openai::OpenAiAdapter;
+use crate::providers::{ProviderAdapter, ProviderContext, UsageFetch};
+use async_trait::async_trait;
+use reqwest::Client;
+
+/// A server speaking OpenAI's API at the account's `base_url`, such as a
+/// LiteLLM proxy or a vLLM deployment, read through the same organization
+/// usage endpoint as OpenAI. Any number of accounts can use it, each under
+/// its own name with `kind = "custom-openai-compatible"`.
+pub struct OpenAiCompatibleAdapter;
+
+impl OpenAiCompatibleAdapter {
+    fn base_url(ctx: &ProviderContext) -> Result<&str, AppError> {
+        ctx.settings.base_url.as_deref().ok_or_else(|| {
+            AppError::Config("custom-openai-compatible accounts need a base_url.".into())
+        })
+    }
+
+    /// The usage URL for `base_url`: the server root or its `/v1` path get
+    /// the organization usage endpoint appended; any other path is taken to
+    /// be that endpoint already.
+    fn usage_url(base_url: &str, hours: i64) -> Result<String, AppError> {
+        let parsed = url::Url::parse(base_url)
+            .map_err(|e| AppError::Config(format!("Invalid base_url '{base_url}': {e}")))?;
+        let path = parsed.path().trim_end_matches('/');
+        if path.is_empty() || path.ends_with("/v1") {
+            let root = base_url.trim_end_matches('/').trim_end_matches("/v1");
+            return Ok(OpenAiAdapter::usage_endpoint(root, hours));
+        }
+        Ok(base_url.to_string())
+    }
+
+    /// Prices from an override for the account itself (`provider` set to its
+    /// name), else the model's vendor, so a proxy in front of OpenAI or
+    /// Anthropic is priced like them.
+    fn pricing_for(row: &UsageRecord, overrides: &[PricingOverride]) -> Option<ModelPricing> {
+        resolve_pricing(&row.provider, &row.model, overrides).or_else(|| {
+            vendor_for_model(&row.model)
+                .and_then(|vendor| resolve_pricing(vendor, &row.model, overrides))
+        })
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for OpenAiCompatibleAdapter {
+    fn name(&self) -> &'static str {
+        "custom-openai-compatible"
+    }
+
+    fn requires_base_url(&self) -> bool {
+        true
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let first_url = Self::usage_url(Self::base_url(ctx)?, ctx.lookback_hours)?;
+        OpenAiAdapter::fetch_usage_pages(client, ctx, self.name(), first_url).await
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let root = Self::base_url(ctx)?;
+        let url = OpenAiAdapter::resolve_test_url(Some(root.to_string()));
+
+        let mut req = client.get(url).bearer_auth(ctx.api_key());
+        if let Some(org) = &ctx.settings.organization_id {
+            req = req.header("OpenAI-Organization", org);
+        }
+
+        let _permit = ctx.acquire().await?;
+        let response = req.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(AppError::Config(format!(
+                "{root} rejected the API key (unauthorized)."
+            )));
+        }
+
+        Err(AppError::Config(format!(
+            "{root} connection failed with HTTP status {}.",
+            status
+        )))
+    }
+
+    fn derive_costs(
+        &self,
+        usage: &[UsageRecord],
+        overrides: &[PricingOverride],
+    ) -> Vec<CostRecord> {
+        usage
+            .iter()
+            .filter_map(|u| {
+                let pricing = Self::pricing_for(u, overrides)?;
+                let input_cost = (u.input_tokens as f64 / 1_000_000.0) * pricing.input_per_1m;
+                let output_cost = (u.output_tokens as f64 / 1_000_000.0) * pricing.output_per_1m;
+                Some(CostRecord {
+                    provider: u.provider.clone(),
+                    model: u.model.clone(),
+                    input_cost,
+                    output_cost,
+                    total_cost: input_cost + output_cost,
+                    currency: "USD".into(),
+                    timestamp: u.timestamp,
+                    bucket_end: u.bucket_end,
+                    source_granularity: u.source_granularity,
+                    source: u.source,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UsageSource;
+    use chrono::Utc;
+
+    #[test]
+    fn base_url_root_gets_the_usage_path_and_accounts_price_before_vendors() {
+        let usage = |base: &str| {
+            OpenAiCompatibleAdapter::usage_url(base, 24)
+                .expect("url")
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        let expected = "http://litellm:4000/v1/organization/usage/completions";
+        assert_eq!(usage("http://litellm:4000"), expected);
+        assert_eq!(usage("http://litellm:4000/v1/"), expected);
+        assert_eq!(
+            usage("https://gw.example.com/team-a/v1"),
+            "https://gw.example.com/team-a/v1/organization/usage/completions"
+        );
+        assert_eq!(
+            usage("https://gw.example.com/usage/report"),
+            "https://gw.example.com/usage/report"
+        );
+        assert!(OpenAiCompatibleAdapter::usage_url("not a url", 24).is_err());
+
+        let row = |model: &str| UsageRecord {
+            provider: "vllm-prod".into(),
+            model: model.into(),
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+            cached_tokens: 0,
+            timestamp: Utc::now(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+            avg_latency_ms: None,
+        };
+        let overrides = vec![PricingOverride {
+            provider: "vllm-prod".into(),
+            model_pattern: "llama".into(),
+            input_per_1m: 0.25,
+            output_per_1m: 0.5,
+        }];
+        let costs = OpenAiCompatibleAdapter.derive_costs(
+            &[row("meta-llama-3.1-8b"), row("gpt-4o"), row("mistral-7b")],
+            &overrides,
+        );
+        let models: Vec<&str> = costs.iter().map(|c| c.model.as_str()).collect();
+        assert_eq!(models, vec!["meta-llama-3.1-8b", "gpt-4o"]);
+        assert_eq!(costs[0].total_cost, 0.25);
+        assert!(costs[1].total_cost > 0.0);
+    }
+}
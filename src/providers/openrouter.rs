@@ -0,0 +1,263 @@
+use crate::error::AppError;
+use crate::models::{CostRecord, Granularity, UsageRecord, UsageSource};
+use crate::providers::{
+    data_items, token_field, ParseContext, ProviderAdapter, ProviderContext, UsageFetch,
+    UsageSchema,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+const API_ROOT: &str = "https://openrouter.ai/api/v1";
+
+/// OpenRouter usage from its activity endpoint: requests, tokens and the
+/// USD charged per model and UTC day, over the last 30 completed days. The
+/// key is a provisioning key. The charged amounts are stored as the costs,
+/// so no prices apply.
+pub struct OpenRouterAdapter;
+
+/// Usage payload shapes, newest first.
+const USAGE_SCHEMAS: &[UsageSchema] = &[UsageSchema {
+    version: "openrouter/activity-v1",
+    detect: OpenRouterAdapter::has_activity,
+    parse: OpenRouterAdapter::parse_activity,
+}];
+
+impl OpenRouterAdapter {
+    fn activity_endpoint(root: &str) -> String {
+        format!("{}/activity", root.trim_end_matches('/'))
+    }
+
+    fn has_activity(body: &Value) -> bool {
+        data_items(body)
+            .first()
+            .is_some_and(|item| item.get("date").is_some())
+    }
+
+    /// Model and `[start, end)` of the UTC day an activity item covers.
+    /// `date` may carry a time after the day, which is ignored.
+    fn item_day(item: &Value) -> Option<(String, DateTime<Utc>, DateTime<Utc>)> {
+        let model = item
+            .get("model")
+            .or_else(|| item.get("model_permaslug"))
+            .and_then(Value::as_str)?;
+        let date = item.get("date").and_then(Value::as_str)?;
+        let day = NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+        let start = day.and_hms_opt(0, 0, 0)?.and_utc();
+        Some((model.to_string(), start, start + Duration::days(1)))
+    }
+
+    /// One row per model and day; items for the same model served by
+    /// different upstream providers are summed.
+    fn parse_activity(body: &Value, ctx: &ParseContext) -> Vec<UsageRecord> {
+        let mut rows: BTreeMap<(String, DateTime<Utc>), UsageRecord> = BTreeMap::new();
+        for item in data_items(body) {
+            let Some((model, start, end)) = Self::item_day(item) else {
+                continue;
+            };
+            let input = token_field(item, &["prompt_tokens"]);
+            let output = token_field(item, &["completion_tokens"]);
+            if input.is_none() && output.is_none() {
+                continue;
+            }
+            let row = rows
+                .entry((model.clone(), start))
+                .or_insert_with(|| UsageRecord {
+                    provider: ctx.provider.to_string(),
+                    model,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    cached_tokens: 0,
+                    timestamp: start,
+                    bucket_end: Some(end),
+                    source_granularity: Some(Granularity::Day),
+                    avg_latency_ms: None,
+                    source: UsageSource::Api,
+                });
+            row.input_tokens += input.unwrap_or(0);
+            row.output_tokens += output.unwrap_or(0);
+        }
+        rows.into_values().collect()
+    }
+
+    /// The USD `usage` OpenRouter charged per model and day. It is not split
+    /// by token type, so only `total_cost` is set.
+    fn parse_costs(body: &Value, provider: &str) -> Vec<CostRecord> {
+        let mut costs: BTreeMap<(String, DateTime<Utc>), CostRecord> = BTreeMap::new();
+        for item in data_items(body) {
+            let Some((model, start, end)) = Self::item_day(item) else {
+                continue;
+            };
+            let Some(amount) = item.get("usage").and_then(Value::as_f64) else {
+                continue;
+            };
+            costs
+                .entry((model.clone(), start))
+                .or_insert_with(|| CostRecord {
+                    provider: provider.to_string(),
+                    model,
+                    input_cost: 0.0,
+                    output_cost: 0.0,
+                    total_cost: 0.0,
+                    currency: "USD".into(),
+                    timestamp: start,
+                    bucket_end: Some(end),
+                    source_granularity: Some(Granularity::Day),
+                    source: UsageSource::Api,
+                })
+                .total_cost += amount;
+        }
+        costs.into_values().collect()
+    }
+}
+
+#[async_trait]
+impl ProviderAdapter for OpenRouterAdapter {
+    fn name(&self) -> &'static str {
+        "openrouter"
+    }
+
+    fn default_hosts(&self) -> &'static [&'static str] {
+        &["openrouter.ai"]
+    }
+
+    /// The activity endpoint only covers the last 30 completed days.
+    fn max_history_days(&self) -> u32 {
+        30
+    }
+
+    async fn fetch_usage(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<UsageFetch, AppError> {
+        let url = Self::activity_endpoint(ctx.settings.base_url.as_deref().unwrap_or(API_ROOT));
+
+        let mut fetch = UsageFetch::default();
+        let permit = ctx.acquire().await?;
+        let started = Instant::now();
+        let response = client.get(&url).bearer_auth(ctx.api_key()).send().await?;
+        fetch.note_server_date(&response);
+        let bytes = response.error_for_status()?.bytes().await?;
+        fetch.http_ms += started.elapsed().as_millis();
+        fetch.pages += 1;
+        drop(permit);
+
+        let started = Instant::now();
+        let body: Value = serde_json::from_slice(&bytes)?;
+        fetch.parse_page(
+            USAGE_SCHEMAS,
+            &body,
+            &ParseContext {
+                provider: self.name(),
+                fallback: ctx.refresh_end,
+                granularity: Some(Granularity::Day),
+            },
+        );
+        // The endpoint ignores the window, so days ending before it are dropped.
+        let since = ctx.refresh_end - Duration::hours(ctx.lookback_hours);
+        let in_window = |end: Option<DateTime<Utc>>| end.is_some_and(|end| end > since);
+        fetch.rows.retain(|row| in_window(row.bucket_end));
+        let mut costs = Self::parse_costs(&body, self.name());
+        costs.retain(|row| in_window(row.bucket_end));
+        fetch.costs = Some(costs);
+        fetch.parse_ms += started.elapsed().as_millis();
+        Ok(fetch)
+    }
+
+    async fn test_connection(
+        &self,
+        client: &Client,
+        ctx: &ProviderContext,
+    ) -> Result<Option<u16>, AppError> {
+        let url = Self::activity_endpoint(ctx.settings.base_url.as_deref().unwrap_or(API_ROOT));
+
+        let _permit = ctx.acquire().await?;
+        let response = client.get(url).bearer_auth(ctx.api_key()).send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Some(status.as_u16()));
+        }
+        if status.as_u16() == 401 || status.as_u16() == 403 {
+            return Err(AppError::Config(
+                "OpenRouter rejected the key (unauthorized); activity needs a provisioning key."
+                    .into(),
+            ));
+        }
+
+        Err(AppError::Config(format!(
+            "OpenRouter connection failed with HTTP status {}.",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn activity_items_merge_per_model_and_day_with_their_charged_cost() {
+        let item = |date: &str, provider: &str, prompt: u64, completion: u64, usage: f64| {
+            json!({
+                "date": date,
+                "model": "openai/gpt-4.1",
+                "model_permaslug": "openai/gpt-4.1-2025-04-14",
+                "provider_name": provider,
+                "usage": usage,
+                "byok_usage_inference": 0.0,
+                "requests": 1,
+                "prompt_tokens": prompt,
+                "completion_tokens": completion,
+                "reasoning_tokens": 0
+            })
+        };
+        let body = json!({
+            "data": [
+                item("2025-08-24", "OpenAI", 1000, 200, 0.015),
+                item("2025-08-24", "Azure", 500, 100, 0.005),
+                item("2025-08-25 00:00:00", "OpenAI", 10, 20, 0.001),
+                { "date": "2025-08-25", "model": "anthropic/claude-sonnet-4", "usage": 0.2 },
+            ]
+        });
+
+        let mut fetch = UsageFetch::default();
+        let ctx = ParseContext {
+            provider: "openrouter",
+            fallback: Utc::now(),
+            granularity: Some(Granularity::Day),
+        };
+        fetch.parse_page(USAGE_SCHEMAS, &body, &ctx);
+        assert_eq!(fetch.parser_version, Some("openrouter/activity-v1"));
+        let rows = fetch.rows;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].timestamp.to_rfc3339(), "2025-08-24T00:00:00+00:00");
+        assert_eq!((rows[0].input_tokens, rows[0].output_tokens), (1500, 300));
+        assert_eq!(
+            rows[1].bucket_end.map(|end| end.to_rfc3339()),
+            Some("2025-08-26T00:00:00+00:00".to_string())
+        );
+
+        let costs = OpenRouterAdapter::parse_costs(&body, "openrouter");
+        let totals: Vec<(&str, f64)> = costs
+            .iter()
+            .map(|c| (c.model.as_str(), (c.total_cost * 1000.0).round() / 1000.0))
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                ("anthropic/claude-sonnet-4", 0.2),
+                ("openai/gpt-4.1", 0.02),
+                ("openai/gpt-4.1", 0.001),
+            ]
+        );
+        assert!(costs
+            .iter()
+            .all(|c| c.input_cost == 0.0 && c.currency == "USD"));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::config::ProviderSettings;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// Request gate for one provider account, shared by every fetch in the
+/// process: at most `max_concurrent` requests in flight, and request starts
+/// at least `min_interval` apart. Adapters hold a [`ThrottlePermit`] from
+/// before `send` until the response body is read.
+#[derive(Debug)]
+pub struct Throttle {
+    max_concurrent: Option<u32>,
+    min_interval: Duration,
+    permits: Option<Semaphore>,
+    next_start: Mutex<Option<Instant>>,
+}
+
+/// Keeps a concurrency slot until dropped.
+pub struct ThrottlePermit<'a> {
+    _permit: Option<SemaphorePermit<'a>>,
+}
+
+impl Throttle {
+    /// `None` or zero `max_concurrent` means no concurrency limit.
+    pub fn new(max_concurrent: Option<u32>, min_interval: Duration) -> Self {
+        let max_concurrent = max_concurrent.filter(|n| *n > 0);
+        Self {
+            max_concurrent,
+            min_interval,
+            permits: max_concurrent.map(|n| Semaphore::new(n as usize)),
+            next_start: Mutex::new(None),
+        }
+    }
+
+    fn from_settings(settings: &ProviderSettings) -> Self {
+        Self::new(
+            settings.max_concurrent_requests,
+            Duration::from_millis(settings.min_request_interval_ms.unwrap_or(0)),
+        )
+    }
+
+    fn matches(&self, settings: &ProviderSettings) -> bool {
+        self.max_concurrent == settings.max_concurrent_requests.filter(|n| *n > 0)
+            && self.min_interval
+                == Duration::from_millis(settings.min_request_interval_ms.unwrap_or(0))
+    }
+
+    /// Waits for a free slot, then for this request's turn under the
+    /// politeness delay.
+    pub async fn acquire(&self) -> ThrottlePermit<'_> {
+        let permit = match &self.permits {
+            Some(permits) => permits.acquire().await.ok(),
+            None => None,
+        };
+        if !self.min_interval.is_zero() {
+            let start = {
+                let mut next = self.next_start.lock().unwrap_or_else(|e| e.into_inner());
+                let start = next.map_or_else(Instant::now, |at| at.max(Instant::now()));
+                *next = Some(start + self.min_interval);
+                start
+            };
+            tokio::time::sleep_until(start).await;
+        }
+        ThrottlePermit { _permit: permit }
+    }
+}
+
+/// The process-wide throttle for `account`, replaced when its settings change.
+pub fn for_account(account: &str, settings: &ProviderSettings) -> Arc<Throttle> {
+    static THROTTLES: OnceLock<Mutex<HashMap<String, Arc<Throttle>>>> = OnceLock::new();
+    let mut throttles = THROTTLES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    match throttles.get(account) {
+        Some(throttle) if throttle.matches(settings) => throttle.clone(),
+        _ => {
+            let throttle = Arc::new(Throttle::from_settings(settings));
+            throttles.insert(account.to_string(), throttle.clone());
+            throttle
+        }
+    }
+}
+
+/// Requests one refresh or backfill may still send, across all its
+/// accounts, from `[request_caps]`. Unlimited by default.
+#[derive(Debug, Default)]
+pub struct RequestBudget {
+    limit: Option<u32>,
+    used: AtomicU32,
+}
+
+impl RequestBudget {
+    pub fn new(limit: Option<u32>) -> Self {
+        Self {
+            limit,
+            used: AtomicU32::new(0),
+        }
+    }
+
+    /// Counts one request; `false` once the limit is spent.
+    pub fn take(&self) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                match self.limit {
+                    Some(limit) if used >= limit => None,
+                    _ => Some(used + 1),
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn used(&self) -> u32 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.limit.is_some_and(|limit| self.used() >= limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_are_spaced_and_capped() {
+        let throttle = Arc::new(Throttle::new(None, Duration::from_millis(30)));
+        let started = Instant::now();
+        for _ in 0..3 {
+            let _permit = throttle.acquire().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(60));
+
+        let throttle = Arc::new(Throttle::new(Some(1), Duration::ZERO));
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..2)
+            .map(|_| {
+                let throttle = throttle.clone();
+                tokio::spawn(async move {
+                    let _permit = throttle.acquire().await;
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn accounts_share_a_throttle_until_settings_change() {
+        let mut settings = ProviderSettings {
+            max_concurrent_requests: Some(2),
+            ..ProviderSettings::default()
+        };
+        let first = for_account("throttle-test", &settings);
+        assert!(Arc::ptr_eq(
+            &first,
+            &for_account("throttle-test", &settings)
+        ));
+        settings.min_request_interval_ms = Some(250);
+        assert!(!Arc::ptr_eq(
+            &first,
+            &for_account("throttle-test", &settings)
+        ));
+    }
+
+    #[test]
+    fn request_budget_stops_at_its_limit() {
+        let budget = RequestBudget::new(Some(2));
+        assert!(budget.take() && budget.take());
+        assert!(!budget.take());
+        assert_eq!(budget.used(), 2);
+        assert!(budget.is_exhausted());
+
+        let unlimited = RequestBudget::default();
+        assert!((0..100).all(|_| unlimited.take()));
+        assert!(!unlimited.is_exhausted());
+    }
+}
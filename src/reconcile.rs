@@ -0,0 +1,172 @@
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Header names, lowercased, that identify an invoice's line-item column.
+const LINE_COLUMNS: &[&str] = &[
+    "model",
+    "line_item",
+    "line item",
+    "description",
+    "product",
+    "item",
+    "name",
+];
+/// Header names, lowercased, that identify an invoice's amount column.
+const AMOUNT_COLUMNS: &[&str] = &[
+    "amount",
+    "amount_usd",
+    "cost",
+    "cost_usd",
+    "total",
+    "total_cost",
+    "subtotal",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub amount: f64,
+}
+
+/// One reconciled line: invoice lines matched to a tracked model are summed
+/// under that model; unmatched invoice lines and untracked-by-invoice models
+/// appear on their own.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReconciledLine {
+    pub line: String,
+    pub invoice: f64,
+    pub tracked: f64,
+    pub diff: f64,
+    pub matched: bool,
+}
+
+/// Reads an invoice CSV export. The line-item and amount columns are found by
+/// header name; amounts may carry a currency symbol and thousands separators.
+/// Rows without a parsable amount (subtotal banners, blank lines) are skipped.
+pub fn parse_invoice(reader: impl Read) -> Result<Vec<InvoiceLine>, AppError> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers: Vec<String> = csv
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .collect();
+    let find = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| headers.iter().position(|h| h == name))
+    };
+    let (Some(line_col), Some(amount_col)) = (find(LINE_COLUMNS), find(AMOUNT_COLUMNS)) else {
+        return Err(AppError::Config(format!(
+            "Invoice needs a line-item column ({}) and an amount column ({}).",
+            LINE_COLUMNS.join(", "),
+            AMOUNT_COLUMNS.join(", ")
+        )));
+    };
+
+    let mut lines = Vec::new();
+    for record in csv.records() {
+        let record = record?;
+        let description = record.get(line_col).unwrap_or_default().trim();
+        let Some(amount) = record.get(amount_col).and_then(parse_amount) else {
+            continue;
+        };
+        if description.is_empty() || description.eq_ignore_ascii_case("total") {
+            continue;
+        }
+        lines.push(InvoiceLine {
+            description: description.to_string(),
+            amount,
+        });
+    }
+    Ok(lines)
+}
+
+fn parse_amount(raw: &str) -> Option<f64> {
+    let cleaned: String = raw
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+        .collect();
+    cleaned.parse().ok()
+}
+
+/// The tracked model an invoice line belongs to: an exact (case-insensitive)
+/// match, else the longest model name the description contains.
+fn match_model<'a>(description: &str, models: &'a [(String, f64)]) -> Option<&'a str> {
+    let description = description.to_ascii_lowercase();
+    models
+        .iter()
+        .map(|(model, _)| model.as_str())
+        .find(|model| model.eq_ignore_ascii_case(&description))
+        .or_else(|| {
+            models
+                .iter()
+                .map(|(model, _)| model.as_str())
+                .filter(|model| description.contains(&model.to_ascii_lowercase()))
+                .max_by_key(|model| model.len())
+        })
+}
+
+/// Diffs invoice lines against tracked per-model cost, largest discrepancy first.
+pub fn reconcile(invoice: &[InvoiceLine], tracked: &[(String, f64)]) -> Vec<ReconciledLine> {
+    let mut by_line: BTreeMap<String, (f64, f64, bool)> = tracked
+        .iter()
+        .map(|(model, cost)| (model.clone(), (0.0, *cost, true)))
+        .collect();
+    for line in invoice {
+        let key = match_model(&line.description, tracked)
+            .map_or_else(|| line.description.clone(), str::to_string);
+        let entry = by_line.entry(key).or_insert((0.0, 0.0, false));
+        entry.0 += line.amount;
+    }
+
+    let mut out: Vec<ReconciledLine> = by_line
+        .into_iter()
+        .map(|(line, (invoice, tracked, matched))| ReconciledLine {
+            line,
+            invoice,
+            tracked,
+            diff: invoice - tracked,
+            matched,
+        })
+        .collect();
+    out.sort_by(|a, b| b.diff.abs().total_cmp(&a.diff.abs()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invoice_lines_match_models_and_report_differences() {
+        let csv = "\
+Date,Description,Units,Amount
+2024-06-30,GPT-4o input tokens,1000,\"$1,200.50\"
+2024-06-30,gpt-4o output tokens,10,$300.00
+2024-06-30,gpt-4o-mini,5,$4.00
+2024-06-30,Fine-tuning storage,1,$2.00
+,Total,,\"$1,506.50\"
+";
+        let invoice = parse_invoice(csv.as_bytes()).unwrap();
+        assert_eq!(invoice.len(), 4);
+        assert_eq!(invoice[0].amount, 1200.5);
+
+        let tracked = vec![
+            ("gpt-4o".to_string(), 1500.0),
+            ("gpt-4o-mini".to_string(), 4.0),
+            ("o1".to_string(), 1.25),
+        ];
+        let lines = reconcile(&invoice, &tracked);
+        let find = |name: &str| lines.iter().find(|l| l.line == name).unwrap();
+        assert_eq!(find("gpt-4o").diff, 0.5);
+        assert_eq!(find("gpt-4o-mini").diff, 0.0);
+        assert_eq!(find("o1").diff, -1.25);
+        assert!(!find("Fine-tuning storage").matched);
+        assert_eq!(lines[0].line, "Fine-tuning storage");
+
+        assert!(parse_invoice("a,b\n1,2\n".as_bytes()).is_err());
+    }
+}
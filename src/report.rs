@@ -0,0 +1,393 @@
+use crate::config;
+use crate::error::AppError;
+use crate::format::MoneyFormat;
+use crate::models::{EdgeBuckets, ProviderRefreshStats, Statement, TimeWindow};
+use crate::storage::{QueryResult, Storage};
+use crate::template::Template;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+const TOP_MODELS: usize = 3;
+
+/// Built-in spend digest. `templates/digest.txt` in the config dir replaces it.
+pub const DEFAULT_DIGEST_TEMPLATE: &str = "\
+LLM spend · last {{ window }} · {{ generated_date }}
+
+Cost: {{ cost }} ({{ delta }} vs previous {{ window }})
+Tokens: {{ tokens }}
+
+Top models:
+{% for m in top_models %}
+{{ m.rank }}. {{ m.model }}: {{ m.cost }} ({{ m.delta }})
+{% else %}
+No spend recorded in this window.
+{% endfor %}
+";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSpend {
+    pub model: String,
+    pub cost: f64,
+    pub previous_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendSummary {
+    pub window: String,
+    pub generated_at: DateTime<Utc>,
+    pub tokens: u64,
+    pub cost: f64,
+    pub previous_cost: f64,
+    pub top_models: Vec<ModelSpend>,
+}
+
+pub fn build_summary(
+    storage: &Storage,
+    window: TimeWindow,
+    edge: EdgeBuckets,
+    now: DateTime<Utc>,
+) -> Result<SpendSummary, AppError> {
+    let span = Duration::hours(window.as_hours());
+    let since = now - span;
+    let previous_since = since - span;
+
+    let (tokens, cost, ..) = storage.aggregate_since(since, edge)?;
+    let current = storage.cost_by_model_between(since, now + Duration::seconds(1))?;
+    let previous = storage.cost_by_model_between(previous_since, since)?;
+
+    let top_models = current
+        .into_iter()
+        .take(TOP_MODELS)
+        .map(|(model, cost)| {
+            let previous_cost = previous
+                .iter()
+                .find(|(m, _)| *m == model)
+                .map_or(0.0, |(_, c)| *c);
+            ModelSpend {
+                model,
+                cost,
+                previous_cost,
+            }
+        })
+        .collect();
+
+    Ok(SpendSummary {
+        window: window.as_label().to_string(),
+        generated_at: now,
+        tokens,
+        cost,
+        previous_cost: previous.iter().map(|(_, c)| c).sum(),
+        top_models,
+    })
+}
+
+/// Values available to digest templates. Amounts are formatted in the display
+/// currency; `*_usd` fields carry the raw numbers.
+pub fn digest_context(summary: &SpendSummary, money: &MoneyFormat) -> Value {
+    let top_models: Vec<Value> = summary
+        .top_models
+        .iter()
+        .enumerate()
+        .map(|(idx, m)| {
+            json!({
+                "rank": idx + 1,
+                "model": m.model,
+                "cost": money.money(m.cost, 2),
+                "previous_cost": money.money(m.previous_cost, 2),
+                "delta": money.delta(m.cost, m.previous_cost),
+                "cost_usd": m.cost,
+            })
+        })
+        .collect();
+    json!({
+        "window": summary.window,
+        "generated_at": summary.generated_at.to_rfc3339(),
+        "generated_date": summary.generated_at.format("%Y-%m-%d").to_string(),
+        "currency": money.currency(),
+        "cost": money.money(summary.cost, 2),
+        "previous_cost": money.money(summary.previous_cost, 2),
+        "delta": money.delta(summary.cost, summary.previous_cost),
+        "cost_usd": summary.cost,
+        "previous_cost_usd": summary.previous_cost,
+        "tokens": summary.tokens,
+        "has_spend": summary.cost > 0.0,
+        "top_models": top_models,
+    })
+}
+
+/// The digest template at `path`, else `templates/digest.txt` in the config
+/// dir when it exists, else [`DEFAULT_DIGEST_TEMPLATE`].
+pub fn load_digest_template(path: Option<&Path>) -> Result<Template, AppError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let path = config::digest_template_path()?;
+            if !path.exists() {
+                return Template::parse(DEFAULT_DIGEST_TEMPLATE);
+            }
+            path
+        }
+    };
+    let source = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::Config(format!("Cannot read {}: {e}", path.display())))?;
+    Template::parse(&source).map_err(|e| match e {
+        AppError::Config(msg) => AppError::Config(format!("{}: {msg}", path.display())),
+        other => other,
+    })
+}
+
+/// Fixed-width table of per-provider refresh metrics for the `refresh` command,
+/// followed by a line per account with suppressed or overlapping rows, or
+/// whose usage only parsed through the schema fallback chain.
+pub fn refresh_stats_table(stats: &[ProviderRefreshStats]) -> String {
+    let mut out = format!(
+        "{:<12} {:>8} {:>6} {:>9} {:>9} {:>7} {:>8}\n",
+        "provider", "fetched", "pages", "http_ms", "parse_ms", "stored", "dropped"
+    );
+    for s in stats {
+        out.push_str(&format!(
+            "{:<12} {:>8} {:>6} {:>9} {:>9} {:>7} {:>8}\n",
+            s.provider,
+            s.rows_fetched,
+            s.pages,
+            s.http_ms,
+            s.parse_ms,
+            s.rows_stored,
+            s.rows_dropped
+        ));
+    }
+    for s in stats {
+        if s.rows_suppressed > 0 {
+            out.push_str(&format!(
+                "{}: suppressed {} rows (${:.4}) already reported by an overlapped API account\n",
+                s.provider, s.rows_suppressed, s.cost_suppressed
+            ));
+        }
+        if s.rows_overlapping > 0 {
+            out.push_str(&format!(
+                "{}: kept {} rows (${:.4}) that an overlapped API account also reports\n",
+                s.provider, s.rows_overlapping, s.cost_overlapping
+            ));
+        }
+        if let (true, Some(version)) = (s.parser_fallback, &s.parser_version) {
+            out.push_str(&format!(
+                "{}: usage payload matched no known schema; parsed it as {version}\n",
+                s.provider
+            ));
+        }
+    }
+    out
+}
+
+/// Fixed-width table for the `statement` command, with a total line.
+pub fn statement_table(rows: &[Statement]) -> String {
+    let mut out = format!(
+        "{:<12} {:<16} {:>14} {:>14} {:>12} {:<8}\n",
+        "provider", "account", "input_tokens", "output_tokens", "cost", "currency"
+    );
+    for row in rows {
+        out.push_str(&format!(
+            "{:<12} {:<16} {:>14} {:>14} {:>12.2} {:<8}\n",
+            row.provider,
+            row.account.as_deref().unwrap_or("-"),
+            row.input_tokens,
+            row.output_tokens,
+            row.total_cost,
+            row.currency
+        ));
+    }
+    out.push_str(&format!(
+        "{:<12} {:<16} {:>14} {:>14} {:>12.2}\n",
+        "total",
+        "",
+        rows.iter().map(|r| r.input_tokens).sum::<u64>(),
+        rows.iter().map(|r| r.output_tokens).sum::<u64>(),
+        rows.iter().map(|r| r.total_cost).sum::<f64>()
+    ));
+    out
+}
+
+/// Columns of a `query` result padded to their widest value, with a row
+/// count line.
+pub fn query_table(result: &QueryResult) -> String {
+    let widths: Vec<usize> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            result
+                .rows
+                .iter()
+                .map(|row| row[idx].chars().count())
+                .chain([name.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |values: &[String]| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:<width$}"))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut out = line(&result.columns);
+    for row in &result.rows {
+        out.push_str(&line(row));
+    }
+    out.push_str(&format!(
+        "({} row{}{})\n",
+        result.rows.len(),
+        if result.rows.len() == 1 { "" } else { "s" },
+        if result.truncated { ", truncated" } else { "" }
+    ));
+    out
+}
+
+/// A `query` result as CSV with a header row.
+pub fn query_csv(result: &QueryResult) -> Result<String, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&result.columns)?;
+    for row in &result.rows {
+        writer.write_record(row)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Io(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostRecord, UsageRecord, UsageSource};
+    use tempfile::TempDir;
+
+    fn cost(model: &str, ts: DateTime<Utc>, total: f64) -> CostRecord {
+        CostRecord {
+            provider: "openai".into(),
+            model: model.into(),
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            timestamp: ts,
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    #[test]
+    fn build_summary_compares_against_previous_window() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc::now();
+        let usage: Vec<UsageRecord> = vec![];
+        storage
+            .replace_snapshot(
+                now - Duration::days(3),
+                &["openai".to_string()],
+                &usage,
+                &[
+                    cost("gpt-4o", now - Duration::hours(2), 4.0),
+                    cost("gpt-4o-mini", now - Duration::hours(3), 1.0),
+                    cost("gpt-4o", now - Duration::hours(30), 2.0),
+                ],
+            )
+            .expect("seed costs");
+
+        let summary = build_summary(&storage, TimeWindow::OneDay, EdgeBuckets::Exclude, now)
+            .expect("summary");
+        assert!((summary.cost - 5.0).abs() < 1e-9);
+        assert!((summary.previous_cost - 2.0).abs() < 1e-9);
+        assert_eq!(summary.top_models[0].model, "gpt-4o");
+        assert!((summary.top_models[0].previous_cost - 2.0).abs() < 1e-9);
+        assert_eq!(summary.top_models[1].previous_cost, 0.0);
+    }
+
+    #[test]
+    fn default_digest_lists_top_models_or_says_there_was_no_spend() {
+        let generated_at = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 3, 9, 8, 0, 0).unwrap();
+        let mut summary = SpendSummary {
+            window: "7d".into(),
+            generated_at,
+            tokens: 1200,
+            cost: 3.0,
+            previous_cost: 2.0,
+            top_models: vec![ModelSpend {
+                model: "gpt-4o".into(),
+                cost: 3.0,
+                previous_cost: 2.0,
+            }],
+        };
+        let template = Template::parse(DEFAULT_DIGEST_TEMPLATE).unwrap();
+        let money = MoneyFormat::default();
+        assert_eq!(
+            template.render(&digest_context(&summary, &money)).unwrap(),
+            "LLM spend · last 7d · 2026-03-09\n\n\
+             Cost: $3.00 (+$1.00 (+50.0%) vs previous 7d)\n\
+             Tokens: 1200\n\n\
+             Top models:\n\
+             1. gpt-4o: $3.00 (+$1.00 (+50.0%))\n"
+        );
+
+        summary.top_models.clear();
+        assert!(template
+            .render(&digest_context(&summary, &money))
+            .unwrap()
+            .ends_with("Top models:\nNo spend recorded in this window.\n"));
+    }
+
+    #[test]
+    fn query_results_render_as_padded_table_or_csv() {
+        let result = QueryResult {
+            columns: vec!["model".into(), "cost".into()],
+            rows: vec![
+                vec!["gpt-4o".into(), "1.5".into()],
+                vec!["o1, preview".into(), "".into()],
+            ],
+            truncated: false,
+        };
+        assert_eq!(
+            query_table(&result),
+            "model        cost\ngpt-4o       1.5\no1, preview\n(2 rows)\n"
+        );
+        assert_eq!(
+            query_csv(&result).unwrap(),
+            "model,cost\ngpt-4o,1.5\n\"o1, preview\",\n"
+        );
+    }
+
+    #[test]
+    fn refresh_stats_table_aligns_columns() {
+        let table = refresh_stats_table(&[ProviderRefreshStats {
+            provider: "openai".into(),
+            rows_fetched: 12,
+            pages: 1,
+            http_ms: 340,
+            parse_ms: 2,
+            rows_stored: 10,
+            rows_dropped: 2,
+            ..ProviderRefreshStats::default()
+        }]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), lines[1].len());
+        assert!(lines[1].starts_with("openai"));
+        assert!(lines[1].ends_with("      10        2"));
+
+        let table = refresh_stats_table(&[ProviderRefreshStats {
+            provider: "anthropic".into(),
+            parser_version: Some("anthropic/flat-v1".into()),
+            parser_fallback: true,
+            ..ProviderRefreshStats::default()
+        }]);
+        assert!(table.ends_with(
+            "anthropic: usage payload matched no known schema; parsed it as anthropic/flat-v1\n"
+        ));
+    }
+}
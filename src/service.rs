@@ -1,12 +1,42 @@
-use crate::config::{normalize_provider_name, AppConfig, ProviderSettings};
+use crate::aws::AwsCredentials;
+use crate::budgets::{self, BudgetCheck};
+use crate::config::{
+    delete_api_key, normalize_provider_name, set_api_key, stored_api_key, AppConfig, BudgetPeriod,
+    ExportPipeline, OverlapPolicy, ProviderSettings,
+};
+use crate::deprecations::{DeprecationCheck, DEPRECATION_ALERT_KIND};
 use crate::error::AppError;
-use crate::models::{Snapshot, TimeWindow};
+use crate::format::MoneyFormat;
+use crate::fx::FxRates;
+use crate::models::{
+    AccountBalance, AlertRecord, BreakdownDetail, ClockSkew, CostDelta, CostRecord, EdgeBuckets,
+    Granularity, ProviderRefreshStats, RateLimit, Snapshot, TimeWindow, UsageRecord,
+};
+use crate::net::{self, HostAllowlist};
+use crate::notifier::{self, AlertLevel};
 use crate::providers::anthropic::AnthropicAdapter;
+use crate::providers::azure_openai::AzureOpenAiAdapter;
+use crate::providers::bedrock::BedrockAdapter;
+use crate::providers::claude_code::ClaudeCodeAdapter;
+use crate::providers::codex_cli::CodexCliAdapter;
+use crate::providers::deepseek::DeepSeekAdapter;
+use crate::providers::gemini::GeminiAdapter;
+use crate::providers::local::vendor_for_model;
+use crate::providers::local_session::LocalSessionAdapter;
+use crate::providers::ollama::OllamaAdapter;
 use crate::providers::openai::OpenAiAdapter;
-use crate::providers::{ProviderAdapter, ProviderContext};
+use crate::providers::openai_compatible::OpenAiCompatibleAdapter;
+use crate::providers::openrouter::OpenRouterAdapter;
+use crate::providers::{
+    throttle, CredentialKind, Credentials, ProviderAdapter, ProviderContext, RequestBudget,
+};
+use crate::report::SpendSummary;
+use crate::sinks::{self, ExportJob};
 use crate::storage::Storage;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, DurationRound, Utc};
 use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::time::Instant;
 
 pub struct ProviderTestReport {
@@ -14,17 +44,339 @@ pub struct ProviderTestReport {
     pub duration_ms: u128,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct FreshnessCache {
+    entries: HashMap<(String, i64), DateTime<Utc>>,
+}
+
+impl FreshnessCache {
+    /// Answers from memory when a remembered fetch is still inside the horizon,
+    /// otherwise consults `refresh_log` for a newer one.
+    fn is_fresh(
+        &mut self,
+        storage: &Storage,
+        provider: &str,
+        window_hours: i64,
+        horizon_start: DateTime<Utc>,
+    ) -> bool {
+        let key = (provider.to_string(), window_hours);
+        if self
+            .entries
+            .get(&key)
+            .is_some_and(|at| *at >= horizon_start)
+        {
+            return true;
+        }
+        match storage.last_successful_refresh(provider, window_hours) {
+            Ok(Some(at)) => {
+                self.entries.insert(key, at);
+                at >= horizon_start
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The longest dashboard window.
+pub const WIDEST_WINDOW: TimeWindow = TimeWindow::ThirtyDays;
+
+/// Lookback and resolution, in hours, of a refresh for `window`.
+pub fn fetch_span(cfg: &AppConfig, window: TimeWindow) -> (i64, i64) {
+    if cfg.refresh_all_windows {
+        (WIDEST_WINDOW.as_hours(), TimeWindow::OneDay.as_hours())
+    } else {
+        (window.as_hours(), window.as_hours())
+    }
+}
+
+pub fn refresh_needed(
+    cfg: &AppConfig,
+    window: TimeWindow,
+    storage: &Storage,
+    cache: &mut FreshnessCache,
+    now: DateTime<Utc>,
+) -> bool {
+    let horizon_start = now - Duration::seconds(cfg.refresh_seconds.max(10) as i64);
+    let (lookback, _) = fetch_span(cfg, window);
+    !cfg.enabled_providers
+        .iter()
+        .all(|provider| cache.is_fresh(storage, provider, lookback, horizon_start))
+}
+
+const QUARANTINE_MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+
+/// Wait before re-probing a provider with `failures` consecutive failures:
+/// `refresh_seconds` at the threshold, doubling with each further failure.
+pub fn quarantine_backoff(cfg: &AppConfig, failures: u32) -> Duration {
+    let extra = failures
+        .saturating_sub(cfg.quarantine_after_failures)
+        .min(20);
+    let secs = (cfg.refresh_seconds.max(10) as i64).saturating_mul(1 << extra);
+    Duration::seconds(secs.min(QUARANTINE_MAX_BACKOFF_SECS))
+}
+
+/// Requests one refresh or backfill may send under `[request_caps]`: the
+/// per-refresh cap, lowered to what is left of today's (UTC) cap.
+pub fn request_budget(
+    cfg: &AppConfig,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<Arc<RequestBudget>, AppError> {
+    let Some(caps) = cfg.request_caps else {
+        return Ok(Arc::default());
+    };
+    let left_today = match caps.per_day {
+        Some(cap) => {
+            let day_start = now.duration_trunc(Duration::days(1)).unwrap_or(now);
+            let used = storage.requests_since(day_start)?;
+            Some(u64::from(cap).saturating_sub(used) as u32)
+        }
+        None => None,
+    };
+    let limit = match (caps.per_refresh, left_today) {
+        (Some(per_refresh), Some(left)) => Some(per_refresh.min(left)),
+        (per_refresh, left) => per_refresh.or(left),
+    };
+    Ok(Arc::new(RequestBudget::new(limit)))
+}
+
+/// Enabled providers that failed `quarantine_after_failures` refreshes in a
+/// row and are still inside their back-off. Once it elapses the provider is
+/// fetched again as a probe; success clears the streak.
+pub fn quarantined_providers(
+    cfg: &AppConfig,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<Vec<String>, AppError> {
+    if cfg.quarantine_after_failures == 0 {
+        return Ok(Vec::new());
+    }
+    let mut out = Vec::new();
+    for provider in &cfg.enabled_providers {
+        let (failures, last) = storage.consecutive_failures(provider)?;
+        if failures < cfg.quarantine_after_failures {
+            continue;
+        }
+        if last.is_some_and(|at| now < at + quarantine_backoff(cfg, failures)) {
+            out.push(provider.clone());
+        }
+    }
+    Ok(out)
+}
+
+/// Alert kind for expired provider keys; the subject is the provider.
+pub const KEY_EXPIRED_ALERT_KIND: &str = "key_expired";
+
+/// Outcome of [`check_key_expiry`]: alerts opened for keys past their
+/// `key_expires_at`, and providers whose open alert was resolved.
+#[derive(Debug, Default)]
+pub struct KeyExpiryCheck {
+    pub expired: Vec<AlertRecord>,
+    pub renewed: Vec<String>,
+}
+
+/// Opens one `key_expired` alert per provider whose key has expired by `now`
+/// and resolves it once the date is moved forward or removed.
+pub fn check_key_expiry(
+    cfg: &AppConfig,
+    storage: &Storage,
+    now: DateTime<Utc>,
+) -> Result<KeyExpiryCheck, AppError> {
+    let mut out = KeyExpiryCheck::default();
+    let expiries = cfg.key_expiries(now.date_naive());
+    for provider in cfg.provider_settings.keys() {
+        match expiries.iter().find(|(name, _)| name == provider) {
+            Some((_, days)) if *days <= 0 => {
+                let message = format!(
+                    "API key for '{provider}' expired on {}; rotate it with `add-provider {provider} --api-key ...`",
+                    now.date_naive() + Duration::days(*days)
+                );
+                if let Some(alert) =
+                    storage.record_alert(KEY_EXPIRED_ALERT_KIND, provider, &message, now)?
+                {
+                    out.expired.push(alert);
+                }
+            }
+            _ => {
+                if storage.resolve_alerts(KEY_EXPIRED_ALERT_KIND, provider, now)? > 0 {
+                    out.renewed.push(provider.clone());
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Latest successful fetch covering `window` for each enabled provider, or
+/// `None` for providers never fetched for a window at least that long.
+pub fn provider_data_as_of(
+    cfg: &AppConfig,
+    window: TimeWindow,
+    storage: &Storage,
+) -> HashMap<String, Option<DateTime<Utc>>> {
+    cfg.enabled_providers
+        .iter()
+        .map(|provider| {
+            let at = storage
+                .last_successful_refresh(provider, window.as_hours())
+                .ok()
+                .flatten();
+            (provider.clone(), at)
+        })
+        .collect()
+}
+
+/// Latest stored balance of each provider that reports one.
+pub fn balances_by_provider(
+    storage: &Storage,
+) -> Result<HashMap<String, AccountBalance>, AppError> {
+    Ok(storage
+        .balances()?
+        .into_iter()
+        .map(|b| (b.provider.clone(), b))
+        .collect())
+}
+
+/// Oldest successful fetch covering `window` across enabled providers, i.e. how
+/// current the stored data for that window is. `None` if any provider has never
+/// been fetched for a window at least that long.
+pub fn window_data_as_of(
+    cfg: &AppConfig,
+    window: TimeWindow,
+    storage: &Storage,
+) -> Option<DateTime<Utc>> {
+    provider_data_as_of(cfg, window, storage)
+        .into_values()
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .min()
+}
+
+/// Cost since UTC midnight, over the last 7 days and over the last 30 days,
+/// for the dashboard's multi-window KPI row. Each goes through the
+/// `aggregate_since` cache, so repeated redraws don't re-query.
+pub fn multi_window_costs(
+    storage: &Storage,
+    edge: EdgeBuckets,
+    now: DateTime<Utc>,
+) -> Result<[f64; 3], AppError> {
+    let today = budgets::period_start(BudgetPeriod::Daily, now);
+    Ok([
+        storage.aggregate_since(today, edge)?.1,
+        storage.aggregate_since(now - Duration::days(7), edge)?.1,
+        storage.aggregate_since(now - Duration::days(30), edge)?.1,
+    ])
+}
+
+/// `rows` (model, cost) merged into `[model_groups]` families, costliest first.
+pub fn group_model_breakdown(cfg: &AppConfig, rows: Vec<(String, f64)>) -> Vec<(String, f64)> {
+    let mut families: Vec<(String, f64)> = Vec::new();
+    for (model, cost) in rows {
+        let family = cfg.model_family(&model);
+        match families.iter_mut().find(|(name, _)| name == family) {
+            Some((_, total)) => *total += cost,
+            None => families.push((family.to_string(), cost)),
+        }
+    }
+    families.sort_by(|a, b| b.1.total_cmp(&a.1));
+    families
+}
+
+/// Most models the dashboard's model panel lists besides pinned ones.
+pub const BREAKDOWN_MODELS: usize = 10;
+
+/// `rows` (name, cost) with the `pinned` names first, in pin order and at zero
+/// cost when they had no spend, then the rest as given, cut to `limit`.
+pub fn pin_breakdown(
+    mut rows: Vec<(String, f64)>,
+    pinned: &[String],
+    limit: Option<usize>,
+) -> Vec<(String, f64)> {
+    let mut out = Vec::with_capacity(rows.len());
+    for name in pinned {
+        if out.iter().any(|(n, _)| n == name) {
+            continue;
+        }
+        match rows.iter().position(|(n, _)| n == name) {
+            Some(idx) => out.push(rows.remove(idx)),
+            None => out.push((name.clone(), 0.0)),
+        }
+    }
+    rows.truncate(limit.unwrap_or(usize::MAX));
+    out.extend(rows);
+    out
+}
+
+/// Per provider and per model (or `[model_groups]` family when `group`),
+/// tokens since `since` and cost in the previous window of the same length,
+/// which ends at `since`.
+pub fn breakdown_details(
+    cfg: &AppConfig,
+    storage: &Storage,
+    group: bool,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<BreakdownDetails, AppError> {
+    let mut details = BreakdownDetails::default();
+    let model_key = |model: &str| {
+        if group {
+            cfg.model_family(model).to_string()
+        } else {
+            model.to_string()
+        }
+    };
+    for row in storage.usage_by_model_since(since)? {
+        for detail in [
+            details.providers.entry(row.provider.clone()).or_default(),
+            details.models.entry(model_key(&row.model)).or_default(),
+        ] {
+            detail.input_tokens += row.input_tokens;
+            detail.output_tokens += row.output_tokens;
+        }
+    }
+    let previous_since = since - (now - since);
+    for (provider, model, cost) in storage.cost_by_provider_model_between(previous_since, since)? {
+        details.providers.entry(provider).or_default().previous_cost += cost;
+        details
+            .models
+            .entry(model_key(&model))
+            .or_default()
+            .previous_cost += cost;
+    }
+    Ok(details)
+}
+
+/// [`BreakdownDetail`]s keyed by provider and by model name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BreakdownDetails {
+    pub providers: HashMap<String, BreakdownDetail>,
+    pub models: HashMap<String, BreakdownDetail>,
+}
+
+/// Data older than two refresh intervals is stale: one late tick is normal,
+/// two mean refreshes stopped, e.g. while the laptop slept.
+pub fn is_stale(cfg: &AppConfig, as_of: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now - as_of > Duration::seconds(2 * cfg.refresh_seconds.max(10) as i64)
+}
+
 pub struct MeterService {
     client: Client,
+    /// Hosts `client` may reach when `cfg.local_only` was set at creation.
+    allowlist: Arc<RwLock<HostAllowlist>>,
 }
 
 impl MeterService {
-    pub fn new() -> Result<Self, AppError> {
-        let client = Client::builder()
-            .connect_timeout(std::time::Duration::from_secs(10))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()?;
-        Ok(Self { client })
+    pub fn new(cfg: &AppConfig) -> Result<Self, AppError> {
+        let allowlist = Arc::new(RwLock::new(HostAllowlist::from_config(cfg, adapter_for)));
+        let client = net::client(cfg.local_only, allowlist.clone())?;
+        Ok(Self { client, allowlist })
+    }
+
+    /// Re-reads the allowed hosts, for providers configured since creation.
+    fn refresh_allowlist(&self, cfg: &AppConfig) {
+        if let Ok(mut allowlist) = self.allowlist.write() {
+            *allowlist = HostAllowlist::from_config(cfg, adapter_for);
+        }
     }
 
     pub async fn test_provider_connection(
@@ -34,80 +386,1521 @@ impl MeterService {
         settings: ProviderSettings,
     ) -> Result<ProviderTestReport, AppError> {
         let provider = normalize_provider_name(provider);
+        let kind = settings
+            .kind
+            .as_deref()
+            .map_or(provider.clone(), normalize_provider_name);
+        let adapter = adapter_for(&kind)
+            .ok_or_else(|| AppError::Config(format!("Unsupported provider '{provider}'.")))?;
+        // The account being set up may not be saved yet.
+        if let Ok(mut allowlist) = self.allowlist.write() {
+            allowlist.add_provider(adapter.as_ref(), &settings);
+        }
+        let credentials = match adapter.credential_kind() {
+            CredentialKind::ApiKey => Credentials::ApiKey(api_key),
+            CredentialKind::Aws => aws_credentials(&settings)?,
+            CredentialKind::None => Credentials::None,
+        };
         let ctx = ProviderContext {
-            api_key,
+            credentials,
+            throttle: throttle::for_account(&provider, &settings),
             settings,
-            window: TimeWindow::SevenDays,
+            lookback_hours: TimeWindow::SevenDays.as_hours(),
+            resolution_hours: TimeWindow::SevenDays.as_hours(),
             refresh_end: Utc::now(),
+            requests: Arc::default(),
         };
         let started = Instant::now();
-        let status_code = match provider.as_str() {
-            "openai" => OpenAiAdapter.test_connection(&self.client, &ctx).await?,
-            "anthropic" => AnthropicAdapter.test_connection(&self.client, &ctx).await?,
-            _ => {
-                return Err(AppError::Config(format!(
-                    "Unsupported provider '{provider}'."
-                )));
-            }
-        };
+        let status_code = adapter.test_connection(&self.client, &ctx).await?;
         Ok(ProviderTestReport {
             status_code,
             duration_ms: started.elapsed().as_millis(),
         })
     }
 
+    pub async fn notify_alert(
+        &self,
+        cfg: &AppConfig,
+        alert: &AlertRecord,
+        level: AlertLevel,
+    ) -> Result<(), AppError> {
+        notifier::notify_triggered(&self.client, &cfg.notifier, alert, level).await
+    }
+
+    pub async fn notify_alert_resolved(
+        &self,
+        cfg: &AppConfig,
+        kind: &str,
+        subject: &str,
+    ) -> Result<(), AppError> {
+        notifier::notify_resolved(&self.client, &cfg.notifier, kind, subject).await
+    }
+
+    /// Notifies for newly expired provider keys and resolves alerts for keys
+    /// that were renewed. Returns one line per expiry or failure.
+    pub async fn notify_key_expiry(&self, cfg: &AppConfig, check: KeyExpiryCheck) -> Vec<String> {
+        let mut lines = Vec::new();
+        for alert in check.expired {
+            lines.push(alert.message.clone());
+            if let Err(e) = self.notify_alert(cfg, &alert, AlertLevel::Warning).await {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+        }
+        for provider in check.renewed {
+            if let Err(e) = self
+                .notify_alert_resolved(cfg, KEY_EXPIRED_ALERT_KIND, &provider)
+                .await
+            {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+        }
+        lines
+    }
+
+    /// Sends a warning for each model newly flagged by
+    /// [`crate::deprecations::check`] and resolves the cleared ones.
+    pub async fn notify_deprecations(
+        &self,
+        cfg: &AppConfig,
+        check: DeprecationCheck,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        for alert in check.flagged {
+            lines.push(alert.message.clone());
+            if let Err(e) = self.notify_alert(cfg, &alert, AlertLevel::Warning).await {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+        }
+        for subject in check.cleared {
+            if let Err(e) = self
+                .notify_alert_resolved(cfg, DEPRECATION_ALERT_KIND, &subject)
+                .await
+            {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+        }
+        lines
+    }
+
+    /// Pages the notifier for newly exceeded budgets and runs their
+    /// `on_exceeded` commands; sends resolves for budgets back under their
+    /// limit. Returns one line per breach or failure for the caller to show.
+    pub async fn enforce_budgets(&self, cfg: &AppConfig, check: BudgetCheck) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (status, alert) in check.breached {
+            lines.push(status.message());
+            if let Err(e) = self.notify_alert(cfg, &alert, AlertLevel::Critical).await {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+            let Some(command) = status.budget.on_exceeded.clone() else {
+                continue;
+            };
+            let name = status.budget.name.clone();
+            let timeout = (cfg.on_exceeded_timeout_secs > 0)
+                .then(|| std::time::Duration::from_secs(cfg.on_exceeded_timeout_secs));
+            let ran = tokio::task::spawn_blocking(move || {
+                budgets::run_on_exceeded(&command, &status, timeout)
+            })
+            .await
+            .map_err(|e| AppError::Config(format!("on_exceeded task failed: {e}")))
+            .and_then(|r| r);
+            match ran {
+                Ok(output) if output.status.success() => {
+                    lines.push(format!("on_exceeded for '{name}' ran successfully"));
+                }
+                Ok(output) => lines.push(format!(
+                    "on_exceeded for '{name}' failed ({}): {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )),
+                Err(e) => lines.push(format!("on_exceeded for '{name}' failed: {e}")),
+            }
+        }
+        for name in check.recovered {
+            if let Err(e) = self
+                .notify_alert_resolved(cfg, budgets::BUDGET_ALERT_KIND, &name)
+                .await
+            {
+                lines.push(format!("alert notifier failed: {e}"));
+            }
+        }
+        lines
+    }
+
+    /// Posts the refresh summary to `[notifier.webhook]`; a no-op when unset.
+    pub async fn send_refresh_webhook(
+        &self,
+        cfg: &AppConfig,
+        snapshot: &Snapshot,
+    ) -> Result<(), AppError> {
+        match &cfg.notifier.webhook {
+            Some(webhook) => {
+                notifier::post_refresh_webhook(&self.client, webhook, snapshot, Utc::now()).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the refresh's cost points to `[notifier.influx]`; a no-op when unset.
+    pub async fn send_influx_points(
+        &self,
+        cfg: &AppConfig,
+        snapshot: &Snapshot,
+    ) -> Result<(), AppError> {
+        match &cfg.notifier.influx {
+            Some(influx) => notifier::post_influx(&self.client, influx, snapshot).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Renders `rows` for `pipeline` and hands them to its sink.
+    pub async fn run_export(
+        &self,
+        pipeline: &ExportPipeline,
+        rows: &[CostRecord],
+        currency: &str,
+        now: DateTime<Utc>,
+    ) -> Result<String, AppError> {
+        let sink = sinks::find_sink(&pipeline.sink)?;
+        sink.deliver(&ExportJob {
+            client: &self.client,
+            pipeline,
+            rows,
+            currency,
+            now,
+        })
+        .await
+    }
+
+    /// Posts `text` as a plain Slack message, e.g. a rendered digest.
+    pub async fn send_slack_text(&self, webhook_url: &str, text: &str) -> Result<(), AppError> {
+        notifier::post_slack(
+            &self.client,
+            webhook_url,
+            &serde_json::json!({ "text": text }),
+        )
+        .await
+    }
+
+    pub async fn send_slack_summary(
+        &self,
+        webhook_url: &str,
+        summary: &SpendSummary,
+        money: &MoneyFormat,
+    ) -> Result<(), AppError> {
+        notifier::post_slack(
+            &self.client,
+            webhook_url,
+            &notifier::slack_summary_blocks(summary, money),
+        )
+        .await
+    }
+
     pub async fn refresh(
         &self,
         cfg: &AppConfig,
         window: TimeWindow,
-        storage: &mut Storage,
+        storage: &Storage,
     ) -> Result<Snapshot, AppError> {
-        let refresh_end = Utc::now();
-        let since = refresh_end - Duration::hours(window.as_hours());
-        let mut usage = Vec::new();
-        let mut cost = Vec::new();
-        let mut refreshed_providers = Vec::new();
-
-        let adapters: Vec<Box<dyn ProviderAdapter>> =
-            vec![Box::new(OpenAiAdapter), Box::new(AnthropicAdapter)];
+        let mut suspended = storage.providers_needing_reauth()?;
+        suspended.extend(quarantined_providers(cfg, storage, Utc::now())?);
+        let requests = request_budget(cfg, storage, Utc::now())?;
+        self.fetch_snapshot(cfg, window, &suspended, requests)
+            .await?
+            .persist(storage)
+    }
 
-        for adapter in adapters {
-            if !cfg
-                .enabled_providers
+    /// Network half of [`MeterService::refresh`]; touches no storage so callers
+    /// on the async runtime can persist the result on a blocking thread.
+    /// Providers in `suspended` (awaiting re-auth or quarantined) are not called.
+    /// With `refresh_all_windows`, every refresh covers [`WIDEST_WINDOW`] at
+    /// the bucket width of the 1d window. `requests` is what
+    /// [`request_budget`] allows. Accounts still unfetched when
+    /// `refresh_timeout_secs` runs out are left out and listed in
+    /// [`Snapshot::timed_out`].
+    pub async fn fetch_snapshot(
+        &self,
+        cfg: &AppConfig,
+        window: TimeWindow,
+        suspended: &[String],
+        requests: Arc<RequestBudget>,
+    ) -> Result<FetchedSnapshot, AppError> {
+        let (lookback, resolution) = fetch_span(cfg, window);
+        let deadline = (cfg.refresh_timeout_secs > 0)
+            .then(|| Instant::now() + std::time::Duration::from_secs(cfg.refresh_timeout_secs));
+        self.fetch_lookback(cfg, lookback, resolution, requests, deadline, |name| {
+            cfg.enabled_providers
                 .iter()
-                .any(|p| p.eq_ignore_ascii_case(adapter.name()))
-            {
+                .any(|p| p.eq_ignore_ascii_case(name))
+                && !suspended.iter().any(|p| p.eq_ignore_ascii_case(name))
+        })
+        .await
+    }
+
+    /// Fetches `days` of history (or the provider's maximum when `None`) for one
+    /// provider, e.g. right after it is first enabled.
+    pub async fn fetch_backfill(
+        &self,
+        cfg: &AppConfig,
+        provider: &str,
+        days: Option<u32>,
+        requests: Arc<RequestBudget>,
+    ) -> Result<FetchedSnapshot, AppError> {
+        let adapter = adapter_for(&cfg.provider_kind(provider))
+            .ok_or_else(|| AppError::Config(format!("Unsupported provider '{provider}'.")))?;
+        let max_days = adapter.max_history_days();
+        let days = days.map_or(max_days, |d| d.clamp(1, max_days));
+        let hours = i64::from(days) * 24;
+        self.fetch_lookback(cfg, hours, hours, requests, None, |name| {
+            name.eq_ignore_ascii_case(provider)
+        })
+        .await
+    }
+
+    pub async fn backfill(
+        &self,
+        cfg: &AppConfig,
+        provider: &str,
+        days: Option<u32>,
+        storage: &Storage,
+    ) -> Result<Snapshot, AppError> {
+        let requests = request_budget(cfg, storage, Utc::now())?;
+        self.fetch_backfill(cfg, provider, days, requests)
+            .await?
+            .persist(storage)
+    }
+
+    async fn fetch_lookback(
+        &self,
+        cfg: &AppConfig,
+        lookback_hours: i64,
+        resolution_hours: i64,
+        requests: Arc<RequestBudget>,
+        deadline: Option<Instant>,
+        include: impl Fn(&str) -> bool,
+    ) -> Result<FetchedSnapshot, AppError> {
+        self.refresh_allowlist(cfg);
+        let refresh_end = Utc::now();
+        let mut fetched = FetchedSnapshot {
+            since: refresh_end - Duration::hours(lookback_hours),
+            window_hours: lookback_hours,
+            providers: Vec::new(),
+            failures: Vec::new(),
+            interrupted: None,
+            snapshot: Snapshot {
+                usage: Vec::new(),
+                cost: Vec::new(),
+                fetched_at: refresh_end,
+                deltas: Vec::new(),
+                stats: Vec::new(),
+                capped: Vec::new(),
+                timed_out: Vec::new(),
+                failed: Vec::new(),
+                clock_skew: None,
+            },
+            rate_limits: Vec::new(),
+            balances: Vec::new(),
+            overlaps: Vec::new(),
+            requests: requests.clone(),
+        };
+
+        let fx = FxRates::from_config(cfg);
+        for (account, adapter) in accounts(cfg) {
+            if !include(&account) {
+                continue;
+            }
+            if requests.is_exhausted() {
+                fetched.snapshot.capped.push(account);
+                continue;
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                fetched.snapshot.timed_out.push(account);
                 continue;
             }
 
             let settings = cfg
                 .provider_settings
-                .get(adapter.name())
+                .get(&account)
                 .cloned()
                 .unwrap_or_default();
-            let api_key = crate::config::get_api_key(adapter.name())?;
+            let credentials = match adapter.credential_kind() {
+                CredentialKind::ApiKey => {
+                    crate::config::provider_api_key(cfg, &account).map(Credentials::ApiKey)
+                }
+                CredentialKind::Aws => aws_credentials(&settings),
+                CredentialKind::None => Ok(Credentials::None),
+            };
+            // A failing account is logged on persist and skipped; the others
+            // are still fetched and stored.
+            let credentials = match credentials {
+                Ok(credentials) => credentials,
+                Err(e) => {
+                    fetched.failures.push((account, e));
+                    continue;
+                }
+            };
 
+            let settings_overlaps = settings.overlaps.clone();
+            let on_overlap = settings.on_overlap.unwrap_or_default();
             let ctx = ProviderContext {
-                api_key,
+                credentials,
+                throttle: throttle::for_account(&account, &settings),
                 settings,
-                window,
+                lookback_hours,
+                resolution_hours,
                 refresh_end,
+                requests: requests.clone(),
+            };
+
+            // An account the cap or the deadline interrupts keeps its stored
+            // rows; replacing them with a partial fetch would lose data.
+            let Some(usage) = until(deadline, adapter.fetch_usage(&self.client, &ctx)).await else {
+                fetched.interrupted = Some(account.clone());
+                fetched.snapshot.timed_out.push(account);
+                continue;
+            };
+            let mut usage = match usage {
+                Ok(usage) => usage,
+                Err(AppError::RequestCap(_)) => {
+                    fetched.snapshot.capped.push(account);
+                    continue;
+                }
+                Err(e) => {
+                    fetched.failures.push((account, e));
+                    continue;
+                }
+            };
+            for row in &mut usage.rows {
+                row.provider.clone_from(&account);
+            }
+            if let Some(skew) = usage.clock_skew {
+                let seconds = skew.num_seconds();
+                if seconds.unsigned_abs() >= cfg.clock_skew_warning_secs
+                    && fetched
+                        .snapshot
+                        .clock_skew
+                        .as_ref()
+                        .is_none_or(|worst| worst.seconds.abs() < seconds.abs())
+                {
+                    fetched.snapshot.clock_skew = Some(ClockSkew {
+                        provider: account.clone(),
+                        seconds,
+                    });
+                }
+            }
+            let rows_fetched = usage.rows.len();
+            let rows = normalize_usage(usage.rows, cfg.usage_granularity);
+            let reported = match usage.costs {
+                Some(cost) => Ok(Some(cost)),
+                None => match until(deadline, adapter.fetch_costs(&self.client, &ctx)).await {
+                    Some(reported) => reported,
+                    None => {
+                        fetched.interrupted = Some(account.clone());
+                        fetched.snapshot.timed_out.push(account);
+                        continue;
+                    }
+                },
+            };
+            let (mut rows_cost, rows_dropped) = match reported {
+                Ok(Some(mut cost)) => {
+                    for row in &mut cost {
+                        row.provider.clone_from(&account);
+                    }
+                    (cost, 0)
+                }
+                Ok(None) => {
+                    let cost = adapter.derive_costs(&rows, &cfg.pricing_overrides);
+                    let dropped = rows.len() - cost.len();
+                    (cost, dropped)
+                }
+                Err(AppError::RequestCap(_)) => {
+                    fetched.snapshot.capped.push(account);
+                    continue;
+                }
+                Err(e) => {
+                    fetched.failures.push((account, e));
+                    continue;
+                }
             };
+            // Costs are stored in the currency the account is billed in.
+            if let Some(currency) = &ctx.settings.billing_currency {
+                if let Err(e) = fx.rows_from_usd(&mut rows_cost, currency) {
+                    fetched.failures.push((account, e));
+                    continue;
+                }
+            }
+            fetched.snapshot.stats.push(ProviderRefreshStats {
+                provider: account.clone(),
+                rows_fetched,
+                pages: usage.pages,
+                http_ms: usage.http_ms,
+                parse_ms: usage.parse_ms,
+                rows_stored: rows_cost.len(),
+                rows_dropped,
+                parser_version: usage.parser_version.map(str::to_string),
+                parser_fallback: usage.parser_fallback,
+                ..ProviderRefreshStats::default()
+            });
+            for api_account in &settings_overlaps {
+                let api_account = normalize_provider_name(api_account);
+                fetched.overlaps.push(OverlapRule {
+                    account: account.clone(),
+                    vendor: cfg.provider_kind(&api_account),
+                    api_account,
+                    policy: on_overlap,
+                });
+            }
 
-            let rows = adapter.fetch_usage(&self.client, &ctx).await?;
-            let rows_cost = adapter.derive_costs(&rows, &cfg.pricing_overrides);
+            // Limits are supplementary; failing to read them keeps the
+            // previously stored ones rather than failing the refresh.
+            if let Some(Ok(Some(mut limits))) =
+                until(deadline, adapter.fetch_rate_limits(&self.client, &ctx)).await
+            {
+                for limit in &mut limits {
+                    limit.provider.clone_from(&account);
+                }
+                fetched.rate_limits.push((account.clone(), limits));
+            }
+            // Balances likewise keep the last one read when this fails.
+            if let Some(Ok(Some(mut balance))) =
+                until(deadline, adapter.fetch_balance(&self.client, &ctx)).await
+            {
+                balance.provider.clone_from(&account);
+                fetched.balances.push(balance);
+            }
 
-            usage.extend(rows);
-            cost.extend(rows_cost);
-            refreshed_providers.push(adapter.name().to_string());
+            fetched.snapshot.usage.extend(rows);
+            fetched.snapshot.cost.extend(rows_cost);
+            fetched.providers.push(account);
         }
 
-        storage.replace_snapshot(since, &refreshed_providers, &usage, &cost)?;
+        Ok(fetched)
+    }
+}
+
+/// Awaits `fut` unless `deadline` passes first, which yields `None` and
+/// drops the request in flight.
+async fn until<T>(
+    deadline: Option<Instant>,
+    fut: impl std::future::Future<Output = T>,
+) -> Option<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// Resamples provider rows onto `granularity` bucket starts, merging rows that
+/// land in the same bucket. Rows from a coarser source cannot be split, so
+/// they stay on their own bucket start; `source_granularity` keeps the
+/// provider's original width either way. `bucket_end` is set from the start
+/// and the stored width, so it does not depend on how the provider reported
+/// it. Merged latencies are averaged, weighted by tokens.
+fn normalize_usage(rows: Vec<UsageRecord>, granularity: Granularity) -> Vec<UsageRecord> {
+    let mut buckets: std::collections::BTreeMap<_, UsageRecord> = Default::default();
+    for row in rows {
+        let width = row
+            .source_granularity
+            .map_or(granularity, |s| s.max(granularity));
+        let start = row
+            .timestamp
+            .duration_trunc(width.duration())
+            .unwrap_or(row.timestamp);
+        let key = (
+            row.provider.clone(),
+            row.model.clone(),
+            start,
+            row.source_granularity,
+        );
+        buckets
+            .entry(key)
+            .and_modify(|b| {
+                b.avg_latency_ms = match (b.avg_latency_ms, row.avg_latency_ms) {
+                    (Some(a), Some(r)) => {
+                        let wa = (b.input_tokens + b.output_tokens).max(1) as f64;
+                        let wr = (row.input_tokens + row.output_tokens).max(1) as f64;
+                        Some((a * wa + r * wr) / (wa + wr))
+                    }
+                    (a, r) => a.or(r),
+                };
+                b.input_tokens += row.input_tokens;
+                b.output_tokens += row.output_tokens;
+                b.cached_tokens += row.cached_tokens;
+            })
+            .or_insert(UsageRecord {
+                timestamp: start,
+                bucket_end: Some(start + width.duration()),
+                ..row
+            });
+    }
+    buckets.into_values().collect()
+}
+
+fn adapters() -> Vec<Box<dyn ProviderAdapter + Send + Sync>> {
+    vec![
+        Box::new(OpenAiAdapter),
+        Box::new(AnthropicAdapter),
+        Box::new(GeminiAdapter),
+        Box::new(AzureOpenAiAdapter),
+        Box::new(BedrockAdapter),
+        Box::new(OpenRouterAdapter),
+        Box::new(DeepSeekAdapter),
+        Box::new(LocalSessionAdapter),
+        Box::new(ClaudeCodeAdapter),
+        Box::new(CodexCliAdapter),
+        Box::new(OllamaAdapter),
+        Box::new(OpenAiCompatibleAdapter),
+    ]
+}
+
+/// AWS credentials for an account from its `aws_profile` and `region`.
+fn aws_credentials(settings: &ProviderSettings) -> Result<Credentials, AppError> {
+    AwsCredentials::resolve(settings.aws_profile.as_deref(), settings.region.as_deref())
+        .map(Credentials::Aws)
+}
+
+/// Names of the built-in provider adapters.
+pub fn adapter_names() -> Vec<&'static str> {
+    adapters().iter().map(|a| a.name()).collect()
+}
+
+/// Whether accounts of adapter `kind` need an API key; unknown kinds do.
+pub fn adapter_needs_api_key(kind: &str) -> bool {
+    adapter_for(kind).is_none_or(|a| a.needs_api_key())
+}
+
+/// Whether accounts of adapter `kind` only work with a `base_url`.
+pub fn adapter_requires_base_url(kind: &str) -> bool {
+    adapter_for(kind).is_some_and(|a| a.requires_base_url())
+}
+
+fn adapter_for(kind: &str) -> Option<Box<dyn ProviderAdapter + Send + Sync>> {
+    adapters()
+        .into_iter()
+        .find(|a| a.name().eq_ignore_ascii_case(kind))
+}
+
+/// Every fetchable account with its adapter: the built-in providers under
+/// their own names plus configured accounts whose `kind` names an adapter.
+fn accounts(cfg: &AppConfig) -> Vec<(String, Box<dyn ProviderAdapter + Send + Sync>)> {
+    let mut names: Vec<String> = adapters().iter().map(|a| a.name().to_string()).collect();
+    let mut extra: Vec<String> = cfg
+        .provider_settings
+        .keys()
+        .filter(|name| !names.contains(name))
+        .cloned()
+        .collect();
+    extra.sort();
+    names.extend(extra);
+    names
+        .into_iter()
+        .filter_map(|name| adapter_for(&cfg.provider_kind(&name)).map(|a| (name, a)))
+        .collect()
+}
 
-        Ok(Snapshot {
-            usage,
-            cost,
-            fetched_at: refresh_end,
+/// Renames the `from` account to `to` across the keyring, stored history and
+/// config. `save` persists the renamed config; if it fails, the key copy and
+/// the storage rename are rolled back so nothing is left half-moved.
+pub fn rename_provider(
+    cfg: &mut AppConfig,
+    storage: &Storage,
+    from: &str,
+    to: &str,
+    save: impl FnOnce(&AppConfig) -> Result<(), AppError>,
+) -> Result<usize, AppError> {
+    let (from, to) = (normalize_provider_name(from), normalize_provider_name(to));
+    let mut renamed = cfg.clone();
+    renamed.rename_provider(&from, &to)?;
+    if stored_api_key(&to)?.is_some() {
+        return Err(AppError::Config(format!(
+            "A key is already stored for '{to}'."
+        )));
+    }
+
+    let key = stored_api_key(&from)?;
+    if let Some(key) = &key {
+        set_api_key(&to, key)?;
+    }
+    let undo_key = || {
+        if key.is_some() {
+            let _ = delete_api_key(&to);
+        }
+    };
+    let moved = match storage.rename_provider(&from, &to) {
+        Ok(moved) => moved,
+        Err(e) => {
+            undo_key();
+            return Err(e);
+        }
+    };
+    if let Err(e) = save(&renamed) {
+        let _ = storage.rename_provider(&to, &from);
+        undo_key();
+        return Err(e);
+    }
+    *cfg = renamed;
+    if key.is_some() {
+        delete_api_key(&from)?;
+    }
+    Ok(moved)
+}
+
+pub struct FetchedSnapshot {
+    since: DateTime<Utc>,
+    window_hours: i64,
+    providers: Vec<String>,
+    /// Accounts whose fetch failed; the others are stored regardless.
+    failures: Vec<(String, AppError)>,
+    /// The account whose requests were still running when the deadline
+    /// passed. Accounts it kept from starting are not logged as failed.
+    interrupted: Option<String>,
+    snapshot: Snapshot,
+    /// Per account, the rate limits it reported this refresh.
+    rate_limits: Vec<(String, Vec<RateLimit>)>,
+    /// Credit left on the fetched accounts that report it.
+    balances: Vec<AccountBalance>,
+    /// `overlaps` entries of the fetched accounts.
+    overlaps: Vec<OverlapRule>,
+    /// The operation's request budget, whose use is logged on persist.
+    requests: Arc<RequestBudget>,
+}
+
+/// A fetched account whose rows of `vendor`'s models `api_account` may also
+/// report.
+struct OverlapRule {
+    account: String,
+    api_account: String,
+    vendor: String,
+    policy: OverlapPolicy,
+}
+
+impl FetchedSnapshot {
+    /// Writes the fetched rows, `refresh_log` entries and per-model cost deltas.
+    /// Failed accounts are logged, keep their stored rows and are listed in
+    /// [`Snapshot::failed`]; the error is only returned when no account was
+    /// fetched. A 401 from a provider that has refreshed before means its key
+    /// expired or was revoked, so it is flagged for re-auth. An account the
+    /// deadline interrupted is logged as failed, so one that keeps hanging
+    /// is quarantined.
+    pub fn persist(mut self, storage: &Storage) -> Result<Snapshot, AppError> {
+        let refresh_end = self.snapshot.fetched_at;
+        let window_hours = self.window_hours;
+        storage.record_requests(refresh_end, self.requests.used())?;
+        for (provider, err) in &self.failures {
+            if err.is_unauthorized() && storage.last_successful_refresh(provider, 0)?.is_some() {
+                storage.mark_needs_reauth(provider, refresh_end, &err.to_string())?;
+            }
+            storage.record_refresh(
+                provider,
+                window_hours,
+                refresh_end,
+                Err(&err.to_string()),
+                None,
+            )?;
+        }
+        if let Some(provider) = &self.interrupted {
+            storage.record_refresh(
+                provider,
+                window_hours,
+                refresh_end,
+                Err("timed out: refresh_timeout_secs reached"),
+                None,
+            )?;
+        }
+        let failures = std::mem::take(&mut self.failures);
+        if self.providers.is_empty() {
+            if let Some((_, err)) = failures.into_iter().next() {
+                return Err(err);
+            }
+        } else {
+            self.snapshot.failed = failures
+                .into_iter()
+                .map(|(provider, err)| (provider, err.to_string()))
+                .collect();
+        }
+        self.resolve_overlaps(storage)?;
+
+        // A provider's first fetch of a window is a backfill, not new spend.
+        let mut baselined = Vec::new();
+        for provider in &self.providers {
+            if storage
+                .last_successful_refresh(provider, window_hours)?
+                .is_some()
+            {
+                baselined.push(provider.clone());
+            }
+        }
+        let before = storage.cost_by_provider_model_since(self.since, &baselined)?;
+
+        storage.replace_snapshot(
+            self.since,
+            &self.providers,
+            &self.snapshot.usage,
+            &self.snapshot.cost,
+        )?;
+        for (provider, limits) in &self.rate_limits {
+            storage.replace_rate_limits(provider, limits)?;
+        }
+        for balance in &self.balances {
+            storage.replace_balance(balance)?;
+        }
+        for stats in &self.snapshot.stats {
+            storage.record_refresh(
+                &stats.provider,
+                window_hours,
+                refresh_end,
+                Ok(stats.rows_fetched),
+                stats.parser_version.as_deref(),
+            )?;
+        }
+
+        let after = storage.cost_by_provider_model_since(self.since, &baselined)?;
+        self.snapshot.deltas = cost_deltas(&before, &after);
+        storage.record_refresh_deltas(refresh_end, &self.snapshot.deltas)?;
+        // Stored rows keep their billing currency; callers sum these in USD.
+        storage.fx_rates().rows_to_usd(&mut self.snapshot.cost);
+        Ok(self.snapshot)
+    }
+
+    /// Applies the `overlaps` rules: rows of an overlapped vendor whose bucket
+    /// ends by the API account's freshness horizon are dropped, or only
+    /// counted under `keep-both`. The horizon is the earlier of the API
+    /// account's latest bucket end and its last successful refresh, taken
+    /// from this refresh when it fetched that account too.
+    fn resolve_overlaps(&mut self, storage: &Storage) -> Result<(), AppError> {
+        for rule in std::mem::take(&mut self.overlaps) {
+            let (latest_end, refreshed_at) = if self.providers.contains(&rule.api_account) {
+                let latest_end = self
+                    .snapshot
+                    .usage
+                    .iter()
+                    .filter(|u| u.provider == rule.api_account)
+                    .map(|u| u.bucket_end.unwrap_or(u.timestamp))
+                    .max();
+                (latest_end, Some(self.snapshot.fetched_at))
+            } else {
+                (
+                    storage.latest_bucket_end(&rule.api_account)?,
+                    storage.last_successful_refresh(&rule.api_account, 0)?,
+                )
+            };
+            let (Some(latest_end), Some(refreshed_at)) = (latest_end, refreshed_at) else {
+                continue;
+            };
+            let horizon = latest_end.min(refreshed_at);
+            let overlaps = |provider: &str, model: &str, start, end: Option<DateTime<Utc>>| {
+                provider == rule.account
+                    && vendor_for_model(model) == Some(rule.vendor.as_str())
+                    && end.unwrap_or(start) <= horizon
+            };
+
+            let rows = self
+                .snapshot
+                .usage
+                .iter()
+                .filter(|u| overlaps(&u.provider, &u.model, u.timestamp, u.bucket_end))
+                .count();
+            let cost: f64 = self
+                .snapshot
+                .cost
+                .iter()
+                .filter(|c| overlaps(&c.provider, &c.model, c.timestamp, c.bucket_end))
+                .map(|c| c.total_cost)
+                .sum();
+            let Some(stats) = self
+                .snapshot
+                .stats
+                .iter_mut()
+                .find(|s| s.provider == rule.account)
+            else {
+                continue;
+            };
+            match rule.policy {
+                OverlapPolicy::PreferApi => {
+                    self.snapshot
+                        .usage
+                        .retain(|u| !overlaps(&u.provider, &u.model, u.timestamp, u.bucket_end));
+                    let before = self.snapshot.cost.len();
+                    self.snapshot
+                        .cost
+                        .retain(|c| !overlaps(&c.provider, &c.model, c.timestamp, c.bucket_end));
+                    // Saturating: adapters that report their own costs need
+                    // not have one cost row per usage row.
+                    let priced = before - self.snapshot.cost.len();
+                    stats.rows_stored = stats.rows_stored.saturating_sub(priced);
+                    stats.rows_dropped = stats
+                        .rows_dropped
+                        .saturating_sub(rows.saturating_sub(priced));
+                    stats.rows_suppressed += rows;
+                    stats.cost_suppressed += cost;
+                }
+                OverlapPolicy::KeepBoth => {
+                    stats.rows_overlapping += rows;
+                    stats.cost_overlapping += cost;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn cost_deltas(
+    before: &HashMap<(String, String), f64>,
+    after: &HashMap<(String, String), f64>,
+) -> Vec<CostDelta> {
+    let mut deltas = before
+        .keys()
+        .chain(after.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|key| {
+            let delta =
+                after.get(key).copied().unwrap_or(0.0) - before.get(key).copied().unwrap_or(0.0);
+            (delta.abs() > 1e-9).then(|| CostDelta {
+                provider: key.0.clone(),
+                model: key.1.clone(),
+                cost_delta: delta,
+            })
         })
+        .collect::<Vec<_>>();
+    deltas.sort_by(|a, b| b.cost_delta.total_cmp(&a.cost_delta));
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RequestCaps;
+    use crate::models::UsageSource;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn cfg_with(providers: &[&str]) -> AppConfig {
+        AppConfig {
+            refresh_seconds: 60,
+            enabled_providers: providers.iter().map(|p| p.to_string()).collect(),
+            ..AppConfig::default()
+        }
+    }
+
+    #[test]
+    fn cost_deltas_cover_new_changed_and_removed_models() {
+        let key = |p: &str, m: &str| (p.to_string(), m.to_string());
+        let before = HashMap::from([
+            (key("openai", "gpt-4o"), 1.0),
+            (key("openai", "gpt-4o-mini"), 0.5),
+            (key("openai", "o1"), 2.0),
+        ]);
+        let after = HashMap::from([
+            (key("openai", "gpt-4o"), 1.42),
+            (key("openai", "gpt-4o-mini"), 0.5),
+            (key("openai", "o3"), 0.1),
+        ]);
+
+        let deltas = cost_deltas(&before, &after);
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(deltas[0].model, "gpt-4o");
+        assert!((deltas[0].cost_delta - 0.42).abs() < 1e-9);
+        assert_eq!(deltas[1].model, "o3");
+        assert_eq!(deltas[2].model, "o1");
+        assert!((deltas[2].cost_delta + 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pinned_rows_lead_the_breakdown_and_survive_the_cut() {
+        let rows: Vec<(String, f64)> = (0..12)
+            .map(|i| (format!("model-{i}"), 12.0 - i as f64))
+            .collect();
+        let pinned = vec![
+            "model-11".to_string(),
+            "claude-3-5-sonnet".to_string(),
+            "model-0".to_string(),
+        ];
+
+        let out = pin_breakdown(rows, &pinned, Some(BREAKDOWN_MODELS));
+        assert_eq!(out.len(), 3 + BREAKDOWN_MODELS);
+        assert_eq!(out[0], ("model-11".to_string(), 1.0));
+        assert_eq!(out[1], ("claude-3-5-sonnet".to_string(), 0.0));
+        assert_eq!(out[2].0, "model-0");
+        assert_eq!(out[3].0, "model-1");
+        assert_eq!(out.last().map(|(m, _)| m.as_str()), Some("model-10"));
+    }
+
+    #[test]
+    fn refresh_needed_is_false_when_all_providers_are_fresh() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc::now();
+        storage
+            .record_refresh("openai", 24 * 30, now - Duration::seconds(10), Ok(1), None)
+            .expect("log refresh");
+
+        let mut cache = FreshnessCache::default();
+        let cfg = cfg_with(&["openai"]);
+        assert!(!refresh_needed(
+            &cfg,
+            TimeWindow::SevenDays,
+            &storage,
+            &mut cache,
+            now
+        ));
+        assert!(refresh_needed(
+            &cfg,
+            TimeWindow::SevenDays,
+            &storage,
+            &mut cache,
+            now + Duration::seconds(120)
+        ));
+    }
+
+    #[test]
+    fn all_window_refreshes_fetch_the_widest_window_at_fine_resolution() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc::now();
+        storage
+            .record_refresh("openai", 24 * 7, now - Duration::seconds(10), Ok(1), None)
+            .expect("log refresh");
+
+        let mut cfg = cfg_with(&["openai"]);
+        assert_eq!(fetch_span(&cfg, TimeWindow::OneDay), (24, 24));
+        let mut cache = FreshnessCache::default();
+        assert!(!refresh_needed(
+            &cfg,
+            TimeWindow::OneDay,
+            &storage,
+            &mut cache,
+            now
+        ));
+
+        cfg.refresh_all_windows = true;
+        assert_eq!(fetch_span(&cfg, TimeWindow::SevenDays), (24 * 30, 24));
+        let mut cache = FreshnessCache::default();
+        assert!(refresh_needed(
+            &cfg,
+            TimeWindow::OneDay,
+            &storage,
+            &mut cache,
+            now
+        ));
+        storage
+            .record_refresh("openai", 24 * 30, now - Duration::seconds(5), Ok(1), None)
+            .expect("log all-window refresh");
+        assert!(!refresh_needed(
+            &cfg,
+            TimeWindow::OneDay,
+            &storage,
+            &mut cache,
+            now
+        ));
+        assert_eq!(
+            window_data_as_of(&cfg, TimeWindow::ThirtyDays, &storage),
+            Some(now - Duration::seconds(5))
+        );
+    }
+
+    #[test]
+    fn window_data_as_of_uses_oldest_covering_fetch() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc::now();
+        storage
+            .record_refresh("openai", 24 * 7, now - Duration::minutes(5), Ok(1), None)
+            .expect("log openai");
+        storage
+            .record_refresh(
+                "anthropic",
+                24 * 30,
+                now - Duration::minutes(1),
+                Ok(1),
+                None,
+            )
+            .expect("log anthropic");
+
+        let cfg = cfg_with(&["openai", "anthropic"]);
+        assert_eq!(
+            window_data_as_of(&cfg, TimeWindow::SevenDays, &storage),
+            Some(now - Duration::minutes(5))
+        );
+        let per_provider = provider_data_as_of(&cfg, TimeWindow::ThirtyDays, &storage);
+        assert_eq!(per_provider["openai"], None);
+        assert_eq!(per_provider["anthropic"], Some(now - Duration::minutes(1)));
+        assert!(!is_stale(&cfg, now - Duration::minutes(1), now));
+        assert!(is_stale(&cfg, now - Duration::minutes(5), now));
+        assert_eq!(
+            window_data_as_of(&cfg, TimeWindow::ThirtyDays, &storage),
+            None
+        );
+    }
+
+    #[test]
+    fn refresh_needed_when_any_provider_lacks_a_covering_fetch() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc::now();
+        storage
+            .record_refresh("openai", 24, now, Ok(1), None)
+            .expect("log refresh");
+
+        let mut cache = FreshnessCache::default();
+        assert!(refresh_needed(
+            &cfg_with(&["openai"]),
+            TimeWindow::ThirtyDays,
+            &storage,
+            &mut cache,
+            now
+        ));
+        assert!(refresh_needed(
+            &cfg_with(&["openai", "anthropic"]),
+            TimeWindow::OneDay,
+            &storage,
+            &mut cache,
+            now
+        ));
+    }
+
+    #[test]
+    fn provider_is_quarantined_with_doubling_backoff() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let cfg = cfg_with(&["openai", "anthropic"]);
+        let start = Utc::now() - Duration::minutes(10);
+
+        storage
+            .record_refresh("openai", 168, start, Ok(5), None)
+            .expect("ok refresh");
+        for i in 1..=2 {
+            storage
+                .record_refresh(
+                    "openai",
+                    168,
+                    start + Duration::seconds(i),
+                    Err("boom"),
+                    None,
+                )
+                .expect("failed refresh");
+        }
+        let now = start + Duration::seconds(3);
+        assert!(quarantined_providers(&cfg, &storage, now)
+            .expect("quarantine")
+            .is_empty());
+
+        let third = start + Duration::seconds(3);
+        storage
+            .record_refresh("openai", 168, third, Err("boom"), None)
+            .expect("failed refresh");
+        assert_eq!(
+            quarantined_providers(&cfg, &storage, third + Duration::seconds(30))
+                .expect("quarantine"),
+            vec!["openai".to_string()]
+        );
+        assert!(
+            quarantined_providers(&cfg, &storage, third + Duration::seconds(61))
+                .expect("quarantine")
+                .is_empty(),
+            "back-off elapsed, provider is probed again"
+        );
+        assert_eq!(quarantine_backoff(&cfg, 3), Duration::seconds(60));
+        assert_eq!(quarantine_backoff(&cfg, 5), Duration::seconds(240));
+        assert_eq!(quarantine_backoff(&cfg, 40), Duration::hours(6));
+
+        storage
+            .record_refresh("openai", 168, third + Duration::seconds(90), Ok(5), None)
+            .expect("ok refresh");
+        assert_eq!(
+            storage.consecutive_failures("openai").expect("failures").0,
+            0
+        );
+    }
+
+    #[test]
+    fn expired_keys_alert_once_and_resolve_when_renewed() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc.with_ymd_and_hms(2026, 3, 31, 9, 0, 0).unwrap();
+        let expiring = |date: &str| ProviderSettings {
+            key_expires_at: Some(date.parse().expect("date")),
+            ..ProviderSettings::default()
+        };
+        let mut cfg = cfg_with(&["openai", "anthropic"]);
+        cfg.provider_settings = HashMap::from([
+            ("openai".to_string(), expiring("2026-03-31")),
+            ("anthropic".to_string(), expiring("2026-04-10")),
+        ]);
+
+        assert_eq!(
+            cfg.expiring_keys(now.date_naive()),
+            vec![("openai".to_string(), 0), ("anthropic".to_string(), 10)]
+        );
+        cfg.key_expiry_warning_days = 7;
+        assert_eq!(cfg.expiring_keys(now.date_naive()).len(), 1);
+
+        let first = check_key_expiry(&cfg, &storage, now).expect("check");
+        assert_eq!(first.expired.len(), 1);
+        assert_eq!(first.expired[0].subject, "openai");
+        assert!(first.expired[0].message.contains("expired on 2026-03-31"));
+        assert!(check_key_expiry(&cfg, &storage, now)
+            .expect("check")
+            .expired
+            .is_empty());
+
+        cfg.provider_settings
+            .insert("openai".into(), expiring("2026-06-30"));
+        let renewed = check_key_expiry(&cfg, &storage, now).expect("check");
+        assert_eq!(renewed.renewed, vec!["openai".to_string()]);
+    }
+
+    #[test]
+    fn overlapping_local_rows_are_suppressed_up_to_the_api_horizon() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let at = |h: u32| Utc.with_ymd_and_hms(2025, 3, 1, h, 0, 0).unwrap();
+        let usage = |provider: &str, model: &str, h: u32, source| UsageRecord {
+            provider: provider.into(),
+            model: model.into(),
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_tokens: 0,
+            timestamp: at(h),
+            bucket_end: Some(at(h + 1)),
+            source_granularity: Some(Granularity::Hour),
+            avg_latency_ms: None,
+            source,
+        };
+        let cost = |u: &UsageRecord, total_cost| CostRecord {
+            provider: u.provider.clone(),
+            model: u.model.clone(),
+            input_cost: total_cost,
+            output_cost: 0.0,
+            total_cost,
+            currency: "USD".into(),
+            timestamp: u.timestamp,
+            bucket_end: u.bucket_end,
+            source_granularity: u.source_granularity,
+            source: u.source,
+        };
+        let rows = vec![
+            usage("anthropic", "claude-sonnet-4", 1, UsageSource::Api),
+            usage("claude-code", "claude-sonnet-4", 1, UsageSource::Local),
+            usage("claude-code", "claude-sonnet-4", 3, UsageSource::Local),
+            usage("claude-code", "gpt-4o", 1, UsageSource::Local),
+        ];
+        let costs: Vec<CostRecord> = rows.iter().map(|u| cost(u, 2.0)).collect();
+        let stats = |provider: &str, n| ProviderRefreshStats {
+            provider: provider.into(),
+            rows_fetched: n,
+            rows_stored: n,
+            ..ProviderRefreshStats::default()
+        };
+        let fetched = |policy| FetchedSnapshot {
+            since: at(0),
+            window_hours: 24,
+            providers: vec!["anthropic".into(), "claude-code".into()],
+            failures: Vec::new(),
+            interrupted: None,
+            snapshot: Snapshot {
+                usage: rows.clone(),
+                cost: costs.clone(),
+                // The API reports up to 02:00, so the 03:00 bucket is new.
+                fetched_at: at(4),
+                deltas: Vec::new(),
+                stats: vec![stats("anthropic", 1), stats("claude-code", 3)],
+                capped: Vec::new(),
+                timed_out: Vec::new(),
+                failed: Vec::new(),
+                clock_skew: None,
+            },
+            rate_limits: Vec::new(),
+            balances: Vec::new(),
+            overlaps: vec![OverlapRule {
+                account: "claude-code".into(),
+                api_account: "anthropic".into(),
+                vendor: "anthropic".into(),
+                policy,
+            }],
+            requests: Arc::default(),
+        };
+
+        let snap = fetched(OverlapPolicy::KeepBoth)
+            .persist(&storage)
+            .expect("persist");
+        assert_eq!(snap.cost.len(), 4);
+        assert_eq!(
+            (
+                snap.stats[1].rows_overlapping,
+                snap.stats[1].cost_overlapping
+            ),
+            (1, 2.0)
+        );
+
+        let snap = fetched(OverlapPolicy::PreferApi)
+            .persist(&storage)
+            .expect("persist");
+        let local = &snap.stats[1];
+        assert_eq!((local.rows_suppressed, local.cost_suppressed), (1, 2.0));
+        assert_eq!(local.rows_stored, 2);
+        let (.., by_source) = storage
+            .aggregate_since(at(0), EdgeBuckets::Exclude)
+            .expect("aggregate");
+        assert_eq!(
+            by_source,
+            vec![("local".to_string(), 4.0), ("api".to_string(), 2.0)]
+        );
+
+        // Adapter-reported costs: the suppressed usage row has no cost row.
+        let mut fetched = fetched(OverlapPolicy::PreferApi);
+        fetched
+            .snapshot
+            .cost
+            .retain(|c| !(c.provider == "claude-code" && c.timestamp == at(1)));
+        let snap = fetched.persist(&storage).expect("persist");
+        let local = &snap.stats[1];
+        assert_eq!((local.rows_suppressed, local.rows_dropped), (1, 0));
+    }
+
+    #[test]
+    fn request_budget_takes_the_tighter_of_the_refresh_and_daily_caps() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let now = Utc.with_ymd_and_hms(2025, 3, 2, 12, 0, 0).unwrap();
+        let mut cfg = cfg_with(&["openai"]);
+        assert_eq!(request_budget(&cfg, &storage, now).unwrap().limit(), None);
+
+        cfg.request_caps = Some(RequestCaps {
+            per_refresh: Some(10),
+            per_day: Some(5),
+        });
+        storage
+            .record_requests(now - Duration::days(1), 50)
+            .expect("log");
+        storage
+            .record_requests(now - Duration::hours(1), 3)
+            .expect("log");
+        assert_eq!(
+            request_budget(&cfg, &storage, now).unwrap().limit(),
+            Some(2)
+        );
+
+        storage.record_requests(now, 4).expect("log");
+        assert!(request_budget(&cfg, &storage, now).unwrap().is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn accounts_past_the_request_cap_are_reported_and_keep_their_rows() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let cfg = cfg_with(&["openai", "anthropic"]);
+        let fetched = MeterService::new(&cfg)
+            .expect("service")
+            .fetch_snapshot(
+                &cfg,
+                TimeWindow::OneDay,
+                &[],
+                Arc::new(RequestBudget::new(Some(0))),
+            )
+            .await
+            .expect("fetch");
+
+        let snap = fetched.persist(&storage).expect("persist");
+        assert_eq!(
+            snap.capped,
+            vec!["openai".to_string(), "anthropic".to_string()]
+        );
+        assert!(snap.stats.is_empty());
+        assert_eq!(
+            storage.last_successful_refresh("openai", 0).expect("log"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn accounts_past_the_deadline_are_reported_and_keep_their_rows() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let cfg = cfg_with(&["openai", "anthropic"]);
+        let fetched = MeterService::new(&cfg)
+            .expect("service")
+            .fetch_lookback(&cfg, 24, 24, Arc::default(), Some(Instant::now()), |name| {
+                cfg.enabled_providers.iter().any(|p| p == name)
+            })
+            .await
+            .expect("fetch");
+
+        let snap = fetched.persist(&storage).expect("persist");
+        assert_eq!(
+            snap.timed_out,
+            vec!["openai".to_string(), "anthropic".to_string()]
+        );
+        assert!(snap.stats.is_empty());
+        // Accounts the deadline kept from starting are not failures.
+        assert_eq!(storage.consecutive_failures("openai").expect("log").0, 0);
+
+        let deadline = Instant::now() + std::time::Duration::from_millis(20);
+        assert_eq!(
+            until(Some(deadline), std::future::pending::<()>()).await,
+            None
+        );
+        assert_eq!(until(None, async { 7 }).await, Some(7));
+    }
+
+    #[tokio::test]
+    async fn a_failing_account_is_logged_and_the_others_are_stored() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let mut cfg = cfg_with(&["openai", "local-session"]);
+        cfg.provider_settings.insert(
+            "openai".into(),
+            ProviderSettings {
+                api_key_env: Some("LLM_METER_TEST_UNSET_KEY".into()),
+                ..ProviderSettings::default()
+            },
+        );
+        cfg.provider_settings.insert(
+            "local-session".into(),
+            ProviderSettings {
+                log_paths: vec![tmp.path().display().to_string()],
+                ..ProviderSettings::default()
+            },
+        );
+        let fetched = MeterService::new(&cfg)
+            .expect("service")
+            .fetch_lookback(&cfg, 24, 24, Arc::default(), None, |name| {
+                cfg.enabled_providers.iter().any(|p| p == name)
+            })
+            .await
+            .expect("fetch");
+
+        let snap = fetched.persist(&storage).expect("persist");
+        assert_eq!(snap.failed.len(), 1);
+        assert_eq!(snap.failed[0].0, "openai");
+        assert_eq!(snap.stats.len(), 1);
+        assert_eq!(snap.stats[0].provider, "local-session");
+        assert_eq!(storage.consecutive_failures("openai").expect("log").0, 1);
+        assert!(storage
+            .last_successful_refresh("local-session", 24)
+            .expect("log")
+            .is_some());
+
+        // With every account failing there is nothing to store.
+        let fetched = MeterService::new(&cfg)
+            .expect("service")
+            .fetch_lookback(&cfg, 24, 24, Arc::default(), None, |name| name == "openai")
+            .await
+            .expect("fetch");
+        assert!(fetched.persist(&storage).is_err());
+        assert_eq!(storage.consecutive_failures("openai").expect("log").0, 2);
+    }
+
+    #[test]
+    fn billing_currency_rows_are_stored_native_and_summed_in_usd() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let mut cfg = AppConfig::default();
+        cfg.fx_rates.insert("EUR".into(), 0.8);
+        storage.set_fx_rates(FxRates::from_config(&cfg));
+        let at = Utc.with_ymd_and_hms(2025, 3, 1, 1, 0, 0).unwrap();
+        let cost = |provider: &str, total_cost, currency: &str| CostRecord {
+            provider: provider.into(),
+            model: "gpt-4o".into(),
+            input_cost: total_cost,
+            output_cost: 0.0,
+            total_cost,
+            currency: currency.into(),
+            timestamp: at,
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        };
+        let fetched = FetchedSnapshot {
+            since: at - Duration::hours(1),
+            window_hours: 24,
+            providers: vec!["azure-openai".into(), "openai".into()],
+            failures: Vec::new(),
+            interrupted: None,
+            snapshot: Snapshot {
+                usage: Vec::new(),
+                cost: vec![cost("azure-openai", 4.0, "EUR"), cost("openai", 1.0, "USD")],
+                fetched_at: at,
+                deltas: Vec::new(),
+                stats: Vec::new(),
+                capped: Vec::new(),
+                timed_out: Vec::new(),
+                failed: Vec::new(),
+                clock_skew: None,
+            },
+            rate_limits: Vec::new(),
+            balances: Vec::new(),
+            overlaps: Vec::new(),
+            requests: Arc::default(),
+        };
+
+        let snap = fetched.persist(&storage).expect("persist");
+        assert!(snap.cost.iter().all(|r| r.currency == "USD"));
+        assert!((snap.cost[0].total_cost - 5.0).abs() < 1e-9);
+
+        let stored: Vec<CostRecord> =
+            serde_json::from_str(&storage.export_cost_json().expect("export")).expect("json");
+        let azure = stored
+            .iter()
+            .find(|r| r.provider == "azure-openai")
+            .unwrap();
+        assert_eq!((azure.currency.as_str(), azure.total_cost), ("EUR", 4.0));
+
+        let (_, total, by_provider, ..) = storage
+            .aggregate_since(at - Duration::hours(1), EdgeBuckets::Exclude)
+            .expect("aggregate");
+        assert!((total - 6.0).abs() < 1e-9);
+        assert_eq!(by_provider[0].0, "azure-openai");
+    }
+
+    #[test]
+    fn normalize_usage_merges_fine_buckets_and_keeps_coarse_ones() {
+        let at = |h: u32, m: u32| Utc.with_ymd_and_hms(2024, 1, 2, h, m, 0).unwrap();
+        let row = |model: &str, ts, tokens, source| UsageRecord {
+            provider: "anthropic".into(),
+            model: model.into(),
+            input_tokens: tokens,
+            output_tokens: 1,
+            cached_tokens: 0,
+            timestamp: ts,
+            bucket_end: None,
+            source_granularity: Some(source),
+            source: UsageSource::Api,
+            avg_latency_ms: None,
+        };
+        let mut rows = vec![
+            row("claude", at(10, 5), 10, Granularity::Minute),
+            row("claude", at(10, 40), 5, Granularity::Minute),
+            row("claude", at(11, 0), 7, Granularity::Minute),
+            row("gpt-4o", at(0, 0), 100, Granularity::Day),
+        ];
+        rows[0].avg_latency_ms = Some(100.0);
+        rows[1].avg_latency_ms = Some(400.0);
+
+        let hourly = normalize_usage(rows.clone(), Granularity::Hour);
+        assert_eq!(hourly.len(), 3);
+        assert_eq!(
+            (hourly[0].timestamp, hourly[0].input_tokens),
+            (at(10, 0), 15)
+        );
+        assert_eq!(hourly[0].output_tokens, 2);
+        assert_eq!(hourly[0].bucket_end, Some(at(11, 0)));
+        assert_eq!(hourly[0].source_granularity, Some(Granularity::Minute));
+        // 11 tokens at 100ms and 6 at 400ms.
+        assert!((hourly[0].avg_latency_ms.unwrap() - 3500.0 / 17.0).abs() < 1e-9);
+        assert_eq!(hourly[1].avg_latency_ms, None);
+        assert_eq!(
+            (hourly[1].timestamp, hourly[1].input_tokens),
+            (at(11, 0), 7)
+        );
+        assert_eq!(
+            (hourly[2].timestamp, hourly[2].input_tokens),
+            (at(0, 0), 100)
+        );
+
+        let daily = normalize_usage(rows, Granularity::Day);
+        assert_eq!(daily.len(), 2);
+        assert_eq!((daily[0].timestamp, daily[0].input_tokens), (at(0, 0), 22));
+        assert_eq!(daily[1].bucket_end, Some(at(0, 0) + Duration::days(1)));
     }
 }
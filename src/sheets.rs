@@ -0,0 +1,192 @@
+use crate::error::AppError;
+use crate::export::CostRollup;
+use chrono::{DateTime, NaiveDate, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+const SHEETS_SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+const SHEETS_API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const TOKEN_LIFETIME_SECS: i64 = 3600;
+
+/// The fields we need from a Google service-account JSON key file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccount {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.into()
+}
+
+impl ServiceAccount {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let raw = std::fs::read_to_string(path).map_err(|e| {
+            AppError::Config(format!(
+                "cannot read service account file {}: {e}",
+                path.display()
+            ))
+        })?;
+        Ok(serde_json::from_str(&raw)?)
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+fn claims(account: &ServiceAccount, now: DateTime<Utc>) -> Claims<'_> {
+    Claims {
+        iss: &account.client_email,
+        scope: SHEETS_SCOPE,
+        aud: &account.token_uri,
+        iat: now.timestamp(),
+        exp: now.timestamp() + TOKEN_LIFETIME_SECS,
+    }
+}
+
+/// Signed JWT exchanged at `token_uri` for an access token.
+fn assertion(account: &ServiceAccount, now: DateTime<Utc>) -> Result<String, AppError> {
+    let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+        .map_err(|e| AppError::Config(format!("invalid service account private key: {e}")))?;
+    jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims(account, now), &key)
+        .map_err(|e| AppError::Config(format!("cannot sign service account token: {e}")))
+}
+
+async fn access_token(client: &Client, account: &ServiceAccount) -> Result<String, AppError> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+    }
+
+    let assertion = assertion(account, Utc::now())?;
+    let response: TokenResponse = client
+        .post(&account.token_uri)
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response.access_token)
+}
+
+/// One `[date, provider, cost, currency]` row per day and provider with
+/// `from <= date <= to`, oldest first.
+pub fn daily_rows(
+    rollup: &CostRollup,
+    from: NaiveDate,
+    to: NaiveDate,
+    currency: &str,
+) -> Vec<Value> {
+    rollup
+        .daily
+        .range((from, String::new())..)
+        .take_while(|((day, _), _)| *day <= to)
+        .map(|((day, provider), cost)| json!([day.to_string(), provider, cost, currency]))
+        .collect()
+}
+
+/// Appends `rows` below the last filled row of `range` in the spreadsheet.
+pub async fn append_rows(
+    client: &Client,
+    account: &ServiceAccount,
+    sheet_id: &str,
+    range: &str,
+    rows: Vec<Value>,
+) -> Result<(), AppError> {
+    let token = access_token(client, account).await?;
+    let mut url = reqwest::Url::parse(SHEETS_API)
+        .map_err(|e| AppError::Config(format!("invalid Sheets API url: {e}")))?;
+    url.path_segments_mut()
+        .map_err(|_| AppError::Config("invalid Sheets API url".into()))?
+        .push(sheet_id)
+        .push("values")
+        .push(&format!("{range}:append"));
+    url.query_pairs_mut()
+        .append_pair("valueInputOption", "USER_ENTERED")
+        .append_pair("insertDataOption", "INSERT_ROWS");
+    client
+        .post(url)
+        .bearer_auth(token)
+        .json(&json!({ "values": rows }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostRecord, UsageSource};
+    use chrono::TimeZone;
+
+    fn cost(day: u32, provider: &str, total: f64) -> CostRecord {
+        CostRecord {
+            provider: provider.into(),
+            model: "m".into(),
+            timestamp: Utc.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap(),
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    #[test]
+    fn daily_rows_keep_only_the_requested_days() {
+        let rollup = CostRollup::from_rows(&[
+            cost(1, "openai", 1.0),
+            cost(2, "openai", 2.0),
+            cost(2, "anthropic", 0.5),
+            cost(2, "openai", 0.25),
+            cost(3, "openai", 4.0),
+        ]);
+        let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        assert_eq!(
+            daily_rows(&rollup, day, day, "USD"),
+            vec![
+                json!(["2026-03-02", "anthropic", 0.5, "USD"]),
+                json!(["2026-03-02", "openai", 2.25, "USD"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn claims_target_the_token_uri_with_sheets_scope() {
+        let account: ServiceAccount = serde_json::from_str(
+            r#"{"client_email":"meter@example.iam.gserviceaccount.com","private_key":"k"}"#,
+        )
+        .unwrap();
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        assert_eq!(
+            claims(&account, now),
+            Claims {
+                iss: "meter@example.iam.gserviceaccount.com",
+                scope: SHEETS_SCOPE,
+                aud: DEFAULT_TOKEN_URI,
+                iat: 1_700_000_000,
+                exp: 1_700_003_600,
+            }
+        );
+        assert!(matches!(assertion(&account, now), Err(AppError::Config(_))));
+    }
+}
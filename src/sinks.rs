@@ -0,0 +1,392 @@
+use crate::config::ExportPipeline;
+use crate::error::AppError;
+use crate::export::{self, CostRollup, CsvOptions, ExportFormat};
+use crate::models::CostRecord;
+use crate::notifier;
+use crate::sheets;
+use crate::upload::{self, Destination};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use croner::Cron;
+use reqwest::Client;
+use std::path::PathBuf;
+
+/// Named schedules and the cron expressions (UTC) they stand for.
+const NAMED_SCHEDULES: [(&str, &str); 3] = [
+    ("hourly", "0 * * * *"),
+    ("daily", "0 0 * * *"),
+    ("weekly", "0 0 * * 1"),
+];
+
+/// Everything a sink needs for one delivery. `rows` are already converted to
+/// `currency`.
+pub struct ExportJob<'a> {
+    pub client: &'a Client,
+    pub pipeline: &'a ExportPipeline,
+    pub rows: &'a [CostRecord],
+    pub currency: &'a str,
+    pub now: DateTime<Utc>,
+}
+
+impl ExportJob<'_> {
+    fn format(&self) -> Result<ExportFormat, AppError> {
+        ExportFormat::parse(&self.pipeline.format)
+    }
+
+    fn render(&self) -> Result<(ExportFormat, Vec<u8>), AppError> {
+        let format = self.format()?;
+        let csv = if format == ExportFormat::Csv {
+            CsvOptions {
+                delimiter: match &self.pipeline.delimiter {
+                    Some(raw) => export::parse_delimiter(raw)?,
+                    None => b',',
+                },
+                header: self.pipeline.header,
+                quote_all: self.pipeline.quote_all,
+            }
+        } else {
+            CsvOptions::default()
+        };
+        let body = export::render_cost(format, self.rows, csv, self.currency)?;
+        Ok((format, body))
+    }
+
+    fn to(&self) -> Result<&str, AppError> {
+        self.pipeline.to.as_deref().ok_or_else(|| {
+            AppError::Config(format!(
+                "Export '{}' needs `to` for the {} sink.",
+                self.pipeline.label(),
+                self.pipeline.sink
+            ))
+        })
+    }
+}
+
+/// A place rendered cost exports can be delivered to. Implementations return
+/// a one-line description of what was delivered where.
+#[async_trait]
+pub trait ExportSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError>;
+}
+
+pub fn sinks() -> Vec<Box<dyn ExportSink>> {
+    vec![
+        Box::new(FileSink),
+        Box::new(S3Sink),
+        Box::new(HttpSink),
+        Box::new(WebhookSink),
+        Box::new(InfluxSink),
+        Box::new(SheetsSink),
+    ]
+}
+
+pub fn find_sink(name: &str) -> Result<Box<dyn ExportSink>, AppError> {
+    let names: Vec<_> = sinks().iter().map(|s| s.name()).collect();
+    sinks()
+        .into_iter()
+        .find(|s| s.name().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| {
+            AppError::Config(format!(
+                "Unknown export sink '{name}'. Use one of: {}.",
+                names.join(", ")
+            ))
+        })
+}
+
+/// The sink a bare `export --to` value implies.
+pub fn sink_for_destination(to: &str) -> Result<&'static str, AppError> {
+    if to.eq_ignore_ascii_case("google-sheets") {
+        Ok("google-sheets")
+    } else if to.starts_with("s3://") {
+        Ok("s3")
+    } else if to.starts_with("https://") || to.starts_with("http://") {
+        Ok("http")
+    } else if to.starts_with("file://") || !to.contains("://") {
+        Ok("file")
+    } else {
+        Err(AppError::Config(
+            "Unsupported export destination. Use google-sheets, s3://bucket/prefix/, an https:// URL or a file path".into(),
+        ))
+    }
+}
+
+/// Catches typos in `[[exports]]` before anything is sent.
+pub fn check_pipeline(pipeline: &ExportPipeline) -> Result<(), AppError> {
+    find_sink(&pipeline.sink)?;
+    ExportFormat::parse(&pipeline.format)?;
+    schedule_cron(pipeline.schedule())
+        .map_err(|e| AppError::Config(format!("Export '{}': {e}", pipeline.label())))?;
+    Ok(())
+}
+
+/// `manual` yields `None`; named schedules and 5-field cron expressions are
+/// evaluated in UTC.
+fn schedule_cron(schedule: &str) -> Result<Option<Cron>, AppError> {
+    let schedule = schedule.trim();
+    if schedule.eq_ignore_ascii_case("manual") {
+        return Ok(None);
+    }
+    let expr = NAMED_SCHEDULES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(schedule))
+        .map_or(schedule, |(_, expr)| expr);
+    Cron::new(expr).parse().map(Some).map_err(|e| {
+        AppError::Config(format!(
+            "invalid schedule '{schedule}' ({e}). Use manual, hourly, daily, weekly or a cron expression."
+        ))
+    })
+}
+
+/// When a pipeline is next due: the first schedule tick after its last run,
+/// or `now` if it never ran. `None` for manual pipelines.
+pub fn next_run(
+    schedule: &str,
+    last_run: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let Some(cron) = schedule_cron(schedule)? else {
+        return Ok(None);
+    };
+    let Some(last_run) = last_run else {
+        return Ok(Some(now));
+    };
+    cron.find_next_occurrence(&last_run, false)
+        .map(Some)
+        .map_err(|e| AppError::Config(format!("schedule '{schedule}' never fires: {e}")))
+}
+
+struct FileSink;
+
+#[async_trait]
+impl ExportSink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let (format, body) = job.render()?;
+        let template = job.to()?;
+        let template = template.strip_prefix("file://").unwrap_or(template);
+        let path = PathBuf::from(upload::expand_template(template, format, job.now));
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &body)?;
+        Ok(format!("wrote {} bytes to {}", body.len(), path.display()))
+    }
+}
+
+struct S3Sink;
+
+#[async_trait]
+impl ExportSink for S3Sink {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let destination = Destination::parse(job.to()?)?;
+        if !matches!(destination, Destination::S3 { .. }) {
+            return Err(AppError::Config(
+                "The s3 sink needs an s3://bucket/prefix destination.".into(),
+            ));
+        }
+        let (format, body) = job.render()?;
+        let size = body.len();
+        let location = upload::put(job.client, &destination, format, body, job.now).await?;
+        Ok(format!("uploaded {size} bytes to {location}"))
+    }
+}
+
+struct HttpSink;
+
+#[async_trait]
+impl ExportSink for HttpSink {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let destination = Destination::parse(job.to()?)?;
+        if !matches!(destination, Destination::Http(_)) {
+            return Err(AppError::Config(
+                "The http sink needs an http:// or https:// URL.".into(),
+            ));
+        }
+        let (format, body) = job.render()?;
+        let size = body.len();
+        let location = upload::put(job.client, &destination, format, body, job.now).await?;
+        Ok(format!("uploaded {size} bytes to {location}"))
+    }
+}
+
+/// POSTs the rendered document with the same HMAC signature headers as the
+/// refresh webhook.
+struct WebhookSink;
+
+#[async_trait]
+impl ExportSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let url = job.to()?;
+        let (format, body) = job.render()?;
+        let size = body.len();
+        notifier::post_signed(job.client, url, format.content_type(), body, job.now).await?;
+        Ok(format!("posted {size} bytes to {url}"))
+    }
+}
+
+/// Always writes line protocol, whatever `format` says.
+struct InfluxSink;
+
+#[async_trait]
+impl ExportSink for InfluxSink {
+    fn name(&self) -> &'static str {
+        "influx"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let url = job.to()?;
+        notifier::post_line_protocol(job.client, url, export::cost_line_protocol(job.rows)).await?;
+        Ok(format!("wrote {} points to {url}", job.rows.len()))
+    }
+}
+
+/// Appends the daily rollup for the last `days` complete days (default 1).
+struct SheetsSink;
+
+#[async_trait]
+impl ExportSink for SheetsSink {
+    fn name(&self) -> &'static str {
+        "google-sheets"
+    }
+
+    async fn deliver(&self, job: &ExportJob<'_>) -> Result<String, AppError> {
+        let pipeline = job.pipeline;
+        let sheet_id = pipeline.sheet_id.as_deref().ok_or_else(|| {
+            AppError::Config("The google-sheets sink requires a sheet id.".into())
+        })?;
+        let credentials = pipeline
+            .credentials
+            .clone()
+            .or_else(|| std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from))
+            .ok_or_else(|| {
+                AppError::Config(
+                    "No service account configured. Pass --credentials or set GOOGLE_APPLICATION_CREDENTIALS.".into(),
+                )
+            })?;
+        let days = pipeline.days.unwrap_or(1);
+        if days == 0 {
+            return Err(AppError::Config("--days must be at least 1".into()));
+        }
+        let account = sheets::ServiceAccount::load(&credentials)?;
+        let yesterday = job.now.date_naive() - Duration::days(1);
+        let from = yesterday - Duration::days(i64::from(days) - 1);
+        let values = sheets::daily_rows(
+            &CostRollup::from_rows(job.rows),
+            from,
+            yesterday,
+            job.currency,
+        );
+        let appended = values.len();
+        let range = pipeline.range.as_deref().unwrap_or("Sheet1");
+        sheets::append_rows(job.client, &account, sheet_id, range, values).await?;
+        Ok(format!(
+            "appended {appended} rows ({from} to {yesterday}) to sheet {sheet_id}"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UsageSource;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn destinations_map_to_sinks() {
+        assert_eq!(
+            sink_for_destination("google-sheets").unwrap(),
+            "google-sheets"
+        );
+        assert_eq!(sink_for_destination("s3://lake/llm/").unwrap(), "s3");
+        assert_eq!(
+            sink_for_destination("https://x.example/c.csv").unwrap(),
+            "http"
+        );
+        assert_eq!(sink_for_destination("out/{date}.csv").unwrap(), "file");
+        assert!(sink_for_destination("ftp://host/c.csv").is_err());
+        assert!(find_sink("S3").is_ok());
+        assert!(find_sink("kafka").is_err());
+    }
+
+    #[test]
+    fn check_pipeline_rejects_unknown_schedule_and_format() {
+        let mut pipeline = ExportPipeline::new("file", "jsonl");
+        pipeline.schedule = Some("daily".into());
+        assert!(check_pipeline(&pipeline).is_ok());
+        pipeline.schedule = Some("*/15 * * * *".into());
+        assert!(check_pipeline(&pipeline).is_ok());
+        pipeline.schedule = Some("nightly".into());
+        assert!(check_pipeline(&pipeline).is_err());
+        pipeline.schedule = None;
+        pipeline.format = "parquet".into();
+        assert!(check_pipeline(&pipeline).is_err());
+    }
+
+    #[test]
+    fn next_run_follows_named_and_cron_schedules() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 9, 12, 30, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2026, 3, 8, 0, 0, 5).unwrap();
+        assert_eq!(next_run("manual", Some(last), now).unwrap(), None);
+        assert_eq!(next_run("daily", None, now).unwrap(), Some(now));
+        assert_eq!(
+            next_run("daily", Some(last), now).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 3, 9, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            next_run("30 2 * * *", Some(now), now).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 3, 10, 2, 30, 0).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn file_sink_expands_template_and_writes_rendered_rows() {
+        let dir = TempDir::new().expect("temp dir");
+        let mut pipeline = ExportPipeline::new("file", "jsonl");
+        pipeline.to = Some(format!("{}/{{year}}/", dir.path().display()));
+        let now = Utc.with_ymd_and_hms(2026, 3, 9, 4, 5, 6).unwrap();
+        let rows = vec![CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            input_cost: 1.0,
+            output_cost: 0.5,
+            total_cost: 1.5,
+            currency: "USD".into(),
+            timestamp: now,
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }];
+        let client = Client::new();
+        let job = ExportJob {
+            client: &client,
+            pipeline: &pipeline,
+            rows: &rows,
+            currency: "USD",
+            now,
+        };
+        let message = find_sink("file").unwrap().deliver(&job).await.unwrap();
+        let path = dir.path().join("2026").join("llm-costs-2026-03-09.jsonl");
+        assert!(message.ends_with(&path.display().to_string()));
+        let written = std::fs::read_to_string(path).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.contains("\"provider\":\"openai\""));
+    }
+}
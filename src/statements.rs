@@ -0,0 +1,116 @@
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::models::Statement;
+use crate::storage::Storage;
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// First day of the `YYYY-MM` month.
+pub fn parse_month(raw: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(&format!("{}-01", raw.trim()), "%Y-%m-%d")
+        .map_err(|_| AppError::Config(format!("Invalid month '{raw}'. Use YYYY-MM.")))
+}
+
+/// `[start, end)` of the month beginning on `first`, in UTC.
+pub fn month_bounds(first: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let next = first + Months::new(1);
+    (
+        Utc.from_utc_datetime(&first.and_time(NaiveTime::MIN)),
+        Utc.from_utc_datetime(&next.and_time(NaiveTime::MIN)),
+    )
+}
+
+/// The statement for `month`. A closed month is frozen the first time it is
+/// asked for and read back unchanged afterwards, unless `force` recomputes it
+/// from the current snapshot. An open month returns live, unfrozen totals.
+pub fn statement(
+    cfg: &AppConfig,
+    storage: &Storage,
+    month: &str,
+    force: bool,
+    now: DateTime<Utc>,
+) -> Result<Vec<Statement>, AppError> {
+    let first = parse_month(month)?;
+    let month = format!("{:04}-{:02}", first.year(), first.month());
+    let (start, end) = month_bounds(first);
+    if end > now {
+        if force {
+            return Err(AppError::Config(format!(
+                "{month} has not closed yet; statements freeze after the month ends."
+            )));
+        }
+        return storage.statement_totals(&month, start, end);
+    }
+
+    let frozen = storage.statement(&month)?;
+    if !frozen.is_empty() && !force {
+        return Ok(frozen);
+    }
+    let rows: Vec<Statement> = storage
+        .statement_totals(&month, start, end)?
+        .into_iter()
+        .map(|row| Statement {
+            account: cfg
+                .provider_settings
+                .get(&row.provider)
+                .and_then(|s| s.organization_id.clone()),
+            frozen_at: Some(now),
+            ..row
+        })
+        .collect();
+    storage.freeze_statement(&month, &rows, force)?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CostRecord, UsageSource};
+    use tempfile::TempDir;
+
+    fn cost(day: u32, total: f64) -> CostRecord {
+        CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            timestamp: Utc.with_ymd_and_hms(2026, 6, day, 12, 0, 0).unwrap(),
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    #[test]
+    fn closed_months_freeze_and_ignore_later_refetches_until_forced() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("meter.db")).expect("open");
+        let since = Utc.with_ymd_and_hms(2026, 6, 1, 0, 0, 0).unwrap();
+        let providers = vec!["openai".to_string()];
+        storage
+            .replace_snapshot(since, &providers, &[], &[cost(3, 2.0), cost(30, 1.5)])
+            .expect("seed");
+
+        let cfg = AppConfig::default();
+        let mid_june = Utc.with_ymd_and_hms(2026, 6, 15, 0, 0, 0).unwrap();
+        let live = statement(&cfg, &storage, "2026-06", false, mid_june).expect("live");
+        assert_eq!(live[0].frozen_at, None);
+        assert!(storage.statement("2026-06").expect("read").is_empty());
+
+        let july = Utc.with_ymd_and_hms(2026, 7, 2, 0, 0, 0).unwrap();
+        let frozen = statement(&cfg, &storage, "2026-06", false, july).expect("freeze");
+        assert_eq!(frozen[0].total_cost, 3.5);
+        assert_eq!(frozen[0].frozen_at, Some(july));
+
+        storage
+            .replace_snapshot(since, &providers, &[], &[cost(3, 9.0)])
+            .expect("refetch");
+        let again = statement(&cfg, &storage, "2026-06", false, july).expect("reread");
+        assert_eq!(again[0].total_cost, 3.5);
+        let forced = statement(&cfg, &storage, "2026-06", true, july).expect("force");
+        assert_eq!(forced[0].total_cost, 9.0);
+
+        assert!(statement(&cfg, &storage, "2026-13", false, july).is_err());
+    }
+}
@@ -1,25 +1,360 @@
+use crate::config::{self, JournalMode};
 use crate::error::AppError;
-use crate::models::{CostRecord, UsageRecord};
-use chrono::{DateTime, Utc};
-use rusqlite::{params, types::Type, Connection};
-use std::path::Path;
+use crate::fx::FxRates;
+use crate::models::{
+    AccountBalance, AlertRecord, AlertState, AuditEntry, CostDelta, CostRecord, EdgeBuckets,
+    ExportRun, Granularity, PeakRate, ProviderHealth, RateLimit, Statement, UsageRecord,
+    UsageSource,
+};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
+const AGGREGATE_CACHE_LIMIT: usize = 16;
+const MAX_IDLE_READERS: usize = 4;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Layout version stamped into `meta`. Bump it whenever a change would make
+/// older binaries misread or mangle the data, so they refuse to write to it.
+/// v2: rows carry `bucket_end` and `source`, and costs are stored in the
+/// account's billing currency instead of always USD.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Cloneable handle over one database file: a single writer connection plus a
+/// pool of read-only connections. By default the database runs in WAL mode,
+/// so readers in other clones (or processes) neither block the writer nor
+/// wait on it.
+#[derive(Clone)]
 pub struct Storage {
-    conn: Connection,
+    writer: Arc<Mutex<Connection>>,
+    readers: Arc<ReaderPool>,
+    /// Aggregation results keyed by the minute-truncated `since` and the edge
+    /// treatment. Cleared on every snapshot write, so entries never outlive
+    /// the rows they summarize.
+    aggregate_cache: Arc<Mutex<HashMap<AggregateKey, AggregateSummary>>>,
+    /// Rates the cost aggregations convert non-USD rows to USD with.
+    fx: Arc<Mutex<FxRates>>,
+}
+
+struct ReaderPool {
+    path: PathBuf,
+    key: Option<String>,
+    idle: Mutex<Vec<Connection>>,
+}
+
+struct PooledReader<'a> {
+    pool: &'a ReaderPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledReader<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+            .as_ref()
+            .expect("pooled connection is present until drop")
+    }
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        if let (Some(conn), Ok(mut idle)) = (self.conn.take(), self.pool.idle.lock()) {
+            if idle.len() < MAX_IDLE_READERS {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+impl ReaderPool {
+    fn checkout(&self) -> Result<PooledReader<'_>, AppError> {
+        let pooled = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        let conn = match pooled {
+            Some(conn) => conn,
+            None => {
+                let conn = Connection::open_with_flags(
+                    &self.path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                apply_key(&conn, self.key.as_deref())?;
+                conn.busy_timeout(BUSY_TIMEOUT)?;
+                conn
+            }
+        };
+        Ok(PooledReader {
+            pool: self,
+            conn: Some(conn),
+        })
+    }
+}
+
+/// Whether this build links SQLCipher (the `encryption` feature).
+pub const ENCRYPTION_SUPPORTED: bool = cfg!(feature = "encryption");
+
+fn require_encryption() -> Result<(), AppError> {
+    if ENCRYPTION_SUPPORTED {
+        Ok(())
+    } else {
+        Err(AppError::Storage(
+            "database encryption needs a build with `--features encryption`".into(),
+        ))
+    }
+}
+
+/// SQLCipher raw-key literal for a hex `key`. Raw keys skip the passphrase
+/// KDF, so pooled readers open as fast as on a plain database.
+fn key_literal(key: &str) -> Result<String, AppError> {
+    if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(AppError::Storage(
+            "database key must be 64 hex digits".into(),
+        ));
+    }
+    Ok(format!("x'{key}'"))
+}
+
+/// Unlocks `conn` with `key`. Plain SQLite ignores `PRAGMA key`, so a build
+/// without SQLCipher refuses instead of silently writing plaintext.
+fn apply_key(conn: &Connection, key: Option<&str>) -> Result<(), AppError> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+    require_encryption()?;
+    conn.pragma_update(None, "key", key_literal(key)?)?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|_| {
+            AppError::Storage("cannot decrypt the database: the keyring key does not match".into())
+        })
+}
+
+/// The rolling backup kept next to `db`, e.g. `snapshots.sqlite.bak`.
+pub fn backup_path(db: &Path) -> PathBuf {
+    let mut name = db.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    db.with_file_name(name)
+}
+
+/// Moves `db` and its WAL and shared-memory files aside as
+/// `<name>.corrupt-<timestamp>`, leaving the path free for a new database.
+/// Returns the archived database path.
+pub fn archive_database(db: &Path, now: DateTime<Utc>) -> Result<PathBuf, AppError> {
+    let suffix = format!("corrupt-{}", now.format("%Y%m%dT%H%M%SZ"));
+    let aside = |path: &Path| {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{suffix}"));
+        path.with_file_name(name)
+    };
+    for side in ["-wal", "-shm"] {
+        let mut name = db.file_name().unwrap_or_default().to_os_string();
+        name.push(side);
+        let path = db.with_file_name(name);
+        if path.exists() {
+            std::fs::rename(&path, aside(&path))?;
+        }
+    }
+    let archived = aside(db);
+    if db.exists() {
+        std::fs::rename(db, &archived)?;
+    }
+    Ok(archived)
+}
+
+/// Runs storage calls on tokio's blocking pool, so SQLite work never stalls
+/// the async runtime driving the TUI.
+#[derive(Clone)]
+pub struct AsyncStorage {
+    inner: Storage,
+}
+
+impl AsyncStorage {
+    pub fn new(storage: Storage) -> Self {
+        Self { inner: storage }
+    }
+
+    pub async fn call<F, R>(&self, f: F) -> Result<R, AppError>
+    where
+        F: FnOnce(&Storage) -> Result<R, AppError> + Send + 'static,
+        R: Send + 'static,
+    {
+        let storage = self.inner.clone();
+        tokio::task::spawn_blocking(move || f(&storage))
+            .await
+            .map_err(|e| AppError::Storage(format!("storage task failed: {e}")))?
+    }
+}
+
+/// Tokens, cost, and cost by provider, by model and by source, costliest first.
+pub type AggregateSummary = (
+    u64,
+    f64,
+    Vec<(String, f64)>,
+    Vec<(String, f64)>,
+    Vec<(String, f64)>,
+);
+type AggregateKey = (DateTime<Utc>, EdgeBuckets);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelUsageSummary {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_tokens: u64,
+    pub cost: f64,
+    /// Token-weighted mean latency over the rows that report one.
+    pub avg_latency_ms: Option<f64>,
+}
+
+/// Filters for [`Storage::search_usage`] and [`Storage::search_cost`]. Empty
+/// strings and `None` match every row.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordFilter {
+    /// Case-insensitive substring of the model id.
+    pub model: String,
+    pub provider: String,
+    /// Rows whose bucket starts at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Rows whose bucket starts before this time.
+    pub until: Option<DateTime<Utc>>,
 }
 
-pub type AggregateSummary = (u64, f64, Vec<(String, f64)>, Vec<(String, f64)>);
+/// Columns and rows of a [`Storage::run_query`], each value as text. `NULL`
+/// is empty and blobs show their size.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// More rows matched than `max_rows` allowed.
+    pub truncated: bool,
+}
+
+impl RecordFilter {
+    /// `WHERE` clause for the filters and the values it binds, in order.
+    fn clause(&self) -> (String, Vec<String>) {
+        let mut terms = vec!["1 = 1"];
+        let mut values = Vec::new();
+        if !self.model.trim().is_empty() {
+            terms.push("instr(lower(model), lower(?)) > 0");
+            values.push(self.model.trim().to_string());
+        }
+        if !self.provider.trim().is_empty() {
+            terms.push("provider = ?");
+            values.push(config::normalize_provider_name(&self.provider));
+        }
+        if let Some(since) = self.since {
+            terms.push("timestamp >= ?");
+            values.push(since.to_rfc3339());
+        }
+        if let Some(until) = self.until {
+            terms.push("timestamp < ?");
+            values.push(until.to_rfc3339());
+        }
+        (terms.join(" AND "), values)
+    }
+}
 
 impl Storage {
+    /// A plain database in the default journal mode, as the tests use it.
+    #[cfg(test)]
     pub fn open(path: &Path) -> Result<Self, AppError> {
+        Self::open_with(path, None, JournalMode::default())
+    }
+
+    /// Opens the database at [`config::db_path`], unlocked with
+    /// [`config::database_key`] when `encrypt_database` is set.
+    pub fn open_configured() -> Result<Self, AppError> {
+        let path = config::db_path()?;
+        let cfg = config::load_config()?;
+        let key = config::database_key()?;
+        let storage = Self::open_with(&path, key.as_deref(), cfg.database_journal_mode)?;
+        storage.set_fx_rates(FxRates::from_config(&cfg));
+        Ok(storage)
+    }
+
+    /// Opens an SQLCipher database encrypted with the hex `key`; `None`
+    /// opens a plain one. The file is switched to `journal` first.
+    pub fn open_with(
+        path: &Path,
+        key: Option<&str>,
+        journal: JournalMode,
+    ) -> Result<Self, AppError> {
         let conn = Connection::open(path)?;
-        let this = Self { conn };
+        apply_key(&conn, key)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", journal.pragma())?;
+        let this = Self::with_writer(conn, path, key);
         this.init()?;
         Ok(this)
     }
 
+    /// Opens `path` without writing to it: no schema setup and no journal
+    /// mode switch, so it works on files another process holds locked. Writes fail
+    /// with SQLite's read-only error.
+    pub fn open_read_only(path: &Path, key: Option<&str>) -> Result<Self, AppError> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        apply_key(&conn, key)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(Self::with_writer(conn, path, key))
+    }
+
+    fn with_writer(conn: Connection, path: &Path, key: Option<&str>) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(conn)),
+            readers: Arc::new(ReaderPool {
+                path: path.to_path_buf(),
+                key: key.map(str::to_string),
+                idle: Mutex::new(Vec::new()),
+            }),
+            aggregate_cache: Arc::new(Mutex::new(HashMap::new())),
+            fx: Arc::new(Mutex::new(FxRates::default())),
+        }
+    }
+
+    /// Replaces the rates cost aggregations convert with; cached summaries
+    /// computed under the old rates are dropped.
+    pub fn set_fx_rates(&self, fx: FxRates) {
+        if let Ok(mut current) = self.fx.lock() {
+            *current = fx;
+        }
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            cache.clear();
+        }
+    }
+
+    /// The rates set by [`Self::set_fx_rates`].
+    pub fn fx_rates(&self) -> FxRates {
+        self.fx.lock().map(|fx| fx.clone()).unwrap_or_default()
+    }
+
+    /// SQL expression for a row's `total_cost` in USD.
+    fn usd_total(&self) -> String {
+        self.fx
+            .lock()
+            .map(|fx| fx.usd_sql("total_cost"))
+            .unwrap_or_else(|_| "total_cost".into())
+    }
+
+    fn writer(&self) -> Result<MutexGuard<'_, Connection>, AppError> {
+        self.writer
+            .lock()
+            .map_err(|_| AppError::Storage("writer lock poisoned".into()))
+    }
+
+    fn reader(&self) -> Result<PooledReader<'_>, AppError> {
+        self.readers.checkout()
+    }
+
     fn init(&self) -> Result<(), AppError> {
-        self.conn.execute_batch(
+        self.check_schema_version(SCHEMA_VERSION)?;
+        self.writer()?.execute_batch(
             r#"
             CREATE TABLE IF NOT EXISTS usage_records (
                 id INTEGER PRIMARY KEY,
@@ -28,7 +363,11 @@ impl Storage {
                 input_tokens INTEGER NOT NULL,
                 output_tokens INTEGER NOT NULL,
                 cached_tokens INTEGER NOT NULL,
-                timestamp TEXT NOT NULL
+                timestamp TEXT NOT NULL,
+                bucket_end TEXT,
+                source_granularity TEXT,
+                avg_latency_ms REAL,
+                source TEXT NOT NULL DEFAULT 'api'
             );
 
             CREATE TABLE IF NOT EXISTS cost_records (
@@ -39,21 +378,195 @@ impl Storage {
                 output_cost REAL NOT NULL,
                 total_cost REAL NOT NULL,
                 currency TEXT NOT NULL,
-                timestamp TEXT NOT NULL
+                timestamp TEXT NOT NULL,
+                bucket_end TEXT,
+                source_granularity TEXT,
+                source TEXT NOT NULL DEFAULT 'api'
+            );
+
+            CREATE TABLE IF NOT EXISTS refresh_log (
+                id INTEGER PRIMARY KEY,
+                provider TEXT NOT NULL,
+                window_hours INTEGER NOT NULL,
+                refreshed_at TEXT NOT NULL,
+                status TEXT NOT NULL,
+                rows_fetched INTEGER NOT NULL,
+                error TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS refresh_deltas (
+                id INTEGER PRIMARY KEY,
+                refreshed_at TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                cost_delta REAL NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS provider_auth (
+                provider TEXT PRIMARY KEY,
+                failed_at TEXT NOT NULL,
+                error TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS export_runs (
+                id INTEGER PRIMARY KEY,
+                pipeline TEXT NOT NULL,
+                ran_at TEXT NOT NULL,
+                status TEXT NOT NULL,
+                message TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS statements (
+                month TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                account TEXT,
+                input_tokens INTEGER NOT NULL,
+                output_tokens INTEGER NOT NULL,
+                total_cost REAL NOT NULL,
+                currency TEXT NOT NULL,
+                frozen_at TEXT NOT NULL,
+                PRIMARY KEY (month, provider)
+            );
+
+            CREATE TRIGGER IF NOT EXISTS statements_immutable
+            BEFORE UPDATE ON statements
+            BEGIN
+                SELECT RAISE(ABORT, 'statements are immutable');
+            END;
+
+            CREATE TABLE IF NOT EXISTS rate_limits (
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                tokens_per_minute INTEGER NOT NULL,
+                requests_per_minute INTEGER,
+                fetched_at TEXT NOT NULL,
+                PRIMARY KEY (provider, model)
+            );
+
+            CREATE TABLE IF NOT EXISTS balances (
+                provider TEXT PRIMARY KEY,
+                amount REAL NOT NULL,
+                currency TEXT NOT NULL,
+                fetched_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS request_log (
+                at TEXT NOT NULL,
+                requests INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY,
+                at TEXT NOT NULL,
+                source TEXT NOT NULL,
+                action TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                detail TEXT NOT NULL
+            );
+
+            CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+            BEFORE UPDATE ON audit_log
+            BEGIN
+                SELECT RAISE(ABORT, 'audit_log is append-only');
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+            BEFORE DELETE ON audit_log
+            BEGIN
+                SELECT RAISE(ABORT, 'audit_log is append-only');
+            END;
+
+            CREATE TABLE IF NOT EXISTS alerts (
+                id INTEGER PRIMARY KEY,
+                kind TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                message TEXT NOT NULL,
+                state TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
             );
             "#,
         )?;
+        self.add_column_if_missing("usage_records", "source_granularity", "TEXT")?;
+        self.add_column_if_missing("usage_records", "avg_latency_ms", "REAL")?;
+        self.add_column_if_missing("usage_records", "bucket_end", "TEXT")?;
+        self.add_column_if_missing("cost_records", "bucket_end", "TEXT")?;
+        self.add_column_if_missing("cost_records", "source_granularity", "TEXT")?;
+        self.add_column_if_missing("usage_records", "source", "TEXT NOT NULL DEFAULT 'api'")?;
+        self.add_column_if_missing("cost_records", "source", "TEXT NOT NULL DEFAULT 'api'")?;
+        self.add_column_if_missing("refresh_log", "parser_version", "TEXT")?;
+        self.writer()?.execute(
+            "INSERT INTO meta (key, value) VALUES ('schema_version', ?1), ('written_by', ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![SCHEMA_VERSION.to_string(), env!("CARGO_PKG_VERSION")],
+        )?;
+        Ok(())
+    }
+
+    /// Refuses databases stamped with a newer schema than `known`, the version
+    /// this binary writes, before anything is created or altered. Unstamped
+    /// databases predate the stamp and are treated as version 1.
+    fn check_schema_version(&self, known: u32) -> Result<(), AppError> {
+        self.writer()?.execute_batch(
+            "CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+        )?;
+        let (version, written_by) = self.schema_stamp()?;
+        match version {
+            Some(version) if version > known => Err(AppError::Storage(format!(
+                "{} uses schema v{version}, written by llm-meter {}; this is llm-meter {} (schema v{known}). \
+                 Upgrade llm-meter here, or point --db at a separate database, so the newer data is not corrupted.",
+                self.readers.path.display(),
+                written_by.as_deref().unwrap_or("(unknown)"),
+                env!("CARGO_PKG_VERSION"),
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Schema version and binary version from `meta`, if stamped.
+    fn schema_stamp(&self) -> Result<(Option<u32>, Option<String>), AppError> {
+        let conn = self.writer()?;
+        let mut stmt = conn.prepare("SELECT key, value FROM meta")?;
+        let mut rows = stmt.query([])?;
+        let (mut version, mut written_by) = (None, None);
+        while let Some(row) = rows.next()? {
+            let value: String = row.get(1)?;
+            match row.get::<_, String>(0)?.as_str() {
+                "schema_version" => {
+                    version = Some(value.parse().map_err(|_| {
+                        AppError::Storage(format!("unreadable schema_version '{value}' in meta"))
+                    })?);
+                }
+                "written_by" => written_by = Some(value),
+                _ => {}
+            }
+        }
+        Ok((version, written_by))
+    }
+
+    /// Upgrades databases created before `column` existed.
+    fn add_column_if_missing(&self, table: &str, column: &str, decl: &str) -> Result<(), AppError> {
+        let conn = self.writer()?;
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?",
+            params![table, column],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+        }
         Ok(())
     }
 
     pub fn replace_snapshot(
-        &mut self,
+        &self,
         since: DateTime<Utc>,
         providers: &[String],
         usage: &[UsageRecord],
         cost: &[CostRecord],
     ) -> Result<(), AppError> {
-        let tx = self.conn.transaction()?;
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
         let since_str = since.to_rfc3339();
 
         if !providers.is_empty() {
@@ -68,8 +581,8 @@ impl Storage {
         }
 
         let mut insert_usage = tx.prepare(
-            "INSERT INTO usage_records (provider, model, input_tokens, output_tokens, cached_tokens, timestamp)
-             VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO usage_records (provider, model, input_tokens, output_tokens, cached_tokens, timestamp, bucket_end, source_granularity, avg_latency_ms, source)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )?;
         for r in usage {
             insert_usage.execute(params![
@@ -79,12 +592,16 @@ impl Storage {
                 r.output_tokens,
                 r.cached_tokens,
                 r.timestamp.to_rfc3339(),
+                r.bucket_end.map(|t| t.to_rfc3339()),
+                r.source_granularity.map(Granularity::as_str),
+                r.avg_latency_ms,
+                r.source.as_str(),
             ])?;
         }
 
         let mut insert_cost = tx.prepare(
-            "INSERT INTO cost_records (provider, model, input_cost, output_cost, total_cost, currency, timestamp)
-             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO cost_records (provider, model, input_cost, output_cost, total_cost, currency, timestamp, bucket_end, source_granularity, source)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )?;
         for r in cost {
             insert_cost.execute(params![
@@ -95,141 +612,1534 @@ impl Storage {
                 r.total_cost,
                 r.currency,
                 r.timestamp.to_rfc3339(),
+                r.bucket_end.map(|t| t.to_rfc3339()),
+                r.source_granularity.map(Granularity::as_str),
+                r.source.as_str(),
             ])?;
         }
 
         drop(insert_usage);
         drop(insert_cost);
         tx.commit()?;
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            cache.clear();
+        }
         Ok(())
     }
 
-    pub fn aggregate_since(&self, since: DateTime<Utc>) -> Result<AggregateSummary, AppError> {
-        let since_str = since.to_rfc3339();
+    /// Logs one provider fetch. `parser_version` is the usage schema that
+    /// parsed it, for adapters that version their parsers.
+    pub fn record_refresh(
+        &self,
+        provider: &str,
+        window_hours: i64,
+        at: DateTime<Utc>,
+        outcome: Result<usize, &str>,
+        parser_version: Option<&str>,
+    ) -> Result<(), AppError> {
+        let conn = self.writer()?;
+        let (status, rows, error) = match outcome {
+            Ok(rows) => ("ok", rows as i64, None),
+            Err(message) => ("error", 0, Some(message)),
+        };
+        conn.execute(
+            "INSERT INTO refresh_log
+                 (provider, window_hours, refreshed_at, status, rows_fetched, error, parser_version)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                provider,
+                window_hours,
+                at.to_rfc3339(),
+                status,
+                rows,
+                error,
+                parser_version
+            ],
+        )?;
+        Ok(())
+    }
 
-        let token_total_raw: i64 = self.conn.query_row(
-            "SELECT COALESCE(SUM(input_tokens + output_tokens + cached_tokens), 0) FROM usage_records WHERE timestamp >= ?",
-            [since_str.clone()],
-            |row| row.get(0),
+    /// Logs the provider API calls one refresh or backfill sent.
+    pub fn record_requests(&self, at: DateTime<Utc>, requests: u32) -> Result<(), AppError> {
+        if requests == 0 {
+            return Ok(());
+        }
+        self.writer()?.execute(
+            "INSERT INTO request_log (at, requests) VALUES (?, ?)",
+            params![at.to_rfc3339(), requests],
         )?;
-        let token_total = token_total_raw.max(0) as u64;
+        Ok(())
+    }
 
-        let cost_total: f64 = self.conn.query_row(
-            "SELECT COALESCE(SUM(total_cost), 0.0) FROM cost_records WHERE timestamp >= ?",
-            [since_str.clone()],
+    /// Provider API calls logged since `since`.
+    pub fn requests_since(&self, since: DateTime<Utc>) -> Result<u64, AppError> {
+        let total: i64 = self.reader()?.query_row(
+            "SELECT COALESCE(SUM(requests), 0) FROM request_log WHERE at >= ?",
+            [since.to_rfc3339()],
             |row| row.get(0),
         )?;
+        Ok(total.max(0) as u64)
+    }
 
-        let mut by_provider_stmt = self.conn.prepare(
-            "SELECT provider, COALESCE(SUM(total_cost), 0.0) AS c
-             FROM cost_records WHERE timestamp >= ?
-             GROUP BY provider ORDER BY c DESC",
+    pub fn record_audit(&self, entries: &[AuditEntry]) -> Result<(), AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO audit_log (at, source, action, subject, detail)
+                 VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for e in entries {
+                insert.execute(params![
+                    e.at.to_rfc3339(),
+                    e.source,
+                    e.action,
+                    e.subject,
+                    e.detail
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The latest `limit` audit entries, newest first.
+    pub fn audit_log(&self, limit: usize) -> Result<Vec<AuditEntry>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT at, source, action, subject, detail FROM audit_log
+             ORDER BY id DESC LIMIT ?",
         )?;
-        let by_provider = by_provider_stmt
-            .query_map([since_str.clone()], |r| Ok((r.get(0)?, r.get(1)?)))?
-            .collect::<Result<Vec<_>, _>>()?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            let at: String = row.get(0)?;
+            Ok(AuditEntry {
+                at: parse_ts(&at, 0)?,
+                source: row.get(1)?,
+                action: row.get(2)?,
+                subject: row.get(3)?,
+                detail: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
 
-        let mut by_model_stmt = self.conn.prepare(
-            "SELECT model, COALESCE(SUM(total_cost), 0.0) AS c
-             FROM cost_records WHERE timestamp >= ?
-             GROUP BY model ORDER BY c DESC LIMIT 10",
+    pub fn record_refresh_deltas(
+        &self,
+        at: DateTime<Utc>,
+        deltas: &[CostDelta],
+    ) -> Result<(), AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO refresh_deltas (refreshed_at, provider, model, cost_delta)
+                 VALUES (?, ?, ?, ?)",
+            )?;
+            for d in deltas {
+                insert.execute(params![at.to_rfc3339(), d.provider, d.model, d.cost_delta])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Flags `provider` as needing a new key; refreshes skip it until
+    /// [`Storage::clear_needs_reauth`] is called.
+    pub fn mark_needs_reauth(
+        &self,
+        provider: &str,
+        at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), AppError> {
+        let conn = self.writer()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO provider_auth (provider, failed_at, error) VALUES (?, ?, ?)",
+            params![provider, at.to_rfc3339(), error],
         )?;
-        let by_model = by_model_stmt
-            .query_map([since_str], |r| Ok((r.get(0)?, r.get(1)?)))?
-            .collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
 
-        Ok((token_total, cost_total, by_provider, by_model))
+    pub fn clear_needs_reauth(&self, provider: &str) -> Result<bool, AppError> {
+        let conn = self.writer()?;
+        let changed = conn.execute(
+            "DELETE FROM provider_auth WHERE provider = ?",
+            params![provider],
+        )?;
+        Ok(changed > 0)
     }
 
-    pub fn export_cost_json(&self) -> Result<String, AppError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT provider, model, input_cost, output_cost, total_cost, currency, timestamp FROM cost_records ORDER BY timestamp DESC",
+    pub fn record_export_run(
+        &self,
+        pipeline: &str,
+        at: DateTime<Utc>,
+        outcome: Result<&str, &str>,
+    ) -> Result<(), AppError> {
+        let (status, message) = match outcome {
+            Ok(message) => ("ok", message),
+            Err(message) => ("error", message),
+        };
+        self.writer()?.execute(
+            "INSERT INTO export_runs (pipeline, ran_at, status, message) VALUES (?, ?, ?, ?)",
+            params![pipeline, at.to_rfc3339(), status, message],
+        )?;
+        Ok(())
+    }
+
+    /// Most recent run of `pipeline`, successful or not.
+    pub fn last_export_run(&self, pipeline: &str) -> Result<Option<ExportRun>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT ran_at, status, message FROM export_runs
+             WHERE pipeline = ? ORDER BY ran_at DESC, id DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![pipeline], |row| {
+            let ran_at: String = row.get(0)?;
+            let status: String = row.get(1)?;
+            Ok(ExportRun {
+                pipeline: pipeline.to_string(),
+                ran_at: parse_ts(&ran_at, 0)?,
+                ok: status == "ok",
+                message: row.get(2)?,
+            })
+        })?;
+        Ok(rows.next().transpose()?)
+    }
+
+    pub fn last_successful_export(
+        &self,
+        pipeline: &str,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let conn = self.reader()?;
+        let raw: Option<String> = conn.query_row(
+            "SELECT MAX(ran_at) FROM export_runs WHERE pipeline = ? AND status = 'ok'",
+            params![pipeline],
+            |row| row.get(0),
         )?;
+        raw.map(|r| parse_ts(&r, 0).map_err(AppError::from))
+            .transpose()
+    }
 
+    /// Frozen statement rows for `month` (`YYYY-MM`), empty if not frozen.
+    pub fn statement(&self, month: &str) -> Result<Vec<Statement>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT provider, account, input_tokens, output_tokens, total_cost, currency, frozen_at
+             FROM statements WHERE month = ? ORDER BY provider",
+        )?;
         let rows = stmt
-            .query_map([], |r| {
-                Ok(CostRecord {
+            .query_map(params![month], |r| {
+                Ok(Statement {
+                    month: month.to_string(),
                     provider: r.get(0)?,
-                    model: r.get(1)?,
-                    input_cost: r.get(2)?,
-                    output_cost: r.get(3)?,
+                    account: r.get(1)?,
+                    input_tokens: r.get::<_, i64>(2)?.max(0) as u64,
+                    output_tokens: r.get::<_, i64>(3)?.max(0) as u64,
                     total_cost: r.get(4)?,
                     currency: r.get(5)?,
-                    timestamp: chrono::DateTime::parse_from_rfc3339(&r.get::<_, String>(6)?)
-                        .map(|d| d.with_timezone(&Utc))
-                        .map_err(|e| {
-                            rusqlite::Error::FromSqlConversionFailure(6, Type::Text, Box::new(e))
-                        })?,
+                    frozen_at: Some(parse_ts(&r.get::<_, String>(6)?, 6)?),
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(serde_json::to_string_pretty(&rows)?)
+        Ok(rows)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::{Duration, TimeZone};
-    use tempfile::TempDir;
 
-    fn sample_usage(provider: &str, model: &str, ts: DateTime<Utc>, tokens: u64) -> UsageRecord {
-        UsageRecord {
-            provider: provider.to_string(),
-            model: model.to_string(),
-            input_tokens: tokens,
-            output_tokens: 0,
-            cached_tokens: 0,
-            timestamp: ts,
+    /// Live per-provider totals for `[start, end)` from the snapshot tables.
+    pub fn statement_totals(
+        &self,
+        month: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Statement>, AppError> {
+        let conn = self.reader()?;
+        let (start, end) = (start.to_rfc3339(), end.to_rfc3339());
+        let range = params![start, end];
+        let mut rows: Vec<Statement> = conn
+            .prepare(&format!(
+                "SELECT provider, COALESCE(SUM({}), 0.0)
+                 FROM cost_records WHERE timestamp >= ? AND timestamp < ?
+                 GROUP BY provider ORDER BY provider",
+                self.usd_total()
+            ))?
+            .query_map(range, |r| {
+                Ok(Statement {
+                    month: month.to_string(),
+                    provider: r.get(0)?,
+                    account: None,
+                    input_tokens: 0,
+                    output_tokens: 0,
+                    total_cost: r.get(1)?,
+                    currency: "USD".into(),
+                    frozen_at: None,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut usage_stmt = conn.prepare(
+            "SELECT provider, COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0)
+             FROM usage_records WHERE timestamp >= ? AND timestamp < ?
+             GROUP BY provider",
+        )?;
+        let usage = usage_stmt
+            .query_map(range, |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, i64>(1)?.max(0) as u64,
+                    r.get::<_, i64>(2)?.max(0) as u64,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (provider, input, output) in usage {
+            if let Some(row) = rows.iter_mut().find(|r| r.provider == provider) {
+                row.input_tokens = input;
+                row.output_tokens = output;
+            }
         }
+        Ok(rows)
     }
 
-    fn sample_cost(provider: &str, model: &str, ts: DateTime<Utc>, total_cost: f64) -> CostRecord {
-        CostRecord {
-            provider: provider.to_string(),
-            model: model.to_string(),
-            input_cost: total_cost,
-            output_cost: 0.0,
-            total_cost,
-            currency: "USD".to_string(),
-            timestamp: ts,
+    /// Stores `rows` as the statement for `month`. An existing statement is
+    /// only replaced when `force` is set; rows are never updated in place.
+    pub fn freeze_statement(
+        &self,
+        month: &str,
+        rows: &[Statement],
+        force: bool,
+    ) -> Result<(), AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        let existing: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM statements WHERE month = ?",
+            params![month],
+            |row| row.get(0),
+        )?;
+        if existing > 0 && !force {
+            return Err(AppError::Config(format!(
+                "The {month} statement is already frozen; pass --force to recompute it."
+            )));
+        }
+        tx.execute("DELETE FROM statements WHERE month = ?", params![month])?;
+        for row in rows {
+            tx.execute(
+                "INSERT INTO statements
+                 (month, provider, account, input_tokens, output_tokens, total_cost, currency, frozen_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    month,
+                    row.provider,
+                    row.account,
+                    row.input_tokens as i64,
+                    row.output_tokens as i64,
+                    row.total_cost,
+                    row.currency,
+                    row.frozen_at.map(|at| at.to_rfc3339()),
+                ],
+            )?;
         }
+        tx.commit()?;
+        Ok(())
     }
 
-    fn fixed_ts(hour: i64) -> DateTime<Utc> {
-        Utc.timestamp_opt(1_700_000_000 + (hour * 3600), 0)
-            .single()
-            .expect("valid fixed timestamp")
+    pub fn providers_needing_reauth(&self) -> Result<Vec<String>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare("SELECT provider FROM provider_auth ORDER BY provider")?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(rows)
     }
 
-    #[test]
-    fn replace_snapshot_replaces_rows_without_double_counting() {
-        let tmp = TempDir::new().expect("tempdir");
-        let db = tmp.path().join("snapshots.sqlite");
-        let mut storage = Storage::open(&db).expect("open storage");
-        let since = fixed_ts(0);
-
-        storage
-            .replace_snapshot(
-                since,
-                &["openai".to_string()],
-                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
-                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0)],
-            )
-            .expect("first snapshot");
+    /// Net cost change made by the most recent successful refresh (zero if it
+    /// changed nothing), or `None` if nothing has been refreshed yet.
+    pub fn latest_refresh_delta(&self) -> Result<Option<(DateTime<Utc>, f64)>, AppError> {
+        let conn = self.reader()?;
+        let latest: Option<String> = conn.query_row(
+            "SELECT MAX(refreshed_at) FROM refresh_log WHERE status = 'ok'",
+            [],
+            |row| row.get(0),
+        )?;
+        let Some(latest) = latest else {
+            return Ok(None);
+        };
+        let total: f64 = conn.query_row(
+            "SELECT COALESCE(SUM(cost_delta), 0.0) FROM refresh_deltas WHERE refreshed_at = ?",
+            [&latest],
+            |row| row.get(0),
+        )?;
+        Ok(Some((parse_ts(&latest, 0)?, total)))
+    }
 
-        storage
-            .replace_snapshot(
-                since,
+    pub fn cost_by_provider_model_since(
+        &self,
+        since: DateTime<Utc>,
+        providers: &[String],
+    ) -> Result<HashMap<(String, String), f64>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT model, COALESCE(SUM({}), 0.0)
+             FROM cost_records WHERE provider = ? AND timestamp >= ?
+             GROUP BY model",
+            self.usd_total()
+        ))?;
+        let mut totals = HashMap::new();
+        for provider in providers {
+            let rows = stmt
+                .query_map(params![provider, since.to_rfc3339()], |r| {
+                    Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (model, cost) in rows {
+                totals.insert((provider.clone(), model), cost);
+            }
+        }
+        Ok(totals)
+    }
+
+    /// USD cost since `since`, optionally limited to one provider and/or model.
+    pub fn cost_since_matching(
+        &self,
+        since: DateTime<Utc>,
+        provider: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<f64, AppError> {
+        let conn = self.reader()?;
+        let total = conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM({}), 0.0) FROM cost_records
+                 WHERE timestamp >= ?1 AND (?2 IS NULL OR provider = ?2) AND (?3 IS NULL OR model = ?3)",
+                self.usd_total()
+            ),
+            params![since.to_rfc3339(), provider, model],
+            |row| row.get(0),
+        )?;
+        Ok(total)
+    }
+
+    pub fn last_successful_refresh(
+        &self,
+        provider: &str,
+        min_window_hours: i64,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let conn = self.reader()?;
+        let raw: Option<String> = conn.query_row(
+            "SELECT MAX(refreshed_at) FROM refresh_log
+             WHERE provider = ? AND status = 'ok' AND window_hours >= ?",
+            params![provider, min_window_hours],
+            |row| row.get(0),
+        )?;
+        raw.map(|r| parse_ts(&r, 0).map_err(AppError::from))
+            .transpose()
+    }
+
+    /// End of `provider`'s latest stored usage bucket, or its start for rows
+    /// stored without an end.
+    pub fn latest_bucket_end(&self, provider: &str) -> Result<Option<DateTime<Utc>>, AppError> {
+        let conn = self.reader()?;
+        let raw: Option<String> = conn.query_row(
+            "SELECT MAX(COALESCE(bucket_end, timestamp)) FROM usage_records WHERE provider = ?",
+            params![provider],
+            |row| row.get(0),
+        )?;
+        raw.map(|r| parse_ts(&r, 0).map_err(AppError::from))
+            .transpose()
+    }
+
+    /// Failed refreshes for `provider` since its last successful one, and the
+    /// time of the most recent failure.
+    pub fn consecutive_failures(
+        &self,
+        provider: &str,
+    ) -> Result<(u32, Option<DateTime<Utc>>), AppError> {
+        let conn = self.reader()?;
+        let (count, last): (i64, Option<String>) = conn.query_row(
+            "SELECT COUNT(*), MAX(refreshed_at) FROM refresh_log
+             WHERE provider = ?1 AND status = 'error' AND refreshed_at > COALESCE(
+                 (SELECT MAX(refreshed_at) FROM refresh_log WHERE provider = ?1 AND status = 'ok'),
+                 ''
+             )",
+            params![provider],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let last = last.map(|r| parse_ts(&r, 1)).transpose()?;
+        Ok((count.max(0) as u32, last))
+    }
+
+    /// `provider`'s last successful refresh, usage rows overlapping the window
+    /// starting at `since`, and the error of its latest refresh when that
+    /// refresh failed.
+    pub fn provider_health(
+        &self,
+        provider: &str,
+        since: DateTime<Utc>,
+    ) -> Result<ProviderHealth, AppError> {
+        let conn = self.reader()?;
+        let last_success: Option<String> = conn.query_row(
+            "SELECT MAX(refreshed_at) FROM refresh_log WHERE provider = ? AND status = 'ok'",
+            params![provider],
+            |row| row.get(0),
+        )?;
+        let rows: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM usage_records
+             WHERE provider = ? AND COALESCE(bucket_end, timestamp) > ?",
+            params![provider, since.to_rfc3339()],
+            |row| row.get(0),
+        )?;
+        let last_error: Option<Option<String>> = conn
+            .query_row(
+                "SELECT CASE WHEN status = 'error' THEN COALESCE(error, 'unknown error') END
+                 FROM refresh_log WHERE provider = ? ORDER BY refreshed_at DESC, id DESC LIMIT 1",
+                params![provider],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(ProviderHealth {
+            last_success: last_success.map(|r| parse_ts(&r, 0)).transpose()?,
+            rows_in_window: rows.max(0) as usize,
+            last_error: last_error.flatten(),
+        })
+    }
+
+    /// Totals and breakdowns since `since`. Results are cached under `since`
+    /// truncated to the minute, so repeated calls from the tick loop share the
+    /// result of the first exact query in that minute until the next write.
+    /// `edge` decides how buckets that straddle `since` count; rows without a
+    /// `bucket_end` only count when they start inside the window.
+    pub fn aggregate_since(
+        &self,
+        since: DateTime<Utc>,
+        edge: EdgeBuckets,
+    ) -> Result<AggregateSummary, AppError> {
+        let key = (
+            since.duration_trunc(TimeDelta::minutes(1)).unwrap_or(since),
+            edge,
+        );
+        if let Some(hit) = self
+            .aggregate_cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&key).cloned())
+        {
+            return Ok(hit);
+        }
+
+        let summary = self.query_aggregate(since, edge)?;
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            if cache.len() >= AGGREGATE_CACHE_LIMIT {
+                cache.clear();
+            }
+            cache.insert(key, summary.clone());
+        }
+        Ok(summary)
+    }
+
+    fn query_aggregate(
+        &self,
+        since: DateTime<Utc>,
+        edge: EdgeBuckets,
+    ) -> Result<AggregateSummary, AppError> {
+        let conn = self.reader()?;
+        let since_str = since.to_rfc3339();
+        // Row filter and per-row weight; `?1` is `since`.
+        let (filter, weight) = match edge {
+            EdgeBuckets::Exclude => ("timestamp >= ?1", "1.0"),
+            EdgeBuckets::Include => ("(timestamp >= ?1 OR bucket_end > ?1)", "1.0"),
+            EdgeBuckets::Prorate => (
+                "(timestamp >= ?1 OR bucket_end > ?1)",
+                "CASE WHEN timestamp >= ?1 THEN 1.0
+                      ELSE (julianday(bucket_end) - julianday(?1))
+                           / (julianday(bucket_end) - julianday(timestamp)) END",
+            ),
+        };
+
+        let token_total_raw: i64 = conn.query_row(
+            &format!(
+                "SELECT CAST(ROUND(COALESCE(SUM((input_tokens + output_tokens + cached_tokens) * {weight}), 0)) AS INTEGER)
+                 FROM usage_records WHERE {filter}"
+            ),
+            [since_str.clone()],
+            |row| row.get(0),
+        )?;
+        let token_total = token_total_raw.max(0) as u64;
+        let cost = self.usd_total();
+
+        let cost_total: f64 = conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM({cost} * {weight}), 0.0) FROM cost_records WHERE {filter}"
+            ),
+            [since_str.clone()],
+            |row| row.get(0),
+        )?;
+
+        let mut by_provider_stmt = conn.prepare(&format!(
+            "SELECT provider, COALESCE(SUM({cost} * {weight}), 0.0) AS c
+             FROM cost_records WHERE {filter}
+             GROUP BY provider ORDER BY c DESC"
+        ))?;
+        let by_provider = by_provider_stmt
+            .query_map([since_str.clone()], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut by_model_stmt = conn.prepare(&format!(
+            "SELECT model, COALESCE(SUM({cost} * {weight}), 0.0) AS c
+             FROM cost_records WHERE {filter}
+             GROUP BY model ORDER BY c DESC"
+        ))?;
+        let by_model = by_model_stmt
+            .query_map([since_str.clone()], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut by_source_stmt = conn.prepare(&format!(
+            "SELECT source, COALESCE(SUM({cost} * {weight}), 0.0) AS c
+             FROM cost_records WHERE {filter}
+             GROUP BY source ORDER BY c DESC"
+        ))?;
+        let by_source = by_source_stmt
+            .query_map([since_str], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((token_total, cost_total, by_provider, by_model, by_source))
+    }
+
+    /// Cost per `(provider, model)` of rows starting in `[start, end)`.
+    pub fn cost_by_provider_model_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(String, String, f64)>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT provider, model, COALESCE(SUM({}), 0.0)
+             FROM cost_records WHERE timestamp >= ? AND timestamp < ?
+             GROUP BY provider, model",
+            self.usd_total()
+        ))?;
+        let rows = stmt
+            .query_map(params![start.to_rfc3339(), end.to_rfc3339()], |r| {
+                Ok((r.get(0)?, r.get(1)?, r.get(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn cost_by_model_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(String, f64)>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT model, COALESCE(SUM({}), 0.0) AS c
+             FROM cost_records WHERE timestamp >= ? AND timestamp < ?
+             GROUP BY model ORDER BY c DESC",
+            self.usd_total()
+        ))?;
+        let rows = stmt
+            .query_map(params![start.to_rfc3339(), end.to_rfc3339()], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Cost per `width` bucket from the bucket holding `since` up to `until`,
+    /// oldest first, with empty buckets as zero. Rows are bucketed by their
+    /// start, so a row coarser than `width` lands whole in its first bucket.
+    pub fn cost_series(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        width: Granularity,
+    ) -> Result<Vec<(DateTime<Utc>, f64)>, AppError> {
+        let step = width.duration();
+        let start = since.duration_trunc(step).unwrap_or(since);
+        let format = match width {
+            Granularity::Minute => "%Y-%m-%dT%H:%M:00Z",
+            Granularity::Hour => "%Y-%m-%dT%H:00:00Z",
+            Granularity::Day => "%Y-%m-%dT00:00:00Z",
+        };
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT strftime(?1, timestamp) AS bucket, COALESCE(SUM({}), 0.0)
+             FROM cost_records WHERE timestamp >= ?2 AND timestamp < ?3
+             GROUP BY bucket",
+            self.usd_total()
+        ))?;
+        let totals = stmt
+            .query_map(
+                params![format, start.to_rfc3339(), until.to_rfc3339()],
+                |r| Ok((r.get::<_, String>(0)?, r.get::<_, f64>(1)?)),
+            )?
+            .map(|row| {
+                let (bucket, cost) = row?;
+                Ok((parse_ts(&bucket, 0)?, cost))
+            })
+            .collect::<Result<HashMap<_, _>, AppError>>()?;
+
+        let mut series = Vec::new();
+        let mut at = start;
+        while at < until {
+            series.push((at, totals.get(&at).copied().unwrap_or(0.0)));
+            at += step;
+        }
+        Ok(series)
+    }
+
+    /// Every model with stored cost rows, for pickers.
+    pub fn known_models(&self) -> Result<Vec<String>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT model FROM cost_records ORDER BY model")?;
+        let rows = stmt
+            .query_map([], |r| r.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Stored `(usage, cost)` row counts for `provider`.
+    pub fn provider_row_counts(&self, provider: &str) -> Result<(usize, usize), AppError> {
+        let conn = self.reader()?;
+        let count = |table: &str| -> Result<usize, AppError> {
+            let n: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM {table} WHERE provider = ?"),
+                params![provider],
+                |row| row.get(0),
+            )?;
+            Ok(n as usize)
+        };
+        Ok((count("usage_records")?, count("cost_records")?))
+    }
+
+    /// Replaces `provider`'s cost rows starting in `[start, end)` with `rows`,
+    /// returning how many were removed. Used for imported billing exports,
+    /// which cover a fixed period rather than a window ending now.
+    pub fn replace_costs_between(
+        &self,
+        provider: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        rows: &[CostRecord],
+    ) -> Result<usize, AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        let removed = tx.execute(
+            "DELETE FROM cost_records WHERE provider = ? AND timestamp >= ? AND timestamp < ?",
+            params![provider, start.to_rfc3339(), end.to_rfc3339()],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO cost_records (provider, model, input_cost, output_cost, total_cost, currency, timestamp, bucket_end, source_granularity, source)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for r in rows {
+                insert.execute(params![
+                    r.provider,
+                    r.model,
+                    r.input_cost,
+                    r.output_cost,
+                    r.total_cost,
+                    r.currency,
+                    r.timestamp.to_rfc3339(),
+                    r.bucket_end.map(|t| t.to_rfc3339()),
+                    r.source_granularity.map(Granularity::as_str),
+                    r.source.as_str(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            cache.clear();
+        }
+        Ok(removed)
+    }
+
+    /// Deletes `provider`'s usage, cost, refresh, re-auth and rate-limit history, returning
+    /// the `(usage, cost)` rows removed. Frozen statements are kept.
+    pub fn purge_provider(&self, provider: &str) -> Result<(usize, usize), AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        let usage = tx.execute(
+            "DELETE FROM usage_records WHERE provider = ?",
+            params![provider],
+        )?;
+        let cost = tx.execute(
+            "DELETE FROM cost_records WHERE provider = ?",
+            params![provider],
+        )?;
+        for table in [
+            "refresh_log",
+            "refresh_deltas",
+            "provider_auth",
+            "rate_limits",
+            "balances",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE provider = ?"),
+                params![provider],
+            )?;
+        }
+        tx.commit()?;
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            cache.clear();
+        }
+        Ok((usage, cost))
+    }
+
+    /// Problems found by `PRAGMA quick_check`; empty when the file is sound.
+    pub fn quick_check(&self) -> Result<Vec<String>, AppError> {
+        let conn = self.writer()?;
+        let mut stmt = conn.prepare("PRAGMA quick_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut problems = Vec::new();
+        for row in rows {
+            let row = row?;
+            if row != "ok" {
+                problems.push(row);
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Rebuilds every index from its table, which fixes the index-only damage
+    /// `quick_check` commonly reports.
+    pub fn reindex(&self) -> Result<(), AppError> {
+        self.writer()?.execute_batch("REINDEX")?;
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the database to `dest`, encrypted with the
+    /// same key as this one. The copy is staged next to `dest` and renamed
+    /// into place, so a failed backup never clobbers the previous one.
+    pub fn backup_to(&self, dest: &Path) -> Result<(), AppError> {
+        let staged = dest.with_extension("partial");
+        if staged.exists() {
+            std::fs::remove_file(&staged)?;
+        }
+        match self.readers.key.as_deref() {
+            Some(key) => self.export_rekeyed(&staged, Some(key))?,
+            None => {
+                self.writer()?
+                    .execute("VACUUM INTO ?1", params![staged.to_string_lossy()])?;
+            }
+        }
+        std::fs::rename(&staged, dest)?;
+        Ok(())
+    }
+
+    /// Refreshes the rolling [`backup_path`] copy when it is missing or older
+    /// than `max_age`. Returns whether a backup was written.
+    pub fn ensure_recent_backup(&self, max_age: Duration) -> Result<bool, AppError> {
+        let dest = backup_path(&self.readers.path);
+        let fresh = std::fs::metadata(&dest)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|at| at.elapsed().ok())
+            .is_some_and(|age| age < max_age);
+        if fresh {
+            return Ok(false);
+        }
+        self.backup_to(&dest)?;
+        Ok(true)
+    }
+
+    /// Writes a copy of the whole database to `dest`, encrypted with the hex
+    /// `key` or in plain SQLite when `key` is `None`. `dest` must not exist.
+    pub fn export_rekeyed(&self, dest: &Path, key: Option<&str>) -> Result<(), AppError> {
+        require_encryption()?;
+        let key = key.map(key_literal).transpose()?.unwrap_or_default();
+        let conn = self.writer()?;
+        conn.execute(
+            "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+            params![dest.to_string_lossy(), key],
+        )?;
+        let exported = conn.query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()));
+        conn.execute_batch("DETACH DATABASE rekeyed")?;
+        Ok(exported?)
+    }
+
+    /// Relabels all of `from`'s stored history as `to` in one transaction,
+    /// returning the usage and cost rows moved. Frozen statements are copied
+    /// rather than updated, since the trigger forbids updates.
+    pub fn rename_provider(&self, from: &str, to: &str) -> Result<usize, AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        let existing: i64 = tx.query_row(
+            "SELECT (SELECT COUNT(*) FROM usage_records WHERE provider = ?1)
+                  + (SELECT COUNT(*) FROM cost_records WHERE provider = ?1)
+                  + (SELECT COUNT(*) FROM statements WHERE provider = ?1)",
+            params![to],
+            |row| row.get(0),
+        )?;
+        if existing > 0 {
+            return Err(AppError::Config(format!(
+                "History is already stored for '{to}'; purge it first."
+            )));
+        }
+        let mut moved = 0;
+        for table in ["usage_records", "cost_records"] {
+            moved += tx.execute(
+                &format!("UPDATE {table} SET provider = ?1 WHERE provider = ?2"),
+                params![to, from],
+            )?;
+        }
+        for table in [
+            "refresh_log",
+            "refresh_deltas",
+            "provider_auth",
+            "rate_limits",
+            "balances",
+        ] {
+            tx.execute(
+                &format!("UPDATE {table} SET provider = ?1 WHERE provider = ?2"),
+                params![to, from],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO statements
+             (month, provider, account, input_tokens, output_tokens, total_cost, currency, frozen_at)
+             SELECT month, ?1, account, input_tokens, output_tokens, total_cost, currency, frozen_at
+             FROM statements WHERE provider = ?2",
+            params![to, from],
+        )?;
+        tx.execute("DELETE FROM statements WHERE provider = ?", params![from])?;
+        tx.commit()?;
+        if let Ok(mut cache) = self.aggregate_cache.lock() {
+            cache.clear();
+        }
+        Ok(moved)
+    }
+
+    /// Like [`Self::cost_by_model_between`], for one provider.
+    pub fn provider_cost_by_model_between(
+        &self,
+        provider: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(String, f64)>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT model, COALESCE(SUM({}), 0.0) AS c
+             FROM cost_records WHERE provider = ? AND timestamp >= ? AND timestamp < ?
+             GROUP BY model ORDER BY c DESC",
+            self.usd_total()
+        ))?;
+        let rows = stmt
+            .query_map(
+                params![provider, start.to_rfc3339(), end.to_rfc3339()],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Replaces `provider`'s stored rate limits with `limits`.
+    pub fn replace_rate_limits(
+        &self,
+        provider: &str,
+        limits: &[RateLimit],
+    ) -> Result<(), AppError> {
+        let mut conn = self.writer()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM rate_limits WHERE provider = ?",
+            params![provider],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO rate_limits
+                 (provider, model, tokens_per_minute, requests_per_minute, fetched_at)
+                 VALUES (?, ?, ?, ?, ?)",
+            )?;
+            for l in limits {
+                insert.execute(params![
+                    provider,
+                    l.model,
+                    l.tokens_per_minute,
+                    l.requests_per_minute,
+                    l.fetched_at.to_rfc3339(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Stores `balance` as its provider's latest.
+    pub fn replace_balance(&self, balance: &AccountBalance) -> Result<(), AppError> {
+        self.writer()?.execute(
+            "INSERT OR REPLACE INTO balances (provider, amount, currency, fetched_at)
+             VALUES (?, ?, ?, ?)",
+            params![
+                balance.provider,
+                balance.amount,
+                balance.currency,
+                balance.fetched_at.to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Latest balance of every provider that reported one.
+    pub fn balances(&self) -> Result<Vec<AccountBalance>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT provider, amount, currency, fetched_at FROM balances ORDER BY provider",
+        )?;
+        let rows = stmt.query_map([], |r| {
+            let fetched_at: String = r.get(3)?;
+            Ok(AccountBalance {
+                provider: r.get(0)?,
+                amount: r.get(1)?,
+                currency: r.get(2)?,
+                fetched_at: parse_ts(&fetched_at, 3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn rate_limit(&self, provider: &str, model: &str) -> Result<Option<RateLimit>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT tokens_per_minute, requests_per_minute, fetched_at
+             FROM rate_limits WHERE provider = ? AND model = ?",
+        )?;
+        let mut rows = stmt.query_map(params![provider, model], |r| {
+            let fetched_at: String = r.get(2)?;
+            Ok(RateLimit {
+                provider: provider.to_string(),
+                model: model.to_string(),
+                tokens_per_minute: r.get(0)?,
+                requests_per_minute: r.get(1)?,
+                fetched_at: parse_ts(&fetched_at, 2)?,
+            })
+        })?;
+        Ok(rows.next().transpose()?)
+    }
+
+    /// Highest tokens-per-minute rate among `provider`/`model`'s usage buckets
+    /// since `since`. Stored buckets are at least `granularity` wide, or the
+    /// provider's own width when that is coarser.
+    pub fn peak_tokens_per_minute(
+        &self,
+        provider: &str,
+        model: &str,
+        since: DateTime<Utc>,
+        granularity: Granularity,
+    ) -> Result<Option<PeakRate>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, source_granularity, SUM(input_tokens + output_tokens)
+             FROM usage_records WHERE provider = ? AND model = ? AND timestamp >= ?
+             GROUP BY timestamp, source_granularity",
+        )?;
+        let mut peak: Option<PeakRate> = None;
+        let rows = stmt.query_map(params![provider, model, since.to_rfc3339()], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, Option<String>>(1)?,
+                r.get::<_, u64>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (at, source, tokens) = row?;
+            let width = source
+                .as_deref()
+                .and_then(Granularity::parse)
+                .map_or(granularity, |s| s.max(granularity));
+            let rate = tokens as f64 / width.duration().num_minutes() as f64;
+            if peak.is_none_or(|p| rate > p.tokens_per_minute) {
+                peak = Some(PeakRate {
+                    tokens_per_minute: rate,
+                    at: parse_ts(&at, 0)?,
+                    width,
+                });
+            }
+        }
+        Ok(peak)
+    }
+
+    pub fn usage_by_model_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsageSummary>, AppError> {
+        let conn = self.reader()?;
+        let since_str = since.to_rfc3339();
+
+        let mut usage_stmt = conn.prepare(
+            "SELECT provider, model, COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0),
+                    COALESCE(SUM(cached_tokens), 0),
+                    SUM(avg_latency_ms * MAX(input_tokens + output_tokens, 1))
+                      / SUM(CASE WHEN avg_latency_ms IS NOT NULL
+                            THEN MAX(input_tokens + output_tokens, 1) END)
+             FROM usage_records WHERE timestamp >= ?
+             GROUP BY provider, model",
+        )?;
+        let mut rows = usage_stmt
+            .query_map([since_str.clone()], |r| {
+                Ok(ModelUsageSummary {
+                    provider: r.get(0)?,
+                    model: r.get(1)?,
+                    input_tokens: r.get::<_, i64>(2)?.max(0) as u64,
+                    output_tokens: r.get::<_, i64>(3)?.max(0) as u64,
+                    cached_tokens: r.get::<_, i64>(4)?.max(0) as u64,
+                    cost: 0.0,
+                    avg_latency_ms: r.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut cost_stmt = conn.prepare(&format!(
+            "SELECT provider, model, COALESCE(SUM({}), 0.0)
+             FROM cost_records WHERE timestamp >= ?
+             GROUP BY provider, model",
+            self.usd_total()
+        ))?;
+        let costs = cost_stmt
+            .query_map([since_str], |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, f64>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (provider, model, cost) in costs {
+            if let Some(row) = rows
+                .iter_mut()
+                .find(|r| r.provider == provider && r.model == model)
+            {
+                row.cost = cost;
+            }
+        }
+
+        rows.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+        Ok(rows)
+    }
+
+    pub fn record_alert(
+        &self,
+        kind: &str,
+        subject: &str,
+        message: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<AlertRecord>, AppError> {
+        let conn = self.writer()?;
+        let open: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM alerts WHERE kind = ? AND subject = ? AND state != 'resolved'",
+            params![kind, subject],
+            |row| row.get(0),
+        )?;
+        if open > 0 {
+            return Ok(None);
+        }
+
+        let at_str = at.to_rfc3339();
+        conn.execute(
+            "INSERT INTO alerts (kind, subject, message, state, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                kind,
+                subject,
+                message,
+                AlertState::New.as_str(),
+                at_str,
+                at_str
+            ],
+        )?;
+        Ok(Some(AlertRecord {
+            id: conn.last_insert_rowid(),
+            kind: kind.to_string(),
+            subject: subject.to_string(),
+            message: message.to_string(),
+            state: AlertState::New,
+            created_at: at,
+            updated_at: at,
+        }))
+    }
+
+    pub fn list_alerts(&self, limit: usize) -> Result<Vec<AlertRecord>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, subject, message, state, created_at, updated_at
+             FROM alerts ORDER BY id DESC LIMIT ?",
+        )?;
+        let rows = stmt
+            .query_map([limit as i64], |r| {
+                let raw_state: String = r.get(4)?;
+                Ok(AlertRecord {
+                    id: r.get(0)?,
+                    kind: r.get(1)?,
+                    subject: r.get(2)?,
+                    message: r.get(3)?,
+                    state: AlertState::parse(&raw_state).unwrap_or(AlertState::New),
+                    created_at: parse_ts(&r.get::<_, String>(5)?, 5)?,
+                    updated_at: parse_ts(&r.get::<_, String>(6)?, 6)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn set_alert_state(
+        &self,
+        id: i64,
+        state: AlertState,
+        at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let conn = self.writer()?;
+        let changed = conn.execute(
+            "UPDATE alerts SET state = ?, updated_at = ? WHERE id = ?",
+            params![state.as_str(), at.to_rfc3339(), id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn resolve_alerts(
+        &self,
+        kind: &str,
+        subject: &str,
+        at: DateTime<Utc>,
+    ) -> Result<usize, AppError> {
+        let conn = self.writer()?;
+        let changed = conn.execute(
+            "UPDATE alerts SET state = 'resolved', updated_at = ?
+             WHERE kind = ? AND subject = ? AND state != 'resolved'",
+            params![at.to_rfc3339(), kind, subject],
+        )?;
+        Ok(changed)
+    }
+
+    /// Subjects with an unresolved alert of `kind`.
+    pub fn open_alert_subjects(&self, kind: &str) -> Result<Vec<String>, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT subject FROM alerts
+             WHERE kind = ? AND state != 'resolved' ORDER BY subject",
+        )?;
+        let rows = stmt
+            .query_map([kind], |r| r.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn unacked_alert_count(&self) -> Result<usize, AppError> {
+        let conn = self.reader()?;
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM alerts WHERE state = 'new'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count.max(0) as usize)
+    }
+
+    pub fn export_cost_json(&self) -> Result<String, AppError> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COST_COLUMNS} FROM cost_records ORDER BY timestamp DESC"
+        ))?;
+
+        let rows = stmt
+            .query_map([], cost_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(serde_json::to_string_pretty(&rows)?)
+    }
+
+    /// Runs one ad-hoc `SELECT` (or `WITH`, `VALUES`, `EXPLAIN`) statement on
+    /// a read-only connection, keeping at most `max_rows` rows. Other
+    /// statements are refused before they run, so `PRAGMA` and `ATTACH`
+    /// cannot change the pooled connection either.
+    pub fn run_query(&self, sql: &str, max_rows: Option<usize>) -> Result<QueryResult, AppError> {
+        let sql = sql.trim().trim_end_matches(';');
+        let keyword = sql
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        if !matches!(keyword.as_str(), "SELECT" | "WITH" | "VALUES" | "EXPLAIN") {
+            return Err(AppError::Storage(
+                "only read-only SELECT queries are allowed".into(),
+            ));
+        }
+        if has_statement_separator(sql) {
+            return Err(AppError::Storage("run one statement at a time".into()));
+        }
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(AppError::Storage(
+                "only read-only SELECT queries are allowed".into(),
+            ));
+        }
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let width = columns.len();
+        let max_rows = max_rows.unwrap_or(usize::MAX);
+        let mut result = QueryResult {
+            columns,
+            ..QueryResult::default()
+        };
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if result.rows.len() == max_rows {
+                result.truncated = true;
+                break;
+            }
+            let values = (0..width)
+                .map(|idx| {
+                    Ok(match row.get_ref(idx)? {
+                        ValueRef::Null => String::new(),
+                        ValueRef::Integer(v) => v.to_string(),
+                        ValueRef::Real(v) => v.to_string(),
+                        ValueRef::Text(v) => String::from_utf8_lossy(v).into_owned(),
+                        ValueRef::Blob(v) => format!("<{} bytes>", v.len()),
+                    })
+                })
+                .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+            result.rows.push(values);
+        }
+        Ok(result)
+    }
+
+    /// Usage rows matching `filter`, newest first: `limit` of them from
+    /// `offset`, and how many match in all.
+    pub fn search_usage(
+        &self,
+        filter: &RecordFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<UsageRecord>, usize), AppError> {
+        let conn = self.reader()?;
+        let (clause, values) = filter.clause();
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM usage_records WHERE {clause}"),
+            params_from_iter(&values),
+            |r| r.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {USAGE_COLUMNS} FROM usage_records WHERE {clause}
+             ORDER BY timestamp DESC, id DESC LIMIT {limit} OFFSET {offset}"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(&values), usage_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((rows, total.max(0) as usize))
+    }
+
+    /// Cost rows matching `filter`, as [`Storage::search_usage`].
+    pub fn search_cost(
+        &self,
+        filter: &RecordFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<CostRecord>, usize), AppError> {
+        let conn = self.reader()?;
+        let (clause, values) = filter.clause();
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM cost_records WHERE {clause}"),
+            params_from_iter(&values),
+            |r| r.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COST_COLUMNS} FROM cost_records WHERE {clause}
+             ORDER BY timestamp DESC, id DESC LIMIT {limit} OFFSET {offset}"
+        ))?;
+        let rows = stmt
+            .query_map(params_from_iter(&values), cost_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((rows, total.max(0) as usize))
+    }
+}
+
+/// Whether `sql` has a `;` outside quotes, i.e. a second statement that
+/// `prepare` would silently skip.
+fn has_statement_separator(sql: &str) -> bool {
+    let mut quote = None;
+    for ch in sql.chars() {
+        match (quote, ch) {
+            (None, '\'' | '"' | '`') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, ';') => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Columns [`usage_from_row`] reads, in order.
+const USAGE_COLUMNS: &str = "provider, model, input_tokens, output_tokens, cached_tokens, \
+     timestamp, bucket_end, source_granularity, avg_latency_ms, source";
+
+/// Columns [`cost_from_row`] reads, in order.
+const COST_COLUMNS: &str = "provider, model, input_cost, output_cost, total_cost, currency, \
+     timestamp, bucket_end, source_granularity, source";
+
+fn usage_from_row(r: &rusqlite::Row<'_>) -> Result<UsageRecord, rusqlite::Error> {
+    Ok(UsageRecord {
+        provider: r.get(0)?,
+        model: r.get(1)?,
+        input_tokens: r.get::<_, i64>(2)?.max(0) as u64,
+        output_tokens: r.get::<_, i64>(3)?.max(0) as u64,
+        cached_tokens: r.get::<_, i64>(4)?.max(0) as u64,
+        timestamp: parse_ts(&r.get::<_, String>(5)?, 5)?,
+        bucket_end: r
+            .get::<_, Option<String>>(6)?
+            .map(|raw| parse_ts(&raw, 6))
+            .transpose()?,
+        source_granularity: r
+            .get::<_, Option<String>>(7)?
+            .as_deref()
+            .and_then(Granularity::parse),
+        avg_latency_ms: r.get(8)?,
+        source: UsageSource::parse(&r.get::<_, String>(9)?).unwrap_or_default(),
+    })
+}
+
+fn cost_from_row(r: &rusqlite::Row<'_>) -> Result<CostRecord, rusqlite::Error> {
+    Ok(CostRecord {
+        provider: r.get(0)?,
+        model: r.get(1)?,
+        input_cost: r.get(2)?,
+        output_cost: r.get(3)?,
+        total_cost: r.get(4)?,
+        currency: r.get(5)?,
+        timestamp: parse_ts(&r.get::<_, String>(6)?, 6)?,
+        bucket_end: r
+            .get::<_, Option<String>>(7)?
+            .map(|raw| parse_ts(&raw, 7))
+            .transpose()?,
+        source_granularity: r
+            .get::<_, Option<String>>(8)?
+            .as_deref()
+            .and_then(Granularity::parse),
+        source: UsageSource::parse(&r.get::<_, String>(9)?).unwrap_or_default(),
+    })
+}
+
+fn parse_ts(raw: &str, idx: usize) -> Result<DateTime<Utc>, rusqlite::Error> {
+    chrono::DateTime::parse_from_rfc3339(raw)
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use tempfile::TempDir;
+
+    fn sample_usage(provider: &str, model: &str, ts: DateTime<Utc>, tokens: u64) -> UsageRecord {
+        UsageRecord {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens: tokens,
+            output_tokens: 0,
+            cached_tokens: 0,
+            timestamp: ts,
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+            avg_latency_ms: None,
+        }
+    }
+
+    fn sample_cost(provider: &str, model: &str, ts: DateTime<Utc>, total_cost: f64) -> CostRecord {
+        CostRecord {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_cost: total_cost,
+            output_cost: 0.0,
+            total_cost,
+            currency: "USD".to_string(),
+            timestamp: ts,
+            bucket_end: None,
+            source_granularity: None,
+            source: UsageSource::Api,
+        }
+    }
+
+    fn fixed_ts(hour: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + (hour * 3600), 0)
+            .single()
+            .expect("valid fixed timestamp")
+    }
+
+    #[test]
+    fn newer_schema_stamp_refuses_to_open_for_writing() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("snapshots.sqlite");
+        let storage = Storage::open(&db).expect("open storage");
+        assert_eq!(
+            storage.schema_stamp().unwrap(),
+            (
+                Some(SCHEMA_VERSION),
+                Some(env!("CARGO_PKG_VERSION").to_string())
+            )
+        );
+        drop(storage);
+
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "UPDATE meta SET value = '99' WHERE key = 'schema_version';
+             UPDATE meta SET value = '9.9.0' WHERE key = 'written_by';",
+        )
+        .unwrap();
+        drop(conn);
+        let err = Storage::open(&db).err().expect("newer schema refused");
+        let message = err.to_string();
+        assert!(message.contains("schema v99, written by llm-meter 9.9.0"));
+        assert!(message.contains("Upgrade llm-meter"));
+
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch("DROP TABLE meta").unwrap();
+        drop(conn);
+        let storage = Storage::open(&db).expect("unstamped database opens");
+        assert_eq!(storage.schema_stamp().unwrap().0, Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn v1_stamp_is_upgraded_and_v2_is_refused_by_a_v1_binary() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("snapshots.sqlite");
+        drop(Storage::open(&db).expect("open storage"));
+
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch("UPDATE meta SET value = '1' WHERE key = 'schema_version'")
+            .unwrap();
+        drop(conn);
+        let storage = Storage::open(&db).expect("v1 database opens");
+        assert_eq!(storage.schema_stamp().unwrap().0, Some(2));
+
+        let err = storage
+            .check_schema_version(1)
+            .expect_err("v1 binary refuses a v2 database");
+        assert!(err.to_string().contains("uses schema v2"));
+        assert!(err.to_string().contains("(schema v1)"));
+        storage.check_schema_version(2).expect("same version opens");
+    }
+
+    #[test]
+    fn replace_snapshot_replaces_rows_without_double_counting() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("snapshots.sqlite");
+        let storage = Storage::open(&db).expect("open storage");
+        let since = fixed_ts(0);
+
+        storage
+            .replace_snapshot(
+                since,
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0)],
+            )
+            .expect("first snapshot");
+
+        storage
+            .replace_snapshot(
+                since,
                 &["openai".to_string()],
                 &[sample_usage("openai", "gpt-4o", fixed_ts(2), 250)],
                 &[sample_cost("openai", "gpt-4o", fixed_ts(2), 2.5)],
             )
             .expect("second snapshot");
 
-        let (tokens, cost, by_provider, by_model) = storage
-            .aggregate_since(since - Duration::hours(1))
+        let (tokens, cost, by_provider, by_model, _) = storage
+            .aggregate_since(since - Duration::hours(1), EdgeBuckets::Exclude)
             .expect("aggregate");
         assert_eq!(tokens, 250);
         assert!((cost - 2.5).abs() < f64::EPSILON);
@@ -237,11 +2147,207 @@ mod tests {
         assert_eq!(by_model, vec![("gpt-4o".to_string(), 2.5)]);
     }
 
+    #[tokio::test]
+    async fn async_storage_runs_calls_off_the_runtime_thread() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage =
+            AsyncStorage::new(Storage::open(&tmp.path().join("db.sqlite")).expect("open storage"));
+
+        let since = fixed_ts(0);
+        storage
+            .call(move |s| {
+                s.replace_snapshot(
+                    since,
+                    &["openai".to_string()],
+                    &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
+                    &[sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0)],
+                )
+            })
+            .await
+            .expect("write snapshot");
+        let (tokens, ..) = storage
+            .clone()
+            .call(move |s| s.aggregate_since(since, EdgeBuckets::Exclude))
+            .await
+            .expect("aggregate");
+        assert_eq!(tokens, 100);
+    }
+
+    #[test]
+    fn cloned_handles_read_concurrently_in_wal_mode() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let mode: String = storage
+            .reader()
+            .expect("reader")
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .expect("journal mode");
+        assert_eq!(mode, "wal");
+
+        let since = fixed_ts(0);
+        storage
+            .replace_snapshot(
+                since,
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0)],
+            )
+            .expect("write snapshot");
+
+        // Hold the writer while other clones read from their own threads.
+        let _writer = storage.writer().expect("writer");
+        let handles = (0..3)
+            .map(|_| {
+                let reader = storage.clone();
+                std::thread::spawn(move || reader.usage_by_model_since(since))
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let rows = handle.join().expect("thread").expect("read");
+            assert_eq!(rows.len(), 1);
+        }
+    }
+
+    #[test]
+    fn delete_journal_mode_leaves_no_wal_file() {
+        let tmp = TempDir::new().expect("tempdir");
+        let path = tmp.path().join("db.sqlite");
+        let storage = Storage::open(&path).expect("open in wal");
+        drop(storage);
+        let storage = Storage::open_with(&path, None, JournalMode::Delete).expect("open storage");
+        let mode: String = storage
+            .writer()
+            .expect("writer")
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .expect("journal mode");
+        assert_eq!(mode, "delete");
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
+                &[],
+            )
+            .expect("write snapshot");
+        assert!(!path.with_extension("sqlite-wal").exists());
+        assert_eq!(
+            storage
+                .usage_by_model_since(fixed_ts(0))
+                .expect("read")
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn edge_buckets_exclude_include_or_prorate_straddling_buckets() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        // Minute-aligned, since `aggregate_since` truncates to the minute.
+        let at = |hour| Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap() + Duration::hours(hour);
+        // A 24h bucket from hour 0 to 24 and an hourly one at hour 20.
+        let mut daily_usage = sample_usage("openai", "gpt-4o", at(0), 240);
+        daily_usage.bucket_end = Some(at(24));
+        let mut daily_cost = sample_cost("openai", "gpt-4o", at(0), 24.0);
+        daily_cost.bucket_end = Some(at(24));
+        let mut hourly_cost = sample_cost("anthropic", "claude", at(20), 1.0);
+        hourly_cost.bucket_end = Some(at(21));
+        storage
+            .replace_snapshot(at(0), &[], &[daily_usage], &[daily_cost, hourly_cost])
+            .expect("seed");
+
+        let since = at(18);
+        let (_, excluded, ..) = storage
+            .aggregate_since(since, EdgeBuckets::Exclude)
+            .expect("exclude");
+        assert!((excluded - 1.0).abs() < 1e-9);
+        let (tokens, included, ..) = storage
+            .aggregate_since(since, EdgeBuckets::Include)
+            .expect("include");
+        assert_eq!(tokens, 240);
+        assert!((included - 25.0).abs() < 1e-9);
+        let (tokens, prorated, by_provider, ..) = storage
+            .aggregate_since(since, EdgeBuckets::Prorate)
+            .expect("prorate");
+        assert_eq!(tokens, 60);
+        assert!((prorated - 7.0).abs() < 1e-9);
+        assert_eq!(by_provider[0].0, "openai");
+        assert!((by_provider[0].1 - 6.0).abs() < 1e-9);
+
+        let (_, past_end, ..) = storage
+            .aggregate_since(at(24), EdgeBuckets::Prorate)
+            .expect("after the bucket");
+        assert_eq!(past_end, 0.0);
+    }
+
+    #[test]
+    fn aggregate_since_cache_is_invalidated_by_snapshot_writes() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("snapshots.sqlite");
+        let storage = Storage::open(&db).expect("open storage");
+        let since = fixed_ts(0);
+
+        storage
+            .replace_snapshot(
+                since,
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 100)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0)],
+            )
+            .expect("first snapshot");
+        let first = storage
+            .aggregate_since(since, EdgeBuckets::Exclude)
+            .expect("aggregate");
+        let again = storage
+            .aggregate_since(since + Duration::seconds(5), EdgeBuckets::Exclude)
+            .expect("cached aggregate");
+        assert_eq!(first, again);
+        assert_eq!(storage.aggregate_cache.lock().expect("cache lock").len(), 1);
+
+        storage
+            .replace_snapshot(
+                since,
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(2), 300)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(2), 3.0)],
+            )
+            .expect("second snapshot");
+        assert!(storage
+            .aggregate_cache
+            .lock()
+            .expect("cache lock")
+            .is_empty());
+        let (tokens, cost, ..) = storage
+            .aggregate_since(since, EdgeBuckets::Exclude)
+            .expect("aggregate");
+        assert_eq!(tokens, 300);
+        assert!((cost - 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn aggregate_since_queries_the_exact_since() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("snapshots.sqlite")).expect("open storage");
+        let bucket = Utc.with_ymd_and_hms(2026, 3, 9, 10, 0, 0).unwrap();
+        storage
+            .replace_snapshot(
+                bucket - Duration::hours(1),
+                &["openai".to_string()],
+                &[sample_usage("openai", "gpt-4o", bucket, 100)],
+                &[sample_cost("openai", "gpt-4o", bucket, 1.0)],
+            )
+            .expect("snapshot");
+        let (tokens, cost, ..) = storage
+            .aggregate_since(bucket + Duration::seconds(30), EdgeBuckets::Exclude)
+            .expect("aggregate");
+        assert_eq!((tokens, cost), (0, 0.0));
+    }
+
     #[test]
     fn replace_snapshot_only_affects_targeted_providers() {
         let tmp = TempDir::new().expect("tempdir");
         let db = tmp.path().join("snapshots.sqlite");
-        let mut storage = Storage::open(&db).expect("open storage");
+        let storage = Storage::open(&db).expect("open storage");
         let since = fixed_ts(0);
 
         storage
@@ -268,8 +2374,8 @@ mod tests {
             )
             .expect("replace openai");
 
-        let (tokens, cost, by_provider, _) = storage
-            .aggregate_since(since - Duration::hours(1))
+        let (tokens, cost, by_provider, ..) = storage
+            .aggregate_since(since - Duration::hours(1), EdgeBuckets::Exclude)
             .expect("aggregate");
         assert_eq!(tokens, 120);
         assert!((cost - 1.2).abs() < 1e-9);
@@ -278,19 +2384,212 @@ mod tests {
         assert_eq!(by_provider[1], ("openai".to_string(), 0.4));
     }
 
+    #[test]
+    fn usage_by_model_since_joins_tokens_and_cost() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("snapshots.sqlite");
+        let storage = Storage::open(&db).expect("open storage");
+        let since = fixed_ts(0);
+
+        storage
+            .replace_snapshot(
+                since,
+                &["openai".to_string()],
+                &[
+                    sample_usage("openai", "gpt-4o", fixed_ts(1), 100),
+                    sample_usage("openai", "gpt-4o", fixed_ts(2), 50),
+                    sample_usage("openai", "gpt-4o-mini", fixed_ts(2), 10),
+                ],
+                &[
+                    sample_cost("openai", "gpt-4o", fixed_ts(1), 1.0),
+                    sample_cost("openai", "gpt-4o", fixed_ts(2), 0.5),
+                ],
+            )
+            .expect("replace snapshot");
+
+        let rows = storage.usage_by_model_since(since).expect("usage by model");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].model, "gpt-4o");
+        assert_eq!(rows[0].input_tokens, 150);
+        assert!((rows[0].cost - 1.5).abs() < 1e-9);
+        assert_eq!(rows[1].model, "gpt-4o-mini");
+        assert_eq!(rows[1].cost, 0.0);
+    }
+
+    #[test]
+    fn cost_series_buckets_by_hour_or_day_and_fills_gaps() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("snapshots.sqlite")).expect("open storage");
+        let at = |d: u32, h: u32, m: u32| Utc.with_ymd_and_hms(2025, 5, d, h, m, 0).unwrap();
+        storage
+            .replace_snapshot(
+                at(1, 0, 0),
+                &["openai".to_string(), "anthropic".to_string()],
+                &[],
+                &[
+                    sample_cost("openai", "gpt-4o", at(1, 10, 0), 1.0),
+                    sample_cost("anthropic", "claude", at(1, 10, 30), 0.5),
+                    sample_cost("openai", "gpt-4o", at(1, 12, 15), 2.0),
+                    sample_cost("openai", "gpt-4o", at(2, 9, 0), 4.0),
+                ],
+            )
+            .expect("replace snapshot");
+
+        let hourly = storage
+            .cost_series(at(1, 10, 20), at(1, 13, 0), Granularity::Hour)
+            .expect("hourly series");
+        assert_eq!(
+            hourly,
+            vec![
+                (at(1, 10, 0), 1.5),
+                (at(1, 11, 0), 0.0),
+                (at(1, 12, 0), 2.0)
+            ]
+        );
+
+        let daily = storage
+            .cost_series(at(1, 6, 0), at(2, 18, 0), Granularity::Day)
+            .expect("daily series");
+        assert_eq!(daily, vec![(at(1, 0, 0), 3.5), (at(2, 0, 0), 4.0)]);
+    }
+
+    #[test]
+    fn record_alert_dedupes_open_alerts_and_tracks_state() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("snapshots.sqlite")).expect("open storage");
+
+        let id = storage
+            .record_alert("budget", "monthly", "Budget exceeded", fixed_ts(0))
+            .expect("record alert")
+            .expect("new alert")
+            .id;
+        assert!(storage
+            .record_alert("budget", "monthly", "Budget exceeded again", fixed_ts(1))
+            .expect("record duplicate")
+            .is_none());
+        assert_eq!(storage.unacked_alert_count().expect("count"), 1);
+
+        storage
+            .set_alert_state(id, AlertState::Acked, fixed_ts(2))
+            .expect("ack alert");
+        assert_eq!(storage.unacked_alert_count().expect("count"), 0);
+
+        storage
+            .set_alert_state(id, AlertState::Resolved, fixed_ts(3))
+            .expect("resolve alert");
+        assert!(storage
+            .record_alert("budget", "monthly", "Budget exceeded", fixed_ts(4))
+            .expect("record after resolve")
+            .is_some());
+
+        let alerts = storage.list_alerts(10).expect("list alerts");
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].state, AlertState::New);
+        assert_eq!(alerts[1].state, AlertState::Resolved);
+    }
+
+    #[test]
+    fn last_successful_refresh_requires_covering_window() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("snapshots.sqlite")).expect("open storage");
+
+        storage
+            .record_refresh("openai", 24, fixed_ts(1), Ok(3), Some("openai/buckets-v2"))
+            .expect("log 1d");
+        storage
+            .record_refresh("openai", 24 * 7, fixed_ts(2), Err("boom"), None)
+            .expect("log failed 7d");
+
+        assert_eq!(
+            storage
+                .last_successful_refresh("openai", 24)
+                .expect("query"),
+            Some(fixed_ts(1))
+        );
+        assert_eq!(
+            storage
+                .last_successful_refresh("openai", 24 * 7)
+                .expect("query"),
+            None
+        );
+        let logged = storage
+            .run_query("SELECT parser_version FROM refresh_log ORDER BY id", None)
+            .expect("query log");
+        assert_eq!(
+            logged.rows,
+            vec![vec!["openai/buckets-v2".to_string()], vec![String::new()]]
+        );
+        assert_eq!(
+            storage
+                .last_successful_refresh("anthropic", 24)
+                .expect("query"),
+            None
+        );
+
+        let health = storage
+            .provider_health("openai", fixed_ts(0))
+            .expect("health");
+        assert_eq!(health.last_success, Some(fixed_ts(1)));
+        assert_eq!(health.last_error.as_deref(), Some("boom"));
+        storage
+            .record_refresh("openai", 24, fixed_ts(3), Ok(0), None)
+            .expect("log recovered");
+        assert_eq!(
+            storage
+                .provider_health("openai", fixed_ts(0))
+                .expect("health")
+                .last_error,
+            None
+        );
+    }
+
+    #[test]
+    fn latest_refresh_delta_is_zero_when_last_refresh_changed_nothing() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        assert_eq!(storage.latest_refresh_delta().expect("query"), None);
+
+        let delta = CostDelta {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            cost_delta: 0.42,
+        };
+        storage
+            .record_refresh("openai", 24, fixed_ts(1), Ok(1), None)
+            .expect("log refresh");
+        storage
+            .record_refresh_deltas(fixed_ts(1), &[delta])
+            .expect("log deltas");
+        assert_eq!(
+            storage.latest_refresh_delta().expect("query"),
+            Some((fixed_ts(1), 0.42))
+        );
+
+        storage
+            .record_refresh("openai", 24, fixed_ts(2), Ok(1), None)
+            .expect("log refresh");
+        assert_eq!(
+            storage.latest_refresh_delta().expect("query"),
+            Some((fixed_ts(2), 0.0))
+        );
+    }
+
     #[test]
     fn export_cost_json_serializes_inserted_rows() {
         let tmp = TempDir::new().expect("tempdir");
         let db = tmp.path().join("snapshots.sqlite");
-        let mut storage = Storage::open(&db).expect("open storage");
+        let storage = Storage::open(&db).expect("open storage");
         let since = fixed_ts(0);
+        let mut cost = sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5);
+        cost.bucket_end = Some(fixed_ts(1) + Duration::days(1));
+        cost.source_granularity = Some(Granularity::Day);
 
         storage
             .replace_snapshot(
                 since,
                 &["openai".to_string()],
                 &[sample_usage("openai", "gpt-4o", fixed_ts(1), 50)],
-                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5)],
+                &[cost],
             )
             .expect("replace snapshot");
 
@@ -300,5 +2599,427 @@ mod tests {
         assert_eq!(rows[0].provider, "openai");
         assert_eq!(rows[0].model, "gpt-4o");
         assert!((rows[0].total_cost - 0.5).abs() < f64::EPSILON);
+        assert_eq!(rows[0].bucket_end, Some(fixed_ts(1) + Duration::days(1)));
+        assert_eq!(rows[0].source_granularity, Some(Granularity::Day));
+    }
+
+    #[test]
+    fn backups_archives_and_read_only_opens() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("db.sqlite");
+        let storage = Storage::open(&db).expect("open storage");
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &[],
+                &[],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5)],
+            )
+            .expect("replace snapshot");
+        assert!(storage
+            .ensure_recent_backup(std::time::Duration::from_secs(3600))
+            .expect("backup"));
+        assert!(!storage
+            .ensure_recent_backup(std::time::Duration::from_secs(3600))
+            .expect("backup"));
+        let backup = backup_path(&db);
+        assert_eq!(backup, tmp.path().join("db.sqlite.bak"));
+        drop(storage);
+
+        let read_only = Storage::open_read_only(&backup, None).expect("open read-only");
+        assert_eq!(
+            read_only.provider_row_counts("openai").expect("count"),
+            (0, 1)
+        );
+        assert!(read_only.purge_provider("openai").is_err());
+
+        let archived = archive_database(&db, fixed_ts(0)).expect("archive");
+        assert!(!db.exists());
+        assert!(archived.exists());
+        assert!(archived
+            .to_string_lossy()
+            .ends_with("db.sqlite.corrupt-20231114T221320Z"));
+    }
+
+    #[test]
+    fn keyed_databases_need_sqlcipher_and_the_matching_key() {
+        let tmp = TempDir::new().expect("tempdir");
+        let plain = tmp.path().join("plain.sqlite");
+        let key = "ab".repeat(32);
+        if !ENCRYPTION_SUPPORTED {
+            assert!(Storage::open_with(&plain, Some(&key), JournalMode::default()).is_err());
+            return;
+        }
+        let storage = Storage::open(&plain).expect("open storage");
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &[],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 50)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5)],
+            )
+            .expect("replace snapshot");
+        let encrypted = tmp.path().join("encrypted.sqlite");
+        storage
+            .export_rekeyed(&encrypted, Some(&key))
+            .expect("export");
+        drop(storage);
+
+        assert!(Storage::open(&encrypted).is_err());
+        assert!(
+            Storage::open_with(&encrypted, Some(&"cd".repeat(32)), JournalMode::default()).is_err()
+        );
+        assert!(Storage::open_with(&encrypted, Some("not-hex"), JournalMode::default()).is_err());
+        let reopened =
+            Storage::open_with(&encrypted, Some(&key), JournalMode::default()).expect("unlock");
+        assert_eq!(
+            reopened.provider_row_counts("openai").expect("count"),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn rate_limits_are_replaced_per_provider_and_peaks_use_bucket_width() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let limit = |model: &str, tpm| RateLimit {
+            provider: "openai".into(),
+            model: model.into(),
+            tokens_per_minute: tpm,
+            requests_per_minute: Some(500),
+            fetched_at: fixed_ts(0),
+        };
+        storage
+            .replace_rate_limits("openai", &[limit("gpt-4o", 30_000), limit("o1", 10_000)])
+            .unwrap();
+        storage
+            .replace_rate_limits("openai", &[limit("gpt-4o", 60_000)])
+            .unwrap();
+        assert_eq!(
+            storage.rate_limit("openai", "gpt-4o").unwrap(),
+            Some(limit("gpt-4o", 60_000))
+        );
+        assert_eq!(storage.rate_limit("openai", "o1").unwrap(), None);
+
+        let mut hourly = sample_usage("openai", "gpt-4o", fixed_ts(1), 120_000);
+        hourly.source_granularity = Some(Granularity::Hour);
+        let mut minute = sample_usage("openai", "gpt-4o", fixed_ts(2), 9_000);
+        minute.source_granularity = Some(Granularity::Minute);
+        storage
+            .replace_snapshot(fixed_ts(0), &[], &[hourly, minute], &[])
+            .unwrap();
+        let peak = storage
+            .peak_tokens_per_minute("openai", "gpt-4o", fixed_ts(0), Granularity::Minute)
+            .unwrap()
+            .expect("peak");
+        assert_eq!(peak.tokens_per_minute, 9_000.0);
+        assert_eq!(peak.width, Granularity::Minute);
+        assert_eq!(peak.utilization(&limit("gpt-4o", 60_000)), Some(0.15));
+        // Stored at hourly width, the 1m bucket averages to 150 tokens/min.
+        let peak = storage
+            .peak_tokens_per_minute("openai", "gpt-4o", fixed_ts(0), Granularity::Hour)
+            .unwrap()
+            .expect("peak");
+        assert_eq!((peak.tokens_per_minute, peak.at), (2_000.0, fixed_ts(1)));
+
+        storage.rename_provider("openai", "openai-prod").unwrap();
+        assert!(storage
+            .rate_limit("openai-prod", "gpt-4o")
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn purge_provider_removes_only_that_providers_history() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &[],
+                &[
+                    sample_usage("openai", "gpt-4o", fixed_ts(1), 50),
+                    sample_usage("openai", "gpt-4o", fixed_ts(2), 50),
+                    sample_usage("anthropic", "claude", fixed_ts(1), 10),
+                ],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5)],
+            )
+            .expect("replace snapshot");
+        storage
+            .mark_needs_reauth("openai", fixed_ts(3), "401")
+            .expect("mark");
+
+        assert_eq!(
+            storage.provider_row_counts("openai").expect("count"),
+            (2, 1)
+        );
+        assert_eq!(storage.purge_provider("openai").expect("purge"), (2, 1));
+        assert_eq!(
+            storage.provider_row_counts("openai").expect("count"),
+            (0, 0)
+        );
+        assert_eq!(
+            storage.provider_row_counts("anthropic").expect("count"),
+            (1, 0)
+        );
+        assert!(storage
+            .providers_needing_reauth()
+            .expect("reauth")
+            .is_empty());
+    }
+
+    #[test]
+    fn rename_provider_moves_history_and_frozen_statements() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &[],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(1), 50)],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 0.5)],
+            )
+            .expect("replace snapshot");
+        let row = Statement {
+            month: "2023-11".into(),
+            provider: "openai".into(),
+            account: None,
+            input_tokens: 50,
+            output_tokens: 0,
+            total_cost: 0.5,
+            currency: "USD".into(),
+            frozen_at: Some(fixed_ts(2)),
+        };
+        storage
+            .freeze_statement("2023-11", &[row], false)
+            .expect("freeze");
+
+        assert_eq!(
+            storage
+                .rename_provider("openai", "openai-prod")
+                .expect("rename"),
+            2
+        );
+        assert_eq!(
+            storage.provider_row_counts("openai").expect("count"),
+            (0, 0)
+        );
+        assert_eq!(
+            storage.provider_row_counts("openai-prod").expect("count"),
+            (1, 1)
+        );
+        let statement = storage.statement("2023-11").expect("statement");
+        assert_eq!(statement.len(), 1);
+        assert_eq!(statement[0].provider, "openai-prod");
+        assert_eq!(statement[0].frozen_at, Some(fixed_ts(2)));
+
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &[],
+                &[sample_usage("openai", "gpt-4o", fixed_ts(3), 5)],
+                &[],
+            )
+            .expect("replace snapshot");
+        assert!(storage.rename_provider("openai", "openai-prod").is_err());
+    }
+
+    #[test]
+    fn export_runs_report_latest_attempt_and_last_success() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        assert!(storage.last_export_run("nightly").expect("none").is_none());
+
+        storage
+            .record_export_run("nightly", fixed_ts(1), Ok("wrote 10 bytes"))
+            .expect("record ok");
+        storage
+            .record_export_run("nightly", fixed_ts(2), Err("403 Forbidden"))
+            .expect("record error");
+
+        let last = storage
+            .last_export_run("nightly")
+            .expect("query")
+            .expect("run");
+        assert_eq!(last.ran_at, fixed_ts(2));
+        assert!(!last.ok);
+        assert_eq!(last.message, "403 Forbidden");
+        assert_eq!(
+            storage.last_successful_export("nightly").expect("query"),
+            Some(fixed_ts(1))
+        );
+    }
+
+    #[test]
+    fn audit_log_is_append_only_and_lists_newest_first() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let entry = |action: &str, at| AuditEntry {
+            at: fixed_ts(at),
+            source: "cli".into(),
+            action: action.into(),
+            subject: "openai".into(),
+            detail: String::new(),
+        };
+        storage
+            .record_audit(&[entry("provider.added", 1), entry("key.set", 1)])
+            .expect("record");
+        storage
+            .record_audit(&[entry("key.deleted", 2)])
+            .expect("record");
+
+        let actions: Vec<String> = storage
+            .audit_log(2)
+            .expect("list")
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(actions, vec!["key.deleted", "key.set"]);
+
+        let conn = storage.writer().expect("writer");
+        assert!(conn.execute("DELETE FROM audit_log", []).is_err());
+        assert!(conn
+            .execute("UPDATE audit_log SET source = 'tui'", [])
+            .is_err());
+    }
+
+    #[test]
+    fn reauth_flags_persist_until_cleared() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+
+        storage
+            .mark_needs_reauth("openai", fixed_ts(1), "401 Unauthorized")
+            .expect("mark");
+        storage
+            .mark_needs_reauth("openai", fixed_ts(2), "401 Unauthorized")
+            .expect("mark again");
+        assert_eq!(
+            storage.providers_needing_reauth().expect("list"),
+            vec!["openai".to_string()]
+        );
+
+        assert!(storage.clear_needs_reauth("openai").expect("clear"));
+        assert!(!storage.clear_needs_reauth("openai").expect("clear again"));
+        assert!(storage.providers_needing_reauth().expect("list").is_empty());
+    }
+
+    #[test]
+    fn spend_is_broken_down_by_source_and_exported_with_it() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let mut local = sample_cost("claude-code", "claude-sonnet-4", fixed_ts(2), 1.0);
+        local.source = UsageSource::Local;
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &["openai".to_string(), "claude-code".to_string()],
+                &[],
+                &[sample_cost("openai", "gpt-4o", fixed_ts(1), 3.0), local],
+            )
+            .expect("write snapshot");
+        let mut imported = sample_cost("azure-openai", "gpt-4o", fixed_ts(3), 2.0);
+        imported.source = UsageSource::Import;
+        storage
+            .replace_costs_between("azure-openai", fixed_ts(3), fixed_ts(4), &[imported])
+            .expect("import");
+
+        let (.., by_source) = storage
+            .aggregate_since(fixed_ts(0), EdgeBuckets::Exclude)
+            .expect("aggregate");
+        assert_eq!(
+            by_source,
+            vec![
+                ("api".to_string(), 3.0),
+                ("import".to_string(), 2.0),
+                ("local".to_string(), 1.0)
+            ]
+        );
+        let exported: Vec<CostRecord> =
+            serde_json::from_str(&storage.export_cost_json().expect("export")).expect("parse");
+        assert!(exported
+            .iter()
+            .any(|r| r.provider == "claude-code" && r.source == UsageSource::Local));
+    }
+
+    #[test]
+    fn search_filters_rows_and_pages_newest_first() {
+        let tmp = TempDir::new().expect("tempdir");
+        let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+        let usage = (0..5)
+            .map(|hour| sample_usage("openai", "gpt-4o-mini", fixed_ts(hour), 10 + hour as u64))
+            .chain([sample_usage(
+                "anthropic",
+                "claude-3-5-sonnet",
+                fixed_ts(2),
+                7,
+            )])
+            .collect::<Vec<_>>();
+        storage
+            .replace_snapshot(
+                fixed_ts(0),
+                &["openai".to_string(), "anthropic".to_string()],
+                &usage,
+                &[
+                    sample_cost("openai", "gpt-4o-mini", fixed_ts(1), 1.0),
+                    sample_cost("anthropic", "claude-3-5-sonnet", fixed_ts(2), 2.0),
+                ],
+            )
+            .expect("write snapshot");
+
+        let filter = RecordFilter {
+            model: "GPT-4O".into(),
+            since: Some(fixed_ts(1)),
+            until: Some(fixed_ts(4)),
+            ..RecordFilter::default()
+        };
+        let (page, total) = storage.search_usage(&filter, 0, 2).expect("search");
+        assert_eq!(total, 3);
+        assert_eq!(
+            page.iter().map(|r| r.input_tokens).collect::<Vec<_>>(),
+            vec![13, 12]
+        );
+        let (page, _) = storage.search_usage(&filter, 2, 2).expect("second page");
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].timestamp, fixed_ts(1));
+
+        let by_provider = RecordFilter {
+            provider: "Anthropic".into(),
+            ..RecordFilter::default()
+        };
+        let (cost, total) = storage.search_cost(&by_provider, 0, 10).expect("cost");
+        assert_eq!(total, 1);
+        assert_eq!(cost[0].model, "claude-3-5-sonnet");
+    }
+
+    #[test]
+    fn open_adds_source_granularity_to_existing_databases() {
+        let tmp = TempDir::new().expect("tempdir");
+        let db = tmp.path().join("db.sqlite");
+        Connection::open(&db)
+            .expect("open raw")
+            .execute_batch(
+                "CREATE TABLE usage_records (
+                    id INTEGER PRIMARY KEY, provider TEXT NOT NULL, model TEXT NOT NULL,
+                    input_tokens INTEGER NOT NULL, output_tokens INTEGER NOT NULL,
+                    cached_tokens INTEGER NOT NULL, timestamp TEXT NOT NULL
+                );",
+            )
+            .expect("legacy schema");
+
+        let storage = Storage::open(&db).expect("open storage");
+        let mut usage = sample_usage("anthropic", "claude", fixed_ts(1), 5);
+        usage.source_granularity = Some(Granularity::Hour);
+        storage
+            .replace_snapshot(fixed_ts(0), &["anthropic".to_string()], &[usage], &[])
+            .expect("replace snapshot");
+        let stored: Option<String> = storage
+            .reader()
+            .expect("reader")
+            .query_row("SELECT source_granularity FROM usage_records", [], |row| {
+                row.get(0)
+            })
+            .expect("query");
+        assert_eq!(stored.as_deref(), Some("1h"));
     }
 }
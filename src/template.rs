@@ -0,0 +1,93 @@
+use crate::error::AppError;
+use minijinja::{AutoEscape, Environment};
+use serde_json::Value;
+
+/// Jinja template (minijinja) for user-editable digests. Block tags alone on
+/// their line leave no blank line behind, and output is not escaped: the
+/// digests are plain text or Slack mrkdwn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+}
+
+impl Template {
+    /// Compiles `src` once up front so syntax errors surface at load time.
+    pub fn parse(src: &str) -> Result<Self, AppError> {
+        environment()
+            .template_from_str(src)
+            .map_err(template_error)?;
+        Ok(Self {
+            source: src.to_string(),
+        })
+    }
+
+    pub fn render(&self, context: &Value) -> Result<String, AppError> {
+        environment()
+            .render_str(&self.source, context)
+            .map_err(template_error)
+    }
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env
+}
+
+fn template_error(err: minijinja::Error) -> AppError {
+    let detail = err
+        .detail()
+        .map_or_else(|| err.kind().to_string(), str::to_string);
+    match err.line() {
+        Some(line) => AppError::Config(format!("template line {line}: {detail}")),
+        None => AppError::Config(format!("template: {detail}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_values_loops_and_filters() {
+        let template = Template::parse(
+            "Spend {{ cost }} over {{ window }}\n\
+             {# one line per model #}\n\
+             {% for m in models %}\n\
+             - {{ m.name | upper }} {{ m.cost }} ({{ window }})\n\
+             {% else %}\n\
+             nothing\n\
+             {% endfor %}\n\
+             {% if meta %}by {{ meta.team }}{% endif %}",
+        )
+        .unwrap();
+        let context = json!({
+            "cost": "$3.00",
+            "window": "7d",
+            "models": [{ "name": "gpt-4o", "cost": "$2.00" }, { "name": "claude", "cost": 1 }],
+            "meta": { "team": "<platform>" },
+        });
+        assert_eq!(
+            template.render(&context).unwrap(),
+            "Spend $3.00 over 7d\n- GPT-4O $2.00 (7d)\n- CLAUDE 1 (7d)\nby <platform>"
+        );
+        assert_eq!(
+            template.render(&json!({ "models": [] })).unwrap(),
+            "Spend  over \nnothing\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let err = Template::parse("ok\n{% for m in models %}\n{{ m }}").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("template line 3: unexpected end of input"));
+        assert!(Template::parse("{% if a %}{% endfor %}").is_err());
+        assert!(Template::parse("{{ cost").is_err());
+    }
+}
@@ -1,14 +1,41 @@
-use crate::models::TimeWindow;
+use crate::config::{
+    normalize_provider_name, BreakdownColumn, Budget, BudgetPeriod, BudgetScope, ProviderSettings,
+};
+use crate::deprecations::DeprecatedSpend;
+use crate::models::{
+    AccountBalance, AlertRecord, ClockSkew, CostRecord, Granularity, PeakRate, ProviderHealth,
+    RateLimit, TimeWindow, UsageRecord,
+};
+use crate::pricing::{cost_for_tokens, ModelPricing};
+use crate::service::{BreakdownDetails, FreshnessCache};
+use crate::storage::QueryResult;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct DashboardView {
     pub tokens: u64,
     pub cost: f64,
     pub provider_breakdown: Vec<(String, f64)>,
+    /// Cost per ingestion source (`api`, `local`, `import`).
+    pub source_breakdown: Vec<(String, f64)>,
     pub model_breakdown: Vec<(String, f64)>,
     pub last_refresh: String,
+    pub data_as_of: Option<DateTime<Utc>>,
+    /// Per enabled provider: latest fetch covering the window.
+    pub provider_as_of: HashMap<String, Option<DateTime<Utc>>>,
+    /// Latest credit left, for providers that report a balance.
+    pub balances: HashMap<String, AccountBalance>,
+    pub last_delta: Option<f64>,
+    /// Cost for today, 7d and 30d; loaded while the multi-window row is on.
+    pub window_costs: Option<[f64; 3]>,
+    /// Models with spend in the window that are retired or retiring soon.
+    pub deprecated: Vec<DeprecatedSpend>,
+    /// Tokens and previous-window cost behind the optional table columns.
+    pub details: BreakdownDetails,
+    /// Cost per chart bucket over the window, oldest first.
+    pub cost_series: Vec<(DateTime<Utc>, f64)>,
 }
 
 impl Default for DashboardView {
@@ -17,8 +44,17 @@ impl Default for DashboardView {
             tokens: 0,
             cost: 0.0,
             provider_breakdown: vec![],
+            source_breakdown: vec![],
             model_breakdown: vec![],
             last_refresh: "never".into(),
+            data_as_of: None,
+            provider_as_of: HashMap::new(),
+            balances: HashMap::new(),
+            last_delta: None,
+            window_costs: None,
+            deprecated: vec![],
+            details: BreakdownDetails::default(),
+            cost_series: vec![],
         }
     }
 }
@@ -26,8 +62,18 @@ impl Default for DashboardView {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Screen {
     Dashboard,
+    Simulator,
+    ModelDetail,
+    Alerts,
     ProviderManager,
     ProviderForm(ProviderFormMode),
+    RenameProvider { provider: String },
+    Budgets,
+    BudgetForm(BudgetFormMode),
+    ColumnPicker,
+    Search,
+    SqlConsole,
+    CommandPalette,
     Confirm(ConfirmAction),
     ErrorDialog,
     InfoDialog,
@@ -39,11 +85,19 @@ pub enum ProviderFormMode {
     Edit { provider: String },
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BudgetFormMode {
+    Add,
+    Edit { index: usize },
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConfirmAction {
     Quit,
+    DeleteBudget { index: usize, name: String },
     DeleteProvider { provider: String },
     DeleteKey { provider: String },
+    Backfill { provider: String },
 }
 
 #[derive(Debug, Clone, Default)]
@@ -71,24 +125,570 @@ pub struct ProviderLogEntry {
     pub duration: Option<Duration>,
 }
 
+/// What a provider or key removal took away, kept for the session so `u`
+/// in the Provider Manager can put it back.
+#[derive(Debug, Clone)]
+pub struct RemovedProvider {
+    pub name: String,
+    pub settings: Option<ProviderSettings>,
+    pub enabled: bool,
+    pub api_key: Option<String>,
+}
+
+/// What confirming a provider or key removal will touch, shown in the
+/// confirm dialog before anything is deleted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletionPreview {
+    /// The provider's `config.toml` section, empty when it has none.
+    pub config_section: Vec<String>,
+    pub keyring_entry: String,
+    pub has_key: bool,
+    pub usage_rows: usize,
+    pub cost_rows: usize,
+    /// Also delete the stored usage/cost history (`p` toggles).
+    pub purge_history: bool,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ProviderDraft {
     pub name: String,
     pub base_url: String,
     pub organization_id: String,
+    pub owner: String,
+    pub notes: String,
+    pub key_expires_at: String,
     pub api_key: String,
+    /// Adapter of the account being edited; empty when adding, where the
+    /// name is the adapter.
+    pub kind: String,
+    pub resource: String,
+    pub api_version: String,
+    /// `deployment=model` pairs separated by commas.
+    pub deployments: String,
     pub enabled: bool,
     pub active_field: usize,
     pub show_advanced: bool,
     pub connection_status: ConnectionStatus,
 }
 
+impl ProviderDraft {
+    /// Adapter the form's account uses.
+    pub fn adapter(&self) -> String {
+        let kind = if self.kind.is_empty() {
+            &self.name
+        } else {
+            &self.kind
+        };
+        normalize_provider_name(kind)
+    }
+
+    /// Whether the form is for an `azure-openai` account, which shows the
+    /// resource, API version and deployments fields.
+    pub fn is_azure(&self) -> bool {
+        self.adapter() == "azure-openai"
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BudgetScopeKind {
+    #[default]
+    Global,
+    Provider,
+    Model,
+    Tag,
+}
+
+impl BudgetScopeKind {
+    pub const ALL: [Self; 4] = [Self::Global, Self::Provider, Self::Model, Self::Tag];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Provider => "provider",
+            Self::Model => "model",
+            Self::Tag => "tag",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetFormField {
+    Name,
+    Scope,
+    Target,
+    Amount,
+    Period,
+    OnExceeded,
+}
+
+impl BudgetFormField {
+    pub const ALL: [Self; 6] = [
+        Self::Name,
+        Self::Scope,
+        Self::Target,
+        Self::Amount,
+        Self::Period,
+        Self::OnExceeded,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Scope => "Scope",
+            Self::Target => "Target",
+            Self::Amount => "Amount (USD)",
+            Self::Period => "Period",
+            Self::OnExceeded => "On exceeded",
+        }
+    }
+}
+
+/// Stored rows the search screen browses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchTable {
+    #[default]
+    Cost,
+    Usage,
+}
+
+impl SearchTable {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Cost => "cost",
+            Self::Usage => "usage",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Table,
+    Model,
+    Provider,
+    From,
+    To,
+}
+
+impl SearchField {
+    pub const ALL: [Self; 5] = [
+        Self::Table,
+        Self::Model,
+        Self::Provider,
+        Self::From,
+        Self::To,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Table => "Rows",
+            Self::Model => "Model contains",
+            Self::Provider => "Provider",
+            Self::From => "From (YYYY-MM-DD)",
+            Self::To => "To (YYYY-MM-DD)",
+        }
+    }
+}
+
+/// Rows per page of the search screen.
+pub const SEARCH_PAGE_SIZE: usize = 12;
+
+/// Filters typed into the search screen and the page of rows they matched.
+/// `total` counts every matching row; only the current `page` is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct SearchState {
+    pub table: SearchTable,
+    pub model: String,
+    pub provider: String,
+    pub from: String,
+    pub to: String,
+    pub active_field: usize,
+    pub page: usize,
+    pub usage: Vec<UsageRecord>,
+    pub cost: Vec<CostRecord>,
+    pub total: usize,
+}
+
+impl SearchState {
+    pub fn active_field(&self) -> SearchField {
+        SearchField::ALL[self.active_field % SearchField::ALL.len()]
+    }
+
+    pub fn pages(&self) -> usize {
+        self.total.div_ceil(SEARCH_PAGE_SIZE).max(1)
+    }
+}
+
+/// Editable copy of a budget. `targets` are the pickable providers, models
+/// or tags for the current scope; `spent` is the live spend for the draft's
+/// scope and period, reloaded whenever either changes.
+#[derive(Debug, Clone, Default)]
+pub struct BudgetDraft {
+    pub name: String,
+    pub scope: BudgetScopeKind,
+    pub target: String,
+    pub targets: Vec<String>,
+    pub amount: String,
+    pub period: BudgetPeriod,
+    pub on_exceeded: String,
+    pub active_field: usize,
+    pub spent: Option<f64>,
+}
+
+impl BudgetDraft {
+    pub fn from_budget(budget: &Budget) -> Self {
+        let (scope, target) = match &budget.scope {
+            BudgetScope::Global => (BudgetScopeKind::Global, String::new()),
+            BudgetScope::Provider(name) => (BudgetScopeKind::Provider, name.clone()),
+            BudgetScope::Model(name) => (BudgetScopeKind::Model, name.clone()),
+            BudgetScope::Tag(name) => (BudgetScopeKind::Tag, name.clone()),
+        };
+        Self {
+            name: budget.name.clone(),
+            scope,
+            target,
+            amount: budget.amount_usd.to_string(),
+            period: budget.period,
+            on_exceeded: budget.on_exceeded.clone().unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    pub fn active_field(&self) -> BudgetFormField {
+        BudgetFormField::ALL[self.active_field % BudgetFormField::ALL.len()]
+    }
+
+    pub fn scope(&self) -> Result<BudgetScope, String> {
+        let target = self.target.trim();
+        if self.scope != BudgetScopeKind::Global && target.is_empty() {
+            return Err(format!("Pick a {} for this budget.", self.scope.label()));
+        }
+        Ok(match self.scope {
+            BudgetScopeKind::Global => BudgetScope::Global,
+            BudgetScopeKind::Provider => BudgetScope::Provider(target.to_ascii_lowercase()),
+            BudgetScopeKind::Model => BudgetScope::Model(target.to_string()),
+            BudgetScopeKind::Tag => BudgetScope::Tag(target.to_string()),
+        })
+    }
+
+    pub fn to_budget(&self) -> Result<Budget, String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Budget name is required.".into());
+        }
+        let amount_usd: f64 = self
+            .amount
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid amount.", self.amount.trim()))?;
+        if amount_usd <= 0.0 {
+            return Err("Amount must be greater than zero.".into());
+        }
+        let on_exceeded = self.on_exceeded.trim();
+        Ok(Budget {
+            name: name.to_string(),
+            scope: self.scope()?,
+            amount_usd,
+            period: self.period,
+            on_exceeded: (!on_exceeded.is_empty()).then(|| on_exceeded.to_string()),
+        })
+    }
+
+    /// Moves the picker at the active field one step (`forward` or back).
+    /// Returns true when the scope or period changed, so spend is stale.
+    pub fn cycle(&mut self, forward: bool) -> bool {
+        fn step<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+            let idx = all.iter().position(|v| *v == current).unwrap_or(0);
+            let next = if forward {
+                (idx + 1) % all.len()
+            } else {
+                (idx + all.len() - 1) % all.len()
+            };
+            all[next]
+        }
+        match self.active_field() {
+            BudgetFormField::Scope => {
+                self.scope = step(&BudgetScopeKind::ALL, self.scope, forward);
+                self.target.clear();
+                true
+            }
+            BudgetFormField::Period => {
+                self.period = step(&BudgetPeriod::ALL, self.period, forward);
+                true
+            }
+            BudgetFormField::Target if !self.targets.is_empty() => {
+                let next = match self.targets.iter().position(|t| *t == self.target) {
+                    Some(idx) if forward => (idx + 1) % self.targets.len(),
+                    Some(idx) => (idx + self.targets.len() - 1) % self.targets.len(),
+                    None if forward => 0,
+                    None => self.targets.len() - 1,
+                };
+                self.target = self.targets[next].clone();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// What a command palette entry does when chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteCommand {
+    Refresh,
+    Window(TimeWindow),
+    OpenProvider(String),
+    ManageProviders,
+    Export(&'static str),
+    ToggleTheme,
+    ToggleCompact,
+    ToggleWindowRow,
+    ToggleShareGauges,
+    ToggleChartGranularity,
+    ToggleModelGroups,
+    ToggleSourceBreakdown,
+    ChooseColumns,
+    Search,
+    SqlConsole,
+    /// Pin the provider to the top of its panel, or unpin it.
+    TogglePinnedProvider(String),
+    /// Pin the model (or family) to the top of its panel, or unpin it.
+    TogglePinnedModel(String),
+    Simulator,
+    Alerts,
+    Budgets,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub label: String,
+    pub command: PaletteCommand,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CommandPalette {
+    pub input: String,
+    pub selected: usize,
+    pub entries: Vec<PaletteEntry>,
+}
+
+impl CommandPalette {
+    /// Entries matching the input, best match first; ties keep list order.
+    pub fn matches(&self) -> Vec<&PaletteEntry> {
+        let mut scored: Vec<(u32, &PaletteEntry)> = self
+            .entries
+            .iter()
+            .filter_map(|e| fuzzy_score(&self.input, &e.label).map(|score| (score, e)))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, e)| e).collect()
+    }
+
+    pub fn selected_command(&self) -> Option<PaletteCommand> {
+        self.matches()
+            .get(self.selected)
+            .map(|entry| entry.command.clone())
+    }
+}
+
+/// Case-insensitive subsequence match of `query` (whitespace ignored) in
+/// `candidate`. Higher is better: consecutive characters and characters at
+/// the start of a word score extra. `None` when the query does not match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for (idx, ch) in candidate.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if *ch != query[next] {
+            continue;
+        }
+        score += 1;
+        if idx > 0 && previous == Some(idx - 1) {
+            score += 4;
+        }
+        if idx == 0 || candidate[idx - 1] == ' ' {
+            score += 2;
+        }
+        previous = Some(idx);
+        next += 1;
+    }
+    (next == query.len()).then_some(score)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SimulatorField {
+    #[default]
+    InputPrice,
+    OutputPrice,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorRow {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub actual_cost: f64,
+    pub input_per_1m: f64,
+    pub output_per_1m: f64,
+    pub base_input_per_1m: f64,
+    pub base_output_per_1m: f64,
+    pub edited: bool,
+    pub mapped_to: Option<String>,
+    pub avg_latency_ms: Option<f64>,
+}
+
+impl SimulatorRow {
+    pub fn reset(&mut self) {
+        self.input_per_1m = self.base_input_per_1m;
+        self.output_per_1m = self.base_output_per_1m;
+        self.edited = false;
+        self.mapped_to = None;
+    }
+
+    pub fn simulated_cost(&self) -> f64 {
+        if self.edited {
+            cost_for_tokens(
+                self.input_tokens,
+                self.output_tokens,
+                self.input_per_1m,
+                self.output_per_1m,
+            )
+        } else {
+            self.actual_cost
+        }
+    }
+}
+
+/// One provider/model pair's usage in the selected window, next to its rate
+/// limit and the busiest bucket seen in that window.
+#[derive(Debug, Clone, Default)]
+pub struct ModelDetail {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+    pub avg_latency_ms: Option<f64>,
+    pub limit: Option<RateLimit>,
+    pub peak: Option<PeakRate>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulatorState {
+    pub rows: Vec<SimulatorRow>,
+    pub targets: Vec<ModelPricing>,
+    pub selected: usize,
+    pub field: SimulatorField,
+    pub input: String,
+}
+
+impl SimulatorState {
+    pub fn actual_total(&self) -> f64 {
+        self.rows.iter().map(|r| r.actual_cost).sum()
+    }
+
+    pub fn simulated_total(&self) -> f64 {
+        self.rows.iter().map(SimulatorRow::simulated_cost).sum()
+    }
+
+    pub fn migration_delta(&self) -> (usize, f64) {
+        self.rows
+            .iter()
+            .filter(|r| r.mapped_to.is_some())
+            .fold((0, 0.0), |(count, delta), r| {
+                (count + 1, delta + r.simulated_cost() - r.actual_cost)
+            })
+    }
+
+    pub fn cycle_mapping(&mut self) -> Option<String> {
+        let row = self.rows.get_mut(self.selected)?;
+        let labels: Vec<String> = self
+            .targets
+            .iter()
+            .map(|t| format!("{}/{}", t.provider, t.model_pattern))
+            .collect();
+        let next = match &row.mapped_to {
+            None => 0,
+            Some(current) => labels
+                .iter()
+                .position(|l| l == current)
+                .map_or(0, |i| i + 1),
+        };
+        match self.targets.get(next) {
+            Some(target) => {
+                row.input_per_1m = target.input_per_1m;
+                row.output_per_1m = target.output_per_1m;
+                row.edited = true;
+                row.mapped_to = Some(labels[next].clone());
+            }
+            None => row.reset(),
+        }
+        self.input.clear();
+        row.mapped_to.clone()
+    }
+
+    pub fn apply_input(&mut self) -> Result<(), String> {
+        let Some(row) = self.rows.get_mut(self.selected) else {
+            return Err("No model selected.".into());
+        };
+        let value: f64 = self
+            .input
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a valid price.", self.input.trim()))?;
+        if value < 0.0 {
+            return Err("Price cannot be negative.".into());
+        }
+        match self.field {
+            SimulatorField::InputPrice => row.input_per_1m = value,
+            SimulatorField::OutputPrice => row.output_per_1m = value,
+        }
+        row.edited = true;
+        row.mapped_to = None;
+        self.input.clear();
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub running: bool,
     pub window: TimeWindow,
     pub status: String,
     pub compact_mode: bool,
+    pub accessible: bool,
+    /// The database was opened read-only from the recovery dialog; refreshes
+    /// only reload stored data.
+    pub read_only: bool,
+    /// Show the today / 7d / 30d cost row above the panels.
+    pub multi_window: bool,
+    /// Draw the provider/model panels as share-of-spend gauges.
+    pub share_gauges: bool,
+    /// Bucket width picked for the cost chart with `t`; `None` follows the
+    /// window.
+    pub chart_granularity: Option<Granularity>,
+    /// Merge Top Models into `[model_groups]` families.
+    pub group_models: bool,
+    /// Break cost down by ingestion source instead of by provider.
+    pub by_source: bool,
+    /// Columns of the provider and model tables, after the name.
+    pub breakdown_columns: Vec<BreakdownColumn>,
+    /// Columns ticked in the column picker, applied on Enter.
+    pub column_draft: Vec<BreakdownColumn>,
+    pub column_selected: usize,
+    pub search: SearchState,
+    /// Read-only query typed into the SQL console, and its last result.
+    pub sql_input: String,
+    pub sql_result: Option<QueryResult>,
     pub view: DashboardView,
     pub screen: Screen,
     pub previous_screen: Screen,
@@ -97,6 +697,34 @@ pub struct AppState {
     pub provider_selected: usize,
     pub confirm_selected: usize,
     pub provider_draft: ProviderDraft,
+    pub simulator: SimulatorState,
+    pub model_detail: ModelDetail,
+    pub alerts: Vec<AlertRecord>,
+    pub alert_selected: usize,
+    pub palette: CommandPalette,
+    /// Last provider or key removal, restorable until the TUI exits.
+    pub undo_removal: Option<RemovedProvider>,
+    pub deletion_preview: DeletionPreview,
+    /// New name typed into the rename prompt.
+    pub rename_input: String,
+    pub budget_selected: usize,
+    pub budget_draft: BudgetDraft,
+    /// Current-period spend per `cfg.budgets` entry, loaded with the screen.
+    pub budget_spend: Vec<Option<f64>>,
+    pub unacked_alerts: usize,
+    /// Providers whose key was rejected; refreshes skip them until re-keyed.
+    pub needs_reauth: Vec<String>,
+    /// Providers skipped after repeated failures until their next re-probe.
+    pub quarantined: Vec<String>,
+    /// Last refresh, window rows and latest error per configured provider,
+    /// shown in the Provider Manager.
+    pub provider_health: HashMap<String, ProviderHealth>,
+    /// Clock skew the last refresh found against a provider, if over the
+    /// configured threshold.
+    pub clock_skew: Option<ClockSkew>,
+    /// When the running background refresh started; `None` when idle.
+    pub refreshing_since: Option<Instant>,
+    pub freshness: FreshnessCache,
     pub provider_test_results: HashMap<String, ConnectionStatus>,
     pub provider_logs: HashMap<String, Vec<ProviderLogEntry>>,
     pub max_provider_logs: usize,
@@ -105,6 +733,17 @@ pub struct AppState {
     pub info_message: String,
 }
 
+impl AppState {
+    /// Bucket width of the cost chart: hourly for the 1d window and daily
+    /// for longer ones, unless toggled.
+    pub fn chart_width(&self) -> Granularity {
+        self.chart_granularity.unwrap_or(match self.window {
+            TimeWindow::OneDay => Granularity::Hour,
+            TimeWindow::SevenDays | TimeWindow::ThirtyDays => Granularity::Day,
+        })
+    }
+}
+
 impl Default for AppState {
     fn default() -> Self {
         Self {
@@ -112,6 +751,19 @@ impl Default for AppState {
             window: TimeWindow::SevenDays,
             status: "ready".into(),
             compact_mode: false,
+            accessible: false,
+            read_only: false,
+            multi_window: false,
+            share_gauges: false,
+            chart_granularity: None,
+            group_models: false,
+            by_source: false,
+            breakdown_columns: vec![BreakdownColumn::Cost],
+            column_draft: Vec::new(),
+            column_selected: 0,
+            search: SearchState::default(),
+            sql_input: String::new(),
+            sql_result: None,
             view: DashboardView::default(),
             screen: Screen::Dashboard,
             previous_screen: Screen::Dashboard,
@@ -120,6 +772,24 @@ impl Default for AppState {
             provider_selected: 0,
             confirm_selected: 0,
             provider_draft: ProviderDraft::default(),
+            simulator: SimulatorState::default(),
+            model_detail: ModelDetail::default(),
+            alerts: vec![],
+            alert_selected: 0,
+            palette: CommandPalette::default(),
+            undo_removal: None,
+            deletion_preview: DeletionPreview::default(),
+            rename_input: String::new(),
+            budget_selected: 0,
+            budget_draft: BudgetDraft::default(),
+            budget_spend: vec![],
+            unacked_alerts: 0,
+            needs_reauth: vec![],
+            quarantined: vec![],
+            provider_health: HashMap::new(),
+            clock_skew: None,
+            refreshing_since: None,
+            freshness: FreshnessCache::default(),
             provider_test_results: HashMap::new(),
             provider_logs: HashMap::new(),
             max_provider_logs: 100,
@@ -129,3 +799,147 @@ impl Default for AppState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(model: &str, input_tokens: u64, output_tokens: u64, actual_cost: f64) -> SimulatorRow {
+        SimulatorRow {
+            provider: "openai".into(),
+            model: model.into(),
+            input_tokens,
+            output_tokens,
+            actual_cost,
+            input_per_1m: 5.0,
+            output_per_1m: 15.0,
+            base_input_per_1m: 5.0,
+            base_output_per_1m: 15.0,
+            edited: false,
+            mapped_to: None,
+            avg_latency_ms: None,
+        }
+    }
+
+    #[test]
+    fn simulator_totals_only_reprice_edited_rows() {
+        let mut sim = SimulatorState {
+            rows: vec![
+                row("gpt-4o", 1_000_000, 1_000_000, 20.0),
+                row("claude", 0, 0, 3.0),
+            ],
+            ..SimulatorState::default()
+        };
+        sim.input = "0.15".into();
+        sim.apply_input().expect("apply input price");
+        sim.field = SimulatorField::OutputPrice;
+        sim.input = "0.6".into();
+        sim.apply_input().expect("apply output price");
+
+        assert!((sim.actual_total() - 23.0).abs() < 1e-9);
+        assert!((sim.simulated_total() - 3.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cycle_mapping_reprices_with_target_and_wraps_to_reset() {
+        let mut sim = SimulatorState {
+            rows: vec![row("gpt-4o", 2_000_000, 1_000_000, 25.0)],
+            targets: vec![ModelPricing {
+                provider: "openai".into(),
+                model_pattern: "gpt-4o-mini".into(),
+                input_per_1m: 0.15,
+                output_per_1m: 0.60,
+            }],
+            ..SimulatorState::default()
+        };
+
+        assert_eq!(sim.cycle_mapping().as_deref(), Some("openai/gpt-4o-mini"));
+        let (count, delta) = sim.migration_delta();
+        assert_eq!(count, 1);
+        assert!((delta - (0.9 - 25.0)).abs() < 1e-9);
+
+        assert_eq!(sim.cycle_mapping(), None);
+        assert!(!sim.rows[0].edited);
+        assert_eq!(sim.rows[0].input_per_1m, 5.0);
+    }
+
+    #[test]
+    fn simulator_rejects_invalid_price_input() {
+        let mut sim = SimulatorState {
+            rows: vec![row("gpt-4o", 1, 1, 1.0)],
+            ..SimulatorState::default()
+        };
+        sim.input = "abc".into();
+        assert!(sim.apply_input().is_err());
+        sim.input = "-1".into();
+        assert!(sim.apply_input().is_err());
+        assert!(!sim.rows[0].edited);
+    }
+
+    #[test]
+    fn budget_draft_cycles_pickers_and_validates() {
+        let mut draft = BudgetDraft {
+            name: "cap".into(),
+            amount: "50".into(),
+            targets: vec!["anthropic".into(), "openai".into()],
+            ..BudgetDraft::default()
+        };
+        draft.active_field = 1;
+        assert!(draft.cycle(true));
+        assert_eq!(draft.scope, BudgetScopeKind::Provider);
+        assert_eq!(
+            draft.to_budget().unwrap_err(),
+            "Pick a provider for this budget."
+        );
+
+        draft.active_field = 2;
+        assert!(draft.cycle(false));
+        assert_eq!(draft.target, "openai");
+        let budget = draft.to_budget().expect("valid budget");
+        assert_eq!(budget.scope, BudgetScope::Provider("openai".into()));
+        assert_eq!(budget.period, BudgetPeriod::Monthly);
+        assert_eq!(budget.on_exceeded, None);
+
+        draft.amount = "0".into();
+        assert!(draft.to_budget().is_err());
+    }
+
+    #[test]
+    fn palette_ranks_fuzzy_matches() {
+        let entry = |label: &str, command| PaletteEntry {
+            label: label.into(),
+            command,
+        };
+        let mut palette = CommandPalette {
+            entries: vec![
+                entry("refresh", PaletteCommand::Refresh),
+                entry("window 1d", PaletteCommand::Window(TimeWindow::OneDay)),
+                entry("window 30d", PaletteCommand::Window(TimeWindow::ThirtyDays)),
+                entry(
+                    "open provider openai",
+                    PaletteCommand::OpenProvider("openai".into()),
+                ),
+                entry("export csv", PaletteCommand::Export("csv")),
+            ],
+            ..CommandPalette::default()
+        };
+        assert_eq!(palette.matches().len(), 5);
+
+        palette.input = "w30".into();
+        assert_eq!(
+            palette.selected_command(),
+            Some(PaletteCommand::Window(TimeWindow::ThirtyDays))
+        );
+        palette.input = "opopenai".into();
+        assert_eq!(
+            palette.selected_command(),
+            Some(PaletteCommand::OpenProvider("openai".into()))
+        );
+        palette.input = "exp csv".into();
+        assert_eq!(palette.matches()[0].label, "export csv");
+        palette.input = "zzz".into();
+        assert!(palette.matches().is_empty());
+
+        assert!(fuzzy_score("ref", "refresh") > fuzzy_score("ref", "open provider foo"));
+    }
+}
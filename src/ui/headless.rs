@@ -0,0 +1,167 @@
+use crate::config::AppConfig;
+use crate::deprecations;
+use crate::error::AppError;
+use crate::models::TimeWindow;
+use crate::service::{
+    balances_by_provider, breakdown_details, group_model_breakdown, multi_window_costs,
+    pin_breakdown, provider_data_as_of, window_data_as_of, BREAKDOWN_MODELS,
+};
+use crate::storage::Storage;
+use crate::ui::app::AppState;
+use crate::ui::run::render;
+use chrono::{Duration, Utc};
+use ratatui::backend::TestBackend;
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::style::{Color, Modifier};
+use ratatui::Terminal;
+
+/// Renders the dashboard for `window` from stored data into an off-screen
+/// buffer. No provider calls are made.
+pub fn render_dashboard(
+    cfg: &AppConfig,
+    storage: &Storage,
+    window: TimeWindow,
+    width: u16,
+    height: u16,
+) -> Result<Buffer, AppError> {
+    let mut state = AppState {
+        window,
+        status: "rendered".into(),
+        accessible: cfg.accessible,
+        multi_window: cfg.display.multi_window_kpis,
+        share_gauges: cfg.display.share_gauges,
+        group_models: cfg.display.group_models,
+        breakdown_columns: cfg.display.breakdown_columns.clone(),
+        ..AppState::default()
+    };
+    let now = Utc::now();
+    let since = now - Duration::hours(window.as_hours());
+    let (tokens, cost, providers, models, sources) =
+        storage.aggregate_since(since, cfg.edge_buckets)?;
+    state.view.details = breakdown_details(cfg, storage, state.group_models, since, now)?;
+    state.view.cost_series = storage.cost_series(since, now, state.chart_width())?;
+    state.view.tokens = tokens;
+    state.view.cost = cost;
+    state.view.provider_breakdown = pin_breakdown(providers, &cfg.display.pinned_providers, None);
+    state.view.source_breakdown = sources;
+    let models = if state.group_models {
+        group_model_breakdown(cfg, models)
+    } else {
+        models
+    };
+    state.view.model_breakdown =
+        pin_breakdown(models, &cfg.display.pinned_models, Some(BREAKDOWN_MODELS));
+    state.view.data_as_of = window_data_as_of(cfg, window, storage);
+    state.view.provider_as_of = provider_data_as_of(cfg, window, storage);
+    state.view.balances = balances_by_provider(storage)?;
+    if state.multi_window {
+        state.view.window_costs = Some(multi_window_costs(storage, cfg.edge_buckets, Utc::now())?);
+    }
+    state.view.deprecated = deprecations::evaluate(cfg, storage, window, Utc::now())?;
+    state.view.last_delta = storage.latest_refresh_delta()?.map(|(_, total)| total);
+    if let Some(at) = state.view.data_as_of {
+        state.view.last_refresh = at.to_rfc3339();
+    }
+
+    let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+    terminal.draw(|f| render(f, cfg, &state))?;
+    Ok(terminal.backend().buffer().clone())
+}
+
+/// One line per buffer row, trailing blanks trimmed.
+pub fn buffer_to_text(buffer: &Buffer) -> String {
+    rows(buffer)
+        .map(|row| {
+            let line: String = row.iter().map(Cell::symbol).collect();
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+/// Like [`buffer_to_text`] but with SGR escapes for colors and modifiers.
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let mut out = String::new();
+    for row in rows(buffer) {
+        let mut current: Option<(Color, Color, Modifier)> = None;
+        for cell in row {
+            let style = (cell.fg, cell.bg, cell.modifier);
+            if current != Some(style) {
+                out.push_str(&sgr(style));
+                current = Some(style);
+            }
+            out.push_str(cell.symbol());
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out
+}
+
+fn rows(buffer: &Buffer) -> impl Iterator<Item = &[Cell]> {
+    buffer.content.chunks(buffer.area.width.max(1) as usize)
+}
+
+fn sgr((fg, bg, modifier): (Color, Color, Modifier)) -> String {
+    let mut codes = vec!["0".to_string()];
+    for (flag, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ] {
+        if modifier.contains(flag) {
+            codes.push(code.into());
+        }
+    }
+    codes.extend(color_code(fg, false));
+    codes.extend(color_code(bg, true));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let base = if background { 40 } else { 30 };
+    let named = |offset: u8| Some((base + offset as u16).to_string());
+    let bright = |offset: u8| Some((base + 60 + offset as u16).to_string());
+    let extended = if background { "48" } else { "38" };
+    match color {
+        Color::Reset => None,
+        Color::Black => named(0),
+        Color::Red => named(1),
+        Color::Green => named(2),
+        Color::Yellow => named(3),
+        Color::Blue => named(4),
+        Color::Magenta => named(5),
+        Color::Cyan => named(6),
+        Color::Gray => named(7),
+        Color::DarkGray => bright(0),
+        Color::LightRed => bright(1),
+        Color::LightGreen => bright(2),
+        Color::LightYellow => bright(3),
+        Color::LightBlue => bright(4),
+        Color::LightMagenta => bright(5),
+        Color::LightCyan => bright(6),
+        Color::White => bright(7),
+        Color::Indexed(i) => Some(format!("{extended};5;{i}")),
+        Color::Rgb(r, g, b) => Some(format!("{extended};2;{r};{g};{b}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    #[test]
+    fn ansi_output_emits_sgr_only_on_style_changes() {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 3, 1));
+        buf.set_string(
+            0,
+            0,
+            "ab",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        );
+        assert_eq!(buffer_to_text(&buf), "ab\n");
+        assert_eq!(buffer_to_ansi(&buf), "\x1b[0;1;31mab\x1b[0m \x1b[0m\n");
+    }
+}
@@ -1,2 +1,4 @@
 pub mod app;
+pub mod headless;
 pub mod run;
+pub mod theme;
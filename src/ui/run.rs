@@ -1,16 +1,37 @@
+use crate::budgets;
 use crate::config::{
-    db_path, delete_api_key, get_api_key, has_api_key, load_config, normalize_provider_name,
-    save_config, set_api_key, AppConfig, ProviderSettings,
+    data_dir, database_key, db_path, delete_api_key, has_api_key, keyring_account, keyring_service,
+    load_config, normalize_provider_name, provider_api_key, save_config, set_api_key,
+    stored_api_key, AppConfig, BreakdownColumn, Budget, JournalMode, ProviderSettings,
 };
+use crate::deprecations;
 use crate::error::AppError;
-use crate::models::TimeWindow;
-use crate::service::{MeterService, ProviderTestReport};
-use crate::storage::Storage;
+use crate::export::{self, CsvOptions, ExportFormat};
+use crate::format::MoneyFormat;
+use crate::fx::FxRates;
+use crate::metrics;
+use crate::models::{
+    AccountBalance, AlertState, BreakdownDetail, CostDelta, CostRecord, Granularity, TimeWindow,
+};
+use crate::notifier::AlertLevel;
+use crate::pricing::{pricing_catalog, resolve_pricing};
+use crate::providers::RequestBudget;
+use crate::service::{
+    self, adapter_needs_api_key, adapter_requires_base_url, balances_by_provider,
+    breakdown_details, group_model_breakdown, is_stale, multi_window_costs, pin_breakdown,
+    provider_data_as_of, quarantined_providers, refresh_needed, request_budget, window_data_as_of,
+    FetchedSnapshot, MeterService, ProviderTestReport, BREAKDOWN_MODELS,
+};
+use crate::storage::{archive_database, backup_path, AsyncStorage, RecordFilter, Storage};
 use crate::ui::app::{
-    AppState, ConfirmAction, ConnectionStatus, LogLevel, ProviderDraft, ProviderFormMode,
-    ProviderLogEntry, Screen,
+    AppState, BudgetDraft, BudgetFormField, BudgetFormMode, BudgetScopeKind, CommandPalette,
+    ConfirmAction, ConnectionStatus, DeletionPreview, LogLevel, ModelDetail, PaletteCommand,
+    PaletteEntry, ProviderDraft, ProviderFormMode, ProviderLogEntry, RemovedProvider, Screen,
+    SearchField, SearchState, SearchTable, SimulatorField, SimulatorRow, SimulatorState,
+    SEARCH_PAGE_SIZE,
 };
-use chrono::{Duration, Utc};
+use crate::ui::theme::{bordered, no_color_requested, selected_marker, strip_colors};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
@@ -20,9 +41,11 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap};
+use ratatui::widgets::{Cell, Clear, LineGauge, Paragraph, Row, Sparkline, Table, Wrap};
 use ratatui::Terminal;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration as StdDuration, Instant};
 use tokio::task::JoinHandle;
 use url::Url;
@@ -37,6 +60,9 @@ const COLOR_ACCENT: Color = Color::Cyan;
 const COLOR_INFO: Color = Color::Green;
 const COLOR_MUTED: Color = Color::DarkGray;
 const COLOR_HEADER: Color = Color::White;
+const COLOR_WARN: Color = Color::Yellow;
+
+const ALERT_LIST_LIMIT: usize = 200;
 
 #[derive(Debug, Clone)]
 enum ProviderTestOrigin {
@@ -51,11 +77,58 @@ struct ProviderTestJob {
     handle: JoinHandle<Result<ProviderTestReport, AppError>>,
 }
 
-pub async fn run_tui() -> Result<(), AppError> {
+/// A dashboard refresh fetching from providers off the UI thread.
+struct RefreshJob {
+    handle: JoinHandle<Result<FetchedSnapshot, AppError>>,
+    /// Shared with the task, so a cancelled refresh still logs the requests
+    /// it sent.
+    requests: Arc<RequestBudget>,
+}
+
+/// Background work the main loop polls between key presses.
+#[derive(Default)]
+struct Jobs {
+    provider_test: Option<ProviderTestJob>,
+    refresh: Option<RefreshJob>,
+}
+
+/// How often the main loop wakes while a job runs, so its result lands and
+/// the refresh timer moves without waiting for a key or the tick.
+const JOB_POLL: StdDuration = StdDuration::from_millis(200);
+
+/// Choices offered when the database can't be opened at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecoveryAction {
+    Retry,
+    ReadOnly,
+    RestoreBackup,
+    StartFresh,
+    Quit,
+}
+
+const RECOVERY_ACTIONS: [(char, &str, RecoveryAction); 5] = [
+    ('r', "Retry", RecoveryAction::Retry),
+    ('o', "Open read-only", RecoveryAction::ReadOnly),
+    ('b', "Restore latest backup", RecoveryAction::RestoreBackup),
+    (
+        'n',
+        "Start a fresh database (archives the old one)",
+        RecoveryAction::StartFresh,
+    ),
+    ('q', "Quit", RecoveryAction::Quit),
+];
+
+const BACKUP_MAX_AGE: StdDuration = StdDuration::from_secs(24 * 60 * 60);
+
+pub async fn run_tui(no_color: bool, no_refresh: bool) -> Result<(), AppError> {
     let mut cfg = load_config()?;
+    let accessible = cfg.accessible || no_color || no_color_requested();
+    let startup_refresh = cfg.startup_refresh && !no_refresh;
     let db = db_path()?;
-    let mut storage = Storage::open(&db)?;
-    let service = MeterService::new()?;
+    let key = database_key()?;
+    let journal = cfg.database_journal_mode;
+    let opened = open_checked(&db, key.as_deref(), journal);
+    let service = MeterService::new(&cfg)?;
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -63,7 +136,44 @@ pub async fn run_tui() -> Result<(), AppError> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let loop_result = run_loop(&mut terminal, &mut cfg, &mut storage, &service).await;
+    let loop_result = match opened {
+        Ok(storage) => Ok(Some((storage, false))),
+        Err(e) => recover_storage(&mut terminal, &db, key.as_deref(), journal, e, accessible),
+    };
+    let loop_result = match loop_result {
+        Ok(Some((storage, read_only))) => {
+            storage.set_fx_rates(FxRates::from_config(&cfg));
+            let backup = if read_only {
+                None
+            } else {
+                storage.ensure_recent_backup(BACKUP_MAX_AGE).err()
+            };
+            let storage = AsyncStorage::new(storage);
+            let mut state = AppState {
+                accessible,
+                read_only,
+                multi_window: cfg.display.multi_window_kpis,
+                share_gauges: cfg.display.share_gauges,
+                group_models: cfg.display.group_models,
+                breakdown_columns: cfg.display.breakdown_columns.clone(),
+                ..AppState::default()
+            };
+            if let Some(e) = backup {
+                state.status = format!("backup failed: {e}");
+            }
+            run_loop(
+                &mut terminal,
+                &mut cfg,
+                &storage,
+                &service,
+                state,
+                startup_refresh,
+            )
+            .await
+        }
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
 
     disable_raw_mode()?;
     terminal.backend_mut().execute(LeaveAlternateScreen)?;
@@ -72,32 +182,121 @@ pub async fn run_tui() -> Result<(), AppError> {
     loop_result
 }
 
+/// Opens the database and runs `quick_check`, so damage is caught before the
+/// dashboard starts writing to it.
+fn open_checked(
+    db: &std::path::Path,
+    key: Option<&str>,
+    journal: JournalMode,
+) -> Result<Storage, AppError> {
+    let storage = Storage::open_with(db, key, journal)?;
+    match storage.quick_check()?.first() {
+        Some(problem) => Err(AppError::Storage(format!(
+            "integrity check failed: {problem}"
+        ))),
+        None => Ok(storage),
+    }
+}
+
+fn recovery_action(code: KeyCode, has_backup: bool) -> Option<RecoveryAction> {
+    let action = match code {
+        KeyCode::Esc => RecoveryAction::Quit,
+        KeyCode::Char(ch) => {
+            RECOVERY_ACTIONS
+                .iter()
+                .find(|(key, _, _)| *key == ch.to_ascii_lowercase())?
+                .2
+        }
+        _ => return None,
+    };
+    (action != RecoveryAction::RestoreBackup || has_backup).then_some(action)
+}
+
+/// Shows the recovery dialog until an action yields a usable database or the
+/// user quits. Returns the storage and whether it is read-only.
+fn recover_storage(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    db: &std::path::Path,
+    key: Option<&str>,
+    journal: JournalMode,
+    mut error: AppError,
+    accessible: bool,
+) -> Result<Option<(Storage, bool)>, AppError> {
+    let backup = backup_path(db);
+    loop {
+        let backup_time = std::fs::metadata(&backup)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        terminal.draw(|f| render_recovery(f, db, &error.to_string(), backup_time, accessible))?;
+        let Event::Key(pressed) = event::read()? else {
+            continue;
+        };
+        if pressed.kind != KeyEventKind::Press {
+            continue;
+        }
+        let attempt = match recovery_action(pressed.code, backup_time.is_some()) {
+            None => continue,
+            Some(RecoveryAction::Quit) => return Ok(None),
+            Some(RecoveryAction::Retry) => open_checked(db, key, journal).map(|s| (s, false)),
+            Some(RecoveryAction::ReadOnly) => Storage::open_read_only(db, key).map(|s| (s, true)),
+            Some(RecoveryAction::RestoreBackup) => archive_database(db, Utc::now())
+                .and_then(|_| Ok(std::fs::copy(&backup, db)?))
+                .and_then(|_| open_checked(db, key, journal))
+                .map(|s| (s, false)),
+            Some(RecoveryAction::StartFresh) => archive_database(db, Utc::now())
+                .and_then(|_| open_checked(db, key, journal))
+                .map(|s| (s, false)),
+        };
+        match attempt {
+            Ok(opened) => return Ok(Some(opened)),
+            Err(e) => error = e,
+        }
+    }
+}
+
 async fn run_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     cfg: &mut AppConfig,
-    storage: &mut Storage,
+    storage: &AsyncStorage,
     service: &MeterService,
+    mut state: AppState,
+    startup_refresh: bool,
 ) -> Result<(), AppError> {
-    let mut state = AppState::default();
-    let mut provider_test_job: Option<ProviderTestJob> = None;
+    let mut jobs = Jobs::default();
     let mut last_tick = Instant::now();
     let tick_rate = StdDuration::from_secs(cfg.refresh_seconds.max(10));
 
-    refresh_dashboard(&mut state, cfg, storage, service).await;
+    if startup_refresh {
+        start_refresh(&mut state, cfg, storage, &mut jobs.refresh, false).await;
+    } else {
+        show_stored_data(&mut state, cfg, storage).await;
+    }
 
     while state.running {
-        if provider_test_job
+        if jobs
+            .provider_test
+            .as_ref()
+            .is_some_and(|job| job.handle.is_finished())
+        {
+            process_provider_test_job(&mut state, &mut jobs.provider_test).await;
+        }
+        if jobs
+            .refresh
             .as_ref()
             .is_some_and(|job| job.handle.is_finished())
         {
-            process_provider_test_job(&mut state, &mut provider_test_job).await;
+            finish_refresh(&mut state, cfg, storage, service, &mut jobs.refresh).await;
         }
 
         terminal.draw(|f| render(f, cfg, &state))?;
 
-        let timeout = tick_rate
+        let mut timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| StdDuration::from_millis(0));
+        if jobs.provider_test.is_some() || jobs.refresh.is_some() {
+            timeout = timeout.min(JOB_POLL);
+        }
 
         if event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
@@ -111,14 +310,16 @@ async fn run_loop(
                     cfg,
                     storage,
                     service,
-                    &mut provider_test_job,
+                    &mut jobs,
                 )
                 .await;
             }
         }
 
         if state.screen == Screen::Dashboard && last_tick.elapsed() >= tick_rate {
-            refresh_dashboard(&mut state, cfg, storage, service).await;
+            if jobs.refresh.is_none() {
+                start_refresh(&mut state, cfg, storage, &mut jobs.refresh, false).await;
+            }
             last_tick = Instant::now();
         }
     }
@@ -131,9 +332,9 @@ async fn handle_key(
     modifiers: KeyModifiers,
     state: &mut AppState,
     cfg: &mut AppConfig,
-    storage: &mut Storage,
+    storage: &AsyncStorage,
     service: &MeterService,
-    provider_test_job: &mut Option<ProviderTestJob>,
+    jobs: &mut Jobs,
 ) {
     if modifiers.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('c') {
         state.previous_screen = state.screen.clone();
@@ -143,7 +344,28 @@ async fn handle_key(
         return;
     }
 
-    if code == KeyCode::Char('z') {
+    if modifiers.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('s') {
+        let saved = data_dir().and_then(|dir| {
+            save_view_csv(
+                state,
+                &MoneyFormat::from_config(&cfg.display),
+                &dir,
+                Utc::now(),
+            )
+        });
+        match saved {
+            Ok(path) => state.status = format!("Saved view to {}", path.display()),
+            Err(e) => show_error(state, format!("Saving view failed: {e}")),
+        }
+        return;
+    }
+
+    if code == KeyCode::Char('z')
+        && !matches!(
+            state.screen,
+            Screen::CommandPalette | Screen::RenameProvider { .. }
+        )
+    {
         state.compact_mode = !state.compact_mode;
         state.status = if state.compact_mode {
             "compact mode enabled".into()
@@ -153,6 +375,11 @@ async fn handle_key(
         return;
     }
 
+    if code == KeyCode::Esc && state.screen == Screen::Dashboard && jobs.refresh.is_some() {
+        cancel_refresh(state, storage, &mut jobs.refresh).await;
+        return;
+    }
+
     if code == KeyCode::Char('a')
         && matches!(state.screen, Screen::Dashboard | Screen::ProviderManager)
     {
@@ -170,19 +397,13 @@ async fn handle_key(
 
     if state.action_focused && matches!(state.screen, Screen::Dashboard | Screen::ProviderManager) {
         match code {
-            KeyCode::Up => {
-                if state.action_selected > 0 {
-                    state.action_selected -= 1;
-                }
-            }
-            KeyCode::Down => {
-                if state.action_selected + 1 < ACTIONS.len() {
-                    state.action_selected += 1;
-                }
+            KeyCode::Up if state.action_selected > 0 => state.action_selected -= 1,
+            KeyCode::Down if state.action_selected + 1 < ACTIONS.len() => {
+                state.action_selected += 1;
             }
             KeyCode::Enter => match state.action_selected {
                 0 => {
-                    refresh_dashboard(state, cfg, storage, service).await;
+                    start_refresh(state, cfg, storage, &mut jobs.refresh, true).await;
                     state.action_focused = false;
                 }
                 1 => {
@@ -210,12 +431,289 @@ async fn handle_key(
                 state.confirm_selected = 0;
                 state.action_focused = false;
             }
-            KeyCode::Char('1') => state.window = TimeWindow::OneDay,
-            KeyCode::Char('7') => state.window = TimeWindow::SevenDays,
-            KeyCode::Char('3') => state.window = TimeWindow::ThirtyDays,
-            KeyCode::Char('r') => refresh_dashboard(state, cfg, storage, service).await,
+            KeyCode::Char('1') => switch_window(state, cfg, storage, TimeWindow::OneDay).await,
+            KeyCode::Char('7') => switch_window(state, cfg, storage, TimeWindow::SevenDays).await,
+            KeyCode::Char('3') => switch_window(state, cfg, storage, TimeWindow::ThirtyDays).await,
+            KeyCode::Char('r') => start_refresh(state, cfg, storage, &mut jobs.refresh, true).await,
+            KeyCode::Char('w') => toggle_window_row(state, cfg, storage).await,
+            KeyCode::Char('g') => toggle_share_gauges(state),
+            KeyCode::Char('t') => toggle_chart_granularity(state, cfg, storage).await,
+            KeyCode::Char('f') => toggle_model_groups(state, cfg, storage).await,
+            KeyCode::Char('o') => toggle_source_breakdown(state),
+            KeyCode::Char('c') => open_column_picker(state),
+            KeyCode::Char('/') => open_search(state, storage).await,
+            KeyCode::Char('s') => open_simulator(state, cfg, storage).await,
+            KeyCode::Char('b') => open_budgets(state, cfg, storage).await,
+            KeyCode::Char(':') => {
+                state.palette = CommandPalette {
+                    entries: palette_entries(cfg, &state.view.model_breakdown),
+                    ..CommandPalette::default()
+                };
+                state.screen = Screen::CommandPalette;
+                state.action_focused = false;
+            }
+            KeyCode::Char('u') => match state.needs_reauth.first().cloned() {
+                Some(provider) => open_provider_edit(state, cfg, &provider),
+                None => state.status = "All provider keys are working.".into(),
+            },
+            KeyCode::Char('h') => {
+                reload_alerts(state, storage).await;
+                state.alert_selected = 0;
+                state.screen = Screen::Alerts;
+                state.action_focused = false;
+            }
+            _ => {}
+        },
+        Screen::Alerts => match code {
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Up if state.alert_selected > 0 => state.alert_selected -= 1,
+            KeyCode::Down if state.alert_selected + 1 < state.alerts.len() => {
+                state.alert_selected += 1;
+            }
+            KeyCode::Char('a') | KeyCode::Enter => {
+                set_selected_alert_state(state, storage, AlertState::Acked).await
+            }
+            KeyCode::Char('x') => {
+                set_selected_alert_state(state, storage, AlertState::Resolved).await
+            }
+            _ => {}
+        },
+        Screen::Simulator => match code {
+            KeyCode::Esc if !state.simulator.input.is_empty() => state.simulator.input.clear(),
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Up if state.simulator.selected > 0 => {
+                state.simulator.selected -= 1;
+                state.simulator.input.clear();
+            }
+            KeyCode::Down if state.simulator.selected + 1 < state.simulator.rows.len() => {
+                state.simulator.selected += 1;
+                state.simulator.input.clear();
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                state.simulator.field = match state.simulator.field {
+                    SimulatorField::InputPrice => SimulatorField::OutputPrice,
+                    SimulatorField::OutputPrice => SimulatorField::InputPrice,
+                };
+                state.simulator.input.clear();
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() || c == '.' => state.simulator.input.push(c),
+            KeyCode::Backspace => {
+                state.simulator.input.pop();
+            }
+            KeyCode::Enter => match state.simulator.apply_input() {
+                Ok(()) => state.status = "simulated price applied".into(),
+                Err(message) => state.status = message,
+            },
+            KeyCode::Char('c') => {
+                let selected = state.simulator.selected;
+                if let Some(row) = state.simulator.rows.get_mut(selected) {
+                    row.reset();
+                    state.simulator.input.clear();
+                    state.status = format!("Reset simulated prices for '{}'", row.model);
+                }
+            }
+            KeyCode::Char('m') => {
+                state.status = match state.simulator.cycle_mapping() {
+                    Some(target) => format!("Simulating traffic moved to '{target}'"),
+                    None => "Migration mapping cleared".into(),
+                };
+            }
+            KeyCode::Char('i') => open_model_detail(state, cfg, storage).await,
+            _ => {}
+        },
+        Screen::ModelDetail => {
+            if matches!(code, KeyCode::Enter | KeyCode::Esc) {
+                state.screen = Screen::Simulator;
+            }
+        }
+        Screen::CommandPalette => match code {
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Up if state.palette.selected > 0 => state.palette.selected -= 1,
+            KeyCode::Down if state.palette.selected + 1 < state.palette.matches().len() => {
+                state.palette.selected += 1;
+            }
+            KeyCode::Enter => match state.palette.selected_command() {
+                Some(command) => {
+                    state.screen = Screen::Dashboard;
+                    run_palette_command(state, cfg, storage, &mut jobs.refresh, command).await;
+                }
+                None => state.status = format!("No command matches '{}'", state.palette.input),
+            },
+            KeyCode::Backspace => {
+                state.palette.input.pop();
+                state.palette.selected = 0;
+            }
+            KeyCode::Char(c) => {
+                state.palette.input.push(c);
+                state.palette.selected = 0;
+            }
+            _ => {}
+        },
+        Screen::RenameProvider { provider } => match code {
+            KeyCode::Esc => state.screen = Screen::ProviderManager,
+            KeyCode::Enter => rename_provider(state, cfg, storage, &provider).await,
+            KeyCode::Backspace => {
+                state.rename_input.pop();
+            }
+            KeyCode::Char(c) => state.rename_input.push(c),
+            _ => {}
+        },
+        Screen::Budgets => match code {
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Up if state.budget_selected > 0 => state.budget_selected -= 1,
+            KeyCode::Down if state.budget_selected + 1 < cfg.budgets.len() => {
+                state.budget_selected += 1;
+            }
+            KeyCode::Char('n') => open_budget_form(state, cfg, storage, BudgetFormMode::Add).await,
+            KeyCode::Enter if state.budget_selected < cfg.budgets.len() => {
+                let mode = BudgetFormMode::Edit {
+                    index: state.budget_selected,
+                };
+                open_budget_form(state, cfg, storage, mode).await;
+            }
+            KeyCode::Char('d') => {
+                if let Some(budget) = cfg.budgets.get(state.budget_selected) {
+                    state.previous_screen = Screen::Budgets;
+                    state.screen = Screen::Confirm(ConfirmAction::DeleteBudget {
+                        index: state.budget_selected,
+                        name: budget.name.clone(),
+                    });
+                    state.confirm_selected = 0;
+                }
+            }
+            _ => {}
+        },
+        Screen::ColumnPicker => match code {
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Up if state.column_selected > 0 => state.column_selected -= 1,
+            KeyCode::Down if state.column_selected + 1 < BreakdownColumn::ALL.len() => {
+                state.column_selected += 1;
+            }
+            KeyCode::Char(' ') => {
+                let column = BreakdownColumn::ALL[state.column_selected];
+                if state.column_draft.contains(&column) {
+                    state.column_draft.retain(|c| *c != column);
+                } else {
+                    state.column_draft = BreakdownColumn::ALL
+                        .into_iter()
+                        .filter(|c| *c == column || state.column_draft.contains(c))
+                        .collect();
+                }
+            }
+            KeyCode::Enter if state.column_draft.is_empty() => {
+                state.status = "pick at least one column".into();
+            }
+            KeyCode::Enter => {
+                state.breakdown_columns = state.column_draft.clone();
+                state.screen = Screen::Dashboard;
+                cfg.display.breakdown_columns = state.breakdown_columns.clone();
+                match save_config(cfg) {
+                    Ok(()) => state.status = "columns saved".into(),
+                    Err(e) => show_error(state, format!("Failed to save config: {e}")),
+                }
+            }
             _ => {}
         },
+        Screen::SqlConsole => match code {
+            KeyCode::Esc => state.screen = Screen::Dashboard,
+            KeyCode::Enter => run_console_query(state, storage).await,
+            KeyCode::Backspace => {
+                state.sql_input.pop();
+            }
+            KeyCode::Char(c) => state.sql_input.push(c),
+            _ => {}
+        },
+        Screen::Search => {
+            let field = state.search.active_field();
+            let fields = SearchField::ALL.len();
+            match code {
+                KeyCode::Esc => state.screen = Screen::Dashboard,
+                KeyCode::Tab | KeyCode::Down => {
+                    state.search.active_field = (state.search.active_field + 1) % fields;
+                }
+                KeyCode::BackTab | KeyCode::Up => {
+                    state.search.active_field = (state.search.active_field + fields - 1) % fields;
+                }
+                KeyCode::Left | KeyCode::Right if field == SearchField::Table => {
+                    state.search.table = match state.search.table {
+                        SearchTable::Cost => SearchTable::Usage,
+                        SearchTable::Usage => SearchTable::Cost,
+                    };
+                    state.search.page = 0;
+                    run_search(state, storage).await;
+                }
+                KeyCode::PageDown if state.search.page + 1 < state.search.pages() => {
+                    state.search.page += 1;
+                    run_search(state, storage).await;
+                }
+                KeyCode::PageUp if state.search.page > 0 => {
+                    state.search.page -= 1;
+                    run_search(state, storage).await;
+                }
+                KeyCode::Enter => {
+                    state.search.page = 0;
+                    run_search(state, storage).await;
+                }
+                KeyCode::Backspace => {
+                    if let Some(text) = search_text_field(&mut state.search, field) {
+                        text.pop();
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(text) = search_text_field(&mut state.search, field) {
+                        text.push(c);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Screen::BudgetForm(mode) => {
+            let field = state.budget_draft.active_field();
+            let fields = BudgetFormField::ALL.len();
+            match code {
+                KeyCode::Esc => state.screen = Screen::Budgets,
+                KeyCode::Tab | KeyCode::Down => {
+                    state.budget_draft.active_field =
+                        (state.budget_draft.active_field + 1) % fields;
+                }
+                KeyCode::BackTab | KeyCode::Up => {
+                    state.budget_draft.active_field =
+                        (state.budget_draft.active_field + fields - 1) % fields;
+                }
+                KeyCode::Left | KeyCode::Right => {
+                    let changed = state.budget_draft.cycle(code == KeyCode::Right);
+                    if changed {
+                        if field == BudgetFormField::Scope {
+                            load_budget_targets(state, cfg, storage).await;
+                        }
+                        reload_draft_spend(state, cfg, storage).await;
+                    }
+                }
+                KeyCode::Enter => {
+                    let saved = submit_budget_form(state, cfg, mode);
+                    if saved {
+                        open_budgets(state, cfg, storage).await;
+                    }
+                }
+                KeyCode::Backspace => {
+                    if let Some(text) = budget_text_field(&mut state.budget_draft, field) {
+                        text.pop();
+                        if field == BudgetFormField::Target {
+                            reload_draft_spend(state, cfg, storage).await;
+                        }
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(text) = budget_text_field(&mut state.budget_draft, field) {
+                        text.push(c);
+                        if field == BudgetFormField::Target {
+                            reload_draft_spend(state, cfg, storage).await;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
         Screen::ProviderManager => {
             let providers = provider_list(cfg);
             let provider_count = providers.len();
@@ -242,19 +740,13 @@ async fn handle_key(
                     state.screen = Screen::ProviderForm(ProviderFormMode::Add);
                     state.action_focused = false;
                 }
-                KeyCode::Up => {
-                    if state.provider_selected > 0 {
-                        state.provider_selected -= 1;
-                    }
-                }
-                KeyCode::Down => {
-                    if state.provider_selected + 1 < provider_count {
-                        state.provider_selected += 1;
-                    }
+                KeyCode::Up if state.provider_selected > 0 => state.provider_selected -= 1,
+                KeyCode::Down if state.provider_selected + 1 < provider_count => {
+                    state.provider_selected += 1;
                 }
                 KeyCode::Char('t') => {
                     if let Some(provider) = providers.get(state.provider_selected) {
-                        if provider_test_job.is_some() {
+                        if jobs.provider_test.is_some() {
                             state.status = "Another provider connection test is running.".into();
                             return;
                         }
@@ -271,7 +763,8 @@ async fn handle_key(
                                     None,
                                 );
                                 queue_provider_test_job(
-                                    provider_test_job,
+                                    &mut jobs.provider_test,
+                                    cfg,
                                     name,
                                     api_key,
                                     settings,
@@ -282,6 +775,16 @@ async fn handle_key(
                         }
                     }
                 }
+                KeyCode::Char('u') => undo_provider_removal(state, cfg),
+                KeyCode::Char('r') => {
+                    if let Some(provider) = providers.get(state.provider_selected) {
+                        state.rename_input = provider.clone();
+                        state.screen = Screen::RenameProvider {
+                            provider: provider.clone(),
+                        };
+                        state.action_focused = false;
+                    }
+                }
                 KeyCode::Char('e') => {
                     if let Some(provider) = providers.get(state.provider_selected) {
                         let normalized = normalize_provider_name(provider);
@@ -312,6 +815,7 @@ async fn handle_key(
                                         show_error(state, format!("Failed to save config: {e}"));
                                     } else {
                                         state.status = format!("Provider '{normalized}' enabled");
+                                        offer_backfill(state, storage, &normalized).await;
                                     }
                                 }
                                 Ok(false) => show_error(
@@ -327,69 +831,49 @@ async fn handle_key(
                 }
                 KeyCode::Enter => {
                     if let Some(provider) = providers.get(state.provider_selected) {
-                        let settings = cfg
-                            .provider_settings
-                            .get(provider)
-                            .cloned()
-                            .unwrap_or_default();
-                        let normalized = normalize_provider_name(provider);
-                        let is_enabled = cfg
-                            .enabled_providers
-                            .iter()
-                            .any(|p| p.eq_ignore_ascii_case(provider));
-                        state.provider_draft = ProviderDraft {
-                            name: provider.clone(),
-                            base_url: settings.base_url.unwrap_or_default(),
-                            organization_id: settings.organization_id.unwrap_or_default(),
-                            api_key: String::new(),
-                            enabled: is_enabled,
-                            active_field: 0,
-                            show_advanced: false,
-                            connection_status: state
-                                .provider_test_results
-                                .get(&normalized)
-                                .cloned()
-                                .unwrap_or({
-                                    if is_enabled {
-                                        ConnectionStatus::Success
-                                    } else {
-                                        ConnectionStatus::NotTested
-                                    }
-                                }),
-                        };
-                        state.screen = Screen::ProviderForm(ProviderFormMode::Edit {
-                            provider: provider.clone(),
-                        });
-                        state.action_focused = false;
+                        open_provider_edit(state, cfg, provider);
                     }
                 }
                 KeyCode::Char('d') => {
                     if let Some(provider) = providers.get(state.provider_selected) {
-                        state.previous_screen = Screen::ProviderManager;
-                        state.screen = Screen::Confirm(ConfirmAction::DeleteProvider {
+                        let action = ConfirmAction::DeleteProvider {
                             provider: provider.clone(),
-                        });
-                        state.confirm_selected = 0;
-                        state.action_focused = false;
+                        };
+                        confirm_removal(state, cfg, storage, action).await;
                     }
                 }
                 KeyCode::Char('k') => {
                     if let Some(provider) = providers.get(state.provider_selected) {
-                        state.previous_screen = Screen::ProviderManager;
-                        state.screen = Screen::Confirm(ConfirmAction::DeleteKey {
+                        let action = ConfirmAction::DeleteKey {
                             provider: provider.clone(),
-                        });
-                        state.confirm_selected = 0;
-                        state.action_focused = false;
+                        };
+                        confirm_removal(state, cfg, storage, action).await;
                     }
                 }
                 _ => {}
             }
         }
         Screen::ProviderForm(mode) => {
-            let field_count = visible_form_fields(&mode, state.provider_draft.show_advanced).len();
+            let field_count = visible_form_fields(
+                &mode,
+                state.provider_draft.show_advanced,
+                state.provider_draft.is_azure(),
+            )
+            .len();
             match code {
                 KeyCode::Esc => state.screen = Screen::ProviderManager,
+                // Resource IDs and deployment names need every letter, so the
+                // single-key commands do not apply on these fields.
+                KeyCode::Char(c)
+                    if matches!(
+                        active_form_field(state, &mode),
+                        ProviderFormField::AzureResource
+                            | ProviderFormField::ApiVersion
+                            | ProviderFormField::Deployments
+                    ) =>
+                {
+                    input_char(state, mode, c);
+                }
                 KeyCode::Tab => {
                     state.provider_draft.active_field =
                         (state.provider_draft.active_field + 1) % field_count;
@@ -403,14 +887,18 @@ async fn handle_key(
                 }
                 KeyCode::Char('v') => {
                     state.provider_draft.show_advanced = !state.provider_draft.show_advanced;
-                    let new_count =
-                        visible_form_fields(&mode, state.provider_draft.show_advanced).len();
+                    let new_count = visible_form_fields(
+                        &mode,
+                        state.provider_draft.show_advanced,
+                        state.provider_draft.is_azure(),
+                    )
+                    .len();
                     if state.provider_draft.active_field >= new_count {
                         state.provider_draft.active_field = new_count.saturating_sub(1);
                     }
                 }
                 KeyCode::Char('t') => {
-                    if provider_test_job.is_some() {
+                    if jobs.provider_test.is_some() {
                         state.status = "Another provider connection test is running.".into();
                     } else {
                         match build_form_test_target(state, cfg, &mode) {
@@ -427,7 +915,8 @@ async fn handle_key(
                                     None,
                                 );
                                 queue_provider_test_job(
-                                    provider_test_job,
+                                    &mut jobs.provider_test,
+                                    cfg,
                                     provider,
                                     api_key,
                                     settings,
@@ -438,7 +927,43 @@ async fn handle_key(
                         }
                     }
                 }
-                KeyCode::Enter => submit_provider_form(state, cfg, mode),
+                KeyCode::Enter => {
+                    let provider = form_provider_name(state, &mode);
+                    let key_changed = !state.provider_draft.api_key.trim().is_empty();
+                    let is_enabled = |cfg: &AppConfig, provider: &Option<String>| {
+                        provider.as_ref().is_some_and(|p| {
+                            cfg.enabled_providers
+                                .iter()
+                                .any(|e| e.eq_ignore_ascii_case(p))
+                        })
+                    };
+                    let was_enabled = is_enabled(cfg, &provider);
+                    submit_provider_form(state, cfg, mode);
+                    if state.screen == Screen::ProviderManager
+                        && !was_enabled
+                        && is_enabled(cfg, &provider)
+                    {
+                        if let Some(provider) = &provider {
+                            offer_backfill(state, storage, provider).await;
+                        }
+                    }
+                    if let Some(provider) = provider.filter(|_| key_changed) {
+                        if state.screen == Screen::ProviderManager
+                            && state.needs_reauth.contains(&provider)
+                        {
+                            let cleared = provider.clone();
+                            if storage
+                                .call(move |s| s.clear_needs_reauth(&cleared))
+                                .await
+                                .is_ok()
+                            {
+                                state.needs_reauth.retain(|p| *p != provider);
+                                state.status =
+                                    format!("Key for '{provider}' updated; refreshes resumed.");
+                            }
+                        }
+                    }
+                }
                 KeyCode::Char('i') => {
                     if let ConnectionStatus::Failure(message) =
                         &state.provider_draft.connection_status
@@ -472,11 +997,12 @@ async fn handle_key(
                         input_char(state, mode, 'e');
                     }
                 }
-                KeyCode::Char(' ') => {
-                    if active_form_field(state, &mode) != ProviderFormField::Enabled {
-                        input_char(state, mode, ' ');
-                    }
+                KeyCode::Char(' ')
+                    if active_form_field(state, &mode) != ProviderFormField::Enabled =>
+                {
+                    input_char(state, mode, ' ');
                 }
+                KeyCode::Char(' ') => {}
                 KeyCode::Backspace => backspace_char(state, mode),
                 KeyCode::Char(c) => input_char(state, mode, c),
                 _ => {}
@@ -487,15 +1013,10 @@ async fn handle_key(
                 state.screen = state.previous_screen.clone();
                 state.action_focused = false;
             }
-            KeyCode::Left => {
-                if state.confirm_selected > 0 {
-                    state.confirm_selected -= 1;
-                }
-            }
-            KeyCode::Right => {
-                if state.confirm_selected < 1 {
-                    state.confirm_selected += 1;
-                }
+            KeyCode::Left if state.confirm_selected > 0 => state.confirm_selected -= 1,
+            KeyCode::Right if state.confirm_selected < 1 => state.confirm_selected += 1,
+            KeyCode::Char('p') if matches!(action, ConfirmAction::DeleteProvider { .. }) => {
+                state.deletion_preview.purge_history = !state.deletion_preview.purge_history;
             }
             KeyCode::Enter => {
                 if state.confirm_selected == 0 {
@@ -506,8 +1027,20 @@ async fn handle_key(
 
                 match action {
                     ConfirmAction::Quit => state.running = false,
+                    ConfirmAction::DeleteBudget { index, name } => {
+                        if index < cfg.budgets.len() {
+                            cfg.budgets.remove(index);
+                        }
+                        if let Err(e) = save_config(cfg) {
+                            show_error(state, format!("Failed to save config: {e}"));
+                            return;
+                        }
+                        state.status = format!("Budget '{name}' removed");
+                        open_budgets(state, cfg, storage).await;
+                    }
                     ConfirmAction::DeleteProvider { provider } => {
                         let normalized = normalize_provider_name(&provider);
+                        let removed = removal_snapshot(cfg, &provider, true);
                         cfg.provider_settings.remove(&provider);
                         cfg.enabled_providers
                             .retain(|p| !p.eq_ignore_ascii_case(&provider));
@@ -521,11 +1054,29 @@ async fn handle_key(
                             show_error(state, format!("Failed to save config: {e}"));
                             return;
                         }
-                        state.status = format!("Provider '{provider}' removed");
+                        state.undo_removal = Some(removed);
+                        state.status = format!("Provider '{provider}' removed (u to undo)");
                         state.screen = Screen::ProviderManager;
+                        if state.deletion_preview.purge_history {
+                            match storage.call(move |s| s.purge_provider(&normalized)).await {
+                                Ok((usage, cost)) => {
+                                    state.status = format!(
+                                        "Provider '{provider}' removed, {usage} usage and {cost} cost rows purged (u restores config and key)"
+                                    );
+                                }
+                                Err(e) => {
+                                    show_error(state, format!("Failed to purge history: {e}"))
+                                }
+                            }
+                        }
+                    }
+                    ConfirmAction::Backfill { provider } => {
+                        state.screen = state.previous_screen.clone();
+                        backfill_provider(state, cfg, storage, service, &provider).await;
                     }
                     ConfirmAction::DeleteKey { provider } => {
                         let normalized = normalize_provider_name(&provider);
+                        let removed = removal_snapshot(cfg, &provider, false);
                         if let Err(e) = delete_api_key(&provider) {
                             show_error(state, format!("Failed to delete key: {e}"));
                             return;
@@ -538,7 +1089,8 @@ async fn handle_key(
                             show_error(state, format!("Failed to save config: {e}"));
                             return;
                         }
-                        state.status = format!("Key removed for '{provider}'");
+                        state.undo_removal = Some(removed);
+                        state.status = format!("Key removed for '{provider}' (u to undo)");
                         state.screen = Screen::ProviderManager;
                     }
                 }
@@ -558,58 +1110,329 @@ async fn handle_key(
     }
 }
 
-fn submit_provider_form(state: &mut AppState, cfg: &mut AppConfig, mode: ProviderFormMode) {
-    let provider_name = match &mode {
-        ProviderFormMode::Add => normalize_provider_name(&state.provider_draft.name),
-        ProviderFormMode::Edit { provider } => normalize_provider_name(provider),
+/// Opens the confirm dialog for a provider or key removal with a preview of
+/// everything it will delete.
+async fn confirm_removal(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    action: ConfirmAction,
+) {
+    let (ConfirmAction::DeleteProvider { provider } | ConfirmAction::DeleteKey { provider }) =
+        &action
+    else {
+        return;
+    };
+    let config_section = match (&action, cfg.provider_settings.get(provider)) {
+        (ConfirmAction::DeleteProvider { .. }, Some(settings)) => {
+            std::iter::once(format!("[provider_settings.{provider}]"))
+                .chain(
+                    toml::to_string_pretty(settings)
+                        .unwrap_or_default()
+                        .lines()
+                        .map(str::to_string),
+                )
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    let normalized = normalize_provider_name(provider);
+    let (usage_rows, cost_rows) = storage
+        .call(move |s| s.provider_row_counts(&normalized))
+        .await
+        .unwrap_or_default();
+    state.deletion_preview = DeletionPreview {
+        config_section,
+        keyring_entry: format!("{} / {}", keyring_service(), keyring_account(provider)),
+        has_key: stored_api_key(provider).ok().flatten().is_some(),
+        usage_rows,
+        cost_rows,
+        purge_history: false,
     };
+    state.previous_screen = Screen::ProviderManager;
+    state.screen = Screen::Confirm(action);
+    state.confirm_selected = 0;
+    state.action_focused = false;
+}
 
-    if provider_name.is_empty() {
-        show_error(state, "Provider name is required".to_string());
+/// Moves `provider`'s config, key and history to the name in the rename prompt.
+async fn rename_provider(
+    state: &mut AppState,
+    cfg: &mut AppConfig,
+    storage: &AsyncStorage,
+    provider: &str,
+) {
+    let to = normalize_provider_name(&state.rename_input);
+    let from = normalize_provider_name(provider);
+    if to == from {
+        state.screen = Screen::ProviderManager;
         return;
     }
-
-    if matches!(mode, ProviderFormMode::Add) && state.provider_draft.api_key.trim().is_empty() {
-        show_error(state, "API key is required for new providers.".to_string());
-        return;
+    let mut renamed = cfg.clone();
+    let (source, target) = (from.clone(), to.clone());
+    let result = storage
+        .call(move |s| {
+            service::rename_provider(&mut renamed, s, &source, &target, save_config)
+                .map(|moved| (moved, renamed))
+        })
+        .await;
+    match result {
+        Ok((moved, renamed)) => {
+            *cfg = renamed;
+            if let Some(status) = state.provider_test_results.remove(&from) {
+                state.provider_test_results.insert(to.clone(), status);
+            }
+            if let Some(logs) = state.provider_logs.remove(&from) {
+                state.provider_logs.insert(to.clone(), logs);
+            }
+            state.undo_removal = None;
+            state.status = format!("Renamed '{from}' to '{to}'; {moved} stored rows moved");
+            state.screen = Screen::ProviderManager;
+        }
+        Err(e) => show_error(state, format!("Rename failed: {e}")),
     }
+}
 
-    if !state.provider_draft.base_url.trim().is_empty()
-        && Url::parse(state.provider_draft.base_url.trim()).is_err()
-    {
-        show_error(state, "Base URL is not valid".to_string());
-        return;
+/// Captures what removing `provider` (or only its key) is about to destroy.
+fn removal_snapshot(cfg: &AppConfig, provider: &str, with_settings: bool) -> RemovedProvider {
+    RemovedProvider {
+        name: provider.to_string(),
+        settings: with_settings
+            .then(|| cfg.provider_settings.get(provider).cloned())
+            .flatten(),
+        enabled: cfg
+            .enabled_providers
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(provider)),
+        api_key: stored_api_key(provider).ok().flatten(),
     }
+}
 
-    let settings = ProviderSettings {
-        base_url: if state.provider_draft.base_url.trim().is_empty() {
-            None
-        } else {
-            Some(state.provider_draft.base_url.trim().to_string())
-        },
-        organization_id: if state.provider_draft.organization_id.trim().is_empty() {
-            None
-        } else {
-            Some(state.provider_draft.organization_id.trim().to_string())
-        },
+fn undo_provider_removal(state: &mut AppState, cfg: &mut AppConfig) {
+    let Some(removed) = state.undo_removal.take() else {
+        state.status = "Nothing to undo.".into();
+        return;
     };
-
-    cfg.provider_settings
-        .insert(provider_name.clone(), settings);
-
-    if !state.provider_draft.api_key.trim().is_empty() {
-        if let Err(e) = set_api_key(&provider_name, state.provider_draft.api_key.trim()) {
-            show_error(state, format!("Failed to save key: {e}"));
+    if let Some(key) = &removed.api_key {
+        if let Err(e) = set_api_key(&removed.name, key) {
+            show_error(state, format!("Failed to restore key: {e}"));
+            state.undo_removal = Some(removed);
             return;
         }
     }
-
-    let mut blocked_enable_without_test = false;
-    if state.provider_draft.enabled {
-        if !matches!(
-            state.provider_draft.connection_status,
-            ConnectionStatus::Success
-        ) {
+    if let Some(settings) = &removed.settings {
+        cfg.provider_settings
+            .insert(removed.name.clone(), settings.clone());
+    }
+    if removed.enabled
+        && !cfg
+            .enabled_providers
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(&removed.name))
+    {
+        cfg.enabled_providers.push(removed.name.clone());
+    }
+    if let Err(e) = save_config(cfg) {
+        show_error(state, format!("Failed to save config: {e}"));
+        return;
+    }
+    state.status = format!("Restored '{}'", removed.name);
+}
+
+/// Prompts to backfill history for a provider that has never been refreshed.
+async fn offer_backfill(state: &mut AppState, storage: &AsyncStorage, provider: &str) {
+    let name = provider.to_string();
+    let never_refreshed = storage
+        .call(move |s| Ok(s.last_successful_refresh(&name, 0)?.is_none()))
+        .await
+        .unwrap_or(false);
+    if never_refreshed {
+        state.previous_screen = state.screen.clone();
+        state.screen = Screen::Confirm(ConfirmAction::Backfill {
+            provider: provider.to_string(),
+        });
+        state.confirm_selected = 0;
+        state.action_focused = false;
+    }
+}
+
+async fn backfill_provider(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    service: &MeterService,
+    provider: &str,
+) {
+    state.status = format!("Backfilling '{provider}'...");
+    let cfg_owned = cfg.clone();
+    let outcome = match storage
+        .call(move |s| request_budget(&cfg_owned, s, Utc::now()))
+        .await
+    {
+        Ok(requests) => match service.fetch_backfill(cfg, provider, None, requests).await {
+            Ok(fetched) => storage.call(move |s| fetched.persist(s)).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(snapshot) if !snapshot.capped.is_empty() => {
+            state.status = format!(
+                "Backfill for '{provider}' stopped at the request cap; nothing stored, retry later"
+            );
+        }
+        Ok(snapshot) => {
+            load_dashboard_view(state, cfg, storage).await;
+            state.status = format!(
+                "Backfilled {} usage records for '{provider}'",
+                snapshot.usage.len()
+            );
+        }
+        Err(e) => show_error(state, format!("Backfill for '{provider}' failed: {e}")),
+    }
+}
+
+/// Opens the edit form for `provider`; in edit mode the API key is the first
+/// (focused) field.
+fn open_provider_edit(state: &mut AppState, cfg: &AppConfig, provider: &str) {
+    let settings = cfg
+        .provider_settings
+        .get(provider)
+        .cloned()
+        .unwrap_or_default();
+    let normalized = normalize_provider_name(provider);
+    let is_enabled = cfg
+        .enabled_providers
+        .iter()
+        .any(|p| p.eq_ignore_ascii_case(provider));
+    state.provider_draft = ProviderDraft {
+        name: provider.to_string(),
+        base_url: settings.base_url.unwrap_or_default(),
+        organization_id: settings.organization_id.unwrap_or_default(),
+        owner: settings.owner.unwrap_or_default(),
+        notes: settings.notes.unwrap_or_default(),
+        key_expires_at: settings
+            .key_expires_at
+            .map(|at| at.to_string())
+            .unwrap_or_default(),
+        api_key: String::new(),
+        kind: cfg.provider_kind(provider),
+        resource: settings.resource.unwrap_or_default(),
+        api_version: settings.api_version.unwrap_or_default(),
+        deployments: settings
+            .deployments
+            .iter()
+            .map(|(deployment, model)| format!("{deployment}={model}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        enabled: is_enabled,
+        active_field: 0,
+        show_advanced: false,
+        connection_status: state
+            .provider_test_results
+            .get(&normalized)
+            .cloned()
+            .unwrap_or({
+                if is_enabled {
+                    ConnectionStatus::Success
+                } else {
+                    ConnectionStatus::NotTested
+                }
+            }),
+    };
+    state.screen = Screen::ProviderForm(ProviderFormMode::Edit {
+        provider: provider.to_string(),
+    });
+    state.action_focused = false;
+}
+
+fn submit_provider_form(state: &mut AppState, cfg: &mut AppConfig, mode: ProviderFormMode) {
+    let provider_name = match &mode {
+        ProviderFormMode::Add => normalize_provider_name(&state.provider_draft.name),
+        ProviderFormMode::Edit { provider } => normalize_provider_name(provider),
+    };
+
+    if provider_name.is_empty() {
+        show_error(state, "Provider name is required".to_string());
+        return;
+    }
+
+    if matches!(mode, ProviderFormMode::Add)
+        && adapter_needs_api_key(&state.provider_draft.adapter())
+        && state.provider_draft.api_key.trim().is_empty()
+    {
+        show_error(state, "API key is required for new providers.".to_string());
+        return;
+    }
+
+    if !state.provider_draft.base_url.trim().is_empty()
+        && Url::parse(state.provider_draft.base_url.trim()).is_err()
+    {
+        show_error(state, "Base URL is not valid".to_string());
+        return;
+    }
+
+    let adapter = state.provider_draft.adapter();
+    if adapter_requires_base_url(&adapter) && state.provider_draft.base_url.trim().is_empty() {
+        show_error(
+            state,
+            format!("Base URL is required for {adapter} accounts."),
+        );
+        return;
+    }
+
+    let key_expires_at = match state.provider_draft.key_expires_at.trim() {
+        "" => None,
+        raw => match NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+            Ok(at) => Some(at),
+            Err(_) => {
+                show_error(state, "Key expiry must be a YYYY-MM-DD date".to_string());
+                return;
+            }
+        },
+    };
+
+    let mut settings = ProviderSettings {
+        base_url: if state.provider_draft.base_url.trim().is_empty() {
+            None
+        } else {
+            Some(state.provider_draft.base_url.trim().to_string())
+        },
+        organization_id: if state.provider_draft.organization_id.trim().is_empty() {
+            None
+        } else {
+            Some(state.provider_draft.organization_id.trim().to_string())
+        },
+        owner: Some(state.provider_draft.owner.trim().to_string()).filter(|s| !s.is_empty()),
+        notes: Some(state.provider_draft.notes.trim().to_string()).filter(|s| !s.is_empty()),
+        key_expires_at,
+        ..cfg
+            .provider_settings
+            .get(&provider_name)
+            .cloned()
+            .unwrap_or_default()
+    };
+    if let Err(message) = apply_azure_draft(&state.provider_draft, &mut settings) {
+        show_error(state, message);
+        return;
+    }
+
+    cfg.provider_settings
+        .insert(provider_name.clone(), settings);
+
+    if !state.provider_draft.api_key.trim().is_empty() {
+        if let Err(e) = set_api_key(&provider_name, state.provider_draft.api_key.trim()) {
+            show_error(state, format!("Failed to save key: {e}"));
+            return;
+        }
+    }
+
+    let mut blocked_enable_without_test = false;
+    if state.provider_draft.enabled {
+        if !matches!(
+            state.provider_draft.connection_status,
+            ConnectionStatus::Success
+        ) {
             cfg.enabled_providers
                 .retain(|p| !p.eq_ignore_ascii_case(&provider_name));
             state.provider_draft.enabled = false;
@@ -678,9 +1501,13 @@ fn build_manager_test_target(
     provider: &str,
 ) -> Result<(String, String, ProviderSettings), String> {
     let provider_name = normalize_provider_name(provider);
-    let api_key = get_api_key(&provider_name).map_err(|_| {
-        format!("Provider '{provider_name}' has no key. Set key first before testing.")
-    })?;
+    let api_key = if adapter_needs_api_key(&cfg.provider_kind(&provider_name)) {
+        provider_api_key(cfg, &provider_name).map_err(|_| {
+            format!("Provider '{provider_name}' has no key. Set key first before testing.")
+        })?
+    } else {
+        String::new()
+    };
     let settings = cfg
         .provider_settings
         .get(&provider_name)
@@ -704,9 +1531,11 @@ fn build_form_test_target(
 
     let api_key = if !state.provider_draft.api_key.trim().is_empty() {
         state.provider_draft.api_key.trim().to_string()
-    } else {
-        get_api_key(&provider_name)
+    } else if adapter_needs_api_key(&cfg.provider_kind(&provider_name)) {
+        provider_api_key(cfg, &provider_name)
             .map_err(|_| "API key is required to run a connection test.".to_string())?
+    } else {
+        String::new()
     };
 
     if !state.provider_draft.base_url.trim().is_empty()
@@ -720,7 +1549,7 @@ fn build_form_test_target(
         .get(&provider_name)
         .cloned()
         .unwrap_or_default();
-    let settings = ProviderSettings {
+    let mut settings = ProviderSettings {
         base_url: if !state.provider_draft.base_url.trim().is_empty() {
             Some(state.provider_draft.base_url.trim().to_string())
         } else {
@@ -731,12 +1560,46 @@ fn build_form_test_target(
         } else {
             existing.organization_id
         },
+        ..existing
     };
+    apply_azure_draft(&state.provider_draft, &mut settings)?;
     Ok((provider_name, api_key, settings))
 }
 
+/// Copies the resource, API version and deployments of an `azure-openai`
+/// form into `settings`; other forms leave them alone.
+fn apply_azure_draft(draft: &ProviderDraft, settings: &mut ProviderSettings) -> Result<(), String> {
+    if !draft.is_azure() {
+        return Ok(());
+    }
+    if draft.resource.trim().is_empty() {
+        return Err("Resource ID is required for azure-openai.".to_string());
+    }
+    let mut deployments = std::collections::BTreeMap::new();
+    for pair in draft.deployments.split(',').map(str::trim) {
+        if pair.is_empty() {
+            continue;
+        }
+        match pair.split_once('=').map(|(d, m)| (d.trim(), m.trim())) {
+            Some((deployment, model)) if !deployment.is_empty() && !model.is_empty() => {
+                deployments.insert(deployment.to_string(), model.to_string());
+            }
+            _ => {
+                return Err(format!(
+                    "Deployments must be deployment=model pairs separated by commas, not '{pair}'."
+                ))
+            }
+        }
+    }
+    settings.resource = Some(draft.resource.trim().to_string());
+    settings.api_version = Some(draft.api_version.trim().to_string()).filter(|v| !v.is_empty());
+    settings.deployments = deployments;
+    Ok(())
+}
+
 fn queue_provider_test_job(
     provider_test_job: &mut Option<ProviderTestJob>,
+    cfg: &AppConfig,
     provider: String,
     api_key: String,
     settings: ProviderSettings,
@@ -744,8 +1607,9 @@ fn queue_provider_test_job(
 ) {
     let provider_for_task = provider.clone();
     let started_at = Instant::now();
+    let svc = MeterService::new(cfg);
     let handle = tokio::spawn(async move {
-        let svc = MeterService::new()?;
+        let svc = svc?;
         svc.test_provider_connection(&provider_for_task, api_key, settings)
             .await
     });
@@ -907,27 +1771,49 @@ fn append_provider_log(
 enum ProviderFormField {
     Name,
     ApiKey,
+    AzureResource,
+    ApiVersion,
+    Deployments,
     BaseUrl,
     OrganizationId,
+    Owner,
+    Notes,
+    KeyExpiresAt,
     Enabled,
 }
 
-fn visible_form_fields(mode: &ProviderFormMode, show_advanced: bool) -> Vec<ProviderFormField> {
+fn visible_form_fields(
+    mode: &ProviderFormMode,
+    show_advanced: bool,
+    azure: bool,
+) -> Vec<ProviderFormField> {
     let mut fields = Vec::new();
     if matches!(mode, ProviderFormMode::Add) {
         fields.push(ProviderFormField::Name);
     }
     fields.push(ProviderFormField::ApiKey);
+    if azure {
+        fields.push(ProviderFormField::AzureResource);
+        fields.push(ProviderFormField::ApiVersion);
+        fields.push(ProviderFormField::Deployments);
+    }
     if show_advanced {
         fields.push(ProviderFormField::BaseUrl);
         fields.push(ProviderFormField::OrganizationId);
+        fields.push(ProviderFormField::Owner);
+        fields.push(ProviderFormField::Notes);
+        fields.push(ProviderFormField::KeyExpiresAt);
     }
     fields.push(ProviderFormField::Enabled);
     fields
 }
 
 fn active_form_field(state: &AppState, mode: &ProviderFormMode) -> ProviderFormField {
-    let fields = visible_form_fields(mode, state.provider_draft.show_advanced);
+    let fields = visible_form_fields(
+        mode,
+        state.provider_draft.show_advanced,
+        state.provider_draft.is_azure(),
+    );
     let index = state
         .provider_draft
         .active_field
@@ -948,8 +1834,14 @@ fn input_char(state: &mut AppState, mode: ProviderFormMode, ch: char) {
     match active_form_field(state, &mode) {
         ProviderFormField::Name => state.provider_draft.name.push(ch),
         ProviderFormField::ApiKey => state.provider_draft.api_key.push(ch),
+        ProviderFormField::AzureResource => state.provider_draft.resource.push(ch),
+        ProviderFormField::ApiVersion => state.provider_draft.api_version.push(ch),
+        ProviderFormField::Deployments => state.provider_draft.deployments.push(ch),
         ProviderFormField::BaseUrl => state.provider_draft.base_url.push(ch),
         ProviderFormField::OrganizationId => state.provider_draft.organization_id.push(ch),
+        ProviderFormField::Owner => state.provider_draft.owner.push(ch),
+        ProviderFormField::Notes => state.provider_draft.notes.push(ch),
+        ProviderFormField::KeyExpiresAt => state.provider_draft.key_expires_at.push(ch),
         ProviderFormField::Enabled => {}
     }
     reset_connection_status_after_edit(state);
@@ -963,12 +1855,30 @@ fn backspace_char(state: &mut AppState, mode: ProviderFormMode) {
         ProviderFormField::ApiKey => {
             state.provider_draft.api_key.pop();
         }
+        ProviderFormField::AzureResource => {
+            state.provider_draft.resource.pop();
+        }
+        ProviderFormField::ApiVersion => {
+            state.provider_draft.api_version.pop();
+        }
+        ProviderFormField::Deployments => {
+            state.provider_draft.deployments.pop();
+        }
         ProviderFormField::BaseUrl => {
             state.provider_draft.base_url.pop();
         }
         ProviderFormField::OrganizationId => {
             state.provider_draft.organization_id.pop();
         }
+        ProviderFormField::Owner => {
+            state.provider_draft.owner.pop();
+        }
+        ProviderFormField::Notes => {
+            state.provider_draft.notes.pop();
+        }
+        ProviderFormField::KeyExpiresAt => {
+            state.provider_draft.key_expires_at.pop();
+        }
         ProviderFormField::Enabled => {}
     }
     reset_connection_status_after_edit(state);
@@ -986,89 +1896,1255 @@ fn show_info(state: &mut AppState, message: String) {
     state.screen = Screen::InfoDialog;
 }
 
-async fn refresh_dashboard(
+/// Starts fetching from providers in the background, showing stored data
+/// meanwhile, unless a refresh is running already or the checks before it
+/// (read-only database, fresh data, request caps) settle it here. The main
+/// loop stores the result with [`finish_refresh`].
+async fn start_refresh(
     state: &mut AppState,
     cfg: &AppConfig,
-    storage: &mut Storage,
-    service: &MeterService,
+    storage: &AsyncStorage,
+    refresh: &mut Option<RefreshJob>,
+    force: bool,
 ) {
+    if refresh.is_some() {
+        state.status = "refresh already running · Esc cancels".into();
+        return;
+    }
+    load_skipped_providers(state, cfg, storage).await;
+
+    if state.read_only {
+        load_dashboard_view(state, cfg, storage).await;
+        state.status = "read-only database: showing stored data, refresh disabled".into();
+        return;
+    }
+
+    if !force {
+        let cfg_owned = cfg.clone();
+        let window = state.window;
+        let mut cache = std::mem::take(&mut state.freshness);
+        let checked = storage
+            .call(move |s| {
+                let needed = refresh_needed(&cfg_owned, window, s, &mut cache, Utc::now());
+                Ok((needed, cache))
+            })
+            .await;
+        if let Ok((needed, cache)) = checked {
+            state.freshness = cache;
+            if !needed {
+                load_dashboard_view(state, cfg, storage).await;
+                state.status = "ok (cached)".into();
+                return;
+            }
+        }
+    }
+
+    let cfg_owned = cfg.clone();
+    let requests = match storage
+        .call(move |s| request_budget(&cfg_owned, s, Utc::now()))
+        .await
+    {
+        Ok(requests) => requests,
+        Err(e) => {
+            state.status = format!("refresh failed: {e}");
+            return;
+        }
+    };
+    if requests.is_exhausted() {
+        load_dashboard_view(state, cfg, storage).await;
+        state.status =
+            "refresh paused: [request_caps] allows no more provider requests today".into();
+        return;
+    }
+
+    load_dashboard_view(state, cfg, storage).await;
     state.status = "refreshing...".into();
-    match service.refresh(cfg, state.window, storage).await {
-        Ok(_) => {
-            let since = Utc::now() - Duration::hours(state.window.as_hours());
-            if let Ok((tokens, cost, providers, models)) = storage.aggregate_since(since) {
-                state.view.tokens = tokens;
-                state.view.cost = cost;
-                state.view.provider_breakdown = providers;
-                state.view.model_breakdown = models;
+    let skipped = [state.needs_reauth.clone(), state.quarantined.clone()].concat();
+    let cfg_owned = cfg.clone();
+    let window = state.window;
+    let svc = MeterService::new(cfg);
+    let task_requests = requests.clone();
+    let handle = tokio::spawn(async move {
+        svc?.fetch_snapshot(&cfg_owned, window, &skipped, task_requests)
+            .await
+    });
+    state.refreshing_since = Some(Instant::now());
+    *refresh = Some(RefreshJob { handle, requests });
+}
+
+/// Loads the dashboard from storage without fetching, for a start with
+/// `startup_refresh` off.
+async fn show_stored_data(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    load_skipped_providers(state, cfg, storage).await;
+    load_dashboard_view(state, cfg, storage).await;
+    if let Ok(count) = storage.call(|s| s.unacked_alert_count()).await {
+        state.unacked_alerts = count;
+    }
+    // A backup failure reported at startup stays visible.
+    if state.status == AppState::default().status {
+        state.status = "showing stored data · press r to refresh".into();
+    }
+}
+
+/// Stops a running refresh. Nothing it fetched is stored, so the dashboard
+/// keeps the stored data; requests it already sent still count towards
+/// `[request_caps]`.
+async fn cancel_refresh(
+    state: &mut AppState,
+    storage: &AsyncStorage,
+    refresh: &mut Option<RefreshJob>,
+) {
+    let Some(job) = refresh.take() else {
+        return;
+    };
+    job.handle.abort();
+    state.refreshing_since = None;
+    let used = job.requests.used();
+    let _ = storage
+        .call(move |s| s.record_requests(Utc::now(), used))
+        .await;
+    state.status = "refresh cancelled; stored data kept".into();
+}
+
+/// Stores a finished refresh, then runs what follows one: alerts, webhooks
+/// and metrics, budget, key and deprecation checks, and reloading the view.
+async fn finish_refresh(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    service: &MeterService,
+    refresh: &mut Option<RefreshJob>,
+) {
+    let Some(job) = refresh.take() else {
+        return;
+    };
+    state.refreshing_since = None;
+    let outcome = match job.handle.await {
+        Ok(Ok(fetched)) => storage.call(move |s| fetched.persist(s)).await,
+        Ok(Err(e)) => Err(e),
+        Err(e) => Err(AppError::Config(format!("background refresh stopped: {e}"))),
+    };
+    match outcome {
+        Ok(snapshot) => {
+            state.clock_skew = snapshot.clock_skew.clone();
+            let resolved = storage
+                .call(|s| s.resolve_alerts("refresh_failed", "dashboard", Utc::now()))
+                .await;
+            if let Ok(resolved) = resolved {
+                if resolved > 0 {
+                    if let Err(e) = service
+                        .notify_alert_resolved(cfg, "refresh_failed", "dashboard")
+                        .await
+                    {
+                        state.status = format!("ok (alert notifier failed: {e})");
+                    }
+                }
+            }
+            if let Err(e) = service.send_refresh_webhook(cfg, &snapshot).await {
+                state.status = format!("ok (refresh webhook failed: {e})");
             }
+            if let Err(e) = service.send_influx_points(cfg, &snapshot).await {
+                state.status = format!("ok (InfluxDB write failed: {e})");
+            }
+            if let Some(sink) = &cfg.metrics {
+                if let Err(e) = metrics::emit(sink, &snapshot, cfg.local_only) {
+                    state.status = format!("ok (metrics emission failed: {e})");
+                }
+            }
+            check_refresh_delta(state, cfg, storage, service, &snapshot.deltas).await;
+            let cfg_owned = cfg.clone();
+            let window = state.window;
+            let checked = storage
+                .call(move |s| {
+                    let now = Utc::now();
+                    Ok((
+                        budgets::check(&cfg_owned, s, now)?,
+                        service::check_key_expiry(&cfg_owned, s, now)?,
+                        deprecations::check(&cfg_owned, s, window, now)?,
+                    ))
+                })
+                .await;
+            match checked {
+                Ok((budget_check, key_check, deprecation_check)) => {
+                    let mut lines = service.enforce_budgets(cfg, budget_check).await;
+                    lines.extend(service.notify_key_expiry(cfg, key_check).await);
+                    lines.extend(service.notify_deprecations(cfg, deprecation_check).await);
+                    if !lines.is_empty() {
+                        state.status = format!("ok ({})", lines.join("; "));
+                    }
+                }
+                Err(e) => state.status = format!("ok (budget/key/deprecation check failed: {e})"),
+            }
+            load_dashboard_view(state, cfg, storage).await;
             state.view.last_refresh = Utc::now().to_rfc3339();
-            state.status = "ok".into();
+            if !state.status.starts_with("ok") {
+                state.status = "ok".into();
+            }
+            if !snapshot.capped.is_empty() {
+                state.status = format!(
+                    "partial: request cap reached before {}",
+                    snapshot.capped.join(", ")
+                );
+            }
+            if !snapshot.timed_out.is_empty() {
+                state.status = format!(
+                    "partial: refresh_timeout_secs reached before {}",
+                    snapshot.timed_out.join(", ")
+                );
+            }
+            if !snapshot.failed.is_empty() {
+                let failed: Vec<String> = snapshot
+                    .failed
+                    .iter()
+                    .map(|(provider, err)| format!("{provider} ({err})"))
+                    .collect();
+                state.status = format!("partial: refresh failed for {}", failed.join(", "));
+            }
         }
         Err(err) => {
             state.status = format!("refresh failed: {err}");
+            let message = format!("Dashboard refresh failed: {err}");
+            let recorded = storage
+                .call(move |s| s.record_alert("refresh_failed", "dashboard", &message, Utc::now()))
+                .await;
+            if let Ok(Some(alert)) = recorded {
+                if let Err(e) = service.notify_alert(cfg, &alert, AlertLevel::Warning).await {
+                    state.status = format!("{} (alert notifier failed: {e})", state.status);
+                }
+            }
         }
     }
+    if let Ok(count) = storage.call(|s| s.unacked_alert_count()).await {
+        state.unacked_alerts = count;
+    }
+    load_skipped_providers(state, cfg, storage).await;
 }
 
-fn provider_list(cfg: &AppConfig) -> Vec<String> {
-    let mut providers: Vec<String> = cfg.provider_settings.keys().cloned().collect();
-    for p in &cfg.enabled_providers {
-        if !providers.iter().any(|x| x.eq_ignore_ascii_case(p)) {
-            providers.push(p.clone());
-        }
+async fn load_skipped_providers(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let cfg_owned = cfg.clone();
+    let loaded = storage
+        .call(move |s| {
+            Ok((
+                s.providers_needing_reauth()?,
+                quarantined_providers(&cfg_owned, s, Utc::now())?,
+            ))
+        })
+        .await;
+    if let Ok((needs_reauth, quarantined)) = loaded {
+        state.needs_reauth = needs_reauth;
+        state.quarantined = quarantined;
     }
-    for p in ["openai", "anthropic"] {
-        if !providers.iter().any(|x| x.eq_ignore_ascii_case(p)) {
-            providers.push(p.to_string());
+}
+
+async fn check_refresh_delta(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    service: &MeterService,
+    deltas: &[CostDelta],
+) {
+    let Some(threshold) = cfg.notifier.refresh_delta_alert_usd else {
+        return;
+    };
+    let total: f64 = deltas.iter().map(|d| d.cost_delta).sum();
+    if total >= threshold {
+        let money = MoneyFormat::from_config(&cfg.display);
+        let message = format!(
+            "A single refresh added {} (threshold {})",
+            money.signed(total, 4),
+            money.money(threshold, 2)
+        );
+        let recorded = storage
+            .call(move |s| s.record_alert("refresh_delta", "dashboard", &message, Utc::now()))
+            .await;
+        if let Ok(Some(alert)) = recorded {
+            if let Err(e) = service.notify_alert(cfg, &alert, AlertLevel::Warning).await {
+                state.status = format!("ok (alert notifier failed: {e})");
+            }
+        }
+    } else if let Ok(resolved) = storage
+        .call(|s| s.resolve_alerts("refresh_delta", "dashboard", Utc::now()))
+        .await
+    {
+        if resolved > 0 {
+            if let Err(e) = service
+                .notify_alert_resolved(cfg, "refresh_delta", "dashboard")
+                .await
+            {
+                state.status = format!("ok (alert notifier failed: {e})");
+            }
         }
     }
-    providers.sort();
-    providers
 }
 
-fn render(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
-    let size = f.area();
-    let compact = state.compact_mode || size.width < 120;
+async fn switch_window(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    window: TimeWindow,
+) {
+    if state.window == window {
+        return;
+    }
+    state.window = window;
+    load_dashboard_view(state, cfg, storage).await;
+    state.status = if state.view.data_as_of.is_some() {
+        "ok (from storage)".into()
+    } else {
+        format!("{} not fetched yet · press r", window.as_label())
+    };
+}
 
-    let root = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),
-            Constraint::Length(5),
-            Constraint::Min(6),
-            Constraint::Length(2),
-        ])
-        .split(size);
+async fn load_dashboard_view(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let cfg_owned = cfg.clone();
+    let window = state.window;
+    let multi_window = state.multi_window;
+    let group_models = state.group_models;
+    let chart_width = state.chart_width();
+    let since = Utc::now() - Duration::hours(window.as_hours());
+    let loaded = storage
+        .call(move |s| {
+            let as_of = window_data_as_of(&cfg_owned, window, s);
+            let provider_as_of = provider_data_as_of(&cfg_owned, window, s);
+            let balances = balances_by_provider(s)?;
+            let delta = s.latest_refresh_delta()?.map(|(_, total)| total);
+            let window_costs = if multi_window {
+                Some(multi_window_costs(s, cfg_owned.edge_buckets, Utc::now())?)
+            } else {
+                None
+            };
+            Ok((
+                as_of,
+                provider_as_of,
+                balances,
+                delta,
+                window_costs,
+                deprecations::evaluate(&cfg_owned, s, window, Utc::now())?,
+                s.aggregate_since(since, cfg_owned.edge_buckets)?,
+                breakdown_details(&cfg_owned, s, group_models, since, Utc::now())?,
+                s.cost_series(since, Utc::now(), chart_width)?,
+            ))
+        })
+        .await;
+    if let Ok((
+        as_of,
+        provider_as_of,
+        balances,
+        delta,
+        window_costs,
+        deprecated,
+        (tokens, cost, providers, models, sources),
+        details,
+        cost_series,
+    )) = loaded
+    {
+        state.view.details = details;
+        state.view.cost_series = cost_series;
+        state.view.window_costs = window_costs;
+        state.view.deprecated = deprecated;
+        state.view.data_as_of = as_of;
+        state.view.provider_as_of = provider_as_of;
+        state.view.balances = balances;
+        state.view.last_delta = delta;
+        state.view.tokens = tokens;
+        state.view.cost = cost;
+        state.view.provider_breakdown =
+            pin_breakdown(providers, &cfg.display.pinned_providers, None);
+        state.view.source_breakdown = sources;
+        let models = if state.group_models {
+            group_model_breakdown(cfg, models)
+        } else {
+            models
+        };
+        state.view.model_breakdown =
+            pin_breakdown(models, &cfg.display.pinned_models, Some(BREAKDOWN_MODELS));
+    }
+    load_provider_health(state, cfg, storage).await;
+}
 
-    let header = Paragraph::new(format!(
-        " llm-meter  ·  {}  ·  {}  ·  {} ",
-        state.window.as_label(),
-        state.status,
-        state.view.last_refresh
-    ))
-    .block(Block::default().borders(Borders::ALL).title(" Session "))
-    .style(Style::default().fg(COLOR_HEADER));
-    f.render_widget(header, root[0]);
+async fn load_provider_health(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let providers = provider_list(cfg);
+    let since = Utc::now() - Duration::hours(state.window.as_hours());
+    let loaded = storage
+        .call(move |s| {
+            providers
+                .into_iter()
+                .map(|p| Ok((p.clone(), s.provider_health(&p, since)?)))
+                .collect::<Result<std::collections::HashMap<_, _>, AppError>>()
+        })
+        .await;
+    if let Ok(health) = loaded {
+        state.provider_health = health;
+    }
+}
 
-    let kpis = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(root[1]);
+async fn toggle_window_row(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    state.multi_window = !state.multi_window;
+    load_dashboard_view(state, cfg, storage).await;
+    state.status = if state.multi_window {
+        "today / 7d / 30d row shown".into()
+    } else {
+        "today / 7d / 30d row hidden".into()
+    };
+}
 
-    let cost = Paragraph::new(format!("${:.4}", state.view.cost))
-        .block(Block::default().borders(Borders::ALL).title(" Cost "))
-        .style(
-            Style::default()
-                .fg(COLOR_ACCENT)
+async fn toggle_model_groups(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    if cfg.model_groups.is_empty() && !state.group_models {
+        state.status = "no [model_groups] configured".into();
+        return;
+    }
+    state.group_models = !state.group_models;
+    load_dashboard_view(state, cfg, storage).await;
+    state.status = if state.group_models {
+        "models grouped by family".into()
+    } else {
+        "raw model ids".into()
+    };
+}
+
+fn open_column_picker(state: &mut AppState) {
+    state.column_draft = state.breakdown_columns.clone();
+    state.column_selected = 0;
+    state.screen = Screen::ColumnPicker;
+    state.action_focused = false;
+}
+
+/// Opens the search screen with its last filters and loads their first page.
+async fn open_search(state: &mut AppState, storage: &AsyncStorage) {
+    state.search.page = 0;
+    state.screen = Screen::Search;
+    state.action_focused = false;
+    run_search(state, storage).await;
+}
+
+fn search_text_field(search: &mut SearchState, field: SearchField) -> Option<&mut String> {
+    match field {
+        SearchField::Table => None,
+        SearchField::Model => Some(&mut search.model),
+        SearchField::Provider => Some(&mut search.provider),
+        SearchField::From => Some(&mut search.from),
+        SearchField::To => Some(&mut search.to),
+    }
+}
+
+/// Start of the UTC day `raw` names, `None` when blank.
+fn search_date(raw: &str, label: &str) -> Result<Option<DateTime<Utc>>, String> {
+    match raw.trim() {
+        "" => Ok(None),
+        raw => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .map(|day| Some(day.and_time(chrono::NaiveTime::MIN).and_utc()))
+            .map_err(|_| format!("{label} must be a YYYY-MM-DD date")),
+    }
+}
+
+/// Loads the current page of rows matching the search filters. `To` is
+/// inclusive: rows on that day match.
+async fn run_search(state: &mut AppState, storage: &AsyncStorage) {
+    let dates = search_date(&state.search.from, "From").and_then(|since| {
+        Ok((
+            since,
+            search_date(&state.search.to, "To")?.map(|to| to + Duration::days(1)),
+        ))
+    });
+    let (since, until) = match dates {
+        Ok(dates) => dates,
+        Err(message) => {
+            state.status = message;
+            return;
+        }
+    };
+    let filter = RecordFilter {
+        model: state.search.model.clone(),
+        provider: state.search.provider.clone(),
+        since,
+        until,
+    };
+    let (table, offset) = (state.search.table, state.search.page * SEARCH_PAGE_SIZE);
+    let loaded = storage
+        .call(move |s| match table {
+            SearchTable::Cost => {
+                let (mut rows, total) = s.search_cost(&filter, offset, SEARCH_PAGE_SIZE)?;
+                s.fx_rates().rows_to_usd(&mut rows);
+                Ok((Vec::new(), rows, total))
+            }
+            SearchTable::Usage => {
+                let (rows, total) = s.search_usage(&filter, offset, SEARCH_PAGE_SIZE)?;
+                Ok((rows, Vec::new(), total))
+            }
+        })
+        .await;
+    match loaded {
+        Ok((usage, cost, total)) => {
+            state.search.usage = usage;
+            state.search.cost = cost;
+            state.search.total = total;
+            state.status = format!("{total} matching {} rows", table.label());
+        }
+        Err(e) => show_error(state, format!("Search failed: {e}")),
+    }
+}
+
+/// Rows the SQL console keeps from one query.
+const CONSOLE_MAX_ROWS: usize = 200;
+
+async fn run_console_query(state: &mut AppState, storage: &AsyncStorage) {
+    let sql = state.sql_input.clone();
+    if sql.trim().is_empty() {
+        return;
+    }
+    match storage
+        .call(move |s| s.run_query(&sql, Some(CONSOLE_MAX_ROWS)))
+        .await
+    {
+        Ok(result) => {
+            state.status = format!("{} rows", result.rows.len());
+            state.sql_result = Some(result);
+        }
+        Err(e) => {
+            state.status = format!("query failed: {e}");
+            state.sql_result = None;
+        }
+    }
+}
+
+/// Pins `name` to the top of the model (`model`) or provider panel, or unpins
+/// it, and saves the pins to the config.
+async fn toggle_pin(
+    state: &mut AppState,
+    cfg: &mut AppConfig,
+    storage: &AsyncStorage,
+    model: bool,
+    name: String,
+) {
+    let pinned = if model {
+        &mut cfg.display.pinned_models
+    } else {
+        &mut cfg.display.pinned_providers
+    };
+    let status = if pinned.contains(&name) {
+        pinned.retain(|p| *p != name);
+        format!("unpinned {name}")
+    } else {
+        pinned.push(name.clone());
+        format!("pinned {name}")
+    };
+    load_dashboard_view(state, cfg, storage).await;
+    match save_config(cfg) {
+        Ok(()) => state.status = status,
+        Err(e) => show_error(state, format!("Failed to save config: {e}")),
+    }
+}
+
+fn toggle_source_breakdown(state: &mut AppState) {
+    state.by_source = !state.by_source;
+    state.status = if state.by_source {
+        "cost by source".into()
+    } else {
+        "cost by provider".into()
+    };
+}
+
+async fn toggle_chart_granularity(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let width = match state.chart_width() {
+        Granularity::Day => Granularity::Hour,
+        Granularity::Minute | Granularity::Hour => Granularity::Day,
+    };
+    state.chart_granularity = Some(width);
+    load_dashboard_view(state, cfg, storage).await;
+    state.status = match width {
+        Granularity::Day => "cost chart by day".into(),
+        _ => "cost chart by hour".into(),
+    };
+}
+
+fn toggle_share_gauges(state: &mut AppState) {
+    state.share_gauges = !state.share_gauges;
+    state.status = if state.share_gauges {
+        "share-of-spend gauges".into()
+    } else {
+        "cost tables".into()
+    };
+}
+
+async fn reload_alerts(state: &mut AppState, storage: &AsyncStorage) {
+    match storage.call(|s| s.list_alerts(ALERT_LIST_LIMIT)).await {
+        Ok(alerts) => {
+            state.alerts = alerts;
+            if state.alert_selected >= state.alerts.len() {
+                state.alert_selected = state.alerts.len().saturating_sub(1);
+            }
+        }
+        Err(e) => state.status = format!("Failed to load alerts: {e}"),
+    }
+    if let Ok(count) = storage.call(|s| s.unacked_alert_count()).await {
+        state.unacked_alerts = count;
+    }
+}
+
+async fn set_selected_alert_state(
+    state: &mut AppState,
+    storage: &AsyncStorage,
+    target: AlertState,
+) {
+    let Some(alert) = state.alerts.get(state.alert_selected) else {
+        return;
+    };
+    let id = alert.id;
+    match storage
+        .call(move |s| s.set_alert_state(id, target, Utc::now()))
+        .await
+    {
+        Ok(_) => state.status = format!("Alert #{id} marked {}", target.as_str()),
+        Err(e) => show_error(state, format!("Failed to update alert: {e}")),
+    }
+    reload_alerts(state, storage).await;
+}
+
+async fn open_simulator(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let since = Utc::now() - Duration::hours(state.window.as_hours());
+    match storage.call(move |s| s.usage_by_model_since(since)).await {
+        Ok(rows) => {
+            state.simulator = SimulatorState {
+                rows: rows
+                    .into_iter()
+                    .map(|r| {
+                        simulator_row(
+                            cfg,
+                            r.provider,
+                            r.model,
+                            r.input_tokens,
+                            r.output_tokens,
+                            r.cost,
+                            r.avg_latency_ms,
+                        )
+                    })
+                    .collect(),
+                targets: pricing_catalog(&cfg.pricing_overrides),
+                ..SimulatorState::default()
+            };
+            state.screen = Screen::Simulator;
+            state.action_focused = false;
+        }
+        Err(e) => show_error(state, format!("Failed to load usage for simulator: {e}")),
+    }
+}
+
+/// Opens the detail screen for the selected simulator row: its rate limit and
+/// peak tokens per minute over the current window.
+async fn open_model_detail(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let Some(row) = state.simulator.rows.get(state.simulator.selected) else {
+        return;
+    };
+    let mut detail = ModelDetail {
+        provider: row.provider.clone(),
+        model: row.model.clone(),
+        input_tokens: row.input_tokens,
+        output_tokens: row.output_tokens,
+        cost: row.actual_cost,
+        avg_latency_ms: row.avg_latency_ms,
+        ..ModelDetail::default()
+    };
+    let (provider, model) = (detail.provider.clone(), detail.model.clone());
+    let since = Utc::now() - Duration::hours(state.window.as_hours());
+    let granularity = cfg.usage_granularity;
+    let loaded = storage
+        .call(move |s| {
+            Ok((
+                s.rate_limit(&provider, &model)?,
+                s.peak_tokens_per_minute(&provider, &model, since, granularity)?,
+            ))
+        })
+        .await;
+    match loaded {
+        Ok((limit, peak)) => {
+            detail.limit = limit;
+            detail.peak = peak;
+            state.model_detail = detail;
+            state.screen = Screen::ModelDetail;
+        }
+        Err(e) => show_error(state, format!("Failed to load model details: {e}")),
+    }
+}
+
+fn palette_entries(cfg: &AppConfig, models: &[(String, f64)]) -> Vec<PaletteEntry> {
+    let entry = |label: String, command| PaletteEntry { label, command };
+    let mut entries = vec![entry("refresh".into(), PaletteCommand::Refresh)];
+    for window in [
+        TimeWindow::OneDay,
+        TimeWindow::SevenDays,
+        TimeWindow::ThirtyDays,
+    ] {
+        entries.push(entry(
+            format!("window {}", window.as_label()),
+            PaletteCommand::Window(window),
+        ));
+    }
+    for provider in provider_list(cfg) {
+        entries.push(entry(
+            format!("open provider {provider}"),
+            PaletteCommand::OpenProvider(provider),
+        ));
+    }
+    let pin_label = |pinned: &[String], name: &str| {
+        if pinned.iter().any(|p| p == name) {
+            "unpin"
+        } else {
+            "pin"
+        }
+    };
+    for provider in provider_list(cfg) {
+        entries.push(entry(
+            format!(
+                "{} {provider} provider",
+                pin_label(&cfg.display.pinned_providers, &provider)
+            ),
+            PaletteCommand::TogglePinnedProvider(provider),
+        ));
+    }
+    for (model, _) in models {
+        entries.push(entry(
+            format!(
+                "{} {model} model",
+                pin_label(&cfg.display.pinned_models, model)
+            ),
+            PaletteCommand::TogglePinnedModel(model.clone()),
+        ));
+    }
+    for format in ["csv", "json", "jsonl", "xlsx"] {
+        entries.push(entry(
+            format!("export {format}"),
+            PaletteCommand::Export(format),
+        ));
+    }
+    entries.extend([
+        entry("manage providers".into(), PaletteCommand::ManageProviders),
+        entry("budgets".into(), PaletteCommand::Budgets),
+        entry("alerts".into(), PaletteCommand::Alerts),
+        entry("simulator".into(), PaletteCommand::Simulator),
+        entry("toggle theme".into(), PaletteCommand::ToggleTheme),
+        entry("toggle compact".into(), PaletteCommand::ToggleCompact),
+        entry("toggle window row".into(), PaletteCommand::ToggleWindowRow),
+        entry(
+            "toggle share gauges".into(),
+            PaletteCommand::ToggleShareGauges,
+        ),
+        entry(
+            "toggle chart hourly / daily".into(),
+            PaletteCommand::ToggleChartGranularity,
+        ),
+        entry(
+            "toggle model groups".into(),
+            PaletteCommand::ToggleModelGroups,
+        ),
+        entry(
+            "toggle cost by source".into(),
+            PaletteCommand::ToggleSourceBreakdown,
+        ),
+        entry("choose columns".into(), PaletteCommand::ChooseColumns),
+        entry("search records".into(), PaletteCommand::Search),
+        entry("sql console".into(), PaletteCommand::SqlConsole),
+        entry("quit".into(), PaletteCommand::Quit),
+    ]);
+    entries
+}
+
+async fn run_palette_command(
+    state: &mut AppState,
+    cfg: &mut AppConfig,
+    storage: &AsyncStorage,
+    refresh: &mut Option<RefreshJob>,
+    command: PaletteCommand,
+) {
+    match command {
+        PaletteCommand::Refresh => start_refresh(state, cfg, storage, refresh, true).await,
+        PaletteCommand::Window(window) => switch_window(state, cfg, storage, window).await,
+        PaletteCommand::OpenProvider(provider) => open_provider_edit(state, cfg, &provider),
+        PaletteCommand::ManageProviders => state.screen = Screen::ProviderManager,
+        PaletteCommand::Export(format) => export_from_tui(state, cfg, storage, format).await,
+        PaletteCommand::ToggleTheme => {
+            state.accessible = !state.accessible;
+            state.status = if state.accessible {
+                "no-color theme".into()
+            } else {
+                "color theme".into()
+            };
+        }
+        PaletteCommand::ToggleCompact => {
+            state.compact_mode = !state.compact_mode;
+            state.status = if state.compact_mode {
+                "compact mode enabled".into()
+            } else {
+                "compact mode disabled".into()
+            };
+        }
+        PaletteCommand::ToggleWindowRow => toggle_window_row(state, cfg, storage).await,
+        PaletteCommand::ToggleShareGauges => toggle_share_gauges(state),
+        PaletteCommand::ToggleChartGranularity => {
+            toggle_chart_granularity(state, cfg, storage).await
+        }
+        PaletteCommand::ToggleModelGroups => toggle_model_groups(state, cfg, storage).await,
+        PaletteCommand::ToggleSourceBreakdown => toggle_source_breakdown(state),
+        PaletteCommand::ChooseColumns => open_column_picker(state),
+        PaletteCommand::Search => open_search(state, storage).await,
+        PaletteCommand::SqlConsole => state.screen = Screen::SqlConsole,
+        PaletteCommand::TogglePinnedProvider(provider) => {
+            toggle_pin(state, cfg, storage, false, provider).await;
+        }
+        PaletteCommand::TogglePinnedModel(model) => {
+            toggle_pin(state, cfg, storage, true, model).await;
+        }
+        PaletteCommand::Simulator => open_simulator(state, cfg, storage).await,
+        PaletteCommand::Alerts => {
+            reload_alerts(state, storage).await;
+            state.alert_selected = 0;
+            state.screen = Screen::Alerts;
+        }
+        PaletteCommand::Budgets => open_budgets(state, cfg, storage).await,
+        PaletteCommand::Quit => {
+            state.previous_screen = Screen::Dashboard;
+            state.screen = Screen::Confirm(ConfirmAction::Quit);
+            state.confirm_selected = 0;
+        }
+    }
+}
+
+/// Writes all stored cost rows, in the display currency, to
+/// `llm-costs-<date>.<ext>` in the working directory.
+async fn export_from_tui(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    format: &str,
+) {
+    let money = MoneyFormat::from_config(&cfg.display);
+    let written = async {
+        let format = ExportFormat::parse(format)?;
+        let json = storage.call(|s| s.export_cost_json()).await?;
+        let mut rows: Vec<CostRecord> = serde_json::from_str(&json)?;
+        let fx = storage.call(|s| Ok(s.fx_rates())).await?;
+        export::convert_cost_rows(&mut rows, &fx, &money);
+        let body = export::render_cost(format, &rows, CsvOptions::default(), money.currency())?;
+        let path = format!(
+            "llm-costs-{}.{}",
+            Utc::now().format("%Y-%m-%d"),
+            format.extension()
+        );
+        std::fs::write(&path, body)?;
+        Ok::<_, AppError>((path, rows.len()))
+    }
+    .await;
+    match written {
+        Ok((path, count)) => state.status = format!("Exported {count} cost rows to {path}"),
+        Err(e) => show_error(state, format!("Export failed: {e}")),
+    }
+}
+
+/// Writes the dashboard's provider and model tables, as currently shown, to
+/// `view-<window>-<timestamp>.csv` in `dir`. Costs are in the display
+/// currency; `as_of` is only set for provider rows.
+fn save_view_csv(
+    state: &AppState,
+    money: &MoneyFormat,
+    dir: &Path,
+    now: DateTime<Utc>,
+) -> Result<PathBuf, AppError> {
+    let window = state.window.as_label();
+    let path = dir.join(format!("view-{window}-{}.csv", now.format("%Y%m%d-%H%M%S")));
+    let mut writer = csv::Writer::from_path(&path)?;
+    writer.write_record(["table", "name", "cost", "currency", "window", "as_of"])?;
+    let providers = state.view.provider_breakdown.iter().map(|(name, cost)| {
+        let as_of = state
+            .view
+            .provider_as_of
+            .get(name)
+            .copied()
+            .flatten()
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default();
+        ("provider", name, *cost, as_of)
+    });
+    let models = state
+        .view
+        .model_breakdown
+        .iter()
+        .map(|(name, cost)| ("model", name, *cost, String::new()));
+    let sources = state
+        .view
+        .source_breakdown
+        .iter()
+        .map(|(name, cost)| ("source", name, *cost, String::new()));
+    for (table, name, cost, as_of) in providers.chain(models).chain(sources) {
+        writer.write_record([
+            table,
+            name.as_str(),
+            &format!("{:.4}", money.convert(cost)),
+            money.currency(),
+            window,
+            &as_of,
+        ])?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+async fn open_budgets(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let cfg_owned = cfg.clone();
+    let loaded = storage
+        .call(move |s| {
+            let now = Utc::now();
+            Ok(cfg_owned
+                .budgets
+                .iter()
+                .map(|b| {
+                    budgets::status(b, &cfg_owned.tags, s, now)
+                        .ok()
+                        .map(|status| status.spent_usd)
+                })
+                .collect::<Vec<_>>())
+        })
+        .await;
+    state.budget_spend = loaded.unwrap_or_default();
+    state.budget_selected = state
+        .budget_selected
+        .min(cfg.budgets.len().saturating_sub(1));
+    state.screen = Screen::Budgets;
+    state.action_focused = false;
+}
+
+async fn open_budget_form(
+    state: &mut AppState,
+    cfg: &AppConfig,
+    storage: &AsyncStorage,
+    mode: BudgetFormMode,
+) {
+    state.budget_draft = match &mode {
+        BudgetFormMode::Add => BudgetDraft::default(),
+        BudgetFormMode::Edit { index } => match cfg.budgets.get(*index) {
+            Some(budget) => BudgetDraft::from_budget(budget),
+            None => return,
+        },
+    };
+    load_budget_targets(state, cfg, storage).await;
+    reload_draft_spend(state, cfg, storage).await;
+    state.screen = Screen::BudgetForm(mode);
+}
+
+/// Fills the target picker for the draft's scope kind.
+async fn load_budget_targets(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    state.budget_draft.targets = match state.budget_draft.scope {
+        BudgetScopeKind::Global => Vec::new(),
+        BudgetScopeKind::Provider => provider_list(cfg),
+        BudgetScopeKind::Model => storage.call(|s| s.known_models()).await.unwrap_or_default(),
+        BudgetScopeKind::Tag => cfg.tags.keys().cloned().collect(),
+    };
+}
+
+/// Recomputes the draft's current-period spend; `None` until its scope is valid.
+async fn reload_draft_spend(state: &mut AppState, cfg: &AppConfig, storage: &AsyncStorage) {
+    let draft = &state.budget_draft;
+    let Ok(scope) = draft.scope() else {
+        state.budget_draft.spent = None;
+        return;
+    };
+    let budget = Budget {
+        name: String::new(),
+        scope,
+        amount_usd: 0.0,
+        period: draft.period,
+        on_exceeded: None,
+    };
+    let tags = cfg.tags.clone();
+    state.budget_draft.spent = storage
+        .call(move |s| budgets::status(&budget, &tags, s, Utc::now()))
+        .await
+        .ok()
+        .map(|status| status.spent_usd);
+}
+
+fn budget_text_field(draft: &mut BudgetDraft, field: BudgetFormField) -> Option<&mut String> {
+    match field {
+        BudgetFormField::Name => Some(&mut draft.name),
+        BudgetFormField::Target if draft.scope != BudgetScopeKind::Global => {
+            Some(&mut draft.target)
+        }
+        BudgetFormField::Amount => Some(&mut draft.amount),
+        BudgetFormField::OnExceeded => Some(&mut draft.on_exceeded),
+        _ => None,
+    }
+}
+
+/// Validates the draft and writes it into the config; false (with an error
+/// dialog) when it could not be saved.
+fn submit_budget_form(state: &mut AppState, cfg: &mut AppConfig, mode: BudgetFormMode) -> bool {
+    let budget = match state.budget_draft.to_budget() {
+        Ok(budget) => budget,
+        Err(message) => {
+            show_error(state, message);
+            return false;
+        }
+    };
+    let editing = match mode {
+        BudgetFormMode::Add => None,
+        BudgetFormMode::Edit { index } => Some(index),
+    };
+    if cfg
+        .budgets
+        .iter()
+        .enumerate()
+        .any(|(idx, b)| Some(idx) != editing && b.name == budget.name)
+    {
+        show_error(
+            state,
+            format!("A budget named '{}' already exists.", budget.name),
+        );
+        return false;
+    }
+    let name = budget.name.clone();
+    match editing {
+        Some(index) if index < cfg.budgets.len() => cfg.budgets[index] = budget,
+        _ => {
+            state.budget_selected = cfg.budgets.len();
+            cfg.budgets.push(budget);
+        }
+    }
+    if let Err(e) = save_config(cfg) {
+        show_error(state, format!("Failed to save config: {e}"));
+        return false;
+    }
+    state.status = format!("Budget '{name}' saved");
+    true
+}
+
+fn simulator_row(
+    cfg: &AppConfig,
+    provider: String,
+    model: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    actual_cost: f64,
+    avg_latency_ms: Option<f64>,
+) -> SimulatorRow {
+    let pricing = resolve_pricing(&provider, &model, &cfg.pricing_overrides);
+    let input_per_1m = pricing.as_ref().map_or(0.0, |p| p.input_per_1m);
+    let output_per_1m = pricing.as_ref().map_or(0.0, |p| p.output_per_1m);
+    SimulatorRow {
+        provider,
+        model,
+        input_tokens,
+        output_tokens,
+        actual_cost,
+        input_per_1m,
+        output_per_1m,
+        base_input_per_1m: input_per_1m,
+        base_output_per_1m: output_per_1m,
+        edited: false,
+        mapped_to: None,
+        avg_latency_ms,
+    }
+}
+
+fn provider_list(cfg: &AppConfig) -> Vec<String> {
+    let mut providers: Vec<String> = cfg.provider_settings.keys().cloned().collect();
+    for p in &cfg.enabled_providers {
+        if !providers.iter().any(|x| x.eq_ignore_ascii_case(p)) {
+            providers.push(p.clone());
+        }
+    }
+    for p in ["openai", "anthropic"] {
+        if !providers.iter().any(|x| x.eq_ignore_ascii_case(p)) {
+            providers.push(p.to_string());
+        }
+    }
+    providers.sort();
+    providers
+}
+
+fn data_freshness_span(cfg: &AppConfig, state: &AppState) -> Span<'static> {
+    let Some(as_of) = state.view.data_as_of else {
+        return Span::styled(
+            format!(" ·  {} data: none ", state.window.as_label()),
+            Style::default().fg(COLOR_WARN),
+        );
+    };
+    let label = format!(
+        " ·  {} data as of {} ",
+        state.window.as_label(),
+        as_of.format("%H:%M:%S")
+    );
+    if is_stale(cfg, as_of, Utc::now()) {
+        Span::styled(format!("{label}(stale) "), Style::default().fg(COLOR_WARN))
+    } else {
+        Span::raw(label)
+    }
+}
+
+/// `HH:MM` for today, `MM-DD HH:MM` for older data.
+fn as_of_label(as_of: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    if as_of.date_naive() == now.date_naive() {
+        as_of.format("%H:%M").to_string()
+    } else {
+        as_of.format("%m-%d %H:%M").to_string()
+    }
+}
+
+/// Panel title suffix and style for data last fetched at `as_of`: fresh data
+/// keeps `fresh`, stale or missing data is muted and says how old it is.
+fn panel_freshness(cfg: &AppConfig, as_of: Option<DateTime<Utc>>, fresh: Style) -> (String, Style) {
+    let now = Utc::now();
+    match as_of {
+        Some(at) if !is_stale(cfg, at, now) => (String::new(), fresh),
+        Some(at) => (
+            format!("· stale, as of {} ", as_of_label(at, now)),
+            Style::default().fg(COLOR_MUTED),
+        ),
+        None => ("· no data ".into(), Style::default().fg(COLOR_MUTED)),
+    }
+}
+
+pub(crate) fn render(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
+    let money = MoneyFormat::from_config(&cfg.display);
+    let size = f.area();
+    let compact = state.compact_mode || size.width < 120;
+
+    let size = if state.needs_reauth.is_empty() {
+        size
+    } else {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(size);
+        let banner = Paragraph::new(format!(
+            " ! Key rejected (401) for {}: refreshes paused · press u to update key ",
+            state.needs_reauth.join(", ")
+        ))
+        .style(
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
                 .add_modifier(Modifier::BOLD),
         );
+        f.render_widget(banner, split[0]);
+        split[1]
+    };
+
+    let size = if state.view.deprecated.is_empty() {
+        size
+    } else {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(size);
+        let models: Vec<String> = state
+            .view
+            .deprecated
+            .iter()
+            .map(|d| match &d.deprecation.replacement {
+                Some(to) => format!("{} ({}, use {to})", d.model, d.when()),
+                None => format!("{} ({})", d.model, d.when()),
+            })
+            .collect();
+        let banner = Paragraph::new(format!(
+            " ! Spend on deprecated models: {} ",
+            models.join(", ")
+        ))
+        .style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+        f.render_widget(banner, split[0]);
+        split[1]
+    };
+
+    let window_costs = state.view.window_costs.filter(|_| state.multi_window);
+    let mut constraints = vec![Constraint::Length(3)];
+    if window_costs.is_some() {
+        constraints.push(Constraint::Length(3));
+    }
+    constraints.extend([
+        Constraint::Length(5),
+        Constraint::Length(6),
+        Constraint::Min(6),
+        Constraint::Length(2),
+    ]);
+    let mut root = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(size)
+        .to_vec();
+    if let Some(costs) = window_costs {
+        render_window_row(f, root.remove(1), state, &money, costs);
+    }
+
+    let mut header_spans = vec![Span::raw(format!(
+        " llm-meter  ·  {}  ·  {}  ·  {} ",
+        state.window.as_label(),
+        state.status,
+        state.view.last_refresh
+    ))];
+    header_spans.push(data_freshness_span(cfg, state));
+    if let Some(delta) = state.view.last_delta {
+        header_spans.push(Span::raw(format!(
+            " ·  since last refresh: {} ",
+            money.signed(delta, 4)
+        )));
+    }
+    let expiring = cfg.expiring_keys(Utc::now().date_naive());
+    if !expiring.is_empty() {
+        let keys: Vec<String> = expiring
+            .iter()
+            .map(|(provider, days)| format!("{provider} {}", expiry_badge(*days)))
+            .collect();
+        header_spans.push(Span::styled(
+            format!(" ·  key expiry: {} ", keys.join(", ")),
+            Style::default().fg(COLOR_WARN).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(skew) = &state.clock_skew {
+        header_spans.push(Span::styled(
+            format!(" ·  clock skew: {} ", skew.describe()),
+            Style::default().fg(COLOR_WARN).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if state.unacked_alerts > 0 {
+        header_spans.push(Span::styled(
+            format!(" ·  {} unacked alert(s) [h] ", state.unacked_alerts),
+            Style::default().fg(COLOR_WARN).add_modifier(Modifier::BOLD),
+        ));
+    }
+    let header = Paragraph::new(Line::from(header_spans))
+        .block(bordered(state.accessible).title(" Session "))
+        .style(Style::default().fg(COLOR_HEADER));
+    f.render_widget(header, root[0]);
+
+    let kpis = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(root[1]);
+
+    let (cost_note, cost_style) = panel_freshness(
+        cfg,
+        state.view.data_as_of,
+        Style::default()
+            .fg(COLOR_ACCENT)
+            .add_modifier(Modifier::BOLD),
+    );
+    let (tokens_note, tokens_style) = panel_freshness(
+        cfg,
+        state.view.data_as_of,
+        Style::default().fg(COLOR_INFO).add_modifier(Modifier::BOLD),
+    );
+    let cost = Paragraph::new(money.money(state.view.cost, 4))
+        .block(bordered(state.accessible).title(format!(" Cost {cost_note}")))
+        .style(cost_style);
     let tokens = Paragraph::new(format!("{}", state.view.tokens))
-        .block(Block::default().borders(Borders::ALL).title(" Tokens "))
-        .style(Style::default().fg(COLOR_INFO).add_modifier(Modifier::BOLD));
+        .block(bordered(state.accessible).title(format!(" Tokens {tokens_note}")))
+        .style(tokens_style);
 
     f.render_widget(cost, kpis[0]);
     f.render_widget(tokens, kpis[1]);
+    render_cost_chart(f, root[2], state, &money);
 
     let body = Layout::default()
         .direction(Direction::Horizontal)
@@ -1085,92 +3161,647 @@ fn render(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
                 Constraint::Percentage(28),
             ]
         })
-        .split(root[2]);
+        .split(root[3]);
 
-    let provider_rows = state
+    let (provider_title, provider_breakdown) = match (compact, state.by_source) {
+        (true, false) => (" Providers ", &state.view.provider_breakdown),
+        (false, false) => (" Cost By Provider ", &state.view.provider_breakdown),
+        (true, true) => (" Sources ", &state.view.source_breakdown),
+        (false, true) => (" Cost By Source ", &state.view.source_breakdown),
+    };
+    let (models_note, models_style) = panel_freshness(cfg, state.view.data_as_of, Style::default());
+    let models_title = format!(
+        "{}{models_note}",
+        match (compact, state.group_models) {
+            (true, _) => " Models ",
+            (false, true) => " Top Model Families ",
+            (false, false) => " Top Models ",
+        }
+    );
+    if state.share_gauges {
+        let labelled: Vec<(String, f64)> = if state.by_source {
+            provider_breakdown.clone()
+        } else {
+            provider_breakdown
+                .iter()
+                .map(|(p, c)| (cfg.provider_label(p), *c))
+                .collect()
+        };
+        render_share_gauges(
+            f,
+            body[0],
+            provider_title,
+            &labelled,
+            Style::default(),
+            state.accessible,
+        );
+        render_share_gauges(
+            f,
+            body[1],
+            &models_title,
+            &state.view.model_breakdown,
+            models_style,
+            state.accessible,
+        );
+    } else if state.by_source {
+        let source_rows = provider_breakdown
+            .iter()
+            .map(|(s, c)| Row::new(vec![Cell::from(s.clone()), Cell::from(money.money(*c, 4))]))
+            .collect::<Vec<_>>();
+        let source_table = Table::new(
+            source_rows,
+            [Constraint::Percentage(50), Constraint::Percentage(50)],
+        )
+        .header(
+            Row::new(vec!["Source", "Cost"]).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        )
+        .block(bordered(state.accessible).title(provider_title));
+        f.render_widget(source_table, body[0]);
+        render_model_table(
+            f,
+            body[1],
+            state,
+            &money,
+            &models_title,
+            models_style,
+            &cfg.display.pinned_models,
+        );
+    } else {
+        let now = Utc::now();
+        let columns = &state.breakdown_columns;
+        let total: f64 = provider_breakdown.iter().map(|(_, c)| c).sum();
+        let show_credit = provider_breakdown
+            .iter()
+            .any(|(p, _)| state.view.balances.contains_key(p));
+        let provider_rows = provider_breakdown
+            .iter()
+            .map(|(p, c)| {
+                let (as_of, stale) = match state.view.provider_as_of.get(p) {
+                    Some(Some(at)) if is_stale(cfg, *at, now) => {
+                        (format!("{} !", as_of_label(*at, now)), true)
+                    }
+                    Some(Some(at)) => (as_of_label(*at, now), false),
+                    Some(None) => ("never".into(), true),
+                    None => ("-".into(), false),
+                };
+                let mut cells = vec![breakdown_name(
+                    p,
+                    &cfg.provider_label(p),
+                    &cfg.display.pinned_providers,
+                )];
+                cells.extend(breakdown_cells(
+                    columns,
+                    *c,
+                    total,
+                    state.view.details.providers.get(p),
+                    &money,
+                ));
+                if show_credit {
+                    cells.push(Cell::from(
+                        state
+                            .view
+                            .balances
+                            .get(p)
+                            .map_or_else(|| "-".into(), |b| balance_label(b, &money)),
+                    ));
+                }
+                cells.push(Cell::from(as_of));
+                let row = Row::new(cells);
+                if stale {
+                    row.style(Style::default().fg(COLOR_MUTED))
+                } else {
+                    row
+                }
+            })
+            .collect::<Vec<_>>();
+        let (mut name_width, mut as_of_width) = if columns.len() > 1 {
+            (24, 20)
+        } else {
+            (34, 36)
+        };
+        let credit_width = if show_credit {
+            name_width -= 10;
+            as_of_width -= 6;
+            24
+        } else {
+            0
+        };
+        let mut widths = breakdown_widths(
+            name_width,
+            100 - name_width - as_of_width - credit_width,
+            columns.len(),
+        );
+        let mut header = breakdown_header("Provider", columns);
+        if show_credit {
+            widths.push(Constraint::Percentage(credit_width));
+            header.push("Credit");
+        }
+        widths.push(Constraint::Percentage(as_of_width));
+        header.push("As of");
+        let provider_table = Table::new(provider_rows, widths)
+            .header(
+                Row::new(header).style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            )
+            .block(bordered(state.accessible).title(provider_title));
+        f.render_widget(provider_table, body[0]);
+        render_model_table(
+            f,
+            body[1],
+            state,
+            &money,
+            &models_title,
+            models_style,
+            &cfg.display.pinned_models,
+        );
+    }
+
+    render_action_panel(f, body[2], state, compact);
+
+    let footer = Paragraph::new(footer_text(state))
+        .block(bordered(state.accessible))
+        .style(Style::default().fg(COLOR_MUTED));
+    f.render_widget(footer, root[4]);
+
+    match &state.screen {
+        Screen::Dashboard if state.refreshing_since.is_some() => {
+            render_refresh_progress(f, cfg, state)
+        }
+        Screen::Dashboard => {}
+        Screen::Simulator => render_simulator(f, state, &money),
+        Screen::ModelDetail => render_model_detail(f, state, &money),
+        Screen::Alerts => render_alerts(f, state),
+        Screen::ProviderManager => render_provider_manager(f, cfg, state),
+        Screen::ProviderForm(mode) => render_provider_form(f, state, mode),
+        Screen::RenameProvider { provider } => render_rename(f, state, provider),
+        Screen::Budgets => render_budgets(f, cfg, state),
+        Screen::CommandPalette => render_palette(f, state),
+        Screen::BudgetForm(mode) => render_budget_form(f, state, mode),
+        Screen::ColumnPicker => render_column_picker(f, state),
+        Screen::Search => render_search(f, state, &money),
+        Screen::SqlConsole => render_sql_console(f, state),
+        Screen::Confirm(action) => render_confirm(f, state, action),
+        Screen::ErrorDialog => render_error(f, state),
+        Screen::InfoDialog => render_info(f, state),
+    }
+
+    if state.accessible {
+        strip_colors(f.buffer_mut());
+    }
+}
+
+/// Spend per chart bucket across the window, newest at the right; the
+/// oldest buckets are cut when there are more than columns.
+fn render_cost_chart(f: &mut ratatui::Frame, area: Rect, state: &AppState, money: &MoneyFormat) {
+    let width = state.chart_width();
+    let series = &state.view.cost_series;
+    let shown = &series[series
+        .len()
+        .saturating_sub(area.width.saturating_sub(2) as usize)..];
+    let (unit, label) = match width {
+        Granularity::Minute => ("minute", "%m-%d %H:%M"),
+        Granularity::Hour => ("hour", "%m-%d %H:00"),
+        Granularity::Day => ("day", "%m-%d"),
+    };
+    let peak = shown
+        .iter()
+        .filter(|(_, cost)| *cost > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    let title = match peak {
+        Some((at, cost)) => format!(
+            " Cost per {unit} · peak {} at {} [t] ",
+            money.money(*cost, 4),
+            at.format(label)
+        ),
+        None => format!(" Cost per {unit} · no spend in this window [t] "),
+    };
+    // Bars are integers; hundredths of a cent keep small buckets visible.
+    let bars: Vec<u64> = shown
+        .iter()
+        .map(|(_, cost)| (cost.max(0.0) * 10_000.0).round() as u64)
+        .collect();
+    let chart = Sparkline::default()
+        .block(bordered(state.accessible).title(title))
+        .data(&bars)
+        .style(Style::default().fg(COLOR_ACCENT));
+    f.render_widget(chart, area);
+}
+
+fn render_model_table(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    state: &AppState,
+    money: &MoneyFormat,
+    title: &str,
+    style: Style,
+    pinned: &[String],
+) {
+    let columns = &state.breakdown_columns;
+    let total: f64 = state.view.model_breakdown.iter().map(|(_, c)| c).sum();
+    let model_rows = state
         .view
-        .provider_breakdown
+        .model_breakdown
         .iter()
-        .map(|(p, c)| {
-            Row::new(vec![
-                Cell::from(p.clone()),
-                Cell::from(format!("${:.4}", c)),
-            ])
+        .map(|(m, c)| {
+            let mut cells = vec![breakdown_name(m, m, pinned)];
+            cells.extend(breakdown_cells(
+                columns,
+                *c,
+                total,
+                state.view.details.models.get(m),
+                money,
+            ));
+            Row::new(cells)
         })
         .collect::<Vec<_>>();
-    let provider_table = Table::new(
-        provider_rows,
-        [Constraint::Percentage(70), Constraint::Percentage(30)],
+    let name_width = if columns.len() > 1 { 40 } else { 70 };
+    let model_table = Table::new(
+        model_rows,
+        breakdown_widths(name_width, 100 - name_width, columns.len()),
     )
     .header(
-        Row::new(vec!["Provider", "Cost"]).style(
+        Row::new(breakdown_header("Model", columns)).style(
             Style::default()
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
         ),
     )
-    .block(Block::default().borders(Borders::ALL).title(if compact {
-        " Providers "
+    .style(style)
+    .block(bordered(state.accessible).title(title.to_string()));
+    f.render_widget(model_table, area);
+}
+
+/// Name cell of a breakdown row, marked with `*` when it is pinned.
+/// A balance in the display currency when it is in USD, otherwise as the
+/// provider reports it, e.g. `110.00 CNY`.
+fn balance_label(balance: &AccountBalance, money: &MoneyFormat) -> String {
+    if balance.currency.eq_ignore_ascii_case("USD") {
+        money.money(balance.amount, 2)
     } else {
-        " Cost By Provider "
-    }));
-    f.render_widget(provider_table, body[0]);
+        format!("{:.2} {}", balance.amount, balance.currency)
+    }
+}
 
-    let model_rows = state
-        .view
-        .model_breakdown
+fn breakdown_name(name: &str, label: &str, pinned: &[String]) -> Cell<'static> {
+    if pinned.iter().any(|p| p == name) {
+        Cell::from(format!("* {label}")).style(Style::default().add_modifier(Modifier::BOLD))
+    } else {
+        Cell::from(label.to_string())
+    }
+}
+
+/// Cells for the breakdown `columns` of a row costing `cost`, out of `total`
+/// for the whole panel.
+fn breakdown_cells(
+    columns: &[BreakdownColumn],
+    cost: f64,
+    total: f64,
+    detail: Option<&BreakdownDetail>,
+    money: &MoneyFormat,
+) -> Vec<Cell<'static>> {
+    let detail = detail.copied().unwrap_or_default();
+    columns
         .iter()
-        .map(|(m, c)| {
-            Row::new(vec![
-                Cell::from(m.clone()),
-                Cell::from(format!("${:.4}", c)),
-            ])
+        .map(|column| {
+            Cell::from(match column {
+                BreakdownColumn::Cost => money.money(cost, 4),
+                BreakdownColumn::Tokens => (detail.input_tokens + detail.output_tokens).to_string(),
+                BreakdownColumn::InputOutput => {
+                    format!("{} / {}", detail.input_tokens, detail.output_tokens)
+                }
+                BreakdownColumn::Share if total > 0.0 => format!("{:.0}%", cost / total * 100.0),
+                BreakdownColumn::Share => "-".into(),
+                BreakdownColumn::Delta => money.signed(cost - detail.previous_cost, 4),
+            })
+        })
+        .collect()
+}
+
+/// The name column at `name_percent`, then `columns_percent` split evenly
+/// across `columns`.
+fn breakdown_widths(name_percent: u16, columns_percent: u16, columns: usize) -> Vec<Constraint> {
+    let each = columns_percent / columns.max(1) as u16;
+    let mut widths = vec![Constraint::Percentage(name_percent)];
+    widths.extend(std::iter::repeat_n(Constraint::Percentage(each), columns));
+    widths
+}
+
+fn breakdown_header(name: &'static str, columns: &[BreakdownColumn]) -> Vec<&'static str> {
+    std::iter::once(name)
+        .chain(columns.iter().map(|c| c.label()))
+        .collect()
+}
+
+fn render_search(f: &mut ratatui::Frame, state: &AppState, money: &MoneyFormat) {
+    let area = centered_rect(90, 80, f.area());
+    f.render_widget(Clear, area);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(7), Constraint::Min(3)])
+        .split(area);
+
+    let search = &state.search;
+    let active = search.active_field();
+    let lines = SearchField::ALL
+        .iter()
+        .map(|field| {
+            let value = match field {
+                SearchField::Table => format!("< {} >", search.table.label()),
+                SearchField::Model => search.model.clone(),
+                SearchField::Provider => search.provider.clone(),
+                SearchField::From => search.from.clone(),
+                SearchField::To => search.to.clone(),
+            };
+            form_line(field.label(), &value, *field == active, false)
         })
         .collect::<Vec<_>>();
-    let model_table = Table::new(
-        model_rows,
-        [Constraint::Percentage(70), Constraint::Percentage(30)],
+    f.render_widget(
+        Paragraph::new(lines).block(bordered(state.accessible).title(" Search Records ")),
+        sections[0],
+    );
+
+    let at = |ts: DateTime<Utc>| ts.format("%Y-%m-%d %H:%M").to_string();
+    let (header, rows, widths) = match search.table {
+        SearchTable::Cost => (
+            vec![
+                "Start (UTC)",
+                "Provider",
+                "Model",
+                "Source",
+                "Input",
+                "Output",
+                "Total",
+            ],
+            search
+                .cost
+                .iter()
+                .map(|r| {
+                    Row::new(vec![
+                        at(r.timestamp),
+                        r.provider.clone(),
+                        r.model.clone(),
+                        r.source.as_str().to_string(),
+                        money.money(r.input_cost, 4),
+                        money.money(r.output_cost, 4),
+                        money.money(r.total_cost, 4),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+            [18, 12, 22, 8, 13, 13, 14],
+        ),
+        SearchTable::Usage => (
+            vec![
+                "Start (UTC)",
+                "Provider",
+                "Model",
+                "Source",
+                "Input",
+                "Output",
+                "Cached",
+            ],
+            search
+                .usage
+                .iter()
+                .map(|r| {
+                    Row::new(vec![
+                        at(r.timestamp),
+                        r.provider.clone(),
+                        r.model.clone(),
+                        r.source.as_str().to_string(),
+                        r.input_tokens.to_string(),
+                        r.output_tokens.to_string(),
+                        r.cached_tokens.to_string(),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+            [18, 12, 22, 8, 13, 13, 14],
+        ),
+    };
+    let title = format!(
+        " {} rows · page {}/{} ",
+        search.total,
+        search.page + 1,
+        search.pages()
+    );
+    let table = Table::new(rows, widths.map(Constraint::Percentage))
+        .header(
+            Row::new(header).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        )
+        .block(bordered(state.accessible).title(title));
+    f.render_widget(table, sections[1]);
+}
+
+fn render_sql_console(f: &mut ratatui::Frame, state: &AppState) {
+    let area = centered_rect(90, 80, f.area());
+    f.render_widget(Clear, area);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)])
+        .split(area);
+    f.render_widget(
+        Paragraph::new(format!("{}_", state.sql_input))
+            .block(bordered(state.accessible).title(" SQL (read-only) ")),
+        sections[0],
+    );
+
+    let Some(result) = &state.sql_result else {
+        f.render_widget(
+            Paragraph::new("Run a SELECT against the database, e.g. SELECT model, SUM(total_cost) FROM cost_records GROUP BY model")
+                .wrap(Wrap { trim: true })
+                .style(Style::default().fg(COLOR_MUTED))
+                .block(bordered(state.accessible).title(" Result ")),
+            sections[1],
+        );
+        return;
+    };
+    let rows = result
+        .rows
+        .iter()
+        .map(|row| Row::new(row.clone()))
+        .collect::<Vec<_>>();
+    let title = format!(
+        " {} rows{} ",
+        result.rows.len(),
+        if result.truncated {
+            format!(", first {CONSOLE_MAX_ROWS} shown")
+        } else {
+            String::new()
+        }
+    );
+    let table = Table::new(
+        rows,
+        std::iter::repeat_n(Constraint::Fill(1), result.columns.len()),
     )
     .header(
-        Row::new(vec!["Model", "Cost"]).style(
+        Row::new(result.columns.clone()).style(
             Style::default()
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
         ),
     )
-    .block(Block::default().borders(Borders::ALL).title(if compact {
-        " Models "
-    } else {
-        " Top Models "
-    }));
-    f.render_widget(model_table, body[1]);
+    .block(bordered(state.accessible).title(title));
+    f.render_widget(table, sections[1]);
+}
 
-    render_action_panel(f, body[2], state, compact);
+fn render_column_picker(f: &mut ratatui::Frame, state: &AppState) {
+    let area = centered_rect(40, 40, f.area());
+    f.render_widget(Clear, area);
+    let lines = BreakdownColumn::ALL
+        .iter()
+        .enumerate()
+        .map(|(idx, column)| {
+            let ticked = if state.column_draft.contains(column) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let selected = idx == state.column_selected;
+            let marker = if selected {
+                selected_marker(state.accessible)
+            } else {
+                ""
+            };
+            let line = Line::from(format!("{marker}{ticked} {}", column.label()));
+            if selected {
+                line.style(Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+    f.render_widget(
+        Paragraph::new(lines).block(bordered(state.accessible).title(" Table Columns ")),
+        area,
+    );
+}
 
-    let footer = Paragraph::new(footer_text(state))
-        .block(Block::default().borders(Borders::ALL))
-        .style(Style::default().fg(COLOR_MUTED));
-    f.render_widget(footer, root[3]);
+/// Share-of-spend bars for `rows`, largest first, one per line as space
+/// allows. Meant to be read at a glance from across the room.
+fn render_share_gauges(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    title: &str,
+    rows: &[(String, f64)],
+    style: Style,
+    accessible: bool,
+) {
+    let block = bordered(accessible).title(title.to_string()).style(style);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let total: f64 = rows.iter().map(|(_, cost)| cost.max(0.0)).sum();
+    if total <= 0.0 {
+        f.render_widget(
+            Paragraph::new("no spend in this window").style(Style::default().fg(COLOR_MUTED)),
+            inner,
+        );
+        return;
+    }
+    let mut sorted: Vec<&(String, f64)> = rows.iter().collect();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let name_width = sorted
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(usize::from(inner.width / 3));
+    let palette = [
+        COLOR_ACCENT,
+        COLOR_INFO,
+        COLOR_WARN,
+        Color::Magenta,
+        Color::Blue,
+    ];
+    for (i, (name, cost)) in sorted.iter().take(usize::from(inner.height)).enumerate() {
+        let ratio = (cost.max(0.0) / total).clamp(0.0, 1.0);
+        let name: String = name.chars().take(name_width).collect();
+        let gauge = LineGauge::default()
+            .ratio(ratio)
+            .label(format!("{name:<name_width$} {:>3.0}%", ratio * 100.0))
+            .filled_style(Style::default().fg(palette[i % palette.len()]))
+            .unfilled_style(Style::default().fg(COLOR_MUTED));
+        let line = Rect {
+            y: inner.y + i as u16,
+            height: 1,
+            ..inner
+        };
+        f.render_widget(gauge, line);
+    }
+}
 
-    match &state.screen {
-        Screen::Dashboard => {}
-        Screen::ProviderManager => render_provider_manager(f, cfg, state),
-        Screen::ProviderForm(mode) => render_provider_form(f, state, mode),
-        Screen::Confirm(action) => render_confirm(f, state, action),
-        Screen::ErrorDialog => render_error(f, state),
-        Screen::InfoDialog => render_info(f, state),
+/// Today / 7d / 30d cost side by side; the box matching the selected window
+/// is highlighted (today stands in for the rolling 1d window).
+fn render_window_row(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    state: &AppState,
+    money: &MoneyFormat,
+    costs: [f64; 3],
+) {
+    let cells = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3])
+        .split(area);
+    let windows = [
+        ("Today", TimeWindow::OneDay),
+        ("Last 7d", TimeWindow::SevenDays),
+        ("Last 30d", TimeWindow::ThirtyDays),
+    ];
+    for ((label, window), (cell, cost)) in windows.into_iter().zip(cells.iter().zip(costs)) {
+        let style = if window == state.window {
+            Style::default()
+                .fg(COLOR_ACCENT)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(COLOR_HEADER)
+        };
+        let title = if window == state.window {
+            format!(" {}{label} ", selected_marker(state.accessible))
+        } else {
+            format!(" {label} ")
+        };
+        let widget = Paragraph::new(money.money(cost, 4))
+            .block(bordered(state.accessible).title(title))
+            .style(style);
+        f.render_widget(widget, *cell);
     }
 }
 
 fn footer_text(state: &AppState) -> &'static str {
     match state.screen {
-        Screen::Dashboard => "a focus actions | r refresh | 1/7/3 window | z compact | q quit | Esc unfocus actions",
+        Screen::Dashboard => ": commands | a focus actions | r refresh | 1/7/3 window | w today/7d/30d | t hour/day chart | g gauges | f families | o by source | c columns | / search | ^S save view | s simulator | b budgets | h alerts | z compact | q quit | Esc unfocus actions",
+        Screen::CommandPalette => "type to filter | Up/Down select | Enter run | Esc close",
+        Screen::Alerts => "Up/Down select | a/Enter acknowledge | x resolve | Esc back",
+        Screen::Simulator => {
+            "Up/Down model | Tab in/out price | type price + Enter apply | m map to model | c reset row | i details | Esc back"
+        }
+        Screen::ModelDetail => "Enter/Esc back",
         Screen::ProviderManager => {
-            "n add | Enter edit | t test | e enable/disable | k del key | d remove | a actions | z compact | Esc back"
+            "n add | Enter edit | r rename | t test | e enable/disable | k del key | d remove | u undo removal | a actions | z compact | Esc back"
         }
         Screen::ProviderForm(_) => {
             "Tab next | Shift+Tab prev | t test | x clear logs | e toggle enabled | v advanced | i details | Enter save | Esc cancel"
         }
+        Screen::RenameProvider { .. } => "type new name | Enter rename | Esc cancel",
+        Screen::Budgets => "Up/Down select | n new | Enter edit | d delete | Esc back",
+        Screen::ColumnPicker => "Up/Down select | Space toggle | Enter apply and save | Esc cancel",
+        Screen::SqlConsole => "type a SELECT | Enter run | Esc back",
+        Screen::Search => {
+            "Tab/Up/Down field | type to filter | Left/Right rows | Enter search | PgUp/PgDn page | Esc back"
+        }
+        Screen::BudgetForm(_) => {
+            "Tab/Up/Down field | Left/Right pick scope, target, period | type to edit | Enter save | Esc cancel"
+        }
         Screen::Confirm(_) => "Left/Right choose | Enter confirm | Esc cancel",
         Screen::ErrorDialog => "Enter/Esc close",
         Screen::InfoDialog => "Enter/Esc close",
@@ -1203,7 +3834,11 @@ fn render_action_panel(f: &mut ratatui::Frame, area: Rect, state: &AppState, com
         let selected = idx == state.action_selected;
         let focused = state.action_focused
             && matches!(state.screen, Screen::Dashboard | Screen::ProviderManager);
-        let marker = if selected { ">" } else { " " };
+        let marker = if selected {
+            selected_marker(state.accessible)
+        } else {
+            "  "
+        };
         let style = if selected && focused {
             Style::default()
                 .fg(Color::Black)
@@ -1218,7 +3853,7 @@ fn render_action_panel(f: &mut ratatui::Frame, area: Rect, state: &AppState, com
         };
 
         lines.push(Line::from(vec![
-            Span::styled(format!("{marker} {label}"), style),
+            Span::styled(format!("{marker}{label}"), style),
             Span::styled(
                 if compact {
                     format!(" [{hint}]")
@@ -1246,16 +3881,30 @@ fn render_action_panel(f: &mut ratatui::Frame, area: Rect, state: &AppState, com
         Style::default().fg(COLOR_MUTED),
     )));
 
-    let panel =
-        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Actions "));
+    let panel = Paragraph::new(lines).block(bordered(state.accessible).title(" Actions "));
     f.render_widget(panel, area);
 }
 
+/// Keychain presence for the Providers table. The test harness stubs it so
+/// snapshots do not depend on the machine's keychain.
+fn key_status(provider: &str) -> &'static str {
+    #[cfg(test)]
+    if let Some(status) = tests::KEY_STATUS_STUB.with(std::cell::Cell::get) {
+        return status;
+    }
+    match has_api_key(provider) {
+        Ok(true) => "present",
+        Ok(false) => "missing",
+        Err(_) => "error",
+    }
+}
+
 fn render_provider_manager(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
     let area = centered_rect(90, 80, f.area());
     f.render_widget(Clear, area);
 
     let providers = provider_list(cfg);
+    let now = Utc::now();
     let mut rows = Vec::new();
 
     for (idx, provider) in providers.iter().enumerate() {
@@ -1263,25 +3912,62 @@ fn render_provider_manager(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppS
             .enabled_providers
             .iter()
             .any(|p| p.eq_ignore_ascii_case(provider));
-        let key_status = match has_api_key(provider) {
-            Ok(true) => "present",
-            Ok(false) => "missing",
-            Err(_) => "error",
+        let provider_state = if state.needs_reauth.contains(provider) {
+            "needs re-auth"
+        } else if state.quarantined.contains(provider) {
+            "quarantined"
+        } else if enabled {
+            "enabled"
+        } else {
+            "disabled"
         };
+        let key_status = key_status(provider);
 
-        let style = if idx == state.provider_selected {
+        let selected = idx == state.provider_selected;
+        let style = if selected {
             Style::default()
                 .bg(Color::DarkGray)
                 .add_modifier(Modifier::BOLD)
         } else {
             Style::default()
         };
+        let label = if selected && state.accessible {
+            format!("{}{}", selected_marker(true), cfg.provider_label(provider))
+        } else {
+            cfg.provider_label(provider)
+        };
+
+        let settings = cfg.provider_settings.get(provider);
+        let expires = settings
+            .and_then(|s| s.key_expires_at)
+            .map(|at| expiry_badge((at - now.date_naive()).num_days()))
+            .unwrap_or_default();
+        let owner = settings.and_then(|s| s.owner.clone()).unwrap_or_default();
+        let notes = settings.and_then(|s| s.notes.clone()).unwrap_or_default();
+        let health = state.provider_health.get(provider);
+        let last_ok = match health.map(|h| h.last_success) {
+            Some(Some(at)) => as_of_label(at, now),
+            Some(None) => "never".into(),
+            None => "-".into(),
+        };
+        let window_rows = health
+            .map(|h| h.rows_in_window.to_string())
+            .unwrap_or_else(|| "-".into());
+        let last_error = health
+            .and_then(|h| h.last_error.clone())
+            .unwrap_or_default();
 
         rows.push(
             Row::new(vec![
-                Cell::from(provider.clone()),
-                Cell::from(if enabled { "enabled" } else { "disabled" }),
+                Cell::from(label),
+                Cell::from(provider_state),
                 Cell::from(key_status),
+                Cell::from(expires),
+                Cell::from(last_ok),
+                Cell::from(window_rows),
+                Cell::from(last_error).style(Style::default().fg(Color::Red)),
+                Cell::from(owner),
+                Cell::from(notes),
             ])
             .style(style),
         );
@@ -1290,35 +3976,531 @@ fn render_provider_manager(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppS
     let table = Table::new(
         rows,
         [
-            Constraint::Percentage(40),
-            Constraint::Percentage(30),
-            Constraint::Percentage(30),
+            Constraint::Percentage(13),
+            Constraint::Percentage(11),
+            Constraint::Percentage(7),
+            Constraint::Percentage(11),
+            Constraint::Percentage(10),
+            Constraint::Percentage(6),
+            Constraint::Percentage(20),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
         ],
     )
     .header(
-        Row::new(vec!["Provider", "State", "Key"]).style(
+        Row::new(vec![
+            "Provider",
+            "State",
+            "Key",
+            "Expires",
+            "Last ok",
+            "Rows",
+            "Last failure",
+            "Owner",
+            "Notes",
+        ])
+        .style(
             Style::default()
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
         ),
     )
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(" Provider Manager "),
+    .block(bordered(state.accessible).title(match &state.undo_removal {
+        Some(removed) => format!(" Provider Manager (u restores '{}') ", removed.name),
+        None => " Provider Manager ".to_string(),
+    }));
+
+    f.render_widget(table, area);
+}
+
+/// Countdown shown next to a key with a `key_expires_at`.
+fn expiry_badge(days_left: i64) -> String {
+    match days_left {
+        d if d < 0 => format!("expired {}d ago", -d),
+        0 => "expires today".into(),
+        d => format!("{d}d left"),
+    }
+}
+
+/// Panel over the dashboard while a refresh runs in the background.
+fn render_refresh_progress(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
+    let elapsed = state
+        .refreshing_since
+        .map_or(0, |since| since.elapsed().as_secs());
+    let deadline = if cfg.refresh_timeout_secs > 0 {
+        format!(
+            "Accounts not done after {}s keep their stored data.",
+            cfg.refresh_timeout_secs
+        )
+    } else {
+        "No deadline (refresh_timeout_secs = 0).".into()
+    };
+    let area = centered_rect(50, 25, f.area());
+    f.render_widget(Clear, area);
+    let content = Paragraph::new(vec![
+        Line::from(Span::styled(
+            format!("Fetching from providers... {elapsed}s"),
+            Style::default()
+                .fg(COLOR_ACCENT)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(deadline, Style::default().fg(COLOR_MUTED))),
+        Line::from(""),
+        Line::from("Esc cancels and keeps the stored data."),
+    ])
+    .block(bordered(state.accessible).title(" Refreshing "))
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true });
+    f.render_widget(content, area);
+}
+
+fn render_rename(f: &mut ratatui::Frame, state: &AppState, provider: &str) {
+    let area = centered_rect(56, 30, f.area());
+    f.render_widget(Clear, area);
+    let content = Paragraph::new(vec![
+        Line::from(format!("New name for '{provider}':")),
+        Line::from(Span::styled(
+            format!("{}_", state.rename_input),
+            Style::default()
+                .fg(COLOR_ACCENT)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Moves config settings, the keyring entry and stored history.",
+            Style::default().fg(COLOR_MUTED),
+        )),
+    ])
+    .block(bordered(state.accessible).title(" Rename Provider "))
+    .alignment(Alignment::Center);
+    f.render_widget(content, area);
+}
+
+fn render_palette(f: &mut ratatui::Frame, state: &AppState) {
+    let area = centered_rect(60, 60, f.area());
+    f.render_widget(Clear, area);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)])
+        .split(area);
+
+    f.render_widget(
+        Paragraph::new(format!(":{}_", state.palette.input))
+            .block(bordered(state.accessible).title(" Command ")),
+        sections[0],
     );
 
+    let matches = state.palette.matches();
+    let lines = if matches.is_empty() {
+        vec![Line::from(Span::styled(
+            "No matching commands",
+            Style::default().fg(COLOR_MUTED),
+        ))]
+    } else {
+        matches
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                if idx == state.palette.selected {
+                    Line::from(Span::styled(
+                        format!("{}{}", selected_marker(state.accessible), entry.label),
+                        Style::default()
+                            .fg(COLOR_ACCENT)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                } else {
+                    Line::from(format!("  {}", entry.label))
+                }
+            })
+            .collect()
+    };
+    let visible = sections[1].height.saturating_sub(2) as usize;
+    let scroll = state
+        .palette
+        .selected
+        .saturating_sub(visible.saturating_sub(1));
+    f.render_widget(
+        Paragraph::new(lines)
+            .scroll((scroll as u16, 0))
+            .block(bordered(state.accessible).title(format!(" {} matches ", matches.len()))),
+        sections[1],
+    );
+}
+
+/// `used` as a share of `limit`, e.g. `82%`.
+fn budget_used(spent: f64, limit: f64) -> String {
+    if limit > 0.0 {
+        format!("{:.0}%", spent / limit * 100.0)
+    } else {
+        "-".into()
+    }
+}
+
+fn render_budgets(f: &mut ratatui::Frame, cfg: &AppConfig, state: &AppState) {
+    let area = centered_rect(90, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let rows = cfg
+        .budgets
+        .iter()
+        .enumerate()
+        .map(|(idx, budget)| {
+            let spent = state.budget_spend.get(idx).copied().flatten();
+            let over = spent.is_some_and(|s| s > budget.amount_usd);
+            let selected = idx == state.budget_selected;
+            let style = match (selected, over) {
+                (true, _) => Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+                (false, true) => Style::default().fg(COLOR_WARN),
+                (false, false) => Style::default(),
+            };
+            let label = if selected && state.accessible {
+                format!("{}{}", selected_marker(true), budget.name)
+            } else {
+                budget.name.clone()
+            };
+            Row::new(vec![
+                Cell::from(label),
+                Cell::from(String::from(budget.scope.clone())),
+                Cell::from(budget.period.as_str()),
+                Cell::from(format!("${:.2}", budget.amount_usd)),
+                Cell::from(spent.map_or_else(|| "-".into(), |s| format!("${s:.2}"))),
+                Cell::from(spent.map_or_else(|| "-".into(), |s| budget_used(s, budget.amount_usd))),
+                Cell::from(if budget.on_exceeded.is_some() {
+                    "yes"
+                } else {
+                    "-"
+                }),
+            ])
+            .style(style)
+        })
+        .collect::<Vec<_>>();
+
+    let title = if cfg.budgets.is_empty() {
+        " Budgets (none; press n to add) "
+    } else {
+        " Budgets (current period, USD) "
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(20),
+            Constraint::Percentage(24),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(10),
+            Constraint::Percentage(12),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Name", "Scope", "Period", "Limit", "Spent", "Used", "Hook",
+        ])
+        .style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+    )
+    .block(bordered(state.accessible).title(title));
     f.render_widget(table, area);
 }
 
-fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &ProviderFormMode) {
-    let area = centered_rect(80, 70, f.area());
+fn render_budget_form(f: &mut ratatui::Frame, state: &AppState, mode: &BudgetFormMode) {
+    let area = centered_rect(70, 60, f.area());
+    f.render_widget(Clear, area);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Min(3)])
+        .split(area);
+
+    let draft = &state.budget_draft;
+    let active = draft.active_field();
+    let picker = |value: &str| format!("< {value} >");
+    let lines = BudgetFormField::ALL
+        .iter()
+        .map(|field| {
+            let value = match field {
+                BudgetFormField::Name => draft.name.clone(),
+                BudgetFormField::Scope => picker(draft.scope.label()),
+                BudgetFormField::Target if draft.scope == BudgetScopeKind::Global => {
+                    "(all spend)".into()
+                }
+                BudgetFormField::Target if draft.targets.is_empty() => draft.target.clone(),
+                BudgetFormField::Target => picker(&draft.target),
+                BudgetFormField::Amount => draft.amount.clone(),
+                BudgetFormField::Period => picker(draft.period.as_str()),
+                BudgetFormField::OnExceeded => draft.on_exceeded.clone(),
+            };
+            form_line(field.label(), &value, *field == active, false)
+        })
+        .collect::<Vec<_>>();
+    let title = match mode {
+        BudgetFormMode::Add => " Add Budget ",
+        BudgetFormMode::Edit { .. } => " Edit Budget ",
+    };
+    f.render_widget(
+        Paragraph::new(lines).block(bordered(state.accessible).title(title)),
+        sections[0],
+    );
+
+    let limit = draft.amount.trim().parse::<f64>().ok();
+    let (text, style) = match (draft.spent, limit) {
+        (None, _) => (
+            format!("Pick a {} to see its spend.", draft.scope.label()),
+            Style::default().fg(COLOR_MUTED),
+        ),
+        (Some(spent), Some(limit)) => (
+            format!(
+                "{} spend so far: ${spent:.2} of ${limit:.2} ({})",
+                draft.period.as_str(),
+                budget_used(spent, limit)
+            ),
+            if spent > limit {
+                Style::default().fg(COLOR_WARN)
+            } else {
+                Style::default().fg(COLOR_INFO)
+            },
+        ),
+        (Some(spent), None) => (
+            format!("{} spend so far: ${spent:.2}", draft.period.as_str()),
+            Style::default(),
+        ),
+    };
+    f.render_widget(
+        Paragraph::new(Line::from(Span::styled(text, style)))
+            .block(bordered(state.accessible).title(" Current Spend "))
+            .wrap(Wrap { trim: true }),
+        sections[1],
+    );
+}
+
+fn render_alerts(f: &mut ratatui::Frame, state: &AppState) {
+    let area = centered_rect(90, 80, f.area());
+    f.render_widget(Clear, area);
+
+    let rows = state
+        .alerts
+        .iter()
+        .enumerate()
+        .map(|(idx, alert)| {
+            let selected = idx == state.alert_selected;
+            let style = match alert.state {
+                AlertState::New => Style::default().fg(COLOR_WARN),
+                AlertState::Acked => Style::default(),
+                AlertState::Resolved => Style::default().fg(COLOR_MUTED),
+            };
+            let style = if selected {
+                style.add_modifier(Modifier::BOLD)
+            } else {
+                style
+            };
+            let marker = if selected {
+                selected_marker(state.accessible)
+            } else {
+                ""
+            };
+            Row::new(vec![
+                Cell::from(format!("{marker}{}", alert.state.as_str())),
+                Cell::from(
+                    alert
+                        .created_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                ),
+                Cell::from(format!("{}: {}", alert.kind, alert.subject)),
+                Cell::from(alert.message.clone()),
+            ])
+            .style(style)
+        })
+        .collect::<Vec<_>>();
+
+    let title = if state.alerts.is_empty() {
+        " Alerts (none recorded) ".to_string()
+    } else {
+        format!(" Alerts ({} unacked) ", state.unacked_alerts)
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(14),
+            Constraint::Percentage(18),
+            Constraint::Percentage(22),
+            Constraint::Percentage(46),
+        ],
+    )
+    .header(
+        Row::new(vec!["State", "Raised", "Source", "Message"]).style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+    )
+    .block(bordered(state.accessible).title(title));
+    f.render_widget(table, area);
+}
+
+/// Average latency for tables; `-` where the provider does not report it.
+fn latency_label(avg_latency_ms: Option<f64>) -> String {
+    avg_latency_ms.map_or_else(|| "-".into(), |ms| format!("{ms:.0} ms"))
+}
+
+fn render_simulator(f: &mut ratatui::Frame, state: &AppState, money: &MoneyFormat) {
+    let area = centered_rect(90, 80, f.area());
     f.render_widget(Clear, area);
     let sections = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(12), Constraint::Min(8)])
+        .constraints([Constraint::Min(6), Constraint::Length(5)])
         .split(area);
 
+    let sim = &state.simulator;
+    let mut rows = Vec::new();
+    for (idx, row) in sim.rows.iter().enumerate() {
+        let selected = idx == sim.selected;
+        let price_cell = |field: SimulatorField, value: f64| {
+            if selected && sim.field == field && !sim.input.is_empty() {
+                format!("{}_", sim.input)
+            } else if selected && sim.field == field {
+                format!("[{value:.4}]")
+            } else {
+                format!("{value:.4}")
+            }
+        };
+        let simulated = row.simulated_cost();
+        let model = match &row.mapped_to {
+            Some(target) => format!("{} -> {target}", row.model),
+            None => row.model.clone(),
+        };
+        let label = if selected {
+            format!("{}{model}", selected_marker(state.accessible))
+        } else {
+            model
+        };
+        let style = if selected {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else if row.edited {
+            Style::default().fg(COLOR_ACCENT)
+        } else {
+            Style::default()
+        };
+        rows.push(
+            Row::new(vec![
+                Cell::from(label),
+                Cell::from(format!("{}", row.input_tokens)),
+                Cell::from(format!("{}", row.output_tokens)),
+                Cell::from(latency_label(row.avg_latency_ms)),
+                Cell::from(price_cell(SimulatorField::InputPrice, row.input_per_1m)),
+                Cell::from(price_cell(SimulatorField::OutputPrice, row.output_per_1m)),
+                Cell::from(money.money(row.actual_cost, 4)),
+                Cell::from(money.money(simulated, 4)),
+                Cell::from(money.signed(simulated - row.actual_cost, 4)),
+            ])
+            .style(style),
+        );
+    }
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(20),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+            Constraint::Percentage(8),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Model",
+            "In tok",
+            "Out tok",
+            "Latency",
+            "$/1M in",
+            "$/1M out",
+            "Actual",
+            "Simulated",
+            "Delta",
+        ])
+        .style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
+    )
+    .block(bordered(state.accessible).title(" What-if Pricing (not saved) "));
+    f.render_widget(table, sections[0]);
+
+    let actual = sim.actual_total();
+    let simulated = sim.simulated_total();
+    let (mapped, migration_delta) = sim.migration_delta();
+    let summary = Paragraph::new(vec![
+        Line::from(format!(
+            "Window {}: actual {} · simulated {} · delta {}",
+            state.window.as_label(),
+            money.money(actual, 4),
+            money.money(simulated, 4),
+            money.signed(simulated - actual, 4)
+        )),
+        Line::from(if mapped == 0 {
+            "Migration: press 'm' to move a model's traffic onto another priced model".to_string()
+        } else {
+            format!(
+                "Migration: {mapped} model(s) remapped · savings delta {}",
+                money.signed(migration_delta, 4)
+            )
+        }),
+        Line::from(Span::styled(
+            if sim.rows.is_empty() {
+                "No stored usage in this window. Refresh first."
+            } else {
+                "Prices are per 1M tokens and only apply to this session."
+            },
+            Style::default().fg(COLOR_MUTED),
+        )),
+    ])
+    .block(bordered(state.accessible).title(" Totals "));
+    f.render_widget(summary, sections[1]);
+}
+
+fn push_azure_form_lines(
+    lines: &mut Vec<Line<'static>>,
+    state: &AppState,
+    active_field: ProviderFormField,
+) {
+    if !state.provider_draft.is_azure() {
+        return;
+    }
+    lines.push(form_line(
+        "Resource ID",
+        &state.provider_draft.resource,
+        active_field == ProviderFormField::AzureResource,
+        false,
+    ));
+    lines.push(form_line(
+        "API version (optional)",
+        &state.provider_draft.api_version,
+        active_field == ProviderFormField::ApiVersion,
+        false,
+    ));
+    lines.push(form_line(
+        "Deployments (name=model, ...)",
+        &state.provider_draft.deployments,
+        active_field == ProviderFormField::Deployments,
+        false,
+    ));
+}
+
+fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &ProviderFormMode) {
+    let area = centered_rect(80, 70, f.area());
+    f.render_widget(Clear, area);
+
     let title = match mode {
         ProviderFormMode::Add => "Add Provider",
         ProviderFormMode::Edit { .. } => "Edit Provider",
@@ -1341,6 +4523,7 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
                 active_field == ProviderFormField::ApiKey,
                 true,
             ));
+            push_azure_form_lines(&mut lines, state, active_field);
             lines.push(Line::from(format!(
                 "Advanced: {} (press 'v' to toggle)",
                 if state.provider_draft.show_advanced {
@@ -1362,6 +4545,24 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
                     active_field == ProviderFormField::OrganizationId,
                     false,
                 ));
+                lines.push(form_line(
+                    "Owner (optional)",
+                    &state.provider_draft.owner,
+                    active_field == ProviderFormField::Owner,
+                    false,
+                ));
+                lines.push(form_line(
+                    "Notes (optional)",
+                    &state.provider_draft.notes,
+                    active_field == ProviderFormField::Notes,
+                    false,
+                ));
+                lines.push(form_line(
+                    "Key expires (YYYY-MM-DD, optional)",
+                    &state.provider_draft.key_expires_at,
+                    active_field == ProviderFormField::KeyExpiresAt,
+                    false,
+                ));
             }
             lines.push(form_line(
                 "Enabled",
@@ -1382,6 +4583,7 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
                 active_field == ProviderFormField::ApiKey,
                 true,
             ));
+            push_azure_form_lines(&mut lines, state, active_field);
             lines.push(Line::from(format!(
                 "Advanced: {} (press 'v' to toggle)",
                 if state.provider_draft.show_advanced {
@@ -1403,6 +4605,24 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
                     active_field == ProviderFormField::OrganizationId,
                     false,
                 ));
+                lines.push(form_line(
+                    "Owner (optional)",
+                    &state.provider_draft.owner,
+                    active_field == ProviderFormField::Owner,
+                    false,
+                ));
+                lines.push(form_line(
+                    "Notes (optional)",
+                    &state.provider_draft.notes,
+                    active_field == ProviderFormField::Notes,
+                    false,
+                ));
+                lines.push(form_line(
+                    "Key expires (YYYY-MM-DD, optional)",
+                    &state.provider_draft.key_expires_at,
+                    active_field == ProviderFormField::KeyExpiresAt,
+                    false,
+                ));
             }
             lines.push(form_line(
                 "Enabled",
@@ -1426,12 +4646,15 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
         "Tab/Shift+Tab switch field | t test | x clear logs | e toggle enabled | v advanced | i details | Enter save | Esc cancel",
     ));
 
+    // Azure accounts add three lines, which would not fit with the advanced
+    // fields in the usual height.
+    let form_height = (lines.len() as u16 + 2).max(15);
+    let sections = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(form_height), Constraint::Min(8)])
+        .split(area);
     let content = Paragraph::new(lines)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(format!(" {} ", title)),
-        )
+        .block(bordered(state.accessible).title(format!(" {} ", title)))
         .style(Style::default().fg(COLOR_HEADER));
     f.render_widget(content, sections[0]);
 
@@ -1453,11 +4676,7 @@ fn render_provider_form(f: &mut ratatui::Frame, state: &AppState, mode: &Provide
     }
 
     let log_panel = Paragraph::new(log_lines)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Test Logs (Edit Provider) "),
-        )
+        .block(bordered(state.accessible).title(" Test Logs (Edit Provider) "))
         .style(Style::default().fg(COLOR_HEADER))
         .wrap(Wrap { trim: true });
     f.render_widget(log_panel, sections[1]);
@@ -1518,7 +4737,17 @@ fn form_line(label: &str, value: &str, active: bool, masked: bool) -> Line<'stat
 }
 
 fn render_confirm(f: &mut ratatui::Frame, state: &AppState, action: &ConfirmAction) {
-    let area = centered_rect(56, 34, f.area());
+    let preview = match action {
+        ConfirmAction::DeleteProvider { .. } | ConfirmAction::DeleteKey { .. } => {
+            deletion_preview_lines(&state.deletion_preview, action)
+        }
+        _ => Vec::new(),
+    };
+    let area = if preview.is_empty() {
+        centered_rect(56, 34, f.area())
+    } else {
+        centered_rect(64, 70, f.area())
+    };
     f.render_widget(Clear, area);
 
     let (title, message, target, consequence): (&str, &str, String, String) = match action {
@@ -1528,6 +4757,12 @@ fn render_confirm(f: &mut ratatui::Frame, state: &AppState, action: &ConfirmActi
             "Target: application session".to_string(),
             "Consequence: closes TUI and returns to shell.".to_string(),
         ),
+        ConfirmAction::DeleteBudget { name, .. } => (
+            "Confirm Budget Removal",
+            "Remove this budget?",
+            format!("Budget: {name}"),
+            "Consequence: removes the budget from config.toml.".to_string(),
+        ),
         ConfirmAction::DeleteProvider { provider } => (
             "Confirm Provider Removal",
             "Remove this provider configuration?",
@@ -1540,6 +4775,12 @@ fn render_confirm(f: &mut ratatui::Frame, state: &AppState, action: &ConfirmActi
             format!("Provider: {provider}"),
             "Consequence: provider key is deleted and provider is disabled.".to_string(),
         ),
+        ConfirmAction::Backfill { provider } => (
+            "Backfill History",
+            "Fetch this provider's usage history now?",
+            format!("Provider: {provider}"),
+            "Consequence: fetches up to 90 days of usage right away.".to_string(),
+        ),
     };
 
     let cancel_style = if state.confirm_selected == 0 {
@@ -1559,41 +4800,213 @@ fn render_confirm(f: &mut ratatui::Frame, state: &AppState, action: &ConfirmActi
         Style::default()
     };
 
-    let content = Paragraph::new(vec![
-        Line::from(message),
-        Line::from(target),
+    let mut lines = vec![Line::from(message), Line::from(target)];
+    lines.extend(preview);
+    lines.extend([
         Line::from(Span::styled(consequence, Style::default().fg(COLOR_MUTED))),
         Line::from(""),
         Line::from(vec![
-            Span::styled("[Cancel (Esc)]", cancel_style),
+            Span::styled(
+                confirm_button_label("[Cancel (Esc)]", state.confirm_selected == 0, state),
+                cancel_style,
+            ),
             Span::raw("   "),
-            Span::styled("[Confirm (Enter)]", confirm_style),
+            Span::styled(
+                confirm_button_label("[Confirm (Enter)]", state.confirm_selected == 1, state),
+                confirm_style,
+            ),
         ]),
         Line::from("Use Left/Right to choose"),
-    ])
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(format!(" {} ", title)),
-    )
-    .alignment(Alignment::Center);
+    ]);
+    let content = Paragraph::new(lines)
+        .block(bordered(state.accessible).title(format!(" {} ", title)))
+        .alignment(Alignment::Center);
+
+    f.render_widget(content, area);
+}
+
+/// The "what will be removed" block of a provider or key removal dialog.
+fn deletion_preview_lines(preview: &DeletionPreview, action: &ConfirmAction) -> Vec<Line<'static>> {
+    let muted = Style::default().fg(COLOR_MUTED);
+    let mut lines = vec![Line::from(""), Line::from("Will remove:")];
+    if matches!(action, ConfirmAction::DeleteProvider { .. }) {
+        if preview.config_section.is_empty() {
+            lines.push(Line::from("  config.toml: no provider section"));
+        } else {
+            lines.push(Line::from("  config.toml:"));
+            lines.extend(
+                preview
+                    .config_section
+                    .iter()
+                    .map(|l| Line::from(Span::styled(format!("    {l}"), muted))),
+            );
+        }
+    }
+    lines.push(Line::from(format!(
+        "  keyring: {}{}",
+        preview.keyring_entry,
+        if preview.has_key {
+            ""
+        } else {
+            " (no key stored)"
+        }
+    )));
+    let rows = format!(
+        "{} usage and {} cost rows",
+        preview.usage_rows, preview.cost_rows
+    );
+    lines.push(Line::from(if preview.purge_history {
+        format!("  history: {rows} will be purged")
+    } else {
+        format!("  history: {rows} kept")
+    }));
+    if matches!(action, ConfirmAction::DeleteProvider { .. }) {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "  [{}] purge stored history (p)",
+                if preview.purge_history { "x" } else { " " }
+            ),
+            muted,
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.into_iter().map(Line::left_aligned).collect()
+}
 
-    f.render_widget(content, area);
+fn confirm_button_label(label: &str, selected: bool, state: &AppState) -> String {
+    if selected && state.accessible {
+        format!("{}{label}", selected_marker(true))
+    } else {
+        label.to_string()
+    }
 }
 
 fn render_error(f: &mut ratatui::Frame, state: &AppState) {
     let area = centered_rect(60, 30, f.area());
     f.render_widget(Clear, area);
+    let message = if state.accessible {
+        format!("[ERROR] {}", state.error_message)
+    } else {
+        state.error_message.clone()
+    };
     let content = Paragraph::new(vec![
-        Line::from(state.error_message.clone()),
+        Line::from(message),
         Line::from(""),
         Line::from("Press Enter or Esc"),
     ])
-    .block(Block::default().borders(Borders::ALL).title(" Error "))
+    .block(bordered(state.accessible).title(" Error "))
     .style(Style::default().fg(Color::Red));
     f.render_widget(content, area);
 }
 
+fn render_recovery(
+    f: &mut ratatui::Frame,
+    db: &std::path::Path,
+    error: &str,
+    backup: Option<DateTime<Utc>>,
+    accessible: bool,
+) {
+    let area = centered_rect(70, 60, f.area());
+    f.render_widget(Clear, area);
+    let mut lines = vec![
+        Line::from(format!("Cannot open {}", db.display())),
+        Line::from(if accessible {
+            format!("[ERROR] {error}")
+        } else {
+            error.to_string()
+        }),
+        Line::from(""),
+    ];
+    for (key, label, action) in RECOVERY_ACTIONS {
+        let line = match (action, backup) {
+            (RecoveryAction::RestoreBackup, Some(at)) => {
+                format!("  {key}  {label} ({})", at.format("%Y-%m-%d %H:%M UTC"))
+            }
+            (RecoveryAction::RestoreBackup, None) => format!("  -  {label} (no backup found)"),
+            _ => format!("  {key}  {label}"),
+        };
+        lines.push(Line::from(line));
+    }
+    let content = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(bordered(accessible).title(" Database Recovery "))
+        .style(Style::default().fg(Color::Red));
+    f.render_widget(content, area);
+}
+
+/// Peak utilization from which the detail screen suggests a limit increase.
+const NEAR_LIMIT: f64 = 0.8;
+
+fn render_model_detail(f: &mut ratatui::Frame, state: &AppState, money: &MoneyFormat) {
+    let area = centered_rect(70, 50, f.area());
+    f.render_widget(Clear, area);
+    let detail = &state.model_detail;
+    let now = Utc::now();
+    let mut lines = vec![
+        Line::from(format!(
+            "Usage ({}): {} in / {} out tokens, {}",
+            state.window.as_label(),
+            detail.input_tokens,
+            detail.output_tokens,
+            money.money(detail.cost, 4)
+        )),
+        Line::from(match detail.avg_latency_ms {
+            Some(ms) => format!("Avg latency: {ms:.0} ms (token-weighted)"),
+            None => "Avg latency: not reported by the provider".into(),
+        }),
+        Line::from(match &detail.limit {
+            Some(limit) => format!(
+                "Rate limit: {} tokens/min{} (as of {})",
+                limit.tokens_per_minute,
+                limit
+                    .requests_per_minute
+                    .map(|rpm| format!(", {rpm} requests/min"))
+                    .unwrap_or_default(),
+                as_of_label(limit.fetched_at, now)
+            ),
+            None => "Rate limit: not reported by the provider".into(),
+        }),
+        Line::from(match &detail.peak {
+            Some(peak) => format!(
+                "Observed peak: {:.0} tokens/min at {} ({} buckets)",
+                peak.tokens_per_minute,
+                as_of_label(peak.at, now),
+                peak.width.as_str()
+            ),
+            None => "Observed peak: no usage in this window".into(),
+        }),
+    ];
+    if detail.peak.is_some_and(|p| p.width > Granularity::Minute) {
+        lines.push(Line::from(Span::styled(
+            "Buckets wider than 1m average out bursts; the true peak can be higher.",
+            Style::default().fg(COLOR_MUTED),
+        )));
+    }
+    lines.push(Line::from(""));
+    let utilization = detail
+        .peak
+        .zip(detail.limit.as_ref())
+        .and_then(|(peak, limit)| peak.utilization(limit));
+    lines.push(match utilization {
+        Some(u) if u >= NEAR_LIMIT => Line::from(Span::styled(
+            format!(
+                "Utilization: {:.0}% of the token limit; consider a limit increase",
+                u * 100.0
+            ),
+            Style::default().fg(COLOR_WARN),
+        )),
+        Some(u) => Line::from(format!("Utilization: {:.0}% of the token limit", u * 100.0)),
+        None => Line::from(Span::styled(
+            "Utilization: needs both a rate limit and usage",
+            Style::default().fg(COLOR_MUTED),
+        )),
+    });
+    let content = Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+        bordered(state.accessible).title(format!(" {} / {} ", detail.provider, detail.model)),
+    );
+    f.render_widget(content, area);
+}
+
 fn render_info(f: &mut ratatui::Frame, state: &AppState) {
     let area = centered_rect(70, 38, f.area());
     f.render_widget(Clear, area);
@@ -1602,7 +5015,7 @@ fn render_info(f: &mut ratatui::Frame, state: &AppState) {
         Line::from(""),
         Line::from("Press Enter or Esc"),
     ])
-    .block(Block::default().borders(Borders::ALL).title(" Details "))
+    .block(bordered(state.accessible).title(" Details "))
     .style(Style::default().fg(Color::Yellow));
     f.render_widget(content, area);
 }
@@ -1610,11 +5023,1105 @@ fn render_info(f: &mut ratatui::Frame, state: &AppState) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::ClockSkew;
+    use crate::ui::headless::buffer_to_text;
+    use ratatui::backend::TestBackend;
+    use std::path::PathBuf;
     use std::time::Duration as StdDuration;
+    use tempfile::TempDir;
+
+    thread_local! {
+        pub(super) static KEY_STATUS_STUB: std::cell::Cell<Option<&'static str>> =
+            const { std::cell::Cell::new(None) };
+    }
+
+    /// Drives the real key handler and renderer against a `TestBackend`.
+    struct Harness {
+        state: AppState,
+        cfg: AppConfig,
+        storage: AsyncStorage,
+        service: MeterService,
+        jobs: Jobs,
+        _tmp: TempDir,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let tmp = TempDir::new().expect("tempdir");
+            let storage = Storage::open(&tmp.path().join("db.sqlite")).expect("open storage");
+            KEY_STATUS_STUB.with(|stub| stub.set(Some("missing")));
+            Self {
+                state: AppState::default(),
+                cfg: AppConfig::default(),
+                storage: AsyncStorage::new(storage),
+                service: MeterService::new(&AppConfig::default()).expect("service"),
+                jobs: Jobs::default(),
+                _tmp: tmp,
+            }
+        }
+
+        async fn press(&mut self, code: KeyCode) {
+            handle_key(
+                code,
+                KeyModifiers::NONE,
+                &mut self.state,
+                &mut self.cfg,
+                &self.storage,
+                &self.service,
+                &mut self.jobs,
+            )
+            .await;
+        }
+
+        /// Waits for the background refresh and stores it, as the main loop
+        /// does once it finishes.
+        async fn settle_refresh(&mut self) {
+            while self
+                .jobs
+                .refresh
+                .as_ref()
+                .is_some_and(|job| !job.handle.is_finished())
+            {
+                tokio::time::sleep(StdDuration::from_millis(10)).await;
+            }
+            finish_refresh(
+                &mut self.state,
+                &self.cfg,
+                &self.storage,
+                &self.service,
+                &mut self.jobs.refresh,
+            )
+            .await;
+        }
+
+        fn render(&self, width: u16, height: u16) -> String {
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("terminal");
+            terminal
+                .draw(|f| render(f, &self.cfg, &self.state))
+                .expect("draw");
+            buffer_to_text(terminal.backend().buffer())
+        }
+    }
+
+    /// Compares against `src/ui/snapshots/<name>.txt`; set `UPDATE_SNAPSHOTS=1`
+    /// to record new or changed snapshots.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/ui/snapshots")
+            .join(format!("{name}.txt"));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().expect("snapshot dir")).expect("mkdir");
+            std::fs::write(&path, actual).expect("write snapshot");
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("snapshot '{name}' is missing; rerun with UPDATE_SNAPSHOTS=1 to record it")
+        });
+        assert!(
+            expected == actual,
+            "snapshot '{name}' differs; rerun with UPDATE_SNAPSHOTS=1 to accept\n--- expected\n{expected}\n--- actual\n{actual}"
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_dashboard() {
+        let h = Harness::new();
+        assert_snapshot("dashboard", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_dashboard_compact_with_actions_focused() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char('z')).await;
+        h.press(KeyCode::Char('a')).await;
+        h.press(KeyCode::Down).await;
+        assert_snapshot("dashboard_compact_actions", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_dashboard_accessible() {
+        let mut h = Harness::new();
+        h.state.accessible = true;
+        assert_snapshot("dashboard_accessible", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_provider_manager() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char('a')).await;
+        h.press(KeyCode::Down).await;
+        h.press(KeyCode::Enter).await;
+        assert_eq!(h.state.screen, Screen::ProviderManager);
+        assert_snapshot("provider_manager", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_provider_form_add() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::ProviderManager;
+        h.press(KeyCode::Char('n')).await;
+        for ch in "acme".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        assert_eq!(h.state.screen, Screen::ProviderForm(ProviderFormMode::Add));
+        assert_snapshot("provider_form_add", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_confirm_quit() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char('q')).await;
+        assert_eq!(h.state.screen, Screen::Confirm(ConfirmAction::Quit));
+        assert_snapshot("confirm_quit", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn snapshot_alerts_and_simulator_empty() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char('h')).await;
+        assert_eq!(h.state.screen, Screen::Alerts);
+        assert_snapshot("alerts_empty", &h.render(120, 30));
+
+        h.press(KeyCode::Esc).await;
+        h.press(KeyCode::Char('s')).await;
+        assert_eq!(h.state.screen, Screen::Simulator);
+        assert_snapshot("simulator_empty", &h.render(120, 30));
+    }
+
+    #[tokio::test]
+    async fn model_detail_compares_peak_tokens_per_minute_with_the_limit() {
+        let mut h = Harness::new();
+        h.cfg.usage_granularity = Granularity::Minute;
+        let now = Utc::now();
+        let usage = crate::models::UsageRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            input_tokens: 20_000,
+            output_tokens: 5_000,
+            cached_tokens: 0,
+            timestamp: now - Duration::minutes(5),
+            bucket_end: None,
+            source_granularity: Some(Granularity::Minute),
+            source: crate::models::UsageSource::Api,
+            avg_latency_ms: Some(820.0),
+        };
+        let limit = crate::models::RateLimit {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            tokens_per_minute: 30_000,
+            requests_per_minute: Some(500),
+            fetched_at: now,
+        };
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &[], &[usage], &[])?;
+                s.replace_rate_limits("openai", &[limit])
+            })
+            .await
+            .expect("seed");
+
+        h.press(KeyCode::Char('s')).await;
+        h.press(KeyCode::Char('i')).await;
+        assert_eq!(h.state.screen, Screen::ModelDetail);
+        let text = h.render(120, 30);
+        assert!(text.contains("openai / gpt-4o"));
+        assert!(text.contains("Avg latency: 820 ms"));
+        assert!(text.contains("Rate limit: 30000 tokens/min, 500 requests/min"));
+        assert!(text.contains("Observed peak: 25000 tokens/min"));
+        assert!(text.contains("Utilization: 83% of the token limit; consider a limit"));
+
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::Simulator);
+    }
+
+    #[tokio::test]
+    async fn budgets_screen_shows_live_spend_while_editing() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let row = |model: &str, total: f64| crate::models::CostRecord {
+            provider: "openai".into(),
+            model: model.into(),
+            timestamp: now,
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        };
+        let rows = vec![row("gpt-4o", 12.0), row("gpt-4o-mini", 3.0)];
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+        h.cfg.budgets = vec![Budget {
+            name: "openai-cap".into(),
+            scope: crate::config::BudgetScope::Provider("openai".into()),
+            amount_usd: 10.0,
+            period: crate::config::BudgetPeriod::Daily,
+            on_exceeded: Some("disable-key".into()),
+        }];
+
+        h.press(KeyCode::Char('b')).await;
+        assert_eq!(h.state.screen, Screen::Budgets);
+        assert_eq!(h.state.budget_spend, vec![Some(15.0)]);
+        assert_snapshot("budgets", &h.render(120, 30));
+
+        h.press(KeyCode::Enter).await;
+        assert_eq!(
+            h.state.screen,
+            Screen::BudgetForm(BudgetFormMode::Edit { index: 0 })
+        );
+        assert_eq!(h.state.budget_draft.spent, Some(15.0));
+        assert_snapshot("budget_form_edit", &h.render(120, 30));
+
+        // Scope -> model picks from stored models and reprices the spend.
+        h.press(KeyCode::Tab).await;
+        h.press(KeyCode::Right).await;
+        assert_eq!(h.state.budget_draft.scope, BudgetScopeKind::Model);
+        assert_eq!(h.state.budget_draft.spent, None);
+        h.press(KeyCode::Tab).await;
+        h.press(KeyCode::Right).await;
+        h.press(KeyCode::Right).await;
+        assert_eq!(h.state.budget_draft.target, "gpt-4o-mini");
+        assert_eq!(h.state.budget_draft.spent, Some(3.0));
+
+        h.press(KeyCode::Esc).await;
+        h.press(KeyCode::Char('d')).await;
+        assert!(matches!(
+            h.state.screen,
+            Screen::Confirm(ConfirmAction::DeleteBudget { index: 0, .. })
+        ));
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::Budgets);
+    }
+
+    #[tokio::test]
+    async fn provider_removal_previews_what_it_deletes() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let rows = vec![crate::models::CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            timestamp: now,
+            input_cost: 1.0,
+            output_cost: 0.0,
+            total_cost: 1.0,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        }];
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+        h.cfg.provider_settings.insert(
+            "openai".into(),
+            ProviderSettings {
+                base_url: Some("https://proxy.example.com".into()),
+                ..ProviderSettings::default()
+            },
+        );
+        h.state.screen = Screen::ProviderManager;
+        h.state.provider_selected = 1;
+
+        h.press(KeyCode::Char('d')).await;
+        assert!(matches!(
+            h.state.screen,
+            Screen::Confirm(ConfirmAction::DeleteProvider { .. })
+        ));
+        let preview = &h.state.deletion_preview;
+        assert_eq!(
+            preview.config_section,
+            vec![
+                "[provider_settings.openai]".to_string(),
+                "base_url = \"https://proxy.example.com\"".to_string()
+            ]
+        );
+        assert_eq!(preview.keyring_entry, "llm-meter/default / provider:openai");
+        assert_eq!((preview.usage_rows, preview.cost_rows), (0, 1));
+        assert!(h
+            .render(120, 40)
+            .contains("history: 0 usage and 1 cost rows kept"));
+
+        h.press(KeyCode::Char('p')).await;
+        assert!(h.state.deletion_preview.purge_history);
+        assert!(h.render(120, 40).contains("[x] purge stored history (p)"));
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::ProviderManager);
+
+        h.press(KeyCode::Char('k')).await;
+        assert!(h.state.deletion_preview.config_section.is_empty());
+        assert!(!h.state.deletion_preview.purge_history);
+        h.press(KeyCode::Char('p')).await;
+        assert!(!h.state.deletion_preview.purge_history);
+    }
+
+    #[tokio::test]
+    async fn undo_restores_a_removed_provider() {
+        let mut h = Harness::new();
+        let settings = ProviderSettings {
+            base_url: Some("https://proxy.example.com".into()),
+            ..ProviderSettings::default()
+        };
+        h.cfg.provider_settings.insert("openai".into(), settings);
+        h.cfg.enabled_providers = vec!["openai".into()];
+        h.state.screen = Screen::ProviderManager;
+        h.state.provider_selected = 1;
+
+        h.press(KeyCode::Char('d')).await;
+        h.press(KeyCode::Right).await;
+        h.press(KeyCode::Enter).await;
+        assert_eq!(h.state.status, "Provider 'openai' removed (u to undo)");
+        assert!(!h.cfg.provider_settings.contains_key("openai"));
+        assert!(h.cfg.enabled_providers.is_empty());
+
+        h.press(KeyCode::Char('u')).await;
+        assert_eq!(h.state.status, "Restored 'openai'");
+        assert_eq!(
+            h.cfg
+                .provider_settings
+                .get("openai")
+                .and_then(|s| s.base_url.as_deref()),
+            Some("https://proxy.example.com")
+        );
+        assert_eq!(h.cfg.enabled_providers, vec!["openai".to_string()]);
+        assert!(h.state.undo_removal.is_none());
+
+        h.press(KeyCode::Char('u')).await;
+        assert_eq!(h.state.status, "Nothing to undo.");
+    }
+
+    #[tokio::test]
+    async fn window_row_shows_today_7d_and_30d_side_by_side() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let cost = |at, total| CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            timestamp: at,
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        };
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(
+                    now - Duration::days(40),
+                    &[],
+                    &[],
+                    &[
+                        cost(now, 1.0),
+                        cost(now - Duration::days(3), 2.0),
+                        cost(now - Duration::days(20), 4.0),
+                    ],
+                )
+            })
+            .await
+            .expect("seed");
+        h.press(KeyCode::Char('w')).await;
+        assert!(h.state.multi_window);
+        assert_eq!(h.state.view.window_costs, Some([1.0, 3.0, 7.0]));
+        let text = h.render(120, 30);
+        assert!(text.contains("> Last 7d"));
+        assert!(text.contains("$7.0000"));
+        h.press(KeyCode::Char('w')).await;
+        assert!(!h.render(120, 30).contains("Last 30d"));
+    }
+
+    #[tokio::test]
+    async fn cost_chart_follows_the_window_until_toggled_between_hours_and_days() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let cost = |at, total| CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            timestamp: at,
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        };
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(
+                    now - Duration::days(10),
+                    &[],
+                    &[],
+                    &[
+                        cost(now - Duration::minutes(30), 1.0),
+                        cost(now - Duration::hours(5), 2.0),
+                        cost(now - Duration::days(3), 4.0),
+                    ],
+                )
+            })
+            .await
+            .expect("seed");
+        let total = |h: &Harness| h.state.view.cost_series.iter().map(|(_, c)| c).sum::<f64>();
+
+        h.press(KeyCode::Char('1')).await;
+        assert_eq!(h.state.chart_width(), Granularity::Hour);
+        assert!(h.state.view.cost_series.len() >= 24);
+        assert!((total(&h) - 3.0).abs() < 1e-9);
+        assert!(h.render(120, 30).contains("Cost per hour · peak $2.0000"));
+
+        h.press(KeyCode::Char('t')).await;
+        assert_eq!(h.state.chart_width(), Granularity::Day);
+        assert!(h.state.view.cost_series.len() <= 2);
+        assert!(h.render(120, 30).contains("Cost per day"));
+
+        h.press(KeyCode::Char('7')).await;
+        h.press(KeyCode::Char('t')).await;
+        assert_eq!(h.state.status, "cost chart by hour");
+        assert!(h.state.view.cost_series.len() >= 24 * 7);
+        assert!((total(&h) - 7.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn share_gauges_show_each_providers_part_of_spend() {
+        let mut h = Harness::new();
+        h.state.view.provider_breakdown = vec![("anthropic".into(), 1.0), ("openai".into(), 3.0)];
+        h.state.view.model_breakdown = vec![("gpt-4o".into(), 3.0), ("claude".into(), 1.0)];
+        h.press(KeyCode::Char('g')).await;
+        assert!(h.state.share_gauges);
+        let text = h.render(120, 30);
+        assert!(text.contains("openai     75%"));
+        assert!(text.contains("anthropic  25%"));
+        assert!(text.contains("gpt-4o  75%"));
+        assert!(text.find("openai").unwrap() < text.find("anthropic").unwrap());
+
+        h.state.view.provider_breakdown.clear();
+        assert!(h.render(120, 30).contains("no spend in this window"));
+        h.press(KeyCode::Char('g')).await;
+        assert!(h.render(120, 30).contains("Provider"));
+    }
+
+    #[tokio::test]
+    async fn source_breakdown_replaces_the_provider_panel() {
+        let mut h = Harness::new();
+        h.state.view.provider_breakdown = vec![("openai".into(), 3.0)];
+        h.state.view.source_breakdown = vec![("api".into(), 3.0), ("local".into(), 1.5)];
+        h.press(KeyCode::Char('o')).await;
+        assert!(h.state.by_source);
+        let text = h.render(120, 30);
+        assert!(text.contains("Cost By Source"));
+        assert!(text.contains("local"));
+        assert!(text.contains("$1.5000"));
+        assert!(!text.contains("Cost By Provider"));
+
+        h.press(KeyCode::Char('o')).await;
+        assert!(h.render(120, 30).contains("Cost By Provider"));
+    }
+
+    #[tokio::test]
+    async fn column_picker_adds_share_and_delta_columns() {
+        let mut h = Harness::new();
+        h.state.view.provider_breakdown = vec![("openai".into(), 3.0), ("anthropic".into(), 1.0)];
+        h.state.view.details.providers.insert(
+            "openai".into(),
+            BreakdownDetail {
+                input_tokens: 900,
+                output_tokens: 100,
+                previous_cost: 2.0,
+            },
+        );
+
+        h.press(KeyCode::Char('c')).await;
+        assert_eq!(h.state.screen, Screen::ColumnPicker);
+        assert!(h.render(120, 30).contains("[x] Cost"));
+        for _ in 0..3 {
+            h.press(KeyCode::Down).await;
+        }
+        h.press(KeyCode::Char(' ')).await;
+        h.press(KeyCode::Down).await;
+        h.press(KeyCode::Char(' ')).await;
+        assert_eq!(
+            h.state.column_draft,
+            vec![
+                BreakdownColumn::Cost,
+                BreakdownColumn::Share,
+                BreakdownColumn::Delta
+            ]
+        );
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.breakdown_columns, vec![BreakdownColumn::Cost]);
+
+        h.state.breakdown_columns = h.state.column_draft.clone();
+        let text = h.render(160, 30);
+        assert!(text.contains("Δ prev"));
+        assert!(text.contains("75%"));
+        assert!(text.contains("+$1.0000"));
+    }
+
+    #[tokio::test]
+    async fn pinned_models_are_marked_and_offered_for_unpinning() {
+        let mut h = Harness::new();
+        h.cfg.display.pinned_models = vec!["claude-3-5-sonnet".into()];
+        h.state.view.model_breakdown = vec![
+            ("claude-3-5-sonnet".into(), 0.0),
+            ("gpt-4o-mini".into(), 4.0),
+        ];
+        let text = h.render(120, 30);
+        assert!(text.contains("* claude-3-5-sonnet"));
+        assert!(!text.contains("* gpt-4o-mini"));
+
+        let labels: Vec<String> = palette_entries(&h.cfg, &h.state.view.model_breakdown)
+            .into_iter()
+            .map(|e| e.label)
+            .collect();
+        assert!(labels.contains(&"unpin claude-3-5-sonnet model".to_string()));
+        assert!(labels.contains(&"pin gpt-4o-mini model".to_string()));
+    }
+
+    #[tokio::test]
+    async fn search_screen_filters_and_pages_stored_rows() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let rows = (0..15)
+            .map(|i| crate::models::CostRecord {
+                provider: "openai".into(),
+                model: if i % 3 == 0 { "o1" } else { "gpt-4o" }.into(),
+                timestamp: now - Duration::hours(i),
+                input_cost: 1.0,
+                output_cost: 0.0,
+                total_cost: 1.0,
+                currency: "USD".into(),
+                bucket_end: None,
+                source_granularity: None,
+                source: crate::models::UsageSource::Api,
+            })
+            .collect::<Vec<_>>();
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+
+        h.press(KeyCode::Char('/')).await;
+        assert_eq!(h.state.screen, Screen::Search);
+        assert_eq!(h.state.search.total, 15);
+        assert_eq!(h.state.search.cost.len(), SEARCH_PAGE_SIZE);
+        h.press(KeyCode::PageDown).await;
+        assert_eq!(h.state.search.cost.len(), 15 - SEARCH_PAGE_SIZE);
+        assert!(h.render(120, 40).contains("15 rows · page 2/2"));
+
+        h.press(KeyCode::Tab).await;
+        for ch in "gpt".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Enter).await;
+        assert_eq!(h.state.search.total, 10);
+        assert_eq!(h.state.search.page, 0);
+
+        h.press(KeyCode::Tab).await;
+        h.press(KeyCode::Tab).await;
+        for ch in "yesterday".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Enter).await;
+        assert_eq!(h.state.status, "From must be a YYYY-MM-DD date");
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::Dashboard);
+    }
+
+    #[tokio::test]
+    async fn sql_console_shows_select_results_and_refuses_writes() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::SqlConsole;
+        for ch in "SELECT 'gpt-4o' AS model, 2 AS n".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Enter).await;
+        let result = h.state.sql_result.clone().expect("result");
+        assert_eq!(result.columns, vec!["model", "n"]);
+        assert_eq!(
+            result.rows,
+            vec![vec!["gpt-4o".to_string(), "2".to_string()]]
+        );
+        assert!(h.render(120, 30).contains(" 1 rows "));
+
+        h.state.sql_input = "DELETE FROM cost_records".into();
+        h.press(KeyCode::Enter).await;
+        assert!(h.state.sql_result.is_none());
+        assert!(h.state.status.starts_with("query failed"));
+    }
+
+    #[tokio::test]
+    async fn model_groups_merge_dated_snapshots_into_their_family() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let row = |model: &str, total: f64| crate::models::CostRecord {
+            provider: "openai".into(),
+            model: model.into(),
+            timestamp: now,
+            input_cost: total,
+            output_cost: 0.0,
+            total_cost: total,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        };
+        let rows = vec![
+            row("gpt-4o-2024-05-13", 2.0),
+            row("gpt-4o-2024-08-06", 3.0),
+            row("gpt-4o-mini", 4.0),
+        ];
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+
+        h.press(KeyCode::Char('f')).await;
+        assert!(!h.state.group_models);
+        assert_eq!(h.state.status, "no [model_groups] configured");
+
+        h.cfg.model_groups =
+            std::collections::BTreeMap::from([("gpt-4o".into(), vec!["gpt-4o-20*".into()])]);
+        h.press(KeyCode::Char('f')).await;
+        assert!(h.state.group_models);
+        assert_eq!(
+            h.state.view.model_breakdown,
+            vec![("gpt-4o".into(), 5.0), ("gpt-4o-mini".into(), 4.0)]
+        );
+        assert!(h.render(120, 30).contains("Top Model Families"));
+
+        h.press(KeyCode::Char('f')).await;
+        assert_eq!(h.state.view.model_breakdown.len(), 3);
+    }
+
+    #[test]
+    fn saved_view_lists_the_shown_tables_in_display_currency() {
+        let tmp = TempDir::new().expect("tempdir");
+        let now = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 3, 12, 15, 30, 0).unwrap();
+        let mut state = AppState::default();
+        state.view.provider_breakdown = vec![("openai".into(), 2.0), ("retired".into(), 0.5)];
+        state.view.provider_as_of = std::collections::HashMap::from([
+            ("openai".to_string(), Some(now)),
+            ("retired".to_string(), None),
+        ]);
+        state.view.model_breakdown = vec![("gpt-4o, mini".into(), 2.0)];
+        let money = MoneyFormat::from_config(&crate::config::DisplayConfig {
+            currency: "EUR".into(),
+            usd_rate: Some(0.5),
+            ..crate::config::DisplayConfig::default()
+        });
+
+        let path = save_view_csv(&state, &money, tmp.path(), now).unwrap();
+        assert_eq!(path, tmp.path().join("view-7d-20260312-153000.csv"));
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "table,name,cost,currency,window,as_of\n\
+             provider,openai,1.0000,EUR,7d,2026-03-12T15:30:00+00:00\n\
+             provider,retired,0.2500,EUR,7d,\n\
+             model,\"gpt-4o, mini\",1.0000,EUR,7d,\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_providers_are_marked_with_their_as_of_time() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        h.state.view.data_as_of = Some(now - Duration::hours(30));
+        h.state.view.provider_breakdown = vec![
+            ("anthropic".into(), 1.0),
+            ("openai".into(), 2.0),
+            ("retired".into(), 0.5),
+        ];
+        h.state.view.provider_as_of = std::collections::HashMap::from([
+            ("openai".to_string(), Some(now - Duration::hours(30))),
+            ("anthropic".to_string(), None),
+        ]);
+        let text = h.render(120, 30);
+        let row = |name: &str| {
+            text.lines()
+                .find(|l| l.contains(&format!("│{name} ")))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let stale_at = (now - Duration::hours(30))
+            .format("%m-%d %H:%M")
+            .to_string();
+        assert!(row("openai").contains(&format!("{stale_at} !")));
+        assert!(row("anthropic").contains("never"));
+        assert!(row("retired").contains(" - "));
+        assert!(text.contains(&format!("Cost · stale, as of {stale_at}")));
+    }
+
+    #[tokio::test]
+    async fn providers_with_a_balance_show_their_credit() {
+        let mut h = Harness::new();
+        h.state.view.provider_breakdown = vec![("deepseek".into(), 1.0), ("openai".into(), 2.0)];
+        let text = h.render(120, 30);
+        assert!(!text.contains("Credit"));
+
+        h.state.view.balances = std::collections::HashMap::from([(
+            "deepseek".to_string(),
+            AccountBalance {
+                provider: "deepseek".into(),
+                amount: 110.0,
+                currency: "CNY".into(),
+                fetched_at: Utc::now(),
+            },
+        )]);
+        let text = h.render(120, 30);
+        let row = |name: &str| {
+            text.lines()
+                .find(|l| l.contains(&format!("│{name} ")))
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(text.contains("Credit"));
+        assert!(row("deepseek").contains("110.00 CNY"));
+        assert!(row("openai").contains(" - "));
+    }
+
+    #[test]
+    fn header_warns_about_clock_skew() {
+        let mut h = Harness::new();
+        assert!(!h.render(160, 30).contains("clock skew"));
+        h.state.clock_skew = Some(ClockSkew {
+            provider: "anthropic".into(),
+            seconds: -400,
+        });
+        assert!(h
+            .render(160, 30)
+            .contains("clock skew: local clock is 6m 40s behind anthropic"));
+    }
+
+    #[tokio::test]
+    async fn refresh_runs_in_the_background_and_esc_cancels_it() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char('r')).await;
+        assert!(h.jobs.refresh.is_some());
+        let text = h.render(120, 30);
+        assert!(text.contains("Refreshing"));
+        assert!(text.contains("Accounts not done after 120s keep their stored data."));
+
+        h.press(KeyCode::Char('r')).await;
+        assert_eq!(h.state.status, "refresh already running · Esc cancels");
+        h.press(KeyCode::Esc).await;
+        assert!(h.jobs.refresh.is_none() && h.state.refreshing_since.is_none());
+        assert_eq!(h.state.status, "refresh cancelled; stored data kept");
+        assert!(!h.render(120, 30).contains("Refreshing"));
+
+        h.press(KeyCode::Char('r')).await;
+        h.settle_refresh().await;
+        assert!(h.jobs.refresh.is_none());
+        assert_eq!(h.state.status, "ok");
+    }
+
+    #[tokio::test]
+    async fn esc_on_the_progress_panel_cancels_and_keeps_stored_data() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let rows = vec![crate::models::CostRecord {
+            provider: "openai".into(),
+            model: "gpt-4o".into(),
+            timestamp: now - Duration::hours(1),
+            input_cost: 2.0,
+            output_cost: 0.0,
+            total_cost: 2.0,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        }];
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+        load_dashboard_view(&mut h.state, &h.cfg, &h.storage).await;
+        let before = h.state.view.model_breakdown.clone();
+        assert_eq!(before, vec![("gpt-4o".to_string(), 2.0)]);
+
+        h.press(KeyCode::Char('r')).await;
+        assert!(h.jobs.refresh.is_some() && h.state.refreshing_since.is_some());
+        assert!(h.render(120, 30).contains("Refreshing"));
+        h.press(KeyCode::Esc).await;
+
+        assert!(h.jobs.refresh.is_none());
+        assert!(h.state.refreshing_since.is_none());
+        assert!(h.state.status.starts_with("refresh cancelled"));
+        assert_eq!(h.state.screen, Screen::Dashboard);
+        assert_eq!(h.state.view.model_breakdown, before);
+        let stored = h
+            .storage
+            .call(|s| s.provider_row_counts("openai"))
+            .await
+            .expect("row counts");
+        assert_eq!(stored, (0, 1));
+    }
+
+    #[tokio::test]
+    async fn startup_without_refresh_shows_stored_data() {
+        let mut h = Harness::new();
+        let at = Utc::now() - Duration::hours(1);
+        h.storage
+            .call(move |s| s.record_alert("refresh_failed", "dashboard", "HTTP 500", at))
+            .await
+            .expect("record alert");
+        show_stored_data(&mut h.state, &h.cfg, &h.storage).await;
+        assert!(h.jobs.refresh.is_none());
+        assert_eq!(h.state.unacked_alerts, 1);
+        assert_eq!(h.state.status, "showing stored data · press r to refresh");
+    }
+
+    #[tokio::test]
+    async fn provider_manager_shows_last_refresh_rows_and_error() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::ProviderManager;
+        let at = Utc::now() - Duration::minutes(5);
+        h.storage
+            .call(move |s| {
+                s.record_refresh("openai", 24, at, Ok(2), None)?;
+                s.record_refresh("anthropic", 24, at, Err("HTTP 500"), None)
+            })
+            .await
+            .expect("record refreshes");
+        load_provider_health(&mut h.state, &h.cfg, &h.storage).await;
+
+        let text = h.render(120, 30);
+        let row = |name: &str| {
+            text.lines()
+                .find(|l| l.contains(&format!("│{name} ")))
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(text.contains("Last ok") && text.contains("Last failure"));
+        assert!(row("openai").contains(&as_of_label(at, Utc::now())));
+        assert!(!row("openai").contains("HTTP 500"));
+        assert!(row("anthropic").contains("never"));
+        assert!(row("anthropic").contains("HTTP 500"));
+    }
+
+    #[test]
+    fn recovery_dialog_lists_actions_and_needs_a_backup_to_restore() {
+        assert_eq!(
+            recovery_action(KeyCode::Char('o'), false),
+            Some(RecoveryAction::ReadOnly)
+        );
+        assert_eq!(recovery_action(KeyCode::Char('b'), false), None);
+        assert_eq!(
+            recovery_action(KeyCode::Char('B'), true),
+            Some(RecoveryAction::RestoreBackup)
+        );
+        assert_eq!(
+            recovery_action(KeyCode::Esc, false),
+            Some(RecoveryAction::Quit)
+        );
+
+        let at = chrono::TimeZone::with_ymd_and_hms(&Utc, 2026, 3, 9, 4, 5, 0).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).expect("terminal");
+        terminal
+            .draw(|f| {
+                render_recovery(
+                    f,
+                    std::path::Path::new("/data/snapshots.sqlite"),
+                    "storage error: database is locked",
+                    Some(at),
+                    false,
+                )
+            })
+            .expect("draw");
+        assert_snapshot(
+            "database_recovery",
+            &buffer_to_text(terminal.backend().buffer()),
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_prompt_starts_from_the_current_name() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::ProviderManager;
+        h.state.provider_selected = 1;
+        h.press(KeyCode::Char('r')).await;
+        assert_eq!(
+            h.state.screen,
+            Screen::RenameProvider {
+                provider: "openai".into()
+            }
+        );
+        for ch in "-prodz".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Backspace).await;
+        assert_eq!(h.state.rename_input, "openai-prod");
+        assert!(!h.state.compact_mode);
+        assert_snapshot("rename_provider", &h.render(120, 30));
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::ProviderManager);
+    }
+
+    #[tokio::test]
+    async fn command_palette_filters_and_runs_commands() {
+        let mut h = Harness::new();
+        h.press(KeyCode::Char(':')).await;
+        assert_eq!(h.state.screen, Screen::CommandPalette);
+        for ch in "win30".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        assert_snapshot("command_palette", &h.render(120, 30));
+        h.press(KeyCode::Enter).await;
+        assert_eq!(h.state.screen, Screen::Dashboard);
+        assert_eq!(h.state.window, TimeWindow::ThirtyDays);
+
+        h.press(KeyCode::Char(':')).await;
+        for ch in "theme".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Enter).await;
+        assert!(h.state.accessible);
+
+        h.press(KeyCode::Char(':')).await;
+        for ch in "provider anthropic".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Enter).await;
+        assert_eq!(
+            h.state.screen,
+            Screen::ProviderForm(ProviderFormMode::Edit {
+                provider: "anthropic".into()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn spend_on_retired_models_raises_a_banner() {
+        let mut h = Harness::new();
+        let now = Utc::now();
+        let rows = vec![crate::models::CostRecord {
+            provider: "openai".into(),
+            model: "o1-preview".into(),
+            timestamp: now - Duration::hours(2),
+            input_cost: 2.0,
+            output_cost: 0.0,
+            total_cost: 2.0,
+            currency: "USD".into(),
+            bucket_end: None,
+            source_granularity: None,
+            source: crate::models::UsageSource::Api,
+        }];
+        h.storage
+            .call(move |s| {
+                s.replace_snapshot(now - Duration::days(1), &["openai".into()], &[], &rows)
+            })
+            .await
+            .expect("seed");
+        h.cfg.enabled_providers = vec!["openai".into()];
+
+        h.press(KeyCode::Char('1')).await;
+        assert_eq!(h.state.view.deprecated.len(), 1);
+        let text = h.render(120, 30);
+        assert!(
+            text.contains("! Spend on deprecated models: o1-preview (retired 2025-07-28, use o3)")
+        );
+    }
+
+    #[tokio::test]
+    async fn reauth_banner_jumps_to_key_field() {
+        let mut h = Harness::new();
+        h.state.needs_reauth = vec!["openai".into()];
+        assert_snapshot("dashboard_reauth_banner", &h.render(120, 30));
+
+        h.press(KeyCode::Char('u')).await;
+        assert_eq!(
+            h.state.screen,
+            Screen::ProviderForm(ProviderFormMode::Edit {
+                provider: "openai".into()
+            })
+        );
+        assert_eq!(
+            active_form_field(
+                &h.state,
+                &ProviderFormMode::Edit {
+                    provider: "openai".into()
+                }
+            ),
+            ProviderFormField::ApiKey
+        );
+    }
+
+    #[tokio::test]
+    async fn backfill_is_offered_only_before_first_refresh() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::ProviderManager;
+        offer_backfill(&mut h.state, &h.storage, "openai").await;
+        assert_eq!(
+            h.state.screen,
+            Screen::Confirm(ConfirmAction::Backfill {
+                provider: "openai".into()
+            })
+        );
+        assert_snapshot("confirm_backfill", &h.render(120, 30));
+        h.press(KeyCode::Esc).await;
+        assert_eq!(h.state.screen, Screen::ProviderManager);
+
+        h.storage
+            .call(|s| s.record_refresh("openai", 24, Utc::now(), Ok(1), None))
+            .await
+            .expect("record refresh");
+        offer_backfill(&mut h.state, &h.storage, "openai").await;
+        assert_eq!(h.state.screen, Screen::ProviderManager);
+    }
+
+    #[tokio::test]
+    async fn azure_form_takes_resource_and_deployment_map() {
+        let mut h = Harness::new();
+        h.state.screen = Screen::ProviderManager;
+        h.press(KeyCode::Char('n')).await;
+        h.state.provider_draft.name = "azure-openai".into();
+        h.press(KeyCode::Tab).await;
+        h.press(KeyCode::Tab).await;
+        let resource =
+            "/subscriptions/s1/resourceGroups/ai/providers/Microsoft.CognitiveServices/accounts/contoso";
+        for ch in resource.chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        h.press(KeyCode::Tab).await;
+        h.press(KeyCode::Tab).await;
+        for ch in "prod-chat=gpt-4o, embed = text-embedding-3-small".chars() {
+            h.press(KeyCode::Char(ch)).await;
+        }
+        assert_eq!(h.state.provider_draft.resource, resource);
+        let screen = h.render(120, 30);
+        assert!(screen.contains("Resource ID"));
+        assert!(screen.contains("prod-chat=gpt-4o"));
+
+        let mut settings = ProviderSettings::default();
+        apply_azure_draft(&h.state.provider_draft, &mut settings).expect("valid form");
+        assert_eq!(settings.resource.as_deref(), Some(resource));
+        assert_eq!(settings.api_version, None);
+        assert_eq!(
+            settings.deployments.get("embed").map(String::as_str),
+            Some("text-embedding-3-small")
+        );
+
+        h.state.provider_draft.deployments = "prod-chat".into();
+        assert!(apply_azure_draft(&h.state.provider_draft, &mut settings).is_err());
+    }
 
     #[test]
     fn visible_form_fields_for_add_defaults_to_minimal_inputs() {
-        let fields = visible_form_fields(&ProviderFormMode::Add, false);
+        let fields = visible_form_fields(&ProviderFormMode::Add, false, false);
         assert_eq!(
             fields,
             vec![
@@ -1632,6 +6139,7 @@ mod tests {
                 provider: "openai".into(),
             },
             true,
+            false,
         );
         assert_eq!(
             fields,
@@ -1639,6 +6147,9 @@ mod tests {
                 ProviderFormField::ApiKey,
                 ProviderFormField::BaseUrl,
                 ProviderFormField::OrganizationId,
+                ProviderFormField::Owner,
+                ProviderFormField::Notes,
+                ProviderFormField::KeyExpiresAt,
                 ProviderFormField::Enabled,
             ]
         );
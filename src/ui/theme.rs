@@ -0,0 +1,67 @@
+use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+use ratatui::symbols::border;
+use ratatui::widgets::{Block, Borders};
+
+pub const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+pub fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+pub fn bordered(accessible: bool) -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    if accessible {
+        block.border_set(ASCII_BORDER)
+    } else {
+        block
+    }
+}
+
+pub fn selected_marker(accessible: bool) -> &'static str {
+    if accessible {
+        "[SELECTED] "
+    } else {
+        "> "
+    }
+}
+
+pub fn strip_colors(buf: &mut Buffer) {
+    for cell in buf.content.iter_mut() {
+        cell.set_fg(Color::Reset);
+        cell.set_bg(Color::Reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    #[test]
+    fn strip_colors_resets_fg_and_bg() {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 4, 1));
+        buf.set_string(0, 0, "ab", Style::default().fg(Color::Red).bg(Color::Blue));
+        strip_colors(&mut buf);
+        for cell in buf.content.iter() {
+            assert_eq!(cell.fg, Color::Reset);
+            assert_eq!(cell.bg, Color::Reset);
+        }
+    }
+
+    #[test]
+    fn selected_marker_is_textual_in_accessible_mode() {
+        assert_eq!(selected_marker(true), "[SELECTED] ");
+        assert_eq!(selected_marker(false), "> ");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::aws::{self, uri_encode, AwsCredentials};
+use crate::error::AppError;
+use crate::export::ExportFormat;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+
+/// Where `export --to` uploads the rendered document. Keys and URLs may carry
+/// `{date}`, `{year}`, `{month}`, `{day}`, `{hour}` and `{timestamp}`
+/// placeholders; a trailing `/` gets a default `llm-costs-{date}.<ext>` name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    S3 { bucket: String, key: String },
+    Http(String),
+}
+
+impl Destination {
+    pub fn parse(raw: &str) -> Result<Self, AppError> {
+        if let Some(rest) = raw.strip_prefix("s3://") {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            if bucket.is_empty() {
+                return Err(AppError::Config(format!("missing bucket in '{raw}'")));
+            }
+            return Ok(Self::S3 {
+                bucket: bucket.into(),
+                key: key.into(),
+            });
+        }
+        if raw.starts_with("https://") || raw.starts_with("http://") {
+            return Ok(Self::Http(raw.into()));
+        }
+        Err(AppError::Config(
+            "Unsupported export destination. Use google-sheets, s3://bucket/prefix/ or an https:// URL".into(),
+        ))
+    }
+}
+
+/// Expands the date placeholders and appends the default file name when the
+/// template ends in `/` (or is empty).
+pub fn expand_template(template: &str, format: ExportFormat, now: DateTime<Utc>) -> String {
+    let mut out = template.to_string();
+    if out.is_empty() || out.ends_with('/') {
+        out.push_str(&format!("llm-costs-{{date}}.{}", format.extension()));
+    }
+    for (placeholder, pattern) in [
+        ("{date}", "%Y-%m-%d"),
+        ("{year}", "%Y"),
+        ("{month}", "%m"),
+        ("{day}", "%d"),
+        ("{hour}", "%H"),
+        ("{timestamp}", "%Y%m%dT%H%M%SZ"),
+    ] {
+        out = out.replace(placeholder, &now.format(pattern).to_string());
+    }
+    out
+}
+
+fn object_url(creds: &AwsCredentials, bucket: &str, key: &str) -> String {
+    let path = uri_encode(key, true);
+    match &creds.endpoint {
+        Some(endpoint) => format!("{}/{bucket}/{path}", endpoint.trim_end_matches('/')),
+        None => format!("https://{bucket}.s3.{}.amazonaws.com/{path}", creds.region),
+    }
+}
+
+/// SigV4 headers for a `PUT` of a body hashing to `payload_hash`.
+fn sign_put(
+    creds: &AwsCredentials,
+    url: &reqwest::Url,
+    payload_hash: &str,
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    aws::sign(
+        creds,
+        &creds.region,
+        "s3",
+        "PUT",
+        url,
+        vec![("x-amz-content-sha256", payload_hash.to_string())],
+        payload_hash,
+        now,
+    )
+}
+
+/// Uploads `body` and returns where it landed (`s3://bucket/key` or the URL).
+pub async fn put(
+    client: &Client,
+    destination: &Destination,
+    format: ExportFormat,
+    body: Vec<u8>,
+    now: DateTime<Utc>,
+) -> Result<String, AppError> {
+    let bad_url = |e: url::ParseError| AppError::Config(format!("invalid export URL: {e}"));
+    match destination {
+        Destination::S3 { bucket, key } => {
+            let creds = AwsCredentials::from_env().ok_or_else(|| {
+                AppError::Config(
+                    "S3 export needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY.".into(),
+                )
+            })?;
+            let key = expand_template(key, format, now);
+            let url = reqwest::Url::parse(&object_url(&creds, bucket, &key)).map_err(bad_url)?;
+            let payload_hash = hex::encode(Sha256::digest(&body));
+            let mut request = client
+                .put(url.clone())
+                .header("content-type", format.content_type());
+            for (name, value) in sign_put(&creds, &url, &payload_hash, now) {
+                request = request.header(name, value);
+            }
+            request.body(body).send().await?.error_for_status()?;
+            Ok(format!("s3://{bucket}/{key}"))
+        }
+        Destination::Http(template) => {
+            let url = expand_template(template, format, now);
+            client
+                .put(reqwest::Url::parse(&url).map_err(bad_url)?)
+                .header("content-type", format.content_type())
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(url)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn creds() -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+            region: "eu-west-1".into(),
+            endpoint: None,
+        }
+    }
+
+    #[test]
+    fn destinations_parse_bucket_prefix_and_urls() {
+        assert_eq!(
+            Destination::parse("s3://lake/llm/{year}/").unwrap(),
+            Destination::S3 {
+                bucket: "lake".into(),
+                key: "llm/{year}/".into()
+            }
+        );
+        assert_eq!(
+            Destination::parse("s3://lake").unwrap(),
+            Destination::S3 {
+                bucket: "lake".into(),
+                key: String::new()
+            }
+        );
+        assert!(matches!(
+            Destination::parse("https://files.example.com/costs.csv"),
+            Ok(Destination::Http(_))
+        ));
+        assert!(Destination::parse("s3:///key").is_err());
+        assert!(Destination::parse("ftp://host/x").is_err());
+    }
+
+    #[test]
+    fn templates_expand_dates_and_default_file_name() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 9, 4, 5, 6).unwrap();
+        assert_eq!(
+            expand_template("llm/{year}/{month}/", ExportFormat::Csv, now),
+            "llm/2026/03/llm-costs-2026-03-09.csv"
+        );
+        assert_eq!(
+            expand_template("costs-{timestamp}.json", ExportFormat::Json, now),
+            "costs-20260309T040506Z.json"
+        );
+    }
+
+    #[test]
+    fn object_urls_are_encoded_and_signed_for_the_region() {
+        let url = object_url(&creds(), "lake", "llm/a b+c.csv");
+        assert_eq!(
+            url,
+            "https://lake.s3.eu-west-1.amazonaws.com/llm/a%20b%2Bc.csv"
+        );
+        let minio = AwsCredentials {
+            endpoint: Some("http://localhost:9000/".into()),
+            ..creds()
+        };
+        assert_eq!(
+            object_url(&minio, "lake", "x.csv"),
+            "http://localhost:9000/lake/x.csv"
+        );
+
+        let now = Utc.with_ymd_and_hms(2026, 3, 9, 4, 5, 6).unwrap();
+        // Reference signature computed with botocore's S3SigV4Auth.
+        let payload_hash = hex::encode(Sha256::digest(b"hello"));
+        let headers = sign_put(
+            &creds(),
+            &reqwest::Url::parse(&url).unwrap(),
+            &payload_hash,
+            now,
+        );
+        let auth = &headers
+            .iter()
+            .find(|(n, _)| *n == "authorization")
+            .unwrap()
+            .1;
+        assert_eq!(
+            auth,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20260309/eu-west-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=aafa6c263b988bdc9722ccd09cc63d953c1afd2a80451c7411fd81e65364b8c0"
+        );
+        assert!(!headers.iter().any(|(n, _)| *n == "host"));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::config::{
+    default_api_key_env, provider_api_key, set_api_key, AppConfig, Budget, BudgetPeriod,
+    BudgetScope,
+};
+use crate::error::AppError;
+use crate::service::{
+    adapter_names, adapter_needs_api_key, adapter_requires_base_url, MeterService,
+};
+use dialoguer::{Confirm, Input, MultiSelect, Password};
+
+/// Name of the budget the wizard creates or updates.
+pub const WIZARD_BUDGET_NAME: &str = "monthly";
+
+/// What `init --interactive` collected; keys are stored as they are entered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WizardAnswers {
+    pub refresh_seconds: u64,
+    /// Built-in providers to enable; the others offered are disabled.
+    pub providers: Vec<String>,
+    pub monthly_budget_usd: Option<f64>,
+}
+
+/// Applies `answers` to `cfg`. Accounts that were not offered (renamed ones
+/// such as `openai-prod`) stay as they are.
+pub fn apply_answers(cfg: &mut AppConfig, offered: &[&str], answers: &WizardAnswers) {
+    cfg.refresh_seconds = answers.refresh_seconds;
+    cfg.enabled_providers
+        .retain(|p| !offered.contains(&p.as_str()) || answers.providers.contains(p));
+    for provider in &answers.providers {
+        if !cfg.enabled_providers.contains(provider) {
+            cfg.enabled_providers.push(provider.clone());
+        }
+    }
+    if let Some(amount_usd) = answers.monthly_budget_usd {
+        match cfg
+            .budgets
+            .iter_mut()
+            .find(|b| b.name == WIZARD_BUDGET_NAME)
+        {
+            Some(budget) => budget.amount_usd = amount_usd,
+            None => cfg.budgets.push(Budget {
+                name: WIZARD_BUDGET_NAME.into(),
+                scope: BudgetScope::Global,
+                amount_usd,
+                period: BudgetPeriod::Monthly,
+                on_exceeded: None,
+            }),
+        }
+    }
+}
+
+/// Walks through refresh interval, providers, keys (hidden input),
+/// connection tests and a monthly budget on a plain terminal, then applies
+/// the answers to `cfg`. The caller saves it.
+pub async fn run(cfg: &mut AppConfig, svc: &MeterService) -> Result<(), AppError> {
+    let refresh_seconds: u64 = Input::new()
+        .with_prompt("Refresh interval in seconds")
+        .default(cfg.refresh_seconds)
+        .validate_with(|v: &u64| {
+            if *v > 0 {
+                Ok(())
+            } else {
+                Err("must be at least 1")
+            }
+        })
+        .interact_text()?;
+
+    // Adapters that need a base_url are added with `add-provider --kind`.
+    let offered: Vec<&str> = adapter_names()
+        .into_iter()
+        .filter(|name| !adapter_requires_base_url(name))
+        .collect();
+    let defaults: Vec<bool> = offered
+        .iter()
+        .map(|name| cfg.enabled_providers.iter().any(|p| p == name))
+        .collect();
+    let picked = MultiSelect::new()
+        .with_prompt("Providers to track (space toggles, enter confirms)")
+        .items(&offered)
+        .defaults(&defaults)
+        .interact()?;
+
+    let mut providers = Vec::new();
+    for name in picked.into_iter().map(|i| offered[i]) {
+        let key = if adapter_needs_api_key(name) {
+            let env = default_api_key_env(name);
+            let key = Password::new()
+                .with_prompt(format!(
+                    "{name} admin API key (empty keeps the stored key or ${env})"
+                ))
+                .allow_empty_password(true)
+                .interact()?;
+            if !key.trim().is_empty() {
+                set_api_key(name, key.trim())?;
+            }
+            let Ok(key) = provider_api_key(cfg, name) else {
+                println!("  no key for {name} yet; set ${env} or run `add-provider {name}` later.");
+                providers.push(name.to_string());
+                continue;
+            };
+            key
+        } else {
+            String::new()
+        };
+        let test = Confirm::new()
+            .with_prompt(format!("Test the {name} connection now?"))
+            .default(true)
+            .interact()?;
+        if test {
+            let settings = cfg.provider_settings.get(name).cloned().unwrap_or_default();
+            match svc.test_provider_connection(name, key, settings).await {
+                Ok(report) => println!(
+                    "  ok ({}, {} ms)",
+                    report
+                        .status_code
+                        .map_or_else(|| "no status".into(), |c| format!("HTTP {c}")),
+                    report.duration_ms
+                ),
+                Err(e) => {
+                    println!("  failed: {e}");
+                    let keep = Confirm::new()
+                        .with_prompt(format!("Enable {name} anyway?"))
+                        .default(false)
+                        .interact()?;
+                    if !keep {
+                        continue;
+                    }
+                }
+            }
+        }
+        providers.push(name.to_string());
+    }
+
+    let current = cfg
+        .budgets
+        .iter()
+        .find(|b| b.name == WIZARD_BUDGET_NAME)
+        .map(|b| b.amount_usd);
+    let monthly_budget_usd = if Confirm::new()
+        .with_prompt("Set a monthly spend budget across all providers?")
+        .default(current.is_some())
+        .interact()?
+    {
+        let mut input = Input::new().with_prompt("Monthly budget in USD");
+        if let Some(amount) = current {
+            input = input.default(amount);
+        }
+        Some(
+            input
+                .validate_with(|v: &f64| {
+                    if v.is_finite() && *v > 0.0 {
+                        Ok(())
+                    } else {
+                        Err("must be a positive amount")
+                    }
+                })
+                .interact_text()?,
+        )
+    } else {
+        None
+    };
+
+    apply_answers(
+        cfg,
+        &offered,
+        &WizardAnswers {
+            refresh_seconds,
+            providers,
+            monthly_budget_usd,
+        },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_toggle_offered_providers_and_upsert_the_budget() {
+        let mut cfg = AppConfig {
+            enabled_providers: vec!["openai".into(), "openai-prod".into()],
+            ..AppConfig::default()
+        };
+        let offered = ["openai", "anthropic"];
+        let answers = WizardAnswers {
+            refresh_seconds: 120,
+            providers: vec!["anthropic".into()],
+            monthly_budget_usd: Some(250.0),
+        };
+        apply_answers(&mut cfg, &offered, &answers);
+        assert_eq!(cfg.refresh_seconds, 120);
+        assert_eq!(cfg.enabled_providers, vec!["openai-prod", "anthropic"]);
+        assert_eq!(cfg.budgets.len(), 1);
+        assert_eq!(cfg.budgets[0].period, BudgetPeriod::Monthly);
+
+        apply_answers(
+            &mut cfg,
+            &offered,
+            &WizardAnswers {
+                monthly_budget_usd: Some(400.0),
+                ..answers
+            },
+        );
+        assert_eq!(cfg.budgets.len(), 1);
+        assert_eq!(cfg.budgets[0].amount_usd, 400.0);
+    }
+}
@@ -17,6 +17,7 @@ fn run_cmd(home: &TempDir, args: &[&str]) -> Output {
     Command::new(bin_path())
         .args(args)
         .env("LLM_METER_HOME", home_path(home))
+        .env_remove("LLM_METER_PROFILE")
         .output()
         .expect("run llm-meter command")
 }
@@ -74,6 +75,26 @@ fn refresh_rejects_invalid_window() {
     assert!(stderr.contains("Unsupported window. Use 1d, 7d, or 30d"));
 }
 
+#[test]
+fn add_provider_checks_the_kind_and_its_base_url() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let add = |extra: &[&str]| {
+        let mut args = vec!["add-provider", "vllm-prod", "--api-key", "sk-test"];
+        args.extend_from_slice(extra);
+        let output = run_cmd(&home, &args);
+        assert!(!output.status.success());
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    assert!(add(&["--kind", "vllm"]).contains("Unknown provider kind 'vllm'"));
+    assert!(add(&["--kind", "custom-openai-compatible"])
+        .contains("'vllm-prod' is a custom-openai-compatible account; pass --base-url"));
+    let config =
+        fs::read_to_string(home.path().join("config").join("config.toml")).expect("read config");
+    assert!(!config.contains("vllm-prod"));
+}
+
 #[test]
 fn export_csv_outputs_header_and_escaped_fields() {
     let home = TempDir::new().expect("temp home");
@@ -87,6 +108,50 @@ fn export_csv_outputs_header_and_escaped_fields() {
     assert!(stdout.contains("\"open,ai\",\"gpt\"\"4o\""));
 }
 
+#[test]
+fn export_csv_honors_delimiter_header_and_quote_options() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 1.25);
+
+    let output = run_cmd(
+        &home,
+        &[
+            "export",
+            "--format",
+            "csv",
+            "--delimiter",
+            ";",
+            "--no-header",
+            "--quote-all",
+        ],
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("provider"));
+    assert!(stdout.starts_with("\"openai\";\"gpt-4o\";\"1.25000000\""));
+}
+
+#[test]
+fn export_to_rejects_unknown_destinations_and_missing_aws_credentials() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+
+    let output = run_cmd(&home, &["export", "--to", "ftp://host/costs.csv"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unsupported export destination"));
+
+    let output = Command::new(bin_path())
+        .args(["export", "--format", "csv", "--to", "s3://lake/llm/"])
+        .env("LLM_METER_HOME", home_path(&home))
+        .env_remove("AWS_ACCESS_KEY_ID")
+        .env_remove("AWS_SECRET_ACCESS_KEY")
+        .output()
+        .expect("run llm-meter command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("AWS_ACCESS_KEY_ID"));
+}
+
 #[test]
 fn export_json_outputs_valid_array() {
     let home = TempDir::new().expect("temp home");
@@ -117,3 +182,537 @@ fn init_is_idempotent() {
 
     assert_eq!(first, second);
 }
+
+#[test]
+fn init_flags_provision_settings_and_providers() {
+    let home = TempDir::new().expect("temp home");
+    let output = Command::new(bin_path())
+        .args([
+            "init",
+            "--refresh-seconds",
+            "300",
+            "--currency",
+            "eur",
+            "--usd-rate",
+            "0.9",
+            "--enable",
+            "openai",
+            "--api-key-env",
+            "CI_OPENAI_KEY",
+            "--enable",
+            "Anthropic",
+        ])
+        .env("LLM_METER_HOME", home_path(&home))
+        .env("CI_OPENAI_KEY", "sk-test")
+        .env_remove("ANTHROPIC_API_KEY")
+        .output()
+        .expect("run init");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Provider 'openai' enabled; key from $CI_OPENAI_KEY"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("$ANTHROPIC_API_KEY is not set"));
+    assert!(!stderr.contains("CI_OPENAI_KEY"));
+
+    let config: toml::Value = toml::from_str(
+        &fs::read_to_string(home.path().join("config").join("config.toml")).expect("config"),
+    )
+    .expect("parse config");
+    assert_eq!(config["refresh_seconds"].as_integer(), Some(300));
+    assert_eq!(config["display"]["currency"].as_str(), Some("EUR"));
+    assert_eq!(
+        config["enabled_providers"],
+        toml::Value::Array(vec!["openai".into(), "anthropic".into()])
+    );
+    assert_eq!(
+        config["provider_settings"]["openai"]["api_key_env"].as_str(),
+        Some("CI_OPENAI_KEY")
+    );
+
+    let output = run_cmd(&home, &["init", "--currency", "GBP"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("--currency GBP needs a positive --usd-rate"));
+    let output = run_cmd(&home, &["init", "--api-key-env", "X"]);
+    assert!(!output.status.success());
+    let output = run_cmd(&home, &["init", "--interactive"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs a terminal"));
+}
+
+#[test]
+fn notify_slack_requires_webhook() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+
+    let output = run_cmd(&home, &["notify", "slack", "--window", "1d"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No Slack webhook configured"));
+}
+
+#[test]
+fn notify_digest_renders_the_user_template_from_the_config_dir() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-3.5", 9.0);
+    let conn = Connection::open(db_path(&home)).expect("open sqlite");
+    conn.execute(
+        "INSERT INTO cost_records (provider, model, input_cost, output_cost, total_cost, currency, timestamp)
+         VALUES ('openai', 'gpt-4o', 2.5, 0.0, 2.5, 'USD', strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '-1 hour'))",
+        [],
+    )
+    .expect("insert cost row");
+
+    let output = run_cmd(&home, &["notify", "digest"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf8 stdout");
+    assert!(stdout.starts_with("LLM spend · last 7d"));
+    assert!(stdout.contains("1. gpt-4o: $2.50"));
+
+    let templates = home.path().join("config").join("templates");
+    fs::create_dir_all(&templates).expect("templates dir");
+    fs::write(
+        templates.join("digest.txt"),
+        "*Acme AI spend* {{ window }}: {{ cost }}\n{% for m in top_models %}{{ m.model }}={{ m.cost_usd }};{% endfor %}\n",
+    )
+    .expect("write template");
+    let output = run_cmd(&home, &["notify", "digest", "--window", "1d"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "*Acme AI spend* 1d: $2.50\ngpt-4o=2.5;"
+    );
+
+    fs::write(templates.join("digest.txt"), "{% for m in top_models %}\n").expect("write template");
+    let output = run_cmd(&home, &["notify", "digest"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("digest.txt: template line 1: unexpected end of input"));
+}
+
+#[test]
+fn render_prints_dashboard_at_requested_width() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+
+    let output = run_cmd(&home, &["render", "--window", "30d", "--width", "100"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf8 stdout");
+    assert!(stdout.contains("llm-meter  ·  30d  ·  rendered"));
+    assert!(!stdout.contains('\x1b'));
+    assert_eq!(stdout.lines().count(), 30);
+    assert!(stdout.lines().all(|l| l.chars().count() <= 100));
+
+    let ansi = run_cmd(&home, &["render", "--ansi"]);
+    assert!(ansi.status.success());
+    assert!(String::from_utf8_lossy(&ansi.stdout).contains("\x1b["));
+}
+
+#[test]
+fn db_flag_places_database_outside_home() {
+    let home = TempDir::new().expect("temp home");
+    let other = TempDir::new().expect("other volume");
+    let db = other.path().join("nested").join("meter.sqlite");
+    assert!(run_cmd(&home, &["init"]).status.success());
+
+    let output = run_cmd(&home, &["--db", db.to_str().expect("utf8 path"), "export"]);
+    assert!(output.status.success());
+    assert!(db.exists());
+    assert!(!db_path(&home).exists());
+}
+
+#[test]
+fn profiles_keep_separate_config_and_database() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let output = run_cmd(&home, &["--profile", "work", "init"]);
+    assert!(output.status.success());
+    let work = home.path().join("config").join("profiles").join("work");
+    assert!(work.join("config.toml").exists());
+
+    seed_cost_row(&home, "openai", "gpt-4o", 2.5);
+    let output = run_cmd(&home, &["--profile", "work", "export", "--format", "json"]);
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout).expect("valid json output");
+    assert_eq!(parsed, serde_json::json!([]));
+    assert!(home
+        .path()
+        .join("data")
+        .join("profiles")
+        .join("work")
+        .join("snapshots.sqlite")
+        .exists());
+
+    let output = run_cmd(&home, &["--profile", "../x", "export"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid profile name"));
+}
+
+#[test]
+fn export_converts_costs_to_display_currency() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let cfg_path = home.path().join("config").join("config.toml");
+    let cfg = fs::read_to_string(&cfg_path).expect("read config").replace(
+        "currency = \"USD\"",
+        "currency = \"EUR\"\nlocale = \"de-DE\"\nusd_rate = 0.5",
+    );
+    fs::write(&cfg_path, cfg).expect("write config");
+    seed_cost_row(&home, "openai", "gpt-4o", 2.5);
+
+    let output = run_cmd(&home, &["export", "--format", "json"]);
+    assert!(output.status.success());
+    let parsed: Value = serde_json::from_slice(&output.stdout).expect("valid json output");
+    assert_eq!(parsed[0]["currency"], "EUR");
+    assert_eq!(parsed[0]["total_cost"], 1.25);
+}
+
+#[test]
+fn check_warns_on_unknown_keys_and_repair_rewrites_config() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let cfg_path = home.path().join("config").join("config.toml");
+    let cfg = fs::read_to_string(&cfg_path).expect("read config");
+    fs::write(&cfg_path, format!("refresh_secs = 30\n{cfg}")).expect("write config");
+
+    let export = run_cmd(&home, &["export"]);
+    assert!(export.status.success());
+    assert!(String::from_utf8_lossy(&export.stderr).contains("unknown key `refresh_secs`"));
+
+    let checked = run_cmd(&home, &["check"]);
+    assert!(checked.status.success());
+    assert!(String::from_utf8_lossy(&checked.stdout).contains("unknown key `refresh_secs`"));
+
+    let refused = run_cmd(&home, &["check", "--repair"]);
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("needs --yes"));
+    assert!(fs::read_to_string(&cfg_path)
+        .expect("read config")
+        .contains("refresh_secs"));
+
+    let repaired = run_cmd(&home, &["check", "--repair", "--yes"]);
+    assert!(repaired.status.success());
+    assert!(String::from_utf8_lossy(&repaired.stdout).contains("Config and database OK."));
+    assert!(!fs::read_to_string(&cfg_path)
+        .expect("read config")
+        .contains("refresh_secs"));
+    assert!(home.path().join("config").join("config.toml.bak").exists());
+
+    fs::write(&cfg_path, "refresh_seconds = \"soon\"\n").expect("write config");
+    let broken = run_cmd(&home, &["export"]);
+    assert!(!broken.status.success());
+    assert!(String::from_utf8_lossy(&broken.stderr).contains("refresh_seconds"));
+}
+
+#[test]
+fn export_redact_hashes_names_stably_and_keeps_amounts() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 2.5);
+
+    let first = run_cmd(&home, &["export", "--format", "json", "--redact"]);
+    assert!(first.status.success());
+    let parsed: Value = serde_json::from_slice(&first.stdout).expect("valid json output");
+    let model = parsed[0]["model"].as_str().expect("model");
+    assert!(model.starts_with("model-"));
+    assert!(parsed[0]["provider"]
+        .as_str()
+        .expect("provider")
+        .starts_with("provider-"));
+    assert_eq!(parsed[0]["total_cost"], 2.5);
+    let cfg =
+        fs::read_to_string(home.path().join("config").join("config.toml")).expect("read config");
+    assert!(cfg.contains("redact_salt"));
+
+    let second = run_cmd(&home, &["export", "--format", "json", "--redact"]);
+    let again: Value = serde_json::from_slice(&second.stdout).expect("valid json output");
+    assert_eq!(again[0]["model"], model);
+}
+
+#[test]
+fn exports_run_delivers_scheduled_file_pipelines() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 1.25);
+    let out_dir = home.path().join("exports");
+    let cfg_path = home.path().join("config").join("config.toml");
+    let mut cfg = fs::read_to_string(&cfg_path).expect("read config");
+    cfg.push_str(&format!(
+        "\n[[exports]]\nname = \"nightly\"\nsink = \"file\"\nformat = \"jsonl\"\nto = \"{}/{{year}}/\"\nschedule = \"daily\"\n\n[[exports]]\nname = \"hourly-csv\"\nsink = \"file\"\nformat = \"csv\"\nto = \"{}/hourly.csv\"\nschedule = \"hourly\"\n",
+        out_dir.display(),
+        out_dir.display()
+    ));
+    fs::write(&cfg_path, cfg).expect("write config");
+
+    let listed = run_cmd(&home, &["exports", "list"]);
+    assert!(listed.status.success());
+    assert!(String::from_utf8_lossy(&listed.stdout).contains("nightly"));
+
+    let output = run_cmd(&home, &["exports", "run", "--schedule", "daily"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("nightly: wrote"));
+    assert!(!out_dir.join("hourly.csv").exists());
+    let year_dir = fs::read_dir(&out_dir)
+        .expect("exports dir")
+        .next()
+        .expect("year dir")
+        .expect("entry")
+        .path();
+    let file = fs::read_dir(year_dir)
+        .expect("year dir")
+        .next()
+        .expect("export file")
+        .expect("entry")
+        .path();
+    assert!(fs::read_to_string(file)
+        .expect("read export")
+        .contains("\"model\":\"gpt-4o\""));
+
+    let status = run_cmd(&home, &["exports", "status"]);
+    assert!(status.status.success());
+    let status = String::from_utf8_lossy(&status.stdout);
+    let nightly = status
+        .lines()
+        .find(|l| l.starts_with("nightly"))
+        .expect("nightly row");
+    assert!(nightly.contains(" ok "));
+    assert!(!nightly.ends_with("now"));
+
+    // Only the never-run hourly pipeline is due now.
+    let output = run_cmd(&home, &["exports", "run", "--due"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("hourly-csv: wrote"));
+    assert!(!stdout.contains("nightly"));
+    assert!(out_dir.join("hourly.csv").exists());
+}
+
+#[test]
+fn reconcile_diffs_invoice_against_frozen_statement() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 10.0);
+    let invoice = home.path().join("invoice.csv");
+    fs::write(&invoice, "Description,Amount\ngpt-4o input,$10.75\n").expect("write invoice");
+    let args = [
+        "reconcile",
+        "--invoice",
+        invoice.to_str().expect("utf-8 path"),
+        "--provider",
+        "openai",
+        "--month",
+        "2024-01",
+        "--json",
+    ];
+
+    let output = run_cmd(&home, &args);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No frozen statement for 2024-01"));
+
+    assert!(run_cmd(&home, &["statement", "2024-01"]).status.success());
+    let output = run_cmd(&home, &args);
+    assert!(output.status.success());
+    let report: Value = serde_json::from_slice(&output.stdout).expect("json report");
+    assert_eq!(report["statement_total"], 10.0);
+    assert_eq!(report["discrepancies"], 1);
+    assert_eq!(report["lines"][0]["line"], "gpt-4o");
+    assert_eq!(report["lines"][0]["diff"], 0.75);
+}
+
+#[test]
+fn import_billing_replaces_the_exported_period() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let export = home.path().join("azure.csv");
+    fs::write(
+        &export,
+        "Date,MeterSubCategory,MeterName,CostInBillingCurrency,BillingCurrencyCode\n\
+         2024-06-01,Azure OpenAI,gpt-4o-0513-Inp-glbl 1M Tokens,3.00,USD\n\
+         2024-06-01,Blob,Hot LRS,9.00,USD\n",
+    )
+    .expect("write export");
+    let args = [
+        "import-billing",
+        "--source",
+        "azure",
+        "--file",
+        export.to_str().expect("utf-8 path"),
+    ];
+
+    let output = run_cmd(&home, &args);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(
+        "Imported 1 line items as 1 cost rows for 'azure-openai' (2024-06-01 to 2024-06-01, $3.00); replaced 0 earlier rows, skipped 1 other rows."
+    ));
+    let output = run_cmd(&home, &args);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("replaced 1 earlier rows"));
+
+    let output = run_cmd(&home, &["export", "--format", "json"]);
+    let rows: Value = serde_json::from_slice(&output.stdout).expect("json export");
+    assert_eq!(rows.as_array().map(Vec::len), Some(1));
+    assert_eq!(rows[0]["provider"], "azure-openai");
+    assert_eq!(rows[0]["model"], "gpt-4o-0513");
+    assert_eq!(rows[0]["total_cost"], 3.0);
+}
+
+#[test]
+fn db_purge_provider_deletes_only_that_providers_rows() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 10.0);
+    seed_cost_row(&home, "openai", "gpt-4o-mini", 1.0);
+    seed_cost_row(&home, "anthropic", "claude", 2.0);
+
+    let output = run_cmd(&home, &["db", "purge-provider", "OpenAI", "--dry-run"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Would purge 0 usage and 2 cost rows for 'openai'.\n"
+    );
+
+    let output = run_cmd(&home, &["db", "purge-provider", "openai"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs --yes"));
+
+    let output = run_cmd(&home, &["db", "purge-provider", "openai", "--yes"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Purged 0 usage and 2 cost rows"));
+
+    let conn = Connection::open(db_path(&home)).expect("open sqlite");
+    let providers: Vec<String> = conn
+        .prepare("SELECT provider FROM cost_records")
+        .expect("prepare")
+        .query_map([], |r| r.get(0))
+        .expect("query")
+        .collect::<Result<_, _>>()
+        .expect("rows");
+    assert_eq!(providers, vec!["anthropic".to_string()]);
+}
+
+#[test]
+fn db_decrypt_needs_confirmation_before_deleting_the_key() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+
+    let output = run_cmd(&home, &["db", "decrypt", "--yes"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not encrypted"));
+
+    let cfg_path = home.path().join("config").join("config.toml");
+    let cfg = fs::read_to_string(&cfg_path).expect("read config");
+    fs::write(&cfg_path, format!("encrypt_database = true\n{cfg}")).expect("write config");
+    let output = run_cmd(&home, &["db", "decrypt"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs --yes"));
+    assert!(fs::read_to_string(&cfg_path)
+        .expect("read config")
+        .contains("encrypt_database = true"));
+}
+
+#[test]
+fn query_runs_selects_and_refuses_writes() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    seed_cost_row(&home, "openai", "gpt-4o", 10.0);
+    seed_cost_row(&home, "anthropic", "claude", 2.0);
+
+    let sql = "SELECT provider, SUM(total_cost) AS cost FROM cost_records GROUP BY provider ORDER BY cost DESC";
+    let output = run_cmd(&home, &["query", sql, "--csv"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "provider,cost\nopenai,10\nanthropic,2\n"
+    );
+    let output = run_cmd(&home, &["query", sql]);
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("(2 rows)\n"));
+
+    for sql in [
+        "DELETE FROM cost_records",
+        "PRAGMA journal_mode = DELETE",
+        "SELECT 1; DELETE FROM cost_records",
+    ] {
+        let output = run_cmd(&home, &["query", sql]);
+        assert!(!output.status.success(), "{sql} should be refused");
+    }
+    let conn = Connection::open(db_path(&home)).expect("open sqlite");
+    let rows: i64 = conn
+        .query_row("SELECT COUNT(*) FROM cost_records", [], |r| r.get(0))
+        .expect("count");
+    assert_eq!(rows, 2);
+}
+
+#[test]
+fn rename_provider_moves_config_and_history() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let config = home.path().join("config").join("config.toml");
+    let raw = fs::read_to_string(&config).expect("read config");
+    fs::write(
+        &config,
+        raw.replace("enabled_providers = []", "enabled_providers = [\"openai\"]"),
+    )
+    .expect("write config");
+    seed_cost_row(&home, "openai", "gpt-4o", 10.0);
+
+    let output = run_cmd(&home, &["rename-provider", "openai", "openai-prod"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Renamed 'openai' to 'openai-prod'; 1 stored rows moved.\n"
+    );
+    let raw = fs::read_to_string(&config).expect("read config");
+    assert!(raw.contains("enabled_providers = [\"openai-prod\"]"));
+    assert!(raw.contains("[provider_settings.openai-prod]\nkind = \"openai\""));
+
+    let conn = Connection::open(db_path(&home)).expect("open sqlite");
+    let provider: String = conn
+        .query_row("SELECT provider FROM cost_records", [], |r| r.get(0))
+        .expect("row");
+    assert_eq!(provider, "openai-prod");
+
+    let output = run_cmd(&home, &["rename-provider", "openai", "other"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'openai' is not configured"));
+}
+
+#[test]
+fn audit_lists_config_changes_with_their_source() {
+    let home = TempDir::new().expect("temp home");
+    assert!(run_cmd(&home, &["init"]).status.success());
+    let output = run_cmd(
+        &home,
+        &["init", "--refresh-seconds", "300", "--enable", "gemini"],
+    );
+    assert!(output.status.success());
+
+    let output = run_cmd(&home, &["audit", "--json"]);
+    assert!(output.status.success());
+    let entries: Value = serde_json::from_slice(&output.stdout).expect("json");
+    let entries: Vec<(&str, &str, &str)> = entries
+        .as_array()
+        .expect("array")
+        .iter()
+        .map(|e| {
+            (
+                e["source"].as_str().unwrap(),
+                e["action"].as_str().unwrap(),
+                e["subject"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("cli", "config.changed", "refresh_seconds"),
+            ("cli", "provider.added", "gemini"),
+        ]
+    );
+
+    let output = run_cmd(&home, &["audit"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("provider.added"));
+}